@@ -41,6 +41,9 @@ fn main() -> Result<(), BoxError> {
             eprintln!("feature dylib not enabled")
         }
     }
+    // the environment is read before any thread is spawned
+    #[cfg(all(unix, feature = "socket-handoff"))]
+    spacegate_shell::upgrade::read_env();
     let rt = tokio::runtime::Builder::new_multi_thread().enable_all().thread_name(env!("CARGO_PKG_NAME")).build().expect("fail to build runtime");
     rt.block_on(async move {
         match args.config {
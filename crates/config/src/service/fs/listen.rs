@@ -3,10 +3,17 @@ mod unix {
     use crate::service::fs::Fs;
     use crate::service::{ConfigEventType, ConfigType, CreateListener, Listen, ListenEvent, Retrieve};
     use crate::{model::Config, service::config_format::ConfigFormat, BoxError};
-    use std::task::ready;
+    use notify::{EventKind, Watcher};
+    use spacegate_model::SgProtocolConfig;
+    use std::collections::{BTreeSet, HashMap};
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+    use std::task::{ready, Poll};
     pub struct FsListener {
-        // hold the watcher, prevent dropping
         signal: tokio::signal::unix::Signal,
+        // hold the watcher, prevent dropping
+        _pem_watcher: Option<notify::RecommendedWatcher>,
+        pem_events: tokio::sync::mpsc::UnboundedReceiver<(ConfigType, ConfigEventType)>,
     }
 
     impl<F> CreateListener for Fs<F>
@@ -17,7 +24,8 @@ mod unix {
         type Listener = FsListener;
         async fn create_listener(&self) -> Result<(Config, Self::Listener), Box<dyn std::error::Error + Sync + Send + 'static>> {
             let config = self.retrieve_config().await?;
-            Ok((config, FsListener::new(self.clone())?))
+            let listener = FsListener::new(self.clone())?.watch_pem_files(&config)?;
+            Ok((config, listener))
         }
     }
 
@@ -28,19 +36,142 @@ mod unix {
             F: ConfigFormat + Clone + Send + 'static,
         {
             let signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
-            Ok(Self { signal })
+            let (_, pem_events) = tokio::sync::mpsc::unbounded_channel();
+            Ok(Self {
+                signal,
+                _pem_watcher: None,
+                pem_events,
+            })
+        }
+
+        /// Watch the PEM files referenced by tls listeners, a gateway update event will be emitted when they change.
+        ///
+        /// The files are collected from the given config, so the files added by later config changes won't be watched.
+        pub fn watch_pem_files(mut self, config: &Config) -> Result<Self, BoxError> {
+            let current_dir = std::env::current_dir()?;
+            // pem file -> gateways
+            let mut files = HashMap::<PathBuf, BTreeSet<String>>::new();
+            for (name, item) in &config.gateways {
+                for listener in &item.gateway.listeners {
+                    let SgProtocolConfig::Https { ref tls } = listener.protocol else {
+                        continue;
+                    };
+                    for path in tls.pem_paths() {
+                        files.entry(current_dir.join(path)).or_default().insert(name.clone());
+                    }
+                }
+            }
+            if files.is_empty() {
+                return Ok(self);
+            }
+            // parent dir -> gateways
+            let mut dirs = HashMap::<PathBuf, BTreeSet<String>>::new();
+            for (file, gateways) in &files {
+                if let Some(dir) = file.parent() {
+                    dirs.entry(dir.to_path_buf()).or_default().extend(gateways.iter().cloned());
+                }
+            }
+            let (evt_tx, evt_rx) = tokio::sync::mpsc::unbounded_channel();
+            let mut watcher = {
+                let dirs = dirs.clone();
+                notify::recommended_watcher(move |next: notify::Result<notify::Event>| {
+                    let Ok(evt) = next else {
+                        return;
+                    };
+                    if !matches!(evt.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+                        return;
+                    }
+                    let mut changed = BTreeSet::new();
+                    for path in &evt.paths {
+                        if let Some(gateways) = files.get(path) {
+                            changed.extend(gateways.iter().cloned());
+                        } else if path.file_name() == Some(OsStr::new("..data")) {
+                            // kubernetes updates the mounted secrets by swapping the `..data` symlink
+                            if let Some(gateways) = path.parent().and_then(|dir| dirs.get(dir)) {
+                                changed.extend(gateways.iter().cloned());
+                            }
+                        }
+                    }
+                    for name in changed {
+                        tracing::debug!("[SG.Config] pem files of gateway {name} changed");
+                        let _result = evt_tx.send((ConfigType::Gateway { name }, ConfigEventType::Update));
+                    }
+                })?
+            };
+            // watch the parent directories, since the files could be replaced rather than modified
+            for dir in dirs.keys().map(PathBuf::as_path) {
+                watcher.watch(dir, notify::RecursiveMode::NonRecursive).map_err(|e| format!("fail to watch {dir}: {e}", dir = Path::display(dir)))?;
+            }
+            self._pem_watcher = Some(watcher);
+            self.pem_events = evt_rx;
+            Ok(self)
         }
     }
 
     impl Listen for FsListener {
-        fn poll_next(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<ListenEvent, BoxError>> {
+        fn poll_next(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<ListenEvent, BoxError>> {
+            if let Poll::Ready(Some(next)) = self.pem_events.poll_recv(cx) {
+                return Poll::Ready(Ok(next.into()));
+            }
             if ready!(self.signal.poll_recv(cx)).is_some() {
-                std::task::Poll::Ready(Ok((ConfigType::Global, ConfigEventType::Update).into()))
+                Poll::Ready(Ok((ConfigType::Global, ConfigEventType::Update).into()))
             } else {
-                std::task::Poll::Pending
+                Poll::Pending
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::service::config_format::Json;
+        use spacegate_model::{ConfigItem, SgGateway, SgListener, SgTlsConfig, SgTlsMode};
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        #[test]
+        fn pem_file_change_emits_gateway_update() {
+            tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+                let unique = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+                let dir = std::env::temp_dir().join(format!("spacegate-config-pem-watch-{unique}"));
+                std::fs::create_dir_all(&dir).unwrap();
+                let cert_path = dir.join("tls.crt");
+                std::fs::write(&cert_path, "old").unwrap();
+                let mut config = Config::default();
+                config.gateways.insert(
+                    "tls".to_string(),
+                    ConfigItem {
+                        gateway: SgGateway {
+                            name: "tls".to_string(),
+                            listeners: vec![SgListener {
+                                protocol: SgProtocolConfig::Https {
+                                    tls: SgTlsConfig {
+                                        mode: SgTlsMode::Terminate,
+                                        key: String::new(),
+                                        cert: String::new(),
+                                        key_path: None,
+                                        cert_path: Some(cert_path.to_string_lossy().to_string()),
+                                        http2: None,
+                                        http3: None,
+                                        certs: Vec::new(),
+                                        client_auth: None,
+                                    },
+                                },
+                                ..Default::default()
+                            }],
+                            ..Default::default()
+                        },
+                        routes: Default::default(),
+                    },
+                );
+                let mut listener = FsListener::new(Fs::new(&dir, Json::default())).unwrap().watch_pem_files(&config).unwrap();
+                std::fs::write(&cert_path, "new").unwrap();
+                let event = tokio::time::timeout(Duration::from_secs(5), std::future::poll_fn(|cx| listener.poll_next(cx))).await.unwrap().unwrap();
+                assert!(matches!(event.config, ConfigType::Gateway { ref name } if name == "tls"));
+                assert!(matches!(event.r#type, ConfigEventType::Update));
+                std::fs::remove_dir_all(dir).unwrap();
+            });
+        }
+    }
 }
 
 #[cfg(target_family = "windows")]
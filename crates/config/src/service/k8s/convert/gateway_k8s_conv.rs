@@ -5,15 +5,19 @@ use k8s_openapi::{api::core::v1::Secret, ByteString};
 use kube::{api::ObjectMeta, ResourceExt};
 use spacegate_model::{ext::k8s::helper_struct::SgTargetKind, ObservabilityConfig, PluginBinding};
 
-use crate::{ext::k8s::crd::sg_filter::K8sSgFilterSpecTargetRef, service::k8s::K8s, SgGateway, SgParameters};
+use crate::{ext::k8s::crd::sg_filter::K8sSgFilterSpecTargetRef, service::k8s::K8s, BoxResult, SgGateway, SgParameters};
 
 use super::ToTarget;
 pub(crate) trait SgGatewayConv {
-    fn to_kube_gateway(self, namespace: &str, gateway_class_name: &str) -> (Gateway, Option<Secret>, Vec<PluginBinding>);
+    /// Tcp and udp listeners are rejected, since their routes can't be stored in k8s.
+    fn to_kube_gateway(self, namespace: &str, gateway_class_name: &str) -> BoxResult<(Gateway, Option<Secret>, Vec<PluginBinding>)>;
 }
 impl SgGatewayConv for SgGateway {
-    fn to_kube_gateway(self, namespace: &str, gateway_class_name: &str) -> (Gateway, Option<Secret>, Vec<PluginBinding>) {
+    fn to_kube_gateway(self, namespace: &str, gateway_class_name: &str) -> BoxResult<(Gateway, Option<Secret>, Vec<PluginBinding>)> {
         let mut secret = None;
+        if let Some(listener) = self.listeners.iter().find(|l| matches!(l.protocol, crate::SgProtocolConfig::Tcp | crate::SgProtocolConfig::Udp)) {
+            return Err(format!("{} listener {} is not supported by k8s config backend yet", listener.protocol, listener.name).into());
+        }
 
         let gateway = Gateway {
             metadata: ObjectMeta {
@@ -64,7 +68,10 @@ impl SgGatewayConv for SgGateway {
                                         namespace: Some(namespace.to_string()),
                                         ..Default::default()
                                     }]),
-                                    options: Some(BTreeMap::from([(K8s::HTTP2_KEY.to_string(), tls.http2.unwrap_or_default().to_string())])),
+                                    options: Some(BTreeMap::from([
+                                        (K8s::HTTP2_KEY.to_string(), tls.http2.unwrap_or_default().to_string()),
+                                        (K8s::HTTP3_KEY.to_string(), tls.http3.unwrap_or_default().to_string()),
+                                    ])),
                                 })
                             }
                             _ => None,
@@ -77,7 +84,7 @@ impl SgGatewayConv for SgGateway {
             status: None,
         };
 
-        (gateway, secret, self.plugins)
+        Ok((gateway, secret, self.plugins))
     }
 }
 
@@ -107,6 +114,15 @@ impl SgParametersConv for SgParameters {
         if let Some(enable_x_request_id) = self.enable_x_request_id {
             ann.insert(crate::constants::GATEWAY_ANNOTATION_ENABLE_X_REQUEST_ID.to_string(), enable_x_request_id.to_string());
         }
+        if let Some(max_request_body_size) = self.max_request_body_size {
+            ann.insert(crate::constants::GATEWAY_ANNOTATION_MAX_REQUEST_BODY_SIZE.to_string(), max_request_body_size.to_string());
+        }
+        if let Some(max_response_body_size) = self.max_response_body_size {
+            ann.insert(crate::constants::GATEWAY_ANNOTATION_MAX_RESPONSE_BODY_SIZE.to_string(), max_response_body_size.to_string());
+        }
+        if let Some(route_precedence) = self.route_precedence {
+            ann.insert(crate::constants::GATEWAY_ANNOTATION_ROUTE_PRECEDENCE.to_string(), route_precedence.to_string());
+        }
         if self.observability.enabled {
             ann.insert(crate::constants::GATEWAY_ANNOTATION_OTEL_ENABLED.to_string(), self.observability.enabled.to_string());
             ann.insert(crate::constants::GATEWAY_ANNOTATION_OTEL_SERVICE_NAME.to_string(), self.observability.service_name);
@@ -171,6 +187,9 @@ impl SgParametersConv for SgParameters {
                 lang: gateway_annotations.get(crate::constants::GATEWAY_ANNOTATION_LANGUAGE).map(|v| v.to_string()),
                 ignore_tls_verification: gateway_annotations.get(crate::constants::GATEWAY_ANNOTATION_IGNORE_TLS_VERIFICATION).and_then(|v| v.parse::<bool>().ok()),
                 enable_x_request_id: gateway_annotations.get(crate::constants::GATEWAY_ANNOTATION_ENABLE_X_REQUEST_ID).and_then(|v| v.parse::<bool>().ok()),
+                max_request_body_size: gateway_annotations.get(crate::constants::GATEWAY_ANNOTATION_MAX_REQUEST_BODY_SIZE).and_then(|v| v.parse::<u64>().ok()),
+                max_response_body_size: gateway_annotations.get(crate::constants::GATEWAY_ANNOTATION_MAX_RESPONSE_BODY_SIZE).and_then(|v| v.parse::<u64>().ok()),
+                route_precedence: gateway_annotations.get(crate::constants::GATEWAY_ANNOTATION_ROUTE_PRECEDENCE).and_then(|v| v.parse().ok()),
                 observability,
             }
         } else {
@@ -180,6 +199,9 @@ impl SgParametersConv for SgParameters {
                 lang: None,
                 ignore_tls_verification: None,
                 enable_x_request_id: None,
+                max_request_body_size: None,
+                max_response_body_size: None,
+                route_precedence: None,
                 observability: Default::default(),
             }
         }
@@ -200,7 +222,7 @@ impl ToTarget for Gateway {
 #[cfg(test)]
 mod tests {
     use super::SgGatewayConv;
-    use crate::{PluginBinding, PluginInstanceName, SgGateway};
+    use crate::{PluginBinding, PluginInstanceName, SgGateway, SgListener, SgProtocolConfig};
 
     #[test]
     fn gateway_conversion_uses_configured_gateway_class() {
@@ -209,7 +231,7 @@ mod tests {
             ..Default::default()
         };
 
-        let (gateway, _, _) = gateway.to_kube_gateway("ai-hai", "ai-spacegate");
+        let (gateway, _, _) = gateway.to_kube_gateway("ai-hai", "ai-spacegate").expect("valid gateway");
 
         assert_eq!(gateway.spec.gateway_class_name, "ai-spacegate");
     }
@@ -221,8 +243,23 @@ mod tests {
             ..Default::default()
         };
 
-        let (_, _, bindings) = gateway.to_kube_gateway("default", "spacegate");
+        let (_, _, bindings) = gateway.to_kube_gateway("default", "spacegate").expect("valid gateway");
 
         assert_eq!(bindings[0].priority, 100);
     }
+
+    #[test]
+    fn gateway_conversion_rejects_stream_listeners() {
+        let gateway = SgGateway {
+            listeners: vec![SgListener {
+                name: "dns".to_string(),
+                port: 53,
+                protocol: SgProtocolConfig::Udp,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert!(gateway.to_kube_gateway("default", "spacegate").is_err());
+    }
 }
@@ -29,11 +29,11 @@ use crate::{
 use super::{filter_k8s_conv::PluginIdConv as _, ToTarget};
 pub(crate) trait SgHttpRouteConv {
     /// Convert to HttpSpaceroute and SgSingeFilter
-    fn to_kube_httproute(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> (HttpSpaceroute, Vec<PluginBinding>);
+    fn to_kube_httproute(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> BoxResult<(HttpSpaceroute, Vec<PluginBinding>)>;
 }
 
 impl SgHttpRouteConv for SgHttpRoute {
-    fn to_kube_httproute(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> (HttpSpaceroute, Vec<PluginBinding>) {
+    fn to_kube_httproute(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> BoxResult<(HttpSpaceroute, Vec<PluginBinding>)> {
         let gateway_ref = ParentReference {
             group: None,
             kind: Some(SgTargetKind::Gateway.into()),
@@ -57,7 +57,7 @@ impl SgHttpRouteConv for SgHttpRoute {
                     parent_refs: Some(vec![gateway_ref.clone()]),
                 },
                 hostnames: self.hostnames,
-                rules: Some(self.rules.into_iter().map(|r| r.into_kube_httproute()).collect::<Vec<_>>()),
+                rules: Some(self.rules.into_iter().map(|r| r.into_kube_httproute()).collect::<BoxResult<Vec<_>>>()?),
             },
             status: Some(HttpSpacerouteStatus {
                 inner: RouteStatus {
@@ -69,7 +69,7 @@ impl SgHttpRouteConv for SgHttpRoute {
                 },
             }),
         };
-        (httproute, self.plugins)
+        Ok((httproute, self.plugins))
     }
 }
 
@@ -100,20 +100,23 @@ impl KubeRoute {
 }
 
 pub(crate) trait SgRouteK8sConv {
-    fn to_kube_route(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> KubeRoute;
+    fn to_kube_route(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> BoxResult<KubeRoute>;
 }
 
 impl SgRouteK8sConv for SgRoute {
-    fn to_kube_route(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> KubeRoute {
+    fn to_kube_route(self, gateway_name: &str, name: &str, gateway_namespace: &str) -> BoxResult<KubeRoute> {
         match self {
             SgRoute::Http(route) => {
-                let (route, plugin_ids) = route.to_kube_httproute(gateway_name, name, gateway_namespace);
-                KubeRoute::Http(route, plugin_ids)
+                let (route, plugin_ids) = route.to_kube_httproute(gateway_name, name, gateway_namespace)?;
+                Ok(KubeRoute::Http(route, plugin_ids))
             }
             SgRoute::Mcp(route) => {
                 let (route, plugin_ids) = mcp_route_to_kube_mcp_route(route, gateway_name, name, gateway_namespace);
-                KubeRoute::Mcp(route, plugin_ids)
+                Ok(KubeRoute::Mcp(route, plugin_ids))
             }
+            SgRoute::Tls(_) => Err(format!("TLSRoute {name} is not supported by k8s config backend yet").into()),
+            SgRoute::Tcp(_) => Err(format!("TCPRoute {name} is not supported by k8s config backend yet").into()),
+            SgRoute::Udp(_) => Err(format!("UDPRoute {name} is not supported by k8s config backend yet").into()),
         }
     }
 }
@@ -143,6 +146,7 @@ fn mcp_route_to_kube_mcp_route(route: SgMcpRoute, gateway_name: &str, name: &str
             legacy_sse: route.legacy_sse,
             backend_refs: route.backends.into_iter().map(|backend| backend.into_kube_httproute()).collect(),
             timeout_mode: route.timeout_mode,
+            timeout_ms: route.timeout_ms,
             session_affinity: route.session_affinity,
         },
         status: Some(McpRouteStatus {
@@ -161,29 +165,34 @@ fn mcp_route_to_kube_mcp_route(route: SgMcpRoute, gateway_name: &str, name: &str
 pub(crate) trait SgHttpRouteRuleConv {
     /// # to_kube_httproute
     /// `SgHttpRouteRule` to `HttpRouteRule`, include `HttpRouteFilter` and  excluding `SgFilter`.
-    fn into_kube_httproute(self) -> HttpRouteRule;
+    ///
+    /// # Errors
+    /// The matches can't be represented by gateway api.
+    fn into_kube_httproute(self) -> BoxResult<HttpRouteRule>;
     fn from_kube_httproute(rule: http_spaceroute::HttpRouteRule) -> BoxResult<SgHttpRouteRule>;
 }
 
 impl SgHttpRouteRuleConv for SgHttpRouteRule {
-    fn into_kube_httproute(self) -> HttpRouteRule {
+    fn into_kube_httproute(self) -> BoxResult<HttpRouteRule> {
         let mut plugin_bindings = self.plugins;
         plugin_bindings.sort_by(|left, right| right.priority.cmp(&left.priority));
-        let (matches, mut plugins): (Option<Vec<HttpRouteMatch>>, Vec<HttpRouteFilter>) = self
-            .matches
-            .map(|m_vec| {
-                let (matches, plugins): (Vec<_>, Vec<_>) = m_vec.into_iter().map(|m| m.into_kube_httproute()).unzip();
+        let (matches, mut plugins): (Option<Vec<HttpRouteMatch>>, Vec<HttpRouteFilter>) = match self.matches {
+            Some(m_vec) => {
+                let (matches, plugins): (Vec<_>, Vec<_>) = m_vec.into_iter().map(|m| m.into_kube_httproute()).collect::<BoxResult<Vec<_>>>()?.into_iter().unzip();
                 (Some(matches.into_iter().flatten().collect()), plugins.into_iter().flatten().collect())
-            })
-            .unwrap_or_default();
+            }
+            None => Default::default(),
+        };
         plugins.append(&mut plugin_bindings.into_iter().filter_map(|binding| binding.id.to_http_route_filter()).collect::<Vec<_>>());
-        HttpRouteRule {
+        Ok(HttpRouteRule {
             matches,
             filters: Some(plugins),
             backend_refs: Some(self.backends.into_iter().map(|b| b.into_kube_httproute()).collect::<Vec<_>>()),
             timeout_ms: self.timeout_ms,
             timeout_mode: self.timeout_mode,
-        }
+            connect_timeout_ms: self.connect_timeout_ms,
+            first_byte_timeout_ms: self.first_byte_timeout_ms,
+        })
     }
 
     fn from_kube_httproute(rule: http_spaceroute::HttpRouteRule) -> BoxResult<SgHttpRouteRule> {
@@ -252,6 +261,8 @@ impl SgHttpRouteRuleConv for SgHttpRouteRule {
                 .unwrap_or_default(),
             timeout_ms: rule.timeout_ms,
             timeout_mode: rule.timeout_mode,
+            connect_timeout_ms: rule.connect_timeout_ms,
+            first_byte_timeout_ms: rule.first_byte_timeout_ms,
             balance_policy: None,
         })
     }
@@ -282,24 +293,31 @@ pub(crate) trait SgHttpRouteMatchConv {
     /// matches_vec:[path_match1, path_match2, path_match3, header1, header2, header3, (path_match4,header6), (path_match5,header7)]
     /// filter_vec:[url_rewrite1,url_rewrite2,request_header_modifier1,request_header_modifier2,url_rewrite3,request_header_modifier4]
     ///
-    ///
-    fn into_kube_httproute(self) -> (Vec<HttpRouteMatch>, Vec<HttpRouteFilter>);
+    /// # Errors
+    /// The cookie, source ip, not and any matches can't be represented by gateway api.
+    fn into_kube_httproute(self) -> BoxResult<(Vec<HttpRouteMatch>, Vec<HttpRouteFilter>)>;
 }
 impl SgHttpRouteMatchConv for SgHttpRouteMatch {
-    fn into_kube_httproute(self) -> (Vec<HttpRouteMatch>, Vec<HttpRouteFilter>) {
+    fn into_kube_httproute(self) -> BoxResult<(Vec<HttpRouteMatch>, Vec<HttpRouteFilter>)> {
+        if self.cookie.is_some() {
+            return Err("cookie match is not supported by k8s config backend".into());
+        }
+        if self.source_ip.is_some() {
+            return Err("source ip match is not supported by k8s config backend".into());
+        }
+        if self.not.is_some() {
+            return Err("not match is not supported by k8s config backend".into());
+        }
+        if self.any.is_some() {
+            return Err("any match is not supported by k8s config backend".into());
+        }
+        let path = self.path.map(|p| p.into_kube_httproute()).transpose()?;
         // TODO: not complete
         let (match_vec, plugins) = if let Some(method_vec) = self.method {
             method_vec
                 .into_iter()
                 .map(|m| {
-                    let (path, plugin) = self
-                        .path
-                        .clone()
-                        .map(|p| {
-                            let (path, plugin) = p.into_kube_httproute();
-                            (Some(path), plugin)
-                        })
-                        .unwrap_or((None, None));
+                    let (path, plugin) = path.clone().map(|(path, plugin)| (Some(path), plugin)).unwrap_or((None, None));
 
                     let (header_path, _) = self
                         .header
@@ -340,14 +358,7 @@ impl SgHttpRouteMatchConv for SgHttpRouteMatch {
                 })
                 .unzip()
         } else {
-            let (path, plugin) = self
-                .path
-                .clone()
-                .map(|p| {
-                    let (path, plugin) = p.into_kube_httproute();
-                    (Some(path), plugin)
-                })
-                .unwrap_or((None, None));
+            let (path, plugin) = path.map(|(path, plugin)| (Some(path), plugin)).unwrap_or((None, None));
             (
                 vec![HttpRouteMatch {
                     path,
@@ -359,7 +370,7 @@ impl SgHttpRouteMatchConv for SgHttpRouteMatch {
                 vec![plugin],
             )
         };
-        (match_vec, plugins.into_iter().flatten().collect())
+        Ok((match_vec, plugins.into_iter().flatten().collect()))
     }
 
     fn from_kube_httproute(route_match: HttpRouteMatch) -> SgHttpRouteMatch {
@@ -368,17 +379,24 @@ impl SgHttpRouteMatchConv for SgHttpRouteMatch {
             path: route_match.path.map(SgHttpPathMatch::from_kube_httproute),
             header: route_match.headers.map(|h_vec| h_vec.into_iter().map(SgHttpHeaderMatch::from_kube_httproute).collect::<Vec<_>>()),
             query: route_match.query_params.map(|q_vec| q_vec.into_iter().map(SgHttpQueryMatch::from_kube_httproute).collect::<Vec<_>>()),
+            grpc: None,
+            cookie: None,
+            source_ip: None,
+            not: None,
+            any: None,
         }
     }
 }
 
 pub(crate) trait SgHttpPathMatchConv {
     fn from_kube_httproute(path_match: HttpPathMatch) -> SgHttpPathMatch;
-    fn into_kube_httproute(self) -> (HttpPathMatch, Option<HttpRouteFilter>);
+    /// # Errors
+    /// The replacement of a path template can't be represented by gateway api.
+    fn into_kube_httproute(self) -> BoxResult<(HttpPathMatch, Option<HttpRouteFilter>)>;
 }
 impl SgHttpPathMatchConv for SgHttpPathMatch {
-    fn into_kube_httproute(self) -> (HttpPathMatch, Option<HttpRouteFilter>) {
-        match self {
+    fn into_kube_httproute(self) -> BoxResult<(HttpPathMatch, Option<HttpRouteFilter>)> {
+        Ok(match self {
             SgHttpPathMatch::Exact { value, replace } => (
                 HttpPathMatch::Exact { value },
                 replace.filter(|r| !r.trim().is_empty()).map(|r| HttpRouteFilter::URLRewrite {
@@ -406,7 +424,30 @@ impl SgHttpPathMatchConv for SgHttpPathMatch {
                     },
                 }),
             ),
-        }
+            // gateway api has no path template, so it's converted into a regular expression
+            SgHttpPathMatch::Template { value, replace } => {
+                if replace.is_some_and(|r| !r.trim().is_empty()) {
+                    return Err(format!("replace of path template {value} is not supported by k8s config backend").into());
+                }
+                let segments = value
+                    .split('/')
+                    .filter(|s| !s.is_empty())
+                    .map(|segment| {
+                        if segment.starts_with('{') && segment.ends_with('}') {
+                            "[^/]+".to_string()
+                        } else {
+                            regex::escape(segment)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                (
+                    HttpPathMatch::RegularExpression {
+                        value: format!("^/{}/?$", segments.join("/")),
+                    },
+                    None,
+                )
+            }
+        })
     }
 
     fn from_kube_httproute(path_match: HttpPathMatch) -> SgHttpPathMatch {
@@ -479,7 +520,8 @@ impl SgHttpQueryMatchConv for SgHttpQueryMatch {
 #[cfg(test)]
 mod tests {
     use super::SgHttpRouteMatchConv;
-    use spacegate_model::{SgHttpPathMatch, SgHttpRouteMatch};
+    use k8s_gateway_api::HttpPathMatch;
+    use spacegate_model::{SgHttpCookieMatch, SgHttpPathMatch, SgHttpRouteMatch};
 
     #[test]
     fn empty_path_replace_does_not_create_a_url_rewrite_filter() {
@@ -491,12 +533,57 @@ mod tests {
             header: None,
             query: None,
             method: None,
+            grpc: None,
+            cookie: None,
+            source_ip: None,
+            not: None,
+            any: None,
         };
 
-        let (_, filters) = route_match.into_kube_httproute();
+        let (_, filters) = route_match.into_kube_httproute().expect("convert match");
 
         assert!(filters.is_empty());
     }
+
+    #[test]
+    fn unrepresentable_matches_are_rejected() {
+        let cookie = SgHttpRouteMatch {
+            cookie: Some(vec![SgHttpCookieMatch::Exact {
+                name: "canary".to_string(),
+                value: "true".to_string(),
+            }]),
+            ..Default::default()
+        };
+        let source_ip = SgHttpRouteMatch {
+            source_ip: Some(vec!["10.0.0.0/8".to_string()]),
+            ..Default::default()
+        };
+        let not = SgHttpRouteMatch {
+            not: Some(Box::default()),
+            ..Default::default()
+        };
+        let any = SgHttpRouteMatch {
+            any: Some(vec![SgHttpRouteMatch::default()]),
+            ..Default::default()
+        };
+        for route_match in [cookie, source_ip, not, any] {
+            assert!(route_match.into_kube_httproute().is_err());
+        }
+    }
+
+    #[test]
+    fn path_template_replace_is_rejected() {
+        let template = |replace: Option<&str>| SgHttpRouteMatch {
+            path: Some(SgHttpPathMatch::Template {
+                value: "/users/{id}".to_string(),
+                replace: replace.map(str::to_string),
+            }),
+            ..Default::default()
+        };
+        let (matches, _) = template(None).into_kube_httproute().expect("convert template");
+        assert!(matches!(matches.first().and_then(|m| m.path.as_ref()), Some(HttpPathMatch::RegularExpression { value }) if value == "^/users/[^/]+/?$"));
+        assert!(template(Some("/profile/{id}")).into_kube_httproute().is_err());
+    }
 }
 
 pub(crate) trait SgBackendRefConv {
@@ -542,6 +629,8 @@ impl SgBackendRefConv for SgBackendRef {
                 weight: self.weight,
                 timeout_ms: self.timeout_ms,
                 timeout_mode: self.timeout_mode,
+                connect_timeout_ms: self.connect_timeout_ms,
+                first_byte_timeout_ms: self.first_byte_timeout_ms,
                 inner: backend_inner_ref,
                 downgrade_http2: self.downgrade_http2,
             }),
@@ -582,6 +671,8 @@ impl SgBackendRefConv for SgBackendRef {
                     port: backend.inner.port,
                     timeout_ms: backend.timeout_ms,
                     timeout_mode: backend.timeout_mode,
+                    connect_timeout_ms: backend.connect_timeout_ms,
+                    first_byte_timeout_ms: backend.first_byte_timeout_ms,
                     protocol,
                     weight: backend.weight,
                     plugins: ext_plugins
@@ -591,6 +682,8 @@ impl SgBackendRefConv for SgBackendRef {
                         .map(|(index, id)| PluginBinding::from(id).with_priority(1000 - index as i32 * 100))
                         .collect(),
                     downgrade_http2: backend.downgrade_http2,
+                    health_check: None,
+                    tls: None,
                 })
             })
             .transpose()
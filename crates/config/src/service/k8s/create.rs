@@ -18,7 +18,7 @@ use super::{
 
 impl Create for K8s {
     async fn create_config_item_gateway(&self, _gateway_name: &str, gateway: crate::model::SgGateway) -> BoxResult<()> {
-        let (gateway, secret, plugin_ids) = gateway.to_kube_gateway(&self.namespace, &self.gateway_class_name);
+        let (gateway, secret, plugin_ids) = gateway.to_kube_gateway(&self.namespace, &self.gateway_class_name)?;
 
         let gateway_api: Api<Gateway> = self.get_namespace_api();
         gateway_api.create(&PostParams::default(), &gateway).await?;
@@ -36,7 +36,7 @@ impl Create for K8s {
     }
 
     async fn create_config_item_route(&self, gateway_name: &str, route_name: &str, route: crate::model::SgRoute) -> BoxResult<()> {
-        let route = route.to_kube_route(gateway_name, route_name, &self.namespace);
+        let route = route.to_kube_route(gateway_name, route_name, &self.namespace)?;
         let target_ref = route.to_target_ref();
         match &route {
             KubeRoute::Http(http_spaceroute, _) => {
@@ -18,7 +18,7 @@ impl Delete for K8s {
         let gateway_api: Api<Gateway> = self.get_namespace_api();
 
         if let Some(sg_gateway) = self.retrieve_config_item_gateway(gateway_name).await? {
-            let (gateway, secret, delete_plugin_ids) = sg_gateway.to_kube_gateway(&self.namespace, &self.gateway_class_name);
+            let (gateway, secret, delete_plugin_ids) = sg_gateway.to_kube_gateway(&self.namespace, &self.gateway_class_name)?;
 
             if let Some(secret) = secret {
                 let secret_api: Api<Secret> = self.get_namespace_api();
@@ -40,7 +40,7 @@ impl Delete for K8s {
         let mcp_route_api: Api<McpRoute> = self.get_namespace_api();
 
         if let Some(sg_http_route) = self.retrieve_config_item_route(gateway_name, route_name).await? {
-            let route = sg_http_route.to_kube_route(gateway_name, route_name, &self.namespace);
+            let route = sg_http_route.to_kube_route(gateway_name, route_name, &self.namespace)?;
             let target_ref = route.to_target_ref();
             for binding in route.plugin_bindings() {
                 binding.id.remove_filter_target(target_ref.clone(), self).await?;
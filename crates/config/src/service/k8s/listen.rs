@@ -345,6 +345,7 @@ impl CreateListener for K8s {
                     .flat_map(|route| match route {
                         spacegate_model::SgRoute::Http(route) => route.plugins.clone(),
                         spacegate_model::SgRoute::Mcp(route) => route.plugins.clone(),
+                        spacegate_model::SgRoute::Tls(_) | spacegate_model::SgRoute::Tcp(_) | spacegate_model::SgRoute::Udp(_) => Vec::new(),
                     })
                     .collect::<Vec<_>>();
                 plugin_ids.extend(route_plugin_ids);
@@ -239,11 +239,12 @@ impl Retrieve for K8s {
 
 impl K8s {
     pub(crate) const HTTP2_KEY: &'static str = "http2";
-    pub(crate) const HTTP2_ENABLE: &'static str = "true";
-    // query is http2 enabled?
-    fn retrieve_http2_config(tls_config: &k8s_gateway_api::GatewayTlsConfig) -> bool {
+    pub(crate) const HTTP3_KEY: &'static str = "http3";
+    pub(crate) const TLS_OPTION_ENABLE: &'static str = "true";
+    // query is a tls option like http2 enabled?
+    fn retrieve_tls_option_enabled(tls_config: &k8s_gateway_api::GatewayTlsConfig, key: &str) -> bool {
         if let Some(options) = &tls_config.options {
-            if let Some(Self::HTTP2_ENABLE) = options.get(Self::HTTP2_KEY).map(String::as_str) {
+            if let Some(Self::TLS_OPTION_ENABLE) = options.get(key).map(String::as_str) {
                 return true;
             }
         }
@@ -332,6 +333,7 @@ impl K8s {
                 .collect::<BoxResult<Vec<_>>>()?,
             plugins,
             timeout_mode: mcp_route.spec.timeout_mode,
+            timeout_ms: mcp_route.spec.timeout_ms,
             session_affinity: mcp_route.spec.session_affinity,
         };
         self.apply_higress_wasm_mcp_route_plugins(&mut route, namespace).await?;
@@ -409,7 +411,12 @@ impl K8s {
                                                             mode: tls_config.mode.clone().into(),
                                                             key: String::from_utf8(tls_key.0.clone()).expect("[SG.Config] Gateway tls secret [tls.key] is not valid utf8"),
                                                             cert: String::from_utf8(tls_crt.0.clone()).expect("[SG.Config] Gateway tls secret [tls.cert] is not valid utf8"),
-                                                            http2: Some(Self::retrieve_http2_config(tls_config)),
+                                                            http2: Some(Self::retrieve_tls_option_enabled(tls_config, Self::HTTP2_KEY)),
+                                                            http3: Some(Self::retrieve_tls_option_enabled(tls_config, Self::HTTP3_KEY)),
+                                                            key_path: None,
+                                                            cert_path: None,
+                                                            certs: Vec::new(),
+                                                            client_auth: None,
                                                         })
                                                     } else {
                                                         tracing::warn!("[SG.Config] Gateway [spec.listener.protocol=https] tls.key is empty");
@@ -444,6 +451,10 @@ impl K8s {
                             _ => return Err("Unsupported protocol".into()),
                         },
                         hostname: listener.hostname.clone(),
+                        // the Gateway API listener has no field for PROXY protocol
+                        proxy_protocol: None,
+                        connection_limits: None,
+                        tcp_services: Vec::new(),
                     };
                     Ok(sg_listener)
                 })
@@ -24,14 +24,14 @@ use super::{
 
 impl Update for K8s {
     async fn update_config_item_gateway(&self, gateway_name: &str, gateway: crate::model::SgGateway) -> BoxResult<()> {
-        let (mut gateway, secret, update_plugin_ids) = gateway.to_kube_gateway(&self.namespace, &self.gateway_class_name);
+        let (mut gateway, secret, update_plugin_ids) = gateway.to_kube_gateway(&self.namespace, &self.gateway_class_name)?;
 
         let gateway_api: Api<Gateway> = self.get_namespace_api();
         let old_gateway = self
             .retrieve_config_item_gateway(gateway_name)
             .await?
             .map(|g| g.to_kube_gateway(&self.namespace, &self.gateway_class_name))
-            .ok_or_else(|| -> BoxError { format!("[Sg.Config] gateway [{gateway_name}] not found ,update failed").into() })?;
+            .ok_or_else(|| -> BoxError { format!("[Sg.Config] gateway [{gateway_name}] not found ,update failed").into() })??;
 
         gateway.metadata.resource_version = gateway_api.get_metadata(gateway_name).await?.resource_version();
         gateway_api.replace(gateway_name, &PostParams::default(), &gateway).await?;
@@ -58,7 +58,7 @@ impl Update for K8s {
     }
 
     async fn update_config_item_route(&self, gateway_name: &str, route_name: &str, route: crate::model::SgRoute) -> BoxResult<()> {
-        let mut kube_route = route.to_kube_route(gateway_name, route_name, &self.namespace);
+        let mut kube_route = route.to_kube_route(gateway_name, route_name, &self.namespace)?;
 
         let http_spaceroute_api: Api<HttpSpaceroute> = self.get_namespace_api();
         let http_route_api: Api<HttpRoute> = self.get_namespace_api();
@@ -98,7 +98,7 @@ impl Update for K8s {
         };
 
         self.update_plugin_ids_changes(
-            old_sg_httproute.map(|r| r.to_kube_route(gateway_name, route_name, &self.namespace).plugin_bindings().to_vec()).unwrap_or_default(),
+            old_sg_httproute.map(|r| r.to_kube_route(gateway_name, route_name, &self.namespace)).transpose()?.map(|r| r.into_plugin_bindings()).unwrap_or_default(),
             kube_route.plugin_bindings().to_vec(),
             kube_route.to_target_ref(),
         )
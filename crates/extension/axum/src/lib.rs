@@ -90,6 +90,12 @@ impl GlobalAxumServer {
         wg.start().await
     }
 
+    /// Start the server on a bound listener instead of binding the address, if the server is already running, it will be restarted.
+    pub async fn start_with_listener(&self, tcp_listener: tokio::net::TcpListener) -> Result<(), std::io::Error> {
+        let mut wg = self.0.write().await;
+        wg.start_with_listener(tcp_listener).await
+    }
+
     /// Shutdown the server.
     pub async fn shutdown(&self) -> Result<(), std::io::Error> {
         let mut wg = self.0.write().await;
@@ -105,11 +111,19 @@ impl AxumServerInner {
     pub async fn start(&mut self) -> Result<(), std::io::Error> {
         let _shutdown_result = self.shutdown().await;
         let tcp_listener = tokio::net::TcpListener::bind(self.bind).await?;
+        self.serve(tcp_listener);
+        Ok(())
+    }
+    pub async fn start_with_listener(&mut self, tcp_listener: tokio::net::TcpListener) -> Result<(), std::io::Error> {
+        let _shutdown_result = self.shutdown().await;
+        self.serve(tcp_listener);
+        Ok(())
+    }
+    fn serve(&mut self, tcp_listener: tokio::net::TcpListener) {
         let cancel = self.cancel_token.clone();
         let router = self.router.clone();
         let task = tokio::spawn(async move { axum::serve(tcp_listener, router).with_graceful_shutdown(cancel.cancelled_owned()).await });
         self.handle = Some(task);
-        Ok(())
     }
     pub async fn shutdown(&mut self) -> Result<(), std::io::Error> {
         if let Some(handle) = self.handle.take() {
@@ -0,0 +1,63 @@
+//! Compare routing by the compiled path tree with scanning rules linearly.
+//!
+//! Run with `cargo bench -p spacegate-kernel --bench router`.
+use std::convert::Infallible;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use hyper::{service::service_fn, Request, Response};
+use regex::Regex;
+use spacegate_kernel::{
+    service::{
+        http_gateway::{create_http_router, path_tree::RoutePrecedence},
+        http_route::{match_request::HttpPathMatchRewrite, HttpRoute, HttpRouteRule},
+    },
+    ArcHyperService, SgBody,
+};
+
+/// Routes of tenants, each has a prefix and an exact path, and every tenth also has a regex.
+fn tenant_routes(count: usize) -> Vec<HttpRoute> {
+    (0..count)
+        .map(|i| {
+            let mut route = HttpRoute::builder()
+                .name(format!("tenant-{i}"))
+                .rule(HttpRouteRule::builder().match_item(HttpPathMatchRewrite::exact(format!("/tenant-{i}/health"))).build())
+                .rule(HttpRouteRule::builder().match_item(HttpPathMatchRewrite::prefix(format!("/tenant-{i}/api"))).build());
+            if i % 10 == 0 {
+                let regex = Regex::new(&format!(r"^/tenant-{i}/files/.*\.png$")).expect("invalid regex");
+                route = route.rule(HttpRouteRule::builder().match_item(HttpPathMatchRewrite::regex(regex)).build());
+            }
+            route.build()
+        })
+        .collect()
+}
+
+fn bench_route(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("fail to build runtime");
+    let _guard = runtime.enter();
+    let fallback = ArcHyperService::new(service_fn(|_: Request<SgBody>| async { Ok::<_, Infallible>(Response::new(SgBody::empty())) }));
+    let mut group = c.benchmark_group("route");
+    for count in [10, 100, 1000, 5000] {
+        let routes = tenant_routes(count);
+        let service = create_http_router(routes.iter(), fallback.clone(), RoutePrecedence::Priority);
+        let tree = service.router().hostname_tree.get("example.com").expect("routes for all hostnames");
+        let requests = [
+            ("hit-last", format!("/tenant-{}/api/users", count - 1)),
+            ("hit-regex", "/tenant-0/files/logo.png".to_string()),
+            ("miss", "/unknown/path".to_string()),
+        ];
+        for (case, path) in requests {
+            let req = Request::get(path).body(SgBody::empty()).expect("invalid request");
+            assert_eq!(tree.find(&req).map(|c| (c.route, c.rule)), tree.find_linear(&req).map(|c| (c.route, c.rule)));
+            group.bench_with_input(BenchmarkId::new(format!("tree/{case}"), count), &req, |b, req| {
+                b.iter(|| tree.find(black_box(req)).is_some())
+            });
+            group.bench_with_input(BenchmarkId::new(format!("linear/{case}"), count), &req, |b, req| {
+                b.iter(|| tree.find_linear(black_box(req)).is_some())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_route);
+criterion_main!(benches);
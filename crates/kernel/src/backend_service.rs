@@ -3,10 +3,14 @@ use std::sync::Arc;
 
 use futures_util::future::BoxFuture;
 use futures_util::Future;
-use hyper::{header::UPGRADE, Request, Response, StatusCode};
+use hyper::{
+    header::{SEC_WEBSOCKET_EXTENSIONS, UPGRADE},
+    Request, Response, StatusCode,
+};
 use tracing::instrument;
 
-use crate::backend_service::http_client_service::get_client;
+use crate::backend_service::http_client_service::{get_client, HttpClient};
+use crate::extension::{GatewayName, WebSocketForward};
 use crate::helper_layers::map_future::MapFuture;
 use crate::utils::x_forwarded_for;
 use crate::BoxError;
@@ -79,11 +83,24 @@ impl hyper::service::Service<Request<SgBody>> for ArcHyperService {
 /// # Errors
 /// 1. Fail to collect body chunks
 /// 2. Fail to upgrade
-pub async fn http_backend_service_inner(mut req: Request<SgBody>) -> Result<SgResponse, BoxError> {
+pub async fn http_backend_service_inner(req: Request<SgBody>) -> Result<SgResponse, BoxError> {
+    http_backend_service_inner_with_client(req, get_client()).await
+}
+
+/// Http backend service which sends requests by the given client.
+///
+/// # Errors
+/// See [`http_backend_service_inner`].
+pub async fn http_backend_service_inner_with_client(mut req: Request<SgBody>, mut client: HttpClient) -> Result<SgResponse, BoxError> {
     tracing::trace!(elapsed = ?req.extensions().get::<crate::extension::EnterTime>().map(crate::extension::EnterTime::elapsed), "start a backend request");
     x_forwarded_for(&mut req)?;
-    let mut client = get_client();
     let response = if req.headers().get(UPGRADE).is_some_and(|upgrade| upgrade.as_bytes().eq_ignore_ascii_case(b"websocket")) {
+        let forward = req.extensions().get::<WebSocketForward>().cloned().unwrap_or_default();
+        let gateway = req.extensions().get::<GatewayName>().map(|g| g.to_string()).unwrap_or_else(|| "unknown".to_string());
+        if !forward.is_passthrough() {
+            // frames are parsed, so no extension could be negotiated
+            req.headers_mut().remove(SEC_WEBSOCKET_EXTENSIONS);
+        }
         // dump request
         let (part, body) = req.into_parts();
         let body = body.dump().await?;
@@ -107,7 +124,11 @@ pub async fn http_backend_service_inner(mut req: Request<SgBody>) -> Result<SgRe
             let upgrade_as_server = s?;
             let upgrade_as_client = c?;
             // start a websocket forward
-            ws_client_service::tcp_transfer(upgrade_as_server, upgrade_as_client).await?;
+            if forward.is_passthrough() {
+                ws_client_service::tcp_transfer(upgrade_as_server, upgrade_as_client, gateway).await;
+            } else {
+                ws_client_service::websocket_transfer(upgrade_as_server, upgrade_as_client, forward, gateway).await;
+            }
             <Result<(), BoxError>>::Ok(())
         });
         // return response to client
@@ -120,7 +141,16 @@ pub async fn http_backend_service_inner(mut req: Request<SgBody>) -> Result<SgRe
 
 #[instrument]
 pub async fn http_backend_service(req: Request<SgBody>) -> Result<Response<SgBody>, Infallible> {
-    match http_backend_service_inner(req).await {
+    http_backend_service_with_client(req, get_client()).await
+}
+
+/// Http backend service which sends requests by the given client.
+///
+/// # Errors
+/// Never, client errors are responded as `502 Bad Gateway`.
+#[instrument(skip(client))]
+pub async fn http_backend_service_with_client(req: Request<SgBody>, client: HttpClient) -> Result<Response<SgBody>, Infallible> {
+    match http_backend_service_inner_with_client(req, client).await {
         Ok(resp) => Ok(resp),
         Err(err) => Ok(Response::with_code_message(StatusCode::BAD_GATEWAY, format!("[Sg.Client] Client error: {err}"))),
     }
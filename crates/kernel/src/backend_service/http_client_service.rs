@@ -1,9 +1,9 @@
-use crate::{extension::Reflect, SgBody, SgResponseExt};
+use crate::{extension::Reflect, service::tls_cert::root_cert_store_from_pem, BoxResult, SgBody, SgResponseExt};
 
-use hyper::StatusCode;
+use hyper::{http::uri::Scheme, StatusCode, Version};
 use hyper::{Request, Response};
 use hyper_rustls::HttpsConnector;
-use hyper_rustls::{ConfigBuilderExt, HttpsConnectorBuilder};
+use hyper_rustls::{ConfigBuilderExt, FixedServerNameResolver, HttpsConnectorBuilder};
 use hyper_util::{
     client::legacy::{connect::HttpConnector, Client},
     rt::TokioExecutor,
@@ -13,7 +13,12 @@ use std::{
     sync::{Arc, Mutex, OnceLock, RwLock},
     time::Duration,
 };
-use tokio_rustls::rustls::{self, client::danger::ServerCertVerifier, SignatureScheme};
+use tokio_rustls::rustls::{
+    self,
+    client::danger::ServerCertVerifier,
+    pki_types::{CertificateDer, ServerName},
+    RootCertStore, SignatureScheme,
+};
 
 #[derive(Debug, Clone)]
 pub struct NoCertificateVerification {}
@@ -74,25 +79,110 @@ fn get_rustls_config_dangerous() -> rustls::ClientConfig {
     config
 }
 
-pub fn get_client() -> HttpClient {
-    ClientRepo::global().read().expect("failed to read global client repo").get_default()
+/// Tls settings of the connections to upstreams.
+///
+/// Clients are cached in [`ClientRepo`] by their profiles, so the backends sharing the same profile share the connection pool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientTlsProfile {
+    /// PEM bundle of the trusted CAs, it replaces the native trust roots.
+    pub ca: Option<Arc<[u8]>>,
+    /// PEM encoded client certificate chain and its private key, for upstreams which require mutual tls.
+    pub client_cert: Option<(Arc<[u8]>, Arc<[u8]>)>,
+    /// Server name sent by SNI and verified against the upstream certificate, instead of the host of request uri.
+    pub sni: Option<String>,
+    /// Verify the upstream certificate, default is true.
+    pub verify: bool,
+    /// Timeout of establishing connections, it's a setting of the connector, so the clients are cached by it as well.
+    pub connect_timeout: Option<Duration>,
 }
 
-#[derive(Debug)]
-pub struct ClientRepo {
-    default: HttpClient,
-    repo: Mutex<HashMap<String, HttpClient>>,
+impl Default for ClientTlsProfile {
+    fn default() -> Self {
+        Self {
+            ca: None,
+            client_cert: None,
+            sni: None,
+            verify: true,
+            connect_timeout: None,
+        }
+    }
 }
 
-impl Default for ClientRepo {
-    fn default() -> Self {
-        let config = get_rustls_config_dangerous();
-        let default = HttpClient::new(config);
+impl ClientTlsProfile {
+    /// A profile which doesn't verify the upstream certificate at all.
+    pub fn dangerous() -> Self {
         Self {
-            default,
-            repo: Default::default(),
+            verify: false,
+            ..Default::default()
         }
     }
+
+    pub fn with_ca(mut self, ca: impl Into<Arc<[u8]>>) -> Self {
+        self.ca = Some(ca.into());
+        self
+    }
+
+    pub fn with_client_cert(mut self, cert: impl Into<Arc<[u8]>>, key: impl Into<Arc<[u8]>>) -> Self {
+        self.client_cert = Some((cert.into(), key.into()));
+        self
+    }
+
+    pub fn with_sni(mut self, sni: impl Into<String>) -> Self {
+        self.sni = Some(sni.into());
+        self
+    }
+
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Build the rustls config of this profile.
+    ///
+    /// # Errors
+    /// If the CA bundle, the client certificate or its key is invalid.
+    pub fn client_config(&self) -> BoxResult<rustls::ClientConfig> {
+        let _ = tokio_rustls::rustls::crypto::ring::default_provider().install_default();
+        let builder = rustls::ClientConfig::builder();
+        let builder = if !self.verify {
+            builder.dangerous().with_custom_certificate_verifier(Arc::new(NoCertificateVerification {}))
+        } else if let Some(ca) = &self.ca {
+            builder.with_root_certificates(root_cert_store_from_pem(ca)?)
+        } else {
+            builder.with_native_roots().unwrap_or_else(|e| {
+                tracing::warn!("[Sg.Client] fail to load native root certificates, no upstream certificate could be verified: {e}");
+                rustls::ClientConfig::builder().with_root_certificates(RootCertStore::empty())
+            })
+        };
+        let config = match &self.client_cert {
+            Some((cert, key)) => {
+                let certs = rustls_pemfile::certs(&mut cert.as_ref()).collect::<Result<Vec<CertificateDer<'static>>, _>>()?;
+                if certs.is_empty() {
+                    return Err("no client certificate found in pem".into());
+                }
+                let key = rustls_pemfile::private_key(&mut key.as_ref())?.ok_or("no client private key found in pem")?;
+                builder.with_client_auth_cert(certs, key)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        Ok(config)
+    }
+}
+
+pub fn get_client() -> HttpClient {
+    ClientRepo::global().read().expect("failed to read global client repo").get_default()
+}
+
+#[derive(Debug, Default)]
+pub struct ClientRepo {
+    default: HttpClient,
+    repo: Mutex<HashMap<String, HttpClient>>,
+    profiles: Mutex<HashMap<ClientTlsProfile, HttpClient>>,
 }
 
 static GLOBAL: OnceLock<Arc<RwLock<ClientRepo>>> = OnceLock::new();
@@ -109,6 +199,24 @@ impl ClientRepo {
     pub fn register(&self, code: &str, client: HttpClient) {
         self.repo.lock().expect("failed to lock client repo").insert(code.to_string(), client);
     }
+    /// Get the client of a tls profile, it will be created and cached if not exists.
+    ///
+    /// The default profile always gets the default client.
+    ///
+    /// # Errors
+    /// If the client config could not be built from the profile, see [`ClientTlsProfile::client_config`].
+    pub fn get_or_create(&self, profile: &ClientTlsProfile) -> BoxResult<HttpClient> {
+        if *profile == ClientTlsProfile::default() {
+            return Ok(self.get_default());
+        }
+        let mut profiles = self.profiles.lock().expect("failed to lock client repo");
+        if let Some(client) = profiles.get(profile) {
+            return Ok(client.clone());
+        }
+        let client = HttpClient::with_profile(profile)?;
+        profiles.insert(profile.clone(), client.clone());
+        Ok(client)
+    }
     pub fn set_default(&mut self, client: HttpClient) {
         self.default = client;
     }
@@ -129,24 +237,43 @@ pub struct SgHttpClientConfig {
 #[derive(Debug, Clone)]
 pub struct HttpClient {
     inner: Client<HttpsConnector<HttpConnector>, SgBody>,
+    /// http2 with prior knowledge, for gRPC requests to cleartext backends
+    h2c: Client<HttpsConnector<HttpConnector>, SgBody>,
 }
 
 impl Default for HttpClient {
     fn default() -> Self {
-        Self::new(rustls::ClientConfig::builder().with_native_roots().expect("failed to init rustls config").with_no_client_auth())
+        Self::with_profile(&ClientTlsProfile::default()).expect("failed to init rustls config")
     }
 }
 
 impl HttpClient {
-    pub fn new(tls_config: rustls::ClientConfig) -> Self {
+    fn from_connector(connector: HttpsConnector<HttpConnector>) -> Self {
         HttpClient {
-            inner: Client::builder(TokioExecutor::new()).build(HttpsConnectorBuilder::new().with_tls_config(tls_config).https_or_http().enable_http1().enable_http2().build()),
+            inner: Client::builder(TokioExecutor::new()).build(connector.clone()),
+            h2c: Client::builder(TokioExecutor::new()).http2_only(true).build(connector),
         }
     }
+    pub fn new(tls_config: rustls::ClientConfig) -> Self {
+        Self::from_connector(HttpsConnectorBuilder::new().with_tls_config(tls_config).https_or_http().enable_http1().enable_http2().build())
+    }
     pub fn new_h1_only(tls_config: rustls::ClientConfig) -> Self {
-        HttpClient {
-            inner: Client::builder(TokioExecutor::new()).build(HttpsConnectorBuilder::new().with_tls_config(tls_config).https_or_http().enable_http1().build()),
-        }
+        Self::from_connector(HttpsConnectorBuilder::new().with_tls_config(tls_config).https_or_http().enable_http1().build())
+    }
+    /// Create a client by a tls profile, prefer [`ClientRepo::get_or_create`] to share the clients.
+    ///
+    /// # Errors
+    /// If the client config could not be built from the profile, see [`ClientTlsProfile::client_config`].
+    pub fn with_profile(profile: &ClientTlsProfile) -> BoxResult<Self> {
+        let connector = HttpsConnectorBuilder::new().with_tls_config(profile.client_config()?).https_or_http();
+        let connector = match &profile.sni {
+            Some(sni) => connector.with_server_name_resolver(FixedServerNameResolver::new(ServerName::try_from(sni.clone())?)),
+            None => connector,
+        };
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_connect_timeout(profile.connect_timeout);
+        Ok(Self::from_connector(connector.enable_http1().enable_http2().wrap_connector(http)))
     }
     pub fn new_dangerous() -> Self {
         let config = get_rustls_config_dangerous();
@@ -156,9 +283,20 @@ impl HttpClient {
         let config = get_rustls_config_dangerous();
         Self::new_h1_only(config)
     }
+    /// Send the request, gRPC requests are always sent by http2, with prior knowledge if the backend is cleartext.
     pub async fn request(&mut self, mut req: Request<SgBody>) -> Response<SgBody> {
         let reflect = req.extensions_mut().remove::<Reflect>();
-        match self.inner.request(req).await.map_err(Response::bad_gateway) {
+        let client = if crate::utils::grpc::is_grpc_request(&req) {
+            *req.version_mut() = Version::HTTP_2;
+            if req.uri().scheme() == Some(&Scheme::HTTPS) {
+                &self.inner
+            } else {
+                &self.h2c
+            }
+        } else {
+            &self.inner
+        };
+        match client.request(req).await.map_err(Response::bad_gateway) {
             Ok(mut response) => {
                 if let Some(reflect) = reflect {
                     response.extensions_mut().extend(reflect.into_inner());
@@ -1,23 +1,206 @@
+use futures_util::{SinkExt, StreamExt};
 use hyper::upgrade::Upgraded;
 use hyper_util::rt::TokioIo;
-use tokio::{self};
+use tokio::time::Instant;
+use tokio_tungstenite::{
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame, Role, WebSocketConfig},
+        Error as WsError, Message,
+    },
+    WebSocketStream,
+};
 
-use crate::BoxError;
+use crate::{
+    extension::{TextMessageAction, WebSocketForward, WsDirection},
+    observability::{record_websocket_connection, WebSocketDirectionStats, WebSocketStats},
+};
 
-/// transfer data between 2 tcp upgraded services
-pub(super) async fn tcp_transfer(as_server: Upgraded, as_client: Upgraded) -> Result<(), BoxError> {
+type WsStream = WebSocketStream<TokioIo<Upgraded>>;
+
+/// What to do after a message is received.
+enum Step {
+    Send(Message),
+    Skip,
+    /// Close both sides, the frame is sent to the other side.
+    Close(CloseFrame),
+}
+
+fn close_frame(code: CloseCode, reason: &str) -> CloseFrame {
+    CloseFrame { code, reason: reason.into() }
+}
+
+fn on_message(forward: &WebSocketForward, direction: WsDirection, message: Message, stats: &mut WebSocketDirectionStats) -> Step {
+    stats.frames += 1;
+    match message {
+        Message::Text(text) => {
+            stats.messages += 1;
+            stats.bytes += text.len() as u64;
+            match forward.on_text(direction, text.as_str()) {
+                TextMessageAction::Forward => Step::Send(Message::Text(text)),
+                TextMessageAction::Replace(text) => Step::Send(Message::Text(text.into())),
+                TextMessageAction::Drop => Step::Skip,
+                TextMessageAction::Reject(reason) => Step::Close(close_frame(CloseCode::Policy, &reason)),
+            }
+        }
+        Message::Binary(data) => {
+            stats.messages += 1;
+            stats.bytes += data.len() as u64;
+            Step::Send(Message::Binary(data))
+        }
+        // pings are answered by each side itself
+        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => Step::Skip,
+        Message::Close(frame) => Step::Close(frame.unwrap_or_else(|| close_frame(CloseCode::Normal, ""))),
+    }
+}
+
+fn on_error(error: &WsError) -> CloseFrame {
+    match error {
+        WsError::Capacity(_) => close_frame(CloseCode::Size, "message too big"),
+        WsError::Protocol(_) | WsError::Utf8(_) => close_frame(CloseCode::Protocol, "protocol error"),
+        _ => close_frame(CloseCode::Away, ""),
+    }
+}
+
+/// Forward messages until one side closes, returns the close frame.
+async fn forward_messages(forward: &WebSocketForward, downstream: &mut WsStream, upstream: &mut WsStream, stats: &mut WebSocketStats) -> CloseFrame {
+    let period = [forward.ping_interval, forward.idle_timeout.map(|idle| idle / 2)].into_iter().flatten().min();
+    let mut ticker = period.map(|period| tokio::time::interval_at(Instant::now() + period, period));
+    let (mut downstream_active, mut upstream_active) = (Instant::now(), Instant::now());
+    loop {
+        let (direction, step) = tokio::select! {
+            message = downstream.next() => {
+                downstream_active = Instant::now();
+                match message {
+                    Some(Ok(message)) => (WsDirection::Upstream, on_message(forward, WsDirection::Upstream, message, &mut stats.upstream)),
+                    Some(Err(e)) => return on_error(&e),
+                    None => return close_frame(CloseCode::Away, ""),
+                }
+            }
+            message = upstream.next() => {
+                upstream_active = Instant::now();
+                match message {
+                    Some(Ok(message)) => (WsDirection::Downstream, on_message(forward, WsDirection::Downstream, message, &mut stats.downstream)),
+                    Some(Err(e)) => return on_error(&e),
+                    None => return close_frame(CloseCode::Away, ""),
+                }
+            }
+            _ = async {
+                match ticker.as_mut() {
+                    Some(ticker) => ticker.tick().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                if let Some(idle) = forward.idle_timeout {
+                    if downstream_active.elapsed() > idle || upstream_active.elapsed() > idle {
+                        return close_frame(CloseCode::Away, "idle timeout");
+                    }
+                }
+                if forward.ping_interval.is_some() {
+                    let _ = downstream.send(Message::Ping(Default::default())).await;
+                    let _ = upstream.send(Message::Ping(Default::default())).await;
+                }
+                continue;
+            }
+        };
+        let target = match direction {
+            WsDirection::Upstream => &mut *upstream,
+            WsDirection::Downstream => &mut *downstream,
+        };
+        match step {
+            Step::Send(message) => {
+                if let Err(e) = target.send(message).await {
+                    return on_error(&e);
+                }
+            }
+            Step::Skip => {}
+            Step::Close(frame) => return frame,
+        }
+    }
+}
+
+async fn close_stream(stream: &mut WsStream, frame: CloseFrame) {
+    if stream.close(Some(frame)).await.is_err() {
+        let _ = stream.flush().await;
+    }
+}
+
+/// Transfer bytes between 2 upgraded connections as they are, only the duration is recorded as metrics.
+pub(super) async fn tcp_transfer(as_server: Upgraded, as_client: Upgraded, gateway: String) {
     let mut server_conn = TokioIo::new(as_server);
     let mut client_conn = TokioIo::new(as_client);
-    tokio::task::spawn(async move {
-        let result = tokio::io::copy_bidirectional(&mut server_conn, &mut client_conn).await;
-        match result {
-            Ok((server_to_client, client_to_server)) => {
-                tracing::debug!("[SG.Upgraded] connection closed, server to client bytes: {server_to_client}, client to server bytes: {client_to_server}");
+    let start = Instant::now();
+    match tokio::io::copy_bidirectional(&mut server_conn, &mut client_conn).await {
+        Ok((server_to_client, client_to_server)) => {
+            tracing::debug!("[SG.Upgraded] connection closed, server to client bytes: {server_to_client}, client to server bytes: {client_to_server}");
+        }
+        Err(error) => {
+            tracing::warn!("[SG.Upgraded] connection close error: {error}");
+        }
+    }
+    record_websocket_connection(gateway, start.elapsed(), &WebSocketStats::default());
+}
+
+/// Forward a websocket connection between 2 upgraded connections, `as_server` is the client side and `as_client` is the backend side.
+///
+/// Messages are counted and recorded as metrics after the connection is closed.
+pub(super) async fn websocket_transfer(as_server: Upgraded, as_client: Upgraded, forward: WebSocketForward, gateway: String) {
+    let mut config = WebSocketConfig::default();
+    // keep the default limit of tungstenite unless it's configured
+    if let Some(max_message_size) = forward.max_message_size {
+        config = config.max_message_size(Some(max_message_size));
+    }
+    let mut downstream = WebSocketStream::from_raw_socket(TokioIo::new(as_server), Role::Server, Some(config)).await;
+    let mut upstream = WebSocketStream::from_raw_socket(TokioIo::new(as_client), Role::Client, Some(config)).await;
+    let start = Instant::now();
+    let mut stats = WebSocketStats::default();
+    let frame = forward_messages(&forward, &mut downstream, &mut upstream, &mut stats).await;
+    // the close reply to the side which closes first is queued, and it's sent by flushing
+    let _ = tokio::join!(close_stream(&mut downstream, frame.clone()), close_stream(&mut upstream, frame.clone()));
+    let duration = start.elapsed();
+    tracing::debug!(
+        "[SG.Websocket] connection closed with {}, duration: {duration:?}, upstream messages: {}, downstream messages: {}",
+        frame.code,
+        stats.upstream.messages,
+        stats.downstream.messages
+    );
+    record_websocket_connection(gateway, duration, &stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_text_hooks() {
+        let mut forward = WebSocketForward::default();
+        forward.add_text_hook(|_, text: &str| TextMessageAction::Replace(text.replace("secret", "******")));
+        forward.add_text_hook(|direction, text: &str| {
+            if direction == WsDirection::Upstream && text.contains("admin") {
+                TextMessageAction::Reject("forbidden".to_string())
+            } else {
+                TextMessageAction::Forward
             }
-            Err(error) => {
-                tracing::warn!("[SG.Upgraded] connection close error: {error}");
+        });
+        let mut stats = WebSocketDirectionStats::default();
+        let Step::Send(Message::Text(text)) = on_message(&forward, WsDirection::Downstream, Message::Text("token: secret".into()), &mut stats) else {
+            panic!("message should be forwarded");
+        };
+        assert_eq!(text.as_str(), "token: ******");
+        let Step::Close(frame) = on_message(&forward, WsDirection::Upstream, Message::Text("subscribe admin".into()), &mut stats) else {
+            panic!("message should be rejected");
+        };
+        assert_eq!(frame.code, CloseCode::Policy);
+        assert!(matches!(
+            on_message(&forward, WsDirection::Upstream, Message::Ping(Default::default()), &mut stats),
+            Step::Skip
+        ));
+        assert_eq!(
+            stats,
+            WebSocketDirectionStats {
+                messages: 2,
+                frames: 3,
+                bytes: 28
             }
-        }
-    });
-    Ok(())
+        );
+    }
 }
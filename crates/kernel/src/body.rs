@@ -1,4 +1,7 @@
 // a read only stream reader with some side effect.
+/// Size limit of a streaming body.
+pub mod idle_timeout;
+pub mod limit;
 pub mod observer;
 use crate::BoxError;
 use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
@@ -0,0 +1,168 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{ready, Poll},
+    time::Duration,
+};
+
+use hyper::body::{Body, Bytes, Frame};
+use tokio::time::{Instant, Sleep};
+
+use crate::BoxError;
+
+use super::{observer::State, SgBody};
+
+/// Error of a body which is idle for too long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleTimeout {
+    pub idle: Duration,
+}
+
+impl std::fmt::Display for IdleTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "body is idle for longer than {:?}", self.idle)
+    }
+}
+
+impl std::error::Error for IdleTimeout {}
+
+/// The last time a frame flows, it's shared by the request and the response of an exchange.
+#[derive(Debug, Clone)]
+pub struct Activity {
+    since: Instant,
+    /// nanoseconds from `since` to the last activity
+    last: Arc<AtomicU64>,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Activity {
+    pub fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            last: Default::default(),
+        }
+    }
+    pub fn touch(&self) {
+        let elapsed = u64::try_from(self.since.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.last.fetch_max(elapsed, Ordering::Relaxed);
+    }
+    pub fn last_active(&self) -> Instant {
+        self.since + Duration::from_nanos(self.last.load(Ordering::Relaxed))
+    }
+}
+
+impl State for Activity {
+    fn update_bytes(&mut self, _data: &Bytes) {
+        self.touch();
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// A body which is aborted with [`IdleTimeout`] if there is no activity in `idle`.
+    ///
+    /// The deadline is checked lazily, so touching the activity is cheap.
+    pub struct IdleTimeoutBody {
+        #[pin]
+        inner: SgBody,
+        #[pin]
+        timeout: Sleep,
+        idle: Duration,
+        activity: Activity,
+    }
+}
+
+impl Body for IdleTimeoutBody {
+    type Data = Bytes;
+    type Error = BoxError;
+    fn poll_frame(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        if let Poll::Ready(frame) = this.inner.poll_frame(cx) {
+            if let Some(Ok(_)) = frame {
+                this.activity.touch();
+            }
+            return Poll::Ready(frame);
+        }
+        loop {
+            ready!(this.timeout.as_mut().poll(cx));
+            let deadline = this.activity.last_active() + *this.idle;
+            if deadline <= Instant::now() {
+                return Poll::Ready(Some(Err(IdleTimeout { idle: *this.idle }.into())));
+            }
+            this.timeout.as_mut().reset(deadline);
+        }
+    }
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl SgBody {
+    /// Abort this body with an [`IdleTimeout`] error if no frame flows in `idle`, frames of other bodies sharing the activity also count.
+    pub fn idle_timeout(self, idle: Duration, activity: Activity) -> Self {
+        if self.is_dumped() {
+            return self;
+        }
+        activity.touch();
+        SgBody::new(IdleTimeoutBody {
+            inner: self,
+            timeout: tokio::time::sleep(idle),
+            idle,
+            activity,
+        })
+    }
+    /// Touch the activity each time a data frame of this body flows.
+    pub fn with_activity(self, activity: Activity) -> Self {
+        if self.is_dumped() {
+            return self;
+        }
+        super::observer::Observer::new(activity, self).to_sg_body()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::{BodyExt, StreamBody};
+
+    use super::*;
+
+    /// A body which yields a frame after each delay.
+    fn delayed(delays: &'static [u64]) -> SgBody {
+        SgBody::new(StreamBody::new(futures_util::stream::unfold(delays.iter(), |mut delays| async move {
+            let delay = delays.next()?;
+            tokio::time::sleep(Duration::from_millis(*delay)).await;
+            Some((Ok::<_, BoxError>(Frame::data(Bytes::from_static(b"data"))), delays))
+        })))
+    }
+
+    #[tokio::test]
+    async fn abort_idle_body() {
+        let body = delayed(&[50, 50, 50, 50]).idle_timeout(Duration::from_millis(100), Activity::new());
+        assert_eq!(body.collect().await.expect("never idle").to_bytes().len(), 16);
+
+        let mut body = delayed(&[50, 300]).idle_timeout(Duration::from_millis(100), Activity::new());
+        assert!(body.frame().await.expect("frame").is_ok());
+        let error = body.frame().await.expect("frame").expect_err("idle timeout");
+        assert_eq!(error.downcast_ref::<IdleTimeout>(), Some(&IdleTimeout { idle: Duration::from_millis(100) }));
+    }
+
+    #[tokio::test]
+    async fn share_activity() {
+        let activity = Activity::new();
+        let mut idle = delayed(&[300]).idle_timeout(Duration::from_millis(100), activity.clone());
+        let busy = delayed(&[50, 50, 50, 50, 50, 50, 50]).with_activity(activity);
+        let (idle, busy) = tokio::join!(idle.frame(), busy.collect());
+        assert!(busy.is_ok());
+        assert!(idle.expect("frame").is_ok());
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use hyper::body::Bytes;
+
+use crate::BoxError;
+
+use super::{observer::State, SgBody};
+
+/// Error of a body which exceeds its size limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyTooLarge {
+    pub limit: u64,
+}
+
+impl std::fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "body size exceeds the limit of {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+impl BodyTooLarge {
+    /// Check if the error, or any of its sources, is a [`BodyTooLarge`].
+    pub fn find(error: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        let mut error = Some(error);
+        while let Some(e) = error {
+            if let Some(too_large) = e.downcast_ref::<Self>() {
+                return Some(*too_large);
+            }
+            error = e.source();
+        }
+        None
+    }
+}
+
+/// Counting the bytes passed through, and abort the body once the limit is exceeded.
+#[derive(Debug, Clone, Default)]
+pub struct BodyLimit {
+    limit: u64,
+    read: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl BodyLimit {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            read: 0,
+            exceeded: Default::default(),
+        }
+    }
+    /// A flag which is set when the limit is exceeded, it's still accessible after the body is consumed.
+    pub fn exceeded_flag(&self) -> Arc<AtomicBool> {
+        self.exceeded.clone()
+    }
+}
+
+impl State for BodyLimit {
+    fn update_bytes(&mut self, data: &Bytes) {
+        self.read = self.read.saturating_add(data.len() as u64);
+    }
+    fn check(&self) -> Result<(), BoxError> {
+        if self.read > self.limit {
+            self.exceeded.store(true, Ordering::Release);
+            Err(BodyTooLarge { limit: self.limit }.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl SgBody {
+    /// Limit the size of this body without buffering it, reading beyond the limit results in a [`BodyTooLarge`] error.
+    ///
+    /// The returned flag is set once the limit is exceeded.
+    pub fn limited(self, limit: u64) -> (Self, Arc<AtomicBool>) {
+        let state = BodyLimit::new(limit);
+        let exceeded = state.exceeded_flag();
+        if let Some(dumped) = self.get_dumped() {
+            if dumped.len() as u64 <= limit {
+                return (self, exceeded);
+            }
+        }
+        (super::observer::Observer::new(state, self).to_sg_body(), exceeded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::body::Frame;
+
+    use super::*;
+
+    fn chunked(chunks: &'static [&'static str]) -> SgBody {
+        SgBody::new(StreamBody::new(futures_util::stream::iter(
+            chunks.iter().map(|c| Ok::<_, BoxError>(Frame::data(Bytes::from_static(c.as_bytes())))),
+        )))
+    }
+
+    #[tokio::test]
+    async fn limit_streaming_body() {
+        let (body, exceeded) = chunked(&["hello", " ", "world"]).limited(11);
+        assert_eq!(body.collect().await.expect("within limit").to_bytes(), "hello world");
+        assert!(!exceeded.load(Ordering::Acquire));
+
+        let (mut body, exceeded) = chunked(&["hello", " ", "world"]).limited(8);
+        assert!(body.frame().await.expect("frame").is_ok());
+        assert!(body.frame().await.expect("frame").is_ok());
+        let error = body.frame().await.expect("frame").expect_err("exceeds the limit");
+        assert_eq!(BodyTooLarge::find(error.as_ref()), Some(BodyTooLarge { limit: 8 }));
+        assert!(exceeded.load(Ordering::Acquire));
+
+        let (body, exceeded) = SgBody::full("hello world").limited(5);
+        assert!(body.dump().await.is_err());
+        assert!(exceeded.load(Ordering::Acquire));
+    }
+}
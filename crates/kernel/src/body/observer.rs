@@ -7,6 +7,13 @@ use super::SgBody;
 
 pub trait State: Sized + Send + Sync + 'static {
     fn update_bytes(&mut self, data: &Bytes);
+    /// Check the state after bytes updated, the body is aborted with the error if it fails.
+    ///
+    /// # Errors
+    /// If the body should not go on.
+    fn check(&self) -> Result<(), BoxError> {
+        Ok(())
+    }
     fn finish(self) {}
     fn error(self, _e: &BoxError) {}
 }
@@ -41,7 +48,13 @@ where
                 Some(Ok(ref frame)) => {
                     if let Some(data) = frame.data_ref() {
                         if let Some(s) = this.state.as_mut() {
-                            s.update_bytes(data)
+                            s.update_bytes(data);
+                            if let Err(e) = s.check() {
+                                if let Some(s) = this.state.take() {
+                                    s.error(&e)
+                                }
+                                return Poll::Ready(Some(Err(e)));
+                            }
                         }
                     }
                 }
@@ -6,8 +6,12 @@ mod route_name;
 pub use route_name::*;
 mod matched;
 pub use matched::*;
+mod path_params;
+pub use path_params::*;
 mod peer_addr;
 pub use peer_addr::*;
+mod peer_certificate;
+pub use peer_certificate::*;
 mod backend_host;
 pub use backend_host::*;
 mod enter_time;
@@ -23,6 +27,8 @@ pub use mcp_proxy::*;
 use crate::{extractor::OptionalExtract, injector::Inject};
 mod is_east_west_traffic;
 mod mcp_proxy;
+mod websocket;
+pub use websocket::*;
 pub mod user_group;
 /// Just extract and attach the extension to the request
 #[derive(Debug, Clone)]
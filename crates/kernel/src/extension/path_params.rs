@@ -0,0 +1,21 @@
+use std::{collections::HashMap, ops::Deref};
+
+/// Named parameters of the matched path template, e.g. `id` of `/users/{id}`.
+///
+/// It's inserted into the request extensions once a route with a path template matches, so plugins could read it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathParams(pub HashMap<String, String>);
+
+impl PathParams {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+impl Deref for PathParams {
+    type Target = HashMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
@@ -0,0 +1,144 @@
+use std::{fmt, net::IpAddr, sync::Arc};
+
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::{extractor::OptionalExtract, BoxResult};
+
+/// The certificate presented by the client, it's verified by the client CAs of the https listener.
+///
+/// It only exists when the listener enables client authentication and the client sends a certificate.
+#[derive(Debug, Clone)]
+pub struct PeerCertificate(Arc<PeerCertificateInner>);
+
+#[derive(Debug)]
+struct PeerCertificateInner {
+    subject: String,
+    common_name: Option<String>,
+    subject_alt_names: Vec<SubjectAltName>,
+    der: CertificateDer<'static>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SubjectAltName {
+    Dns(String),
+    Uri(String),
+    Email(String),
+    Ip(IpAddr),
+}
+
+impl fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubjectAltName::Dns(name) => write!(f, "DNS:{name}"),
+            SubjectAltName::Uri(uri) => write!(f, "URI:{uri}"),
+            SubjectAltName::Email(email) => write!(f, "email:{email}"),
+            SubjectAltName::Ip(ip) => write!(f, "IP:{ip}"),
+        }
+    }
+}
+
+impl PeerCertificate {
+    /// Parse the end entity certificate.
+    ///
+    /// # Errors
+    /// If the certificate is not a valid x509 certificate.
+    pub fn from_der(der: CertificateDer<'static>) -> BoxResult<Self> {
+        let (_, cert) = X509Certificate::from_der(der.as_ref())?;
+        let subject = cert.subject().to_string();
+        let common_name = cert.subject().iter_common_name().next().and_then(|cn| cn.as_str().ok()).map(str::to_string);
+        let subject_alt_names = cert
+            .subject_alternative_name()?
+            .map(|ext| {
+                ext.value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(name) => Some(SubjectAltName::Dns(name.to_string())),
+                        GeneralName::URI(uri) => Some(SubjectAltName::Uri(uri.to_string())),
+                        GeneralName::RFC822Name(email) => Some(SubjectAltName::Email(email.to_string())),
+                        GeneralName::IPAddress(ip) => match ip.len() {
+                            4 => <[u8; 4]>::try_from(*ip).ok().map(|ip| SubjectAltName::Ip(IpAddr::from(ip))),
+                            16 => <[u8; 16]>::try_from(*ip).ok().map(|ip| SubjectAltName::Ip(IpAddr::from(ip))),
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self(Arc::new(PeerCertificateInner {
+            subject,
+            common_name,
+            subject_alt_names,
+            der,
+        })))
+    }
+
+    /// Subject distinguished name, e.g. `CN=client, O=example`.
+    pub fn subject(&self) -> &str {
+        &self.0.subject
+    }
+
+    pub fn common_name(&self) -> Option<&str> {
+        self.0.common_name.as_deref()
+    }
+
+    pub fn subject_alt_names(&self) -> &[SubjectAltName] {
+        &self.0.subject_alt_names
+    }
+
+    pub fn der(&self) -> &CertificateDer<'static> {
+        &self.0.der
+    }
+
+    /// Check if this certificate has the identity, the type of the identity is given by its prefix:
+    /// - `cn:` the common name,
+    /// - `subject:` the subject distinguished name,
+    /// - `dns:`, `uri:`, `email:` or `ip:` a subject alternative name of that type.
+    ///
+    /// An identity without a known prefix, e.g. `spiffe://cluster.local/ns/default/sa/client`, only matches the dns and uri subject alternative names.
+    pub fn has_identity(&self, identity: &str) -> bool {
+        let names = self.subject_alt_names();
+        match identity.split_once(':').map(|(kind, value)| (kind.to_ascii_lowercase(), value)) {
+            Some((kind, value)) if kind == "cn" => self.common_name() == Some(value),
+            Some((kind, value)) if kind == "subject" => self.subject() == value,
+            Some((kind, value)) if kind == "dns" => names.iter().any(|name| matches!(name, SubjectAltName::Dns(name) if name == value)),
+            Some((kind, value)) if kind == "uri" => names.iter().any(|name| matches!(name, SubjectAltName::Uri(name) if name == value)),
+            Some((kind, value)) if kind == "email" => names.iter().any(|name| matches!(name, SubjectAltName::Email(name) if name == value)),
+            Some((kind, value)) if kind == "ip" => value.parse::<IpAddr>().is_ok_and(|value| names.contains(&SubjectAltName::Ip(value))),
+            _ => names.iter().any(|name| matches!(name, SubjectAltName::Dns(name) | SubjectAltName::Uri(name) if name == identity)),
+        }
+    }
+}
+
+impl OptionalExtract for PeerCertificate {
+    fn extract(req: &hyper::Request<crate::SgBody>) -> Option<Self> {
+        req.extensions().get::<PeerCertificate>().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_peer_certificate() {
+        let pem = include_str!("../../tests/test_mtls/client.crt");
+        let der = rustls_pemfile::certs(&mut pem.as_bytes()).next().and_then(Result::ok).expect("certificate in pem");
+        let cert = PeerCertificate::from_der(der).expect("parse certificate");
+        assert_eq!(cert.subject(), "CN=client, O=spacegate");
+        assert_eq!(cert.common_name(), Some("client"));
+        assert_eq!(cert.subject_alt_names(), [SubjectAltName::Uri("spiffe://cluster.local/ns/default/sa/client".to_string())]);
+        assert!(cert.has_identity("cn:client"));
+        assert!(cert.has_identity("subject:CN=client, O=spacegate"));
+        assert!(cert.has_identity("spiffe://cluster.local/ns/default/sa/client"));
+        assert!(cert.has_identity("uri:spiffe://cluster.local/ns/default/sa/client"));
+        assert!(!cert.has_identity("spiffe://cluster.local/ns/default/sa/other"));
+        // identities of different types are not interchangeable
+        assert!(!cert.has_identity("client"));
+        assert!(!cert.has_identity("dns:client"));
+        assert!(!cert.has_identity("dns:spiffe://cluster.local/ns/default/sa/client"));
+        assert!(!cert.has_identity("cn:spiffe://cluster.local/ns/default/sa/client"));
+    }
+}
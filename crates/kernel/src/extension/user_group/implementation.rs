@@ -1,2 +1 @@
-#[cfg(feature = "ipnet")]
 pub mod ipnet;
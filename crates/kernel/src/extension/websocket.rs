@@ -0,0 +1,97 @@
+use std::{sync::Arc, time::Duration};
+
+/// The direction a websocket message flows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WsDirection {
+    /// From the client to the backend.
+    Upstream,
+    /// From the backend to the client.
+    Downstream,
+}
+
+impl WsDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WsDirection::Upstream => "upstream",
+            WsDirection::Downstream => "downstream",
+        }
+    }
+}
+
+/// What to do with a text message, returned by [`TextMessageHook`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextMessageAction {
+    /// Forward the message as it is.
+    Forward,
+    /// Forward the message with the text replaced.
+    Replace(String),
+    /// Drop the message silently.
+    Drop,
+    /// Close the connection of both sides with `1008 Policy Violation` and the reason.
+    Reject(String),
+}
+
+/// A hook to inspect or transform text messages of a websocket connection, e.g. redact or authorize subscription messages.
+pub trait TextMessageHook: Send + Sync + 'static {
+    fn on_text(&self, direction: WsDirection, text: &str) -> TextMessageAction;
+}
+
+impl<F> TextMessageHook for F
+where
+    F: Fn(WsDirection, &str) -> TextMessageAction + Send + Sync + 'static,
+{
+    fn on_text(&self, direction: WsDirection, text: &str) -> TextMessageAction {
+        (self)(direction, text)
+    }
+}
+
+/// Settings of forwarding a websocket connection.
+///
+/// Plugins could modify it in the request extensions before the connection is upgraded, it's default if absent.
+///
+/// Messages are parsed only if something is configured, see [`WebSocketForward::is_passthrough`].
+/// In that case `Sec-WebSocket-Extensions` is not forwarded, since frames of extensions can't be parsed by the gateway.
+#[derive(Clone, Default)]
+pub struct WebSocketForward {
+    /// The max size of a message, the connection is closed with `1009 Message Too Big` if it's exceeded.
+    pub max_message_size: Option<usize>,
+    /// Send pings to both sides at this interval.
+    pub ping_interval: Option<Duration>,
+    /// Close the connection if one side sends nothing, pongs included, in this duration.
+    pub idle_timeout: Option<Duration>,
+    text_hooks: Vec<Arc<dyn TextMessageHook>>,
+}
+
+impl std::fmt::Debug for WebSocketForward {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocketForward")
+            .field("max_message_size", &self.max_message_size)
+            .field("ping_interval", &self.ping_interval)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("text_hooks", &self.text_hooks.len())
+            .finish()
+    }
+}
+
+impl WebSocketForward {
+    /// Nothing is configured, so the connection is forwarded as opaque bytes, extensions like `permessage-deflate` and pings are end to end.
+    pub fn is_passthrough(&self) -> bool {
+        self.max_message_size.is_none() && self.ping_interval.is_none() && self.idle_timeout.is_none() && self.text_hooks.is_empty()
+    }
+    /// Add a hook of text messages, hooks are applied in the order they are added.
+    pub fn add_text_hook(&mut self, hook: impl TextMessageHook) {
+        self.text_hooks.push(Arc::new(hook));
+    }
+    /// Apply the hooks to a text message in order, the replaced text is passed to the next hook.
+    pub fn on_text(&self, direction: WsDirection, text: &str) -> TextMessageAction {
+        let mut replaced: Option<String> = None;
+        for hook in &self.text_hooks {
+            match hook.on_text(direction, replaced.as_deref().unwrap_or(text)) {
+                TextMessageAction::Forward => {}
+                TextMessageAction::Replace(text) => replaced = Some(text),
+                action => return action,
+            }
+        }
+        replaced.map_or(TextMessageAction::Forward, TextMessageAction::Replace)
+    }
+}
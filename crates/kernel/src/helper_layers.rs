@@ -4,6 +4,9 @@ pub mod map_future;
 /// Map service's response.
 pub mod map_request;
 
+/// Map service's response.
+pub mod map_response;
+
 /// Create a function or closure layer.
 pub mod function;
 
@@ -1,3 +1,7 @@
+pub mod canary;
+pub use canary::{Canary, CanaryConfig, CanaryKey, Fnv1a, StickyCookie};
+pub mod health;
+pub use health::HealthState;
 pub mod ip_hash;
 pub use ip_hash::IpHash;
 pub mod mcp_session;
@@ -9,16 +13,76 @@ pub struct Balancer<P, S> {
     pub policy: P,
     pub instances: Vec<S>,
     pub fallback: S,
+    /// Health states of instances, aligned with `instances` by index.
+    ///
+    /// An instance without a health state is always considered healthy.
+    pub health: Vec<HealthState>,
 }
 
 impl<P, S> Balancer<P, S> {
     pub fn new(policy: P, instances: Vec<S>, fallback: S) -> Self {
-        Self { policy, instances, fallback }
+        Self {
+            policy,
+            instances,
+            fallback,
+            health: Vec::new(),
+        }
+    }
+    pub fn with_health(mut self, health: Vec<HealthState>) -> Self {
+        self.health = health;
+        self
+    }
+    fn is_healthy(&self, index: usize) -> bool {
+        !matches!(self.health.get(index), Some(state) if !state.is_healthy())
+    }
+    /// Pick an instance for the request.
+    ///
+    /// If the instance picked by policy is unhealthy, the next healthy instance will be used instead,
+    /// so that requests for the same key still stick to one instance. Instances the policy never picks by itself are skipped.
+    /// If all of them are unhealthy, the picked one will be used anyway.
+    pub fn pick<R>(&self, req: &R) -> &S
+    where
+        P: BalancePolicy<S, R>,
+    {
+        let Some(picked) = self.policy.pick(&self.instances, req) else {
+            return &self.fallback;
+        };
+        if self.health.is_empty() {
+            return picked;
+        }
+        let Some(index) = self.instances.iter().position(|instance| std::ptr::eq(instance, picked)) else {
+            return picked;
+        };
+        if self.is_healthy(index) {
+            return picked;
+        }
+        let len = self.instances.len();
+        (1..len)
+            .map(|offset| (index + offset) % len)
+            .find(|index| self.is_healthy(*index) && self.policy.is_fallback(*index))
+            .and_then(|index| self.instances.get(index))
+            .unwrap_or(picked)
     }
 }
 
 pub trait BalancePolicy<S, R> {
     fn pick<'s>(&self, instances: &'s [S], req: &R) -> Option<&'s S>;
+    /// Whether the instance can take requests of an unhealthy one, instances the policy never picks by itself should not.
+    fn is_fallback(&self, _index: usize) -> bool {
+        true
+    }
+}
+
+impl<P, S, R> BalancePolicy<S, R> for std::sync::Arc<P>
+where
+    P: BalancePolicy<S, R>,
+{
+    fn pick<'s>(&self, instances: &'s [S], req: &R) -> Option<&'s S> {
+        self.as_ref().pick(instances, req)
+    }
+    fn is_fallback(&self, index: usize) -> bool {
+        self.as_ref().is_fallback(index)
+    }
 }
 
 impl<P, R, S> hyper::service::Service<R> for Balancer<P, S>
@@ -34,6 +98,51 @@ where
     type Future = S::Future;
 
     fn call(&self, req: R) -> Self::Future {
-        self.policy.pick(&self.instances, &req).unwrap_or(&self.fallback).call(req)
+        self.pick(&req).call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct First;
+    impl<S> BalancePolicy<S, ()> for First {
+        fn pick<'s>(&self, instances: &'s [S], _req: &()) -> Option<&'s S> {
+            instances.first()
+        }
+    }
+
+    #[test]
+    fn unhealthy_instance_is_skipped() {
+        let (a, b, c) = (HealthState::new(), HealthState::new(), HealthState::new());
+        let balancer = Balancer::new(First, vec!["a", "b", "c"], "fallback").with_health(vec![a.clone(), b.clone(), c.clone()]);
+        assert_eq!(*balancer.pick(&()), "a");
+        a.set_healthy(false);
+        assert_eq!(*balancer.pick(&()), "b");
+        b.set_healthy(false);
+        assert_eq!(*balancer.pick(&()), "c");
+        c.set_healthy(false);
+        assert_eq!(*balancer.pick(&()), "a");
+    }
+
+    struct FirstOfWeighted(Vec<u16>);
+    impl<S> BalancePolicy<S, ()> for FirstOfWeighted {
+        fn pick<'s>(&self, instances: &'s [S], _req: &()) -> Option<&'s S> {
+            instances.first()
+        }
+        fn is_fallback(&self, index: usize) -> bool {
+            self.0.get(index).is_some_and(|weight| *weight > 0)
+        }
+    }
+
+    #[test]
+    fn unhealthy_instance_falls_back_to_weighted() {
+        let (a, b, c) = (HealthState::new(), HealthState::new(), HealthState::new());
+        let balancer = Balancer::new(FirstOfWeighted(vec![1, 0, 1]), vec!["a", "b", "c"], "fallback").with_health(vec![a.clone(), b, c.clone()]);
+        a.set_healthy(false);
+        assert_eq!(*balancer.pick(&()), "c");
+        c.set_healthy(false);
+        assert_eq!(*balancer.pick(&()), "a");
     }
 }
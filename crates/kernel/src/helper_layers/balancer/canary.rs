@@ -0,0 +1,399 @@
+use std::{borrow::Cow, hash::Hasher, marker::PhantomData, time::Duration};
+
+use base64::Engine;
+use hyper::{
+    header::{HeaderName, HeaderValue, AUTHORIZATION, COOKIE, SET_COOKIE},
+    Request, Response,
+};
+use rand::Rng;
+
+use crate::{
+    extension::BackendHost,
+    helper_layers::function::{FnLayerMethod, Inner},
+    SgBody, SgRequest,
+};
+
+use super::BalancePolicy;
+
+/// Where the key of a request is read from, requests of the same key are assigned to the same backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanaryKey {
+    Header(HeaderName),
+    Cookie(String),
+    /// A claim of the JWT in the `Authorization: Bearer` header, the token is not verified here.
+    JwtClaim(String),
+}
+
+/// A cookie to keep a session on the assigned backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StickyCookie {
+    pub name: String,
+    /// It's a session cookie if it's `None`.
+    pub max_age: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CanaryConfig {
+    /// Requests without the key are assigned randomly by weight.
+    pub key: Option<CanaryKey>,
+    /// A header to force the backend, its value is the label or the index of a backend.
+    pub override_header: Option<HeaderName>,
+    /// Set the cookie to the label of the backend which served the request, and requests with the cookie go to that backend.
+    ///
+    /// A cookie naming a backend of weight 0 is ignored, since such a backend is never assigned.
+    pub sticky_cookie: Option<StickyCookie>,
+}
+
+/// 64-bit FNV-1a, the hash of a key never changes across builds, so that users stay on their backends after an upgrade.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The backend index assigned by [`Canary`] as a [`FnLayerMethod`], so that the balancer picks the same one.
+#[derive(Debug, Clone, Copy)]
+struct CanaryAssigned(usize);
+
+/// A policy that assigns a request to a backend by the hash of its key, so that a user stays on one version.
+///
+/// The hash is mapped into buckets sized by the weights, a backend of weight 0 is only reachable by the override header.
+///
+/// The backend is chosen in this order:
+/// 1. the override header,
+/// 2. the sticky cookie,
+/// 3. the hash of the key,
+/// 4. randomly by weight.
+///
+/// To set the sticky cookie, it should also be layered in front of the balancer as a [`FnLayerMethod`].
+#[derive(Debug, Clone)]
+pub struct Canary<H = Fnv1a> {
+    config: CanaryConfig,
+    /// cumulative weights of the backends
+    bounds: Vec<u64>,
+    labels: Vec<String>,
+    hasher: PhantomData<fn() -> H>,
+}
+
+impl Canary {
+    /// Create a policy for backends of the `weights`, the `labels` are used to match the override header.
+    pub fn new(config: CanaryConfig, weights: impl IntoIterator<Item = u16>, labels: impl IntoIterator<Item = String>) -> Self {
+        let bounds = weights
+            .into_iter()
+            .scan(0u64, |sum, weight| {
+                *sum += u64::from(weight);
+                Some(*sum)
+            })
+            .collect();
+        Self {
+            config,
+            bounds,
+            labels: labels.into_iter().collect(),
+            hasher: PhantomData,
+        }
+    }
+}
+
+fn cookie<'r>(req: &'r SgRequest, name: &str) -> Option<&'r str> {
+    req.headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|cookies| cookies.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find_map(|(key, value)| (key == name).then_some(value))
+}
+
+fn jwt_claim(req: &SgRequest, claim: &str) -> Option<String> {
+    let token = req.headers().get(AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")?;
+    let payload = token.trim().split('.').nth(1)?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    match serde_json::from_slice::<serde_json::Value>(&payload).ok()?.get(claim)? {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+impl<H> Canary<H>
+where
+    H: Hasher + Default,
+{
+    fn len(&self) -> usize {
+        self.bounds.len()
+    }
+    fn key<'r>(&self, req: &'r SgRequest) -> Option<Cow<'r, [u8]>> {
+        match self.config.key.as_ref()? {
+            CanaryKey::Header(name) => req.headers().get(name).map(|value| Cow::Borrowed(value.as_bytes())),
+            CanaryKey::Cookie(name) => cookie(req, name).map(|value| Cow::Borrowed(value.as_bytes())),
+            CanaryKey::JwtClaim(claim) => jwt_claim(req, claim).map(|value| Cow::Owned(value.into_bytes())),
+        }
+    }
+    /// The backend forced by the override header.
+    fn overridden(&self, req: &SgRequest) -> Option<usize> {
+        let value = req.headers().get(self.config.override_header.as_ref()?)?.to_str().ok()?.trim();
+        self.labels.iter().position(|label| label == value).or_else(|| value.parse().ok().filter(|index| *index < self.len()))
+    }
+    fn total(&self) -> u64 {
+        self.bounds.last().copied().unwrap_or_default()
+    }
+    /// Backends of weight 0 are never assigned, unless all of them are.
+    fn is_assignable(&self, index: usize) -> bool {
+        let Some(bound) = self.bounds.get(index) else {
+            return false;
+        };
+        let weight = bound - index.checked_sub(1).and_then(|prev| self.bounds.get(prev)).copied().unwrap_or_default();
+        weight > 0 || self.total() == 0
+    }
+    /// The backend kept by the sticky cookie.
+    fn sticky(&self, req: &SgRequest) -> Option<usize> {
+        let sticky = self.config.sticky_cookie.as_ref()?;
+        let value = cookie(req, &sticky.name)?;
+        let index = self.labels.iter().position(|label| label == value)?;
+        self.is_assignable(index).then_some(index)
+    }
+    /// The backend which served the request, it's not the assigned one if that one is unhealthy.
+    fn served(&self, host: &str, assigned: usize) -> Option<usize> {
+        let is_host = |label: &String| label == host || label.strip_prefix(host).is_some_and(|port| port.starts_with(':'));
+        if self.labels.get(assigned).is_some_and(is_host) {
+            return Some(assigned);
+        }
+        self.labels.iter().enumerate().position(|(index, label)| is_host(label) && self.is_assignable(index))
+    }
+    /// Assign a backend by the key or randomly, by weight.
+    fn assign(&self, req: &SgRequest) -> usize {
+        let total = self.total();
+        let point = match self.key(req) {
+            Some(key) => {
+                // the bytes are written as they are, `Hash` would add a platform dependent length prefix
+                let mut hasher = H::default();
+                hasher.write(&key);
+                hasher.finish()
+            }
+            None => rand::rng().random(),
+        };
+        if total == 0 {
+            return (point % self.len().max(1) as u64) as usize;
+        }
+        let point = point % total;
+        self.bounds.partition_point(|bound| *bound <= point)
+    }
+    /// The index of the backend the request goes to.
+    pub fn backend_index(&self, req: &SgRequest) -> usize {
+        if let Some(CanaryAssigned(index)) = req.extensions().get::<CanaryAssigned>() {
+            return *index;
+        }
+        self.overridden(req).or_else(|| self.sticky(req)).unwrap_or_else(|| self.assign(req))
+    }
+}
+
+impl<S, H> BalancePolicy<S, SgRequest> for Canary<H>
+where
+    H: Hasher + Default,
+{
+    fn pick<'s>(&self, instances: &'s [S], req: &SgRequest) -> Option<&'s S> {
+        if instances.len() <= 1 {
+            return instances.first();
+        }
+        instances.get(self.backend_index(req)).or_else(|| instances.first())
+    }
+    fn is_fallback(&self, index: usize) -> bool {
+        self.is_assignable(index)
+    }
+}
+
+impl<H> FnLayerMethod for Canary<H>
+where
+    H: Hasher + Default + 'static,
+{
+    /// Assign the backend before balancing, and set the sticky cookie if the request is neither overridden nor sticky yet.
+    async fn call(&self, mut req: Request<SgBody>, inner: Inner) -> Response<SgBody> {
+        let Some(sticky) = &self.config.sticky_cookie else {
+            return inner.call(req).await;
+        };
+        if self.overridden(&req).is_some() || self.sticky(&req).is_some() {
+            return inner.call(req).await;
+        }
+        let index = self.assign(&req);
+        req.extensions_mut().insert(CanaryAssigned(index));
+        let mut resp = inner.call(req).await;
+        // the assigned backend may be unhealthy, keep the session on the one which served it
+        let served = resp.extensions().get::<BackendHost>().and_then(|host| self.served(&host.0, index)).unwrap_or(index);
+        let Some(label) = self.labels.get(served) else {
+            return resp;
+        };
+        let mut set_cookie = format!("{}={label}; Path=/; HttpOnly", sticky.name);
+        if let Some(max_age) = sticky.max_age {
+            set_cookie.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+        match HeaderValue::from_str(&set_cookie) {
+            Ok(set_cookie) => {
+                resp.headers_mut().append(SET_COOKIE, set_cookie);
+            }
+            Err(e) => tracing::warn!("[SG.Canary] invalid sticky cookie {set_cookie}: {e}"),
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::service::service_fn;
+
+    use super::*;
+    use crate::ArcHyperService;
+
+    fn canary(config: CanaryConfig) -> Canary {
+        Canary::new(config, [90, 10, 0], ["stable", "canary", "next"].map(String::from))
+    }
+
+    fn request(headers: &[(&str, &str)]) -> SgRequest {
+        let mut req = Request::builder().uri("/api");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(SgBody::empty()).expect("invalid request")
+    }
+
+    fn jwt(claims: &str) -> String {
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(claims);
+        format!("Bearer eyJhbGciOiJIUzI1NiJ9.{payload}.signature")
+    }
+
+    #[test]
+    fn assign_by_key_and_weight() {
+        let policy = canary(CanaryConfig {
+            key: Some(CanaryKey::Header(HeaderName::from_static("x-user-id"))),
+            ..Default::default()
+        });
+        let instances = ["stable", "canary", "next"];
+        let mut counts = std::collections::HashMap::new();
+        for user in 0..1000 {
+            let user = user.to_string();
+            let picked = policy.backend_index(&request(&[("x-user-id", &user)]));
+            assert_eq!(picked, policy.backend_index(&request(&[("x-user-id", &user)])));
+            counts.entry(picked).and_modify(|count| *count += 1).or_insert(1);
+        }
+        assert!(counts.get(&1).is_some_and(|count| (50..150).contains(count)), "{counts:?}");
+        assert!(!counts.contains_key(&2));
+        assert!(policy.pick(&instances, &request(&[])).is_some());
+
+        let policy = canary(CanaryConfig {
+            key: Some(CanaryKey::JwtClaim("sub".to_string())),
+            ..Default::default()
+        });
+        let alice = policy.backend_index(&request(&[("authorization", &jwt(r#"{"sub":"alice","exp":1}"#))]));
+        assert_eq!(alice, policy.backend_index(&request(&[("authorization", &jwt(r#"{"sub":"alice","exp":2}"#))])));
+        assert_eq!(jwt_claim(&request(&[("authorization", &jwt(r#"{"sub":42}"#))]), "sub").as_deref(), Some("42"));
+        assert_eq!(jwt_claim(&request(&[("authorization", "Bearer invalid")]), "sub"), None);
+    }
+
+    #[test]
+    fn stable_hash() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = Fnv1a::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        // test vectors of FNV-1a 64
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn override_header() {
+        let policy = canary(CanaryConfig {
+            key: Some(CanaryKey::Cookie("uid".to_string())),
+            override_header: Some(HeaderName::from_static("x-canary")),
+            ..Default::default()
+        });
+        assert_eq!(policy.backend_index(&request(&[("x-canary", "next"), ("cookie", "uid=1")])), 2);
+        assert_eq!(policy.backend_index(&request(&[("x-canary", "1")])), 1);
+        // unknown backend falls back to the key
+        let uid = policy.backend_index(&request(&[("cookie", "a=b; uid=1")]));
+        assert_eq!(policy.backend_index(&request(&[("x-canary", "3"), ("cookie", "uid=1")])), uid);
+    }
+
+    #[tokio::test]
+    async fn sticky_cookie() {
+        let policy = canary(CanaryConfig {
+            override_header: Some(HeaderName::from_static("x-canary")),
+            sticky_cookie: Some(StickyCookie {
+                name: "sg-canary".to_string(),
+                max_age: Some(Duration::from_secs(3600)),
+            }),
+            ..Default::default()
+        });
+        let echo = |policy: Canary| {
+            Inner::new(ArcHyperService::new(service_fn(move |req: SgRequest| {
+                let index = policy.backend_index(&req);
+                async move { Ok(Response::new(SgBody::full(index.to_string()))) }
+            })))
+        };
+        let labels = ["stable", "canary", "next"];
+        let call = |cookie: &'static str| {
+            let policy = policy.clone();
+            async move {
+                let headers = if cookie.is_empty() { vec![] } else { vec![("cookie", cookie)] };
+                let resp = policy.call(request(&headers), echo(policy.clone())).await;
+                let set_cookie = resp.headers().get(SET_COOKIE).map(|set_cookie| set_cookie.to_str().expect("invalid cookie").to_string());
+                let index = resp.into_body().dump().await.expect("echo").get_dumped().map(|body| String::from_utf8_lossy(body).parse::<usize>().expect("index")).expect("dumped");
+                (set_cookie, index)
+            }
+        };
+        let (set_cookie, index) = call("").await;
+        assert_eq!(set_cookie, Some(format!("sg-canary={}; Path=/; HttpOnly; Max-Age=3600", labels.get(index).expect("label"))));
+
+        // the session stays on the backend
+        assert_eq!(call("sg-canary=canary").await, (None, 1));
+        // a backend of weight 0 is never assigned, so the cookie is forged
+        let (set_cookie, index) = call("sg-canary=next").await;
+        assert_ne!(index, 2);
+        assert!(set_cookie.is_some());
+
+        // overridden requests are not pinned
+        let resp = policy.call(request(&[("x-canary", "canary")]), echo(policy.clone())).await;
+        assert!(resp.headers().get(SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn sticky_cookie_of_served_backend() {
+        let policy = canary(CanaryConfig {
+            sticky_cookie: Some(StickyCookie {
+                name: "sg-canary".to_string(),
+                max_age: None,
+            }),
+            ..Default::default()
+        });
+        let served_by = |host: &'static str| {
+            Inner::new(ArcHyperService::new(service_fn(move |_: SgRequest| async move {
+                let mut resp = Response::new(SgBody::empty());
+                resp.extensions_mut().insert(BackendHost::new(host));
+                Ok(resp)
+            })))
+        };
+        // the assigned backend may be unhealthy, the session stays on the one which served it
+        for _ in 0..16 {
+            let resp = policy.call(request(&[]), served_by("canary")).await;
+            assert_eq!(resp.headers().get(SET_COOKIE), Some(&HeaderValue::from_static("sg-canary=canary; Path=/; HttpOnly")));
+        }
+        // a backend of weight 0 is never kept
+        let resp = policy.call(request(&[]), served_by("next")).await;
+        assert_ne!(resp.headers().get(SET_COOKIE), Some(&HeaderValue::from_static("sg-canary=next; Path=/; HttpOnly")));
+    }
+}
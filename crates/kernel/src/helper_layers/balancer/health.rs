@@ -0,0 +1,100 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc, Weak,
+};
+
+/// Shared health state of a balanced instance.
+///
+/// The state is healthy by default, it would be updated by some prober through [`HealthState::record`].
+#[derive(Debug, Clone, Default)]
+pub struct HealthState {
+    inner: Arc<HealthStateInner>,
+}
+
+#[derive(Debug, Default)]
+struct HealthStateInner {
+    unhealthy: AtomicBool,
+    consecutive_successes: AtomicU32,
+    consecutive_failures: AtomicU32,
+}
+
+/// A weak reference to a [`HealthState`], a prober holds it so that it stops once no balancer uses the state anymore.
+#[derive(Debug, Clone, Default)]
+pub struct WeakHealthState {
+    inner: Weak<HealthStateInner>,
+}
+
+impl WeakHealthState {
+    pub fn upgrade(&self) -> Option<HealthState> {
+        self.inner.upgrade().map(|inner| HealthState { inner })
+    }
+}
+
+impl HealthState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn downgrade(&self) -> WeakHealthState {
+        WeakHealthState {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+    pub fn is_healthy(&self) -> bool {
+        !self.inner.unhealthy.load(Ordering::Relaxed)
+    }
+    pub fn set_healthy(&self, healthy: bool) {
+        self.inner.unhealthy.store(!healthy, Ordering::Relaxed);
+    }
+    pub fn consecutive_successes(&self) -> u32 {
+        self.inner.consecutive_successes.load(Ordering::Relaxed)
+    }
+    pub fn consecutive_failures(&self) -> u32 {
+        self.inner.consecutive_failures.load(Ordering::Relaxed)
+    }
+    /// Record a probe result.
+    ///
+    /// The instance turns unhealthy after `unhealthy_threshold` consecutive failures, and turns healthy again after `healthy_threshold` consecutive successes.
+    ///
+    /// Returns `true` if the health state changed.
+    pub fn record(&self, success: bool, healthy_threshold: u32, unhealthy_threshold: u32) -> bool {
+        let inner = &self.inner;
+        if success {
+            inner.consecutive_failures.store(0, Ordering::Relaxed);
+            let successes = inner.consecutive_successes.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+            successes >= healthy_threshold && inner.unhealthy.swap(false, Ordering::Relaxed)
+        } else {
+            inner.consecutive_successes.store(0, Ordering::Relaxed);
+            let failures = inner.consecutive_failures.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+            failures >= unhealthy_threshold && !inner.unhealthy.swap(true, Ordering::Relaxed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_respects_thresholds() {
+        let state = HealthState::new();
+        assert!(state.is_healthy());
+        assert!(!state.record(false, 2, 3));
+        assert!(!state.record(false, 2, 3));
+        assert!(state.is_healthy());
+        assert!(state.record(false, 2, 3));
+        assert!(!state.is_healthy());
+        assert!(!state.record(true, 2, 3));
+        assert!(!state.is_healthy());
+        assert!(state.record(true, 2, 3));
+        assert!(state.is_healthy());
+    }
+
+    #[test]
+    fn weak_state_expires_with_owner() {
+        let state = HealthState::new();
+        let weak = state.downgrade();
+        assert!(weak.upgrade().is_some());
+        drop(state);
+        assert!(weak.upgrade().is_none());
+    }
+}
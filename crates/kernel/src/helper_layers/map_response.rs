@@ -28,6 +28,7 @@ where
     }
 }
 
+#[derive(Debug)]
 pub struct MapResponse<F, S> {
     map: F,
     inner: S,
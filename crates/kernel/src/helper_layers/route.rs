@@ -29,6 +29,9 @@ where
     pub fn new(services: S, router: R, fallback: F) -> Self {
         Self { services, router, fallback }
     }
+    pub fn router(&self) -> &R {
+        &self.router
+    }
 }
 
 impl<S, R, F> hyper::service::Service<Request<SgBody>> for RouterService<S, R, F>
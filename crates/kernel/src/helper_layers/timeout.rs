@@ -1,6 +1,6 @@
 use std::{convert::Infallible, time::Duration};
 
-use crate::SgBody;
+use crate::{body::idle_timeout::Activity, SgBody};
 use futures_util::Future;
 use hyper::{Request, Response};
 use tokio::time::Sleep;
@@ -94,3 +94,100 @@ where
         this.inner.poll(cx)
     }
 }
+
+/// Timeout when no frame of the request or the response flows in `idle`, for streaming responses like server-sent events.
+///
+/// Before the response head arrives, it responds `504 Gateway Timeout` like [`TimeoutLayer`], after that the response body is aborted.
+#[derive(Clone, Debug)]
+pub struct IdleTimeoutLayer {
+    pub idle: Duration,
+    pub timeout_response: hyper::body::Bytes,
+}
+
+impl IdleTimeoutLayer {
+    pub fn new(idle: Duration) -> Self {
+        Self {
+            idle,
+            timeout_response: hyper::body::Bytes::default(),
+        }
+    }
+}
+
+impl<S> Layer<S> for IdleTimeoutLayer {
+    type Service = IdleTimeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdleTimeout {
+            inner,
+            idle: self.idle,
+            timeout_response: self.timeout_response.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IdleTimeout<S> {
+    inner: S,
+    idle: Duration,
+    timeout_response: hyper::body::Bytes,
+}
+
+impl<S> hyper::service::Service<Request<SgBody>> for IdleTimeout<S>
+where
+    S: hyper::service::Service<Request<SgBody>, Response = Response<SgBody>, Error = Infallible> + Send + 'static,
+    <S as hyper::service::Service<Request<SgBody>>>::Future: std::marker::Send,
+{
+    type Response = Response<SgBody>;
+
+    type Error = Infallible;
+
+    type Future = IdleTimeoutFuture<S::Future>;
+
+    fn call(&self, req: Request<SgBody>) -> Self::Future {
+        let activity = Activity::new();
+        let req = req.map(|body| body.with_activity(activity.clone()));
+        IdleTimeoutFuture {
+            inner: self.inner.call(req),
+            timeout: tokio::time::sleep(self.idle),
+            idle: self.idle,
+            activity,
+            timeout_response: self.timeout_response.clone(),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct IdleTimeoutFuture<F> {
+        #[pin]
+        inner: F,
+        #[pin]
+        timeout: Sleep,
+        idle: Duration,
+        activity: Activity,
+        timeout_response: hyper::body::Bytes,
+    }
+}
+
+impl<F> Future for IdleTimeoutFuture<F>
+where
+    F: Future<Output = Result<Response<SgBody>, Infallible>> + Send + 'static,
+{
+    type Output = Result<Response<SgBody>, Infallible>;
+
+    fn poll(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+        let mut this = self.project();
+        if let std::task::Poll::Ready(Ok(response)) = this.inner.poll(cx) {
+            let (idle, activity) = (*this.idle, this.activity.clone());
+            return std::task::Poll::Ready(Ok(response.map(|body| body.idle_timeout(idle, activity))));
+        }
+        loop {
+            std::task::ready!(this.timeout.as_mut().poll(cx));
+            let deadline = this.activity.last_active() + *this.idle;
+            if deadline <= tokio::time::Instant::now() {
+                let response = Response::builder().status(hyper::StatusCode::GATEWAY_TIMEOUT).body(SgBody::full(this.timeout_response.clone())).expect("invalid response");
+                return std::task::Poll::Ready(Ok(response));
+            }
+            this.timeout.as_mut().reset(deadline);
+        }
+    }
+}
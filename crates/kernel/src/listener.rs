@@ -1,17 +1,48 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use futures_util::TryFutureExt;
+use tokio::{sync::Semaphore, task::JoinSet};
 use tokio_util::sync::CancellationToken;
 use tracing::{instrument, Instrument};
 
 use crate::{service::TcpService, BoxError, BoxResult};
 
+pub mod proxy_protocol;
+pub use proxy_protocol::ProxyProtocol;
+pub mod udp;
+pub use udp::SgUdpListen;
+#[cfg(all(unix, feature = "socket-handoff"))]
+pub mod handoff;
+#[cfg(feature = "http3")]
+pub mod http3;
+#[cfg(feature = "http3")]
+pub use http3::SgHttp3Listen;
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
 /// Listener embodies the concept of a logical endpoint where a Gateway accepts network connections.
+///
+/// The connection limits and the grace period only cover the tcp connections of this listener,
+/// the quic connections of http3 and the udp sessions are not counted nor drained,
+/// the latter are bounded by [`SgUdpListen::with_max_sessions`].
 #[derive(Clone)]
 pub struct SgListen {
     pub socket_addr: SocketAddr,
     pub services: Vec<Arc<dyn TcpService>>,
     pub listener_id: String,
+    /// Read the PROXY protocol header before sniffing the services, if it's set.
+    pub proxy_protocol: Option<Arc<ProxyProtocol>>,
+    /// Max concurrent connections, new connections beyond it are closed at once.
+    pub max_connections: Option<usize>,
+    /// Max concurrent connections from one client ip, which is the source address of the PROXY protocol header if it's enabled.
+    pub max_connections_per_ip: Option<usize>,
+    /// How long to wait for the connections to finish after cancelled, the rest connections are aborted then.
+    pub grace_period: Duration,
     cancel_token: CancellationToken,
 }
 
@@ -21,6 +52,10 @@ impl std::fmt::Debug for SgListen {
             .field("socket_addr", &self.socket_addr)
             .field("listener_id", &self.listener_id)
             .field("services", &self.services.iter().map(|s| s.protocol_name()).collect::<Vec<_>>())
+            .field("proxy_protocol", &self.proxy_protocol)
+            .field("max_connections", &self.max_connections)
+            .field("max_connections_per_ip", &self.max_connections_per_ip)
+            .field("grace_period", &self.grace_period)
             .finish_non_exhaustive()
     }
 }
@@ -33,6 +68,10 @@ impl SgListen {
             services: Vec::new(),
             cancel_token,
             listener_id: Default::default(),
+            proxy_protocol: None,
+            max_connections: None,
+            max_connections_per_ip: None,
+            grace_period: DEFAULT_GRACE_PERIOD,
         }
     }
 
@@ -58,6 +97,29 @@ impl SgListen {
         self.listener_id = listener_id.into();
         self
     }
+
+    /// Expect the connections from trusted sources to start with a PROXY protocol header,
+    /// the source address in the header is used as the peer address of the connection.
+    pub fn with_proxy_protocol(mut self, proxy_protocol: ProxyProtocol) -> Self {
+        self.proxy_protocol = Some(Arc::new(proxy_protocol));
+        self
+    }
+
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    pub fn with_max_connections_per_ip(mut self, max_connections_per_ip: usize) -> Self {
+        self.max_connections_per_ip = Some(max_connections_per_ip);
+        self
+    }
+
+    /// How long to drain the connections after cancelled, default is 10 seconds.
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
 }
 
 impl SgListen {
@@ -69,33 +131,84 @@ impl SgListen {
     }
 
     /// Listen on the socket address.
+    ///
+    /// After cancelled, it stops accepting, asks the connections to finish gracefully,
+    /// and waits for them at most the grace period.
+    ///
+    /// # Errors
+    /// If fail to bind the socket.
     #[instrument(skip(self), fields(bind=%self.socket_addr))]
     pub async fn listen(self) -> Result<(), BoxError> {
         tracing::debug!("start binding...");
+        #[cfg(all(unix, feature = "socket-handoff"))]
+        let (listener, _active) = handoff::SocketRegistry::global().bind_tcp(self.socket_addr).await?;
+        #[cfg(not(all(unix, feature = "socket-handoff")))]
         let listener = tokio::net::TcpListener::bind(self.socket_addr).await?;
         let cancel_token = self.cancel_token;
         tracing::debug!("start listening...");
         let peek_size = self.services.iter().fold(0, |acc, s| acc.max(s.sniff_peek_size()));
         let services: Arc<[Arc<dyn TcpService>]> = self.services.clone().into();
+        let proxy_protocol = self.proxy_protocol;
+        let connections_per_ip = self.max_connections_per_ip.map(|max| Arc::new(ConnectionsPerIp::new(max)));
+        // a permit is held by the connection task, so that finished connections are not counted before they are reaped
+        let connection_permits = self.max_connections.map(|max| Arc::new(Semaphore::new(max.min(Semaphore::MAX_PERMITS))));
+        let shutdown = CancellationToken::new();
+        let mut connections = JoinSet::new();
         loop {
             let accepted = tokio::select! {
                 () = cancel_token.cancelled() => {
                     tracing::warn!("cancelled");
-                    return Ok(());
+                    break;
                 },
+                Some(_) = connections.join_next(), if !connections.is_empty() => continue,
                 accepted = listener.accept() => accepted
             };
             match accepted {
-                Ok((stream, peer_addr)) => {
+                Ok((mut stream, peer_addr)) => {
+                    let permit = match &connection_permits {
+                        Some(permits) => match permits.clone().try_acquire_owned() {
+                            Ok(permit) => Some(permit),
+                            Err(_) => {
+                                tracing::debug!(peer = %peer_addr, "[Sg.Listen] too many connections, connection closed");
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
                     let services = services.clone();
-                    let _task = tokio::spawn(
+                    let proxy_protocol = proxy_protocol.clone();
+                    let connections_per_ip = connections_per_ip.clone();
+                    let shutdown = shutdown.clone();
+                    connections.spawn(
                         async move {
+                            let _permit = permit;
+                            let mut peer_addr = peer_addr;
+                            if let Some(proxy_protocol) = proxy_protocol.filter(|p| p.is_trusted(peer_addr.ip())) {
+                                let header = proxy_protocol.read_header(&mut stream).await?;
+                                if let Some(source) = header.source {
+                                    tracing::trace!(%source, "[Sg.Listen] proxy protocol header accepted");
+                                    peer_addr = source;
+                                }
+                            }
+                            let _guard = match &connections_per_ip {
+                                Some(connections_per_ip) => match connections_per_ip.acquire(peer_addr.ip()) {
+                                    Some(guard) => Some(guard),
+                                    None => {
+                                        tracing::debug!(peer = %peer_addr, "[Sg.Listen] too many connections from the ip, connection closed");
+                                        return Ok(());
+                                    }
+                                },
+                                None => None,
+                            };
                             let mut peek_buf = vec![0u8; peek_size];
-                            stream.peek(&mut peek_buf).await?;
+                            // don't wait for the client if no service sniffs, the server may speak first
+                            if peek_size > 0 {
+                                stream.peek(&mut peek_buf).await?;
+                            }
                             for s in services.iter() {
                                 if s.sniff(&peek_buf) {
                                     tracing::debug!(tcp_service=%s.protocol_name(), "accepted");
-                                    s.handle(stream, peer_addr).await?;
+                                    s.handle_graceful(stream, peer_addr, shutdown).await?;
                                     break;
                                 }
                             }
@@ -112,5 +225,62 @@ impl SgListen {
                 }
             }
         }
+        drop(listener);
+        shutdown.cancel();
+        if !connections.is_empty() {
+            tracing::info!(connections = connections.len(), grace_period = ?self.grace_period, "draining connections");
+            let drained = tokio::time::timeout(self.grace_period, async { while connections.join_next().await.is_some() {} }).await;
+            if drained.is_err() {
+                tracing::warn!(connections = connections.len(), "grace period expired, connections aborted");
+                connections.abort_all();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Count the connections of every client ip.
+#[derive(Debug)]
+struct ConnectionsPerIp {
+    max: usize,
+    counts: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl ConnectionsPerIp {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a connection until the guard is dropped, `None` if the ip has too many connections.
+    fn acquire(self: &Arc<Self>, ip: IpAddr) -> Option<ConnectionPerIpGuard> {
+        let ip = ip.to_canonical();
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let count = counts.entry(ip).or_default();
+        if *count >= self.max {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionPerIpGuard { counter: self.clone(), ip })
+    }
+}
+
+#[derive(Debug)]
+struct ConnectionPerIpGuard {
+    counter: Arc<ConnectionsPerIp>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionPerIpGuard {
+    fn drop(&mut self) {
+        let mut counts = self.counter.counts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.ip);
+            }
+        }
     }
 }
@@ -0,0 +1,407 @@
+//! Socket handoff
+//!
+//! Listener sockets can be inherited by a new process, so that the binary can be upgraded without closing them:
+//! - from systemd socket activation, see [`SocketRegistry::inherit_systemd`];
+//! - from the running process, over a unix socket, see [`SocketRegistry::serve_handoff`] and [`SocketRegistry::receive_handoff`].
+//!
+//! The listeners take the inherited socket of their address before binding a new one,
+//! and register their sockets as active while listening, which are the sockets sent to the new process.
+
+use std::{
+    collections::HashMap,
+    io::{self, IoSlice, IoSliceMut},
+    net::{SocketAddr, TcpListener, UdpSocket},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+use nix::sys::socket::{getsockopt, recvmsg, sendmsg, sockopt, ControlMessage, ControlMessageOwned, MsgFlags, SockType};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, Interest},
+    net::{UnixListener, UnixStream},
+};
+
+/// The first fd passed by systemd.
+const SD_LISTEN_FDS_START: RawFd = 3;
+/// Max fds sent in one message.
+const FDS_PER_MESSAGE: usize = 32;
+/// Sent by the new process after it's serving.
+const READY: u8 = 1;
+
+#[derive(Debug)]
+enum InheritedSocket {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+}
+
+impl InheritedSocket {
+    /// Classify a socket by its type, connected or non-inet sockets are rejected.
+    fn new(fd: OwnedFd) -> io::Result<Self> {
+        match getsockopt(&fd, sockopt::SockType)? {
+            SockType::Stream if getsockopt(&fd, sockopt::AcceptConn)? => {
+                let listener = TcpListener::from(fd);
+                listener.local_addr()?;
+                Ok(Self::Tcp(listener))
+            }
+            SockType::Datagram => {
+                let socket = UdpSocket::from(fd);
+                socket.local_addr()?;
+                Ok(Self::Udp(socket))
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a tcp listener or udp socket")),
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            Self::Tcp(listener) => listener.local_addr(),
+            Self::Udp(socket) => socket.local_addr(),
+        }
+    }
+}
+
+/// An inherited socket serves a listener of the same address,
+/// and a socket bound to unspecified ip serves a listener bound to unspecified ip of either family, e.g. `[::]:80` for `0.0.0.0:80`.
+fn addr_matches(socket: SocketAddr, listener: SocketAddr) -> bool {
+    socket == listener || (socket.port() == listener.port() && socket.ip().is_unspecified() && listener.ip().is_unspecified())
+}
+
+/// Sockets passed by systemd socket activation, described by the `LISTEN_PID` and `LISTEN_FDS` environment variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemdSockets {
+    pid: u32,
+    fds: RawFd,
+}
+
+impl SystemdSockets {
+    /// Parse the values of `LISTEN_PID` and `LISTEN_FDS`.
+    ///
+    /// # Errors
+    /// If the values are invalid, e.g. a negative count of fds.
+    pub fn parse(pid: &str, fds: &str) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid systemd socket activation variables: {e}"));
+        let pid = pid.parse::<u32>().map_err(|e| invalid(e.to_string()))?;
+        let fds = fds.parse::<u16>().map_err(|e| invalid(format!("LISTEN_FDS {fds}: {e}")))?;
+        Ok(Self { pid, fds: fds.into() })
+    }
+
+    /// Read the environment variables, `None` if they are not set.
+    ///
+    /// Since the environment is not safe to access among threads, call it before any thread is spawned, e.g. before the runtime is built.
+    ///
+    /// # Errors
+    /// If the variables are invalid.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        match (std::env::var("LISTEN_PID"), std::env::var("LISTEN_FDS")) {
+            (Ok(pid), Ok(fds)) => Self::parse(&pid, &fds).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Sockets inherited from the previous process, and sockets of running listeners.
+#[derive(Debug, Default)]
+pub struct SocketRegistry {
+    inherited: Mutex<Vec<InheritedSocket>>,
+    active: Mutex<HashMap<u64, OwnedFd>>,
+    next_id: AtomicU64,
+}
+
+impl SocketRegistry {
+    pub fn global() -> &'static Self {
+        static GLOBAL: OnceLock<SocketRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(Default::default)
+    }
+
+    fn inherit(&self, fd: OwnedFd) {
+        match InheritedSocket::new(fd) {
+            Ok(socket) => {
+                tracing::debug!(addr = ?socket.local_addr().ok(), "[Sg.Handoff] inherit socket");
+                self.inherited.lock().unwrap_or_else(|e| e.into_inner()).push(socket);
+            }
+            Err(e) => tracing::warn!("[Sg.Handoff] ignore inherited fd: {e}"),
+        }
+    }
+
+    /// Number of inherited sockets which are not taken by listeners yet.
+    pub fn inherited_count(&self) -> usize {
+        self.inherited.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Close the inherited sockets which are not taken by listeners, returns the number of them.
+    pub fn release_inherited(&self) -> usize {
+        std::mem::take(&mut *self.inherited.lock().unwrap_or_else(|e| e.into_inner())).len()
+    }
+
+    /// Inherit the sockets passed by systemd socket activation, if they are passed to this process.
+    ///
+    /// Returns the number of inherited sockets.
+    ///
+    /// # Errors
+    /// If fail to set `FD_CLOEXEC` on a passed fd.
+    pub fn inherit_systemd(&self, sockets: SystemdSockets) -> io::Result<usize> {
+        if sockets.pid != std::process::id() {
+            return Ok(0);
+        }
+        for fd in SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + sockets.fds {
+            // SAFETY: systemd passes the fds from SD_LISTEN_FDS_START, and they are owned by this process from now on
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            nix::fcntl::fcntl(fd.as_raw_fd(), nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC))?;
+            self.inherit(fd);
+        }
+        Ok(sockets.fds as usize)
+    }
+
+    /// Take the inherited tcp listener of the address.
+    pub fn take_tcp(&self, addr: SocketAddr) -> Option<TcpListener> {
+        let mut inherited = self.inherited.lock().unwrap_or_else(|e| e.into_inner());
+        let index = inherited.iter().position(|s| matches!(s, InheritedSocket::Tcp(_)) && s.local_addr().is_ok_and(|local| addr_matches(local, addr)))?;
+        match inherited.swap_remove(index) {
+            InheritedSocket::Tcp(listener) => Some(listener),
+            InheritedSocket::Udp(_) => None,
+        }
+    }
+
+    /// Take the inherited udp socket of the address.
+    pub fn take_udp(&self, addr: SocketAddr) -> Option<UdpSocket> {
+        let mut inherited = self.inherited.lock().unwrap_or_else(|e| e.into_inner());
+        let index = inherited.iter().position(|s| matches!(s, InheritedSocket::Udp(_)) && s.local_addr().is_ok_and(|local| addr_matches(local, addr)))?;
+        match inherited.swap_remove(index) {
+            InheritedSocket::Udp(socket) => Some(socket),
+            InheritedSocket::Tcp(_) => None,
+        }
+    }
+
+    /// Register the socket of a running listener, it would be sent to the new process until the guard is dropped.
+    ///
+    /// # Errors
+    /// If fail to duplicate the fd.
+    pub fn register(&'static self, socket: BorrowedFd<'_>) -> io::Result<ActiveSocket> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.active.lock().unwrap_or_else(|e| e.into_inner()).insert(id, socket.try_clone_to_owned()?);
+        Ok(ActiveSocket { registry: self, id })
+    }
+
+    /// Take the inherited tcp listener of the address or bind a new one, and register it.
+    ///
+    /// # Errors
+    /// If fail to bind the socket.
+    pub async fn bind_tcp(&'static self, addr: SocketAddr) -> io::Result<(tokio::net::TcpListener, ActiveSocket)> {
+        let listener = match self.take_tcp(addr) {
+            Some(listener) => {
+                tracing::info!(%addr, "[Sg.Handoff] listen on inherited socket");
+                listener.set_nonblocking(true)?;
+                tokio::net::TcpListener::from_std(listener)?
+            }
+            None => tokio::net::TcpListener::bind(addr).await?,
+        };
+        let active = self.register(listener.as_fd())?;
+        Ok((listener, active))
+    }
+
+    /// Take the inherited udp socket of the address or bind a new one, and register it.
+    ///
+    /// # Errors
+    /// If fail to bind the socket.
+    pub fn bind_udp(&'static self, addr: SocketAddr) -> io::Result<(UdpSocket, ActiveSocket)> {
+        let socket = match self.take_udp(addr) {
+            Some(socket) => {
+                tracing::info!(%addr, "[Sg.Handoff] listen on inherited socket");
+                socket
+            }
+            None => UdpSocket::bind(addr)?,
+        };
+        socket.set_nonblocking(true)?;
+        let active = self.register(socket.as_fd())?;
+        Ok((socket, active))
+    }
+
+    /// Accept a new process on the unix socket and send it the active sockets,
+    /// then wait for it to be ready in `ready_timeout`.
+    ///
+    /// After this, the listeners of this process should be cancelled to drain their connections.
+    ///
+    /// # Errors
+    /// If the handoff fails, or the new process is not ready in time. This process keeps serving then.
+    pub async fn serve_handoff(&self, listener: &UnixListener, ready_timeout: Duration) -> io::Result<()> {
+        let (mut stream, _) = listener.accept().await?;
+        // the sockets are only handed to a process of the same user
+        let peer_uid = stream.peer_cred()?.uid();
+        if peer_uid != nix::unistd::geteuid().as_raw() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("handoff requested by another user {peer_uid}")));
+        }
+        let fds = self.active.lock().unwrap_or_else(|e| e.into_inner()).values().map(|fd| fd.try_clone()).collect::<io::Result<Vec<_>>>()?;
+        tracing::info!(sockets = fds.len(), "[Sg.Handoff] send sockets to the new process");
+        for chunk in fds.chunks(FDS_PER_MESSAGE) {
+            send_fds(&stream, chunk).await?;
+        }
+        send_fds(&stream, &[]).await?;
+        let mut ready = [0u8];
+        match tokio::time::timeout(ready_timeout, stream.read_exact(&mut ready)).await {
+            Ok(Ok(_)) if ready == [READY] => Ok(()),
+            Ok(Ok(_)) => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected handoff message")),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "the new process is not ready in time")),
+        }
+    }
+
+    /// Connect to the running process and inherit its sockets.
+    ///
+    /// Call [`Handoff::ready`] after the listeners are started, so that the running process begins to drain.
+    ///
+    /// # Errors
+    /// If fail to connect or receive the sockets.
+    pub async fn receive_handoff(&self, path: impl AsRef<Path>) -> io::Result<Handoff> {
+        let stream = UnixStream::connect(path).await?;
+        let mut count = 0;
+        loop {
+            let fds = recv_fds(&stream).await?;
+            if fds.is_empty() {
+                break;
+            }
+            count += fds.len();
+            fds.into_iter().for_each(|fd| self.inherit(fd));
+        }
+        tracing::info!(sockets = count, "[Sg.Handoff] received sockets from the running process");
+        Ok(Handoff { stream })
+    }
+}
+
+/// The socket of a running listener, see [`SocketRegistry::register`].
+#[derive(Debug)]
+pub struct ActiveSocket {
+    registry: &'static SocketRegistry,
+    id: u64,
+}
+
+impl Drop for ActiveSocket {
+    fn drop(&mut self) {
+        self.registry.active.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id);
+    }
+}
+
+/// Connection to the previous process, see [`SocketRegistry::receive_handoff`].
+#[derive(Debug)]
+pub struct Handoff {
+    stream: UnixStream,
+}
+
+impl Handoff {
+    /// Tell the previous process that this process is serving.
+    ///
+    /// # Errors
+    /// If the previous process is gone.
+    pub async fn ready(mut self) -> io::Result<()> {
+        self.stream.write_all(&[READY]).await?;
+        self.stream.flush().await
+    }
+}
+
+/// Send a message of the fd count, with the fds attached.
+async fn send_fds(stream: &UnixStream, fds: &[OwnedFd]) -> io::Result<()> {
+    let count = (fds.len() as u32).to_le_bytes();
+    let raw_fds = fds.iter().map(AsRawFd::as_raw_fd).collect::<Vec<_>>();
+    let cmsgs = if raw_fds.is_empty() { vec![] } else { vec![ControlMessage::ScmRights(&raw_fds)] };
+    let sent = stream
+        .async_io(Interest::WRITABLE, || {
+            sendmsg::<()>(stream.as_raw_fd(), &[IoSlice::new(&count)], &cmsgs, MsgFlags::empty(), None).map_err(io::Error::from)
+        })
+        .await?;
+    if sent != count.len() {
+        return Err(io::Error::new(io::ErrorKind::WriteZero, "partial handoff message"));
+    }
+    Ok(())
+}
+
+/// Receive a message sent by [`send_fds`].
+async fn recv_fds(stream: &UnixStream) -> io::Result<Vec<OwnedFd>> {
+    let mut count = [0u8; 4];
+    let mut cmsg_buffer = nix::cmsg_space!([RawFd; FDS_PER_MESSAGE]);
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = MsgFlags::MSG_CMSG_CLOEXEC;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags = MsgFlags::empty();
+    let (read, raw_fds) = stream
+        .async_io(Interest::READABLE, || {
+            let mut iov = [IoSliceMut::new(&mut count)];
+            let message = recvmsg::<()>(stream.as_raw_fd(), &mut iov, Some(&mut cmsg_buffer), flags).map_err(io::Error::from)?;
+            let mut raw_fds = Vec::new();
+            for cmsg in message.cmsgs()? {
+                if let ControlMessageOwned::ScmRights(fds) = cmsg {
+                    raw_fds.extend(fds);
+                }
+            }
+            Ok((message.bytes, raw_fds))
+        })
+        .await?;
+    // SAFETY: the fds are received by SCM_RIGHTS, they are owned by this process
+    let fds = raw_fds.into_iter().map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }).collect::<Vec<_>>();
+    if read != count.len() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "handoff connection closed"));
+    }
+    if u32::from_le_bytes(count) as usize != fds.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "handoff message truncated"));
+    }
+    Ok(fds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inherited_socket_matches_address() {
+        let addr = |s: &str| s.parse::<SocketAddr>().expect("invalid addr");
+        assert!(addr_matches(addr("127.0.0.1:8080"), addr("127.0.0.1:8080")));
+        assert!(addr_matches(addr("[::]:8080"), addr("0.0.0.0:8080")));
+        assert!(!addr_matches(addr("[::]:8080"), addr("0.0.0.0:8081")));
+        assert!(!addr_matches(addr("127.0.0.1:8080"), addr("0.0.0.0:8080")));
+    }
+
+    #[test]
+    fn parse_systemd_sockets() {
+        assert_eq!(SystemdSockets::parse("42", "2").expect("valid variables"), SystemdSockets { pid: 42, fds: 2 });
+        assert!(SystemdSockets::parse("42", "-1").is_err());
+        assert!(SystemdSockets::parse("pid", "1").is_err());
+        // not passed to this process
+        let sockets = SystemdSockets::parse("0", "1").expect("valid variables");
+        assert_eq!(SocketRegistry::default().inherit_systemd(sockets).expect("nothing to inherit"), 0);
+    }
+
+    #[tokio::test]
+    async fn handoff_sockets() {
+        // the global registry is not used, so that the test is isolated
+        let old: &'static SocketRegistry = Box::leak(Box::default());
+        let new: &'static SocketRegistry = Box::leak(Box::default());
+        let (tcp, _tcp_active) = old.bind_tcp(addr("127.0.0.1:0")).await.expect("fail to bind tcp");
+        let (udp, _udp_active) = old.bind_udp(addr("127.0.0.1:0")).expect("fail to bind udp");
+        let (tcp_addr, udp_addr) = (tcp.local_addr().expect("tcp addr"), udp.local_addr().expect("udp addr"));
+
+        let path = std::env::temp_dir().join(format!("spacegate-handoff-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).expect("fail to bind unix socket");
+        let serving = tokio::spawn(async move { old.serve_handoff(&listener, Duration::from_secs(1)).await });
+        let handoff = new.receive_handoff(&path).await.expect("fail to receive sockets");
+        assert_eq!(new.inherited_count(), 2);
+        assert!(new.take_udp(tcp_addr).is_none());
+        let inherited = new.take_tcp(tcp_addr).expect("tcp listener is inherited");
+        assert!(new.take_udp(udp_addr).is_some());
+        handoff.ready().await.expect("fail to send ready");
+        serving.await.expect("join error").expect("handoff failed");
+        let _ = std::fs::remove_file(&path);
+
+        // both processes accept on the same socket
+        drop(tcp);
+        let _client = std::net::TcpStream::connect(tcp_addr).expect("fail to connect");
+        inherited.accept().expect("fail to accept on inherited listener");
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().expect("invalid addr")
+    }
+}
@@ -0,0 +1,276 @@
+//! HTTP/3 listener
+//!
+//! Serve the gateway service over QUIC. Clients usually discover it by the `Alt-Svc` header of the https listener on the same port, see [`alt_svc`].
+
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+
+use futures_util::TryFutureExt;
+use h3::server::RequestStream;
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body, Buf, Bytes, Frame, SizeHint},
+    header::{HeaderName, HeaderValue, CONNECTION, CONTENT_LENGTH, TRANSFER_ENCODING, UPGRADE},
+    service::Service,
+    Request, Response,
+};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::{self, pki_types::CertificateDer};
+use tokio_util::sync::CancellationToken;
+use tracing::{instrument, Instrument};
+
+use crate::{extension::PeerCertificate, service::HyperServiceAdapter, ArcHyperService, BoxError, BoxResult};
+
+/// ALPN protocol id of HTTP/3.
+pub const ALPN_H3: &[u8] = b"h3";
+
+/// Value of the `Alt-Svc` header which advertises HTTP/3 on the port.
+pub fn alt_svc(port: u16) -> HeaderValue {
+    HeaderValue::from_str(&format!("h3=\":{port}\"; ma=86400")).expect("alt-svc header value is always valid")
+}
+
+/// Connection-specific headers are not allowed in HTTP/3.
+const CONNECTION_SPECIFIC_HEADERS: [HeaderName; 5] = [
+    CONNECTION,
+    TRANSFER_ENCODING,
+    UPGRADE,
+    HeaderName::from_static("keep-alive"),
+    HeaderName::from_static("proxy-connection"),
+];
+
+/// Listener which accepts QUIC connections on a UDP socket and serves HTTP/3 requests.
+#[derive(Debug, Clone)]
+pub struct SgHttp3Listen {
+    pub socket_addr: SocketAddr,
+    pub listener_id: String,
+    service: ArcHyperService,
+    gateway_name: Arc<str>,
+    tls_config: Arc<rustls::ServerConfig>,
+    cancel_token: CancellationToken,
+}
+
+impl SgHttp3Listen {
+    /// The ALPN protocols of the tls config are replaced by `h3`, and QUIC requires TLS 1.3.
+    pub fn new(socket_addr: SocketAddr, service: ArcHyperService, mut tls_config: rustls::ServerConfig, cancel_token: CancellationToken) -> Self {
+        tls_config.alpn_protocols = vec![ALPN_H3.to_vec()];
+        Self {
+            socket_addr,
+            listener_id: Default::default(),
+            service,
+            gateway_name: Arc::<str>::from("unknown"),
+            tls_config: Arc::new(tls_config),
+            cancel_token,
+        }
+    }
+
+    pub fn with_gateway_name(mut self, gateway_name: Arc<str>) -> Self {
+        self.gateway_name = gateway_name;
+        self
+    }
+
+    pub fn with_listener_id(mut self, listener_id: impl Into<String>) -> Self {
+        self.listener_id = listener_id.into();
+        self
+    }
+
+    /// Spawn the listener on the tokio runtime.
+    ///
+    /// It's a shortcut for `tokio::spawn(listener.listen())`.
+    pub fn spawn(self) -> tokio::task::JoinHandle<Result<(), BoxError>> {
+        tokio::spawn(self.listen())
+    }
+
+    /// Listen on the udp socket address.
+    ///
+    /// # Errors
+    /// If the tls config doesn't support TLS 1.3, or fail to bind the socket.
+    #[instrument(skip(self), fields(bind=%self.socket_addr))]
+    pub async fn listen(self) -> Result<(), BoxError> {
+        tracing::debug!("start binding...");
+        let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(self.tls_config)?;
+        let server_config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+        #[cfg(all(unix, feature = "socket-handoff"))]
+        let (endpoint, _active) = {
+            let (socket, active) = super::handoff::SocketRegistry::global().bind_udp(self.socket_addr)?;
+            let endpoint = quinn::Endpoint::new(quinn::EndpointConfig::default(), Some(server_config), socket, Arc::new(quinn::TokioRuntime))?;
+            (endpoint, active)
+        };
+        #[cfg(not(all(unix, feature = "socket-handoff")))]
+        let endpoint = quinn::Endpoint::server(server_config, self.socket_addr)?;
+        tracing::debug!("start listening...");
+        loop {
+            let incoming = tokio::select! {
+                () = self.cancel_token.cancelled() => {
+                    tracing::warn!("cancelled");
+                    endpoint.close(0u32.into(), b"shutdown");
+                    return Ok(());
+                },
+                incoming = endpoint.accept() => incoming
+            };
+            let Some(incoming) = incoming else {
+                return Ok(());
+            };
+            let peer_addr = incoming.remote_address();
+            let service = self.service.clone();
+            let gateway_name = self.gateway_name.clone();
+            let _task = tokio::spawn(
+                serve_connection(incoming, service, gateway_name)
+                    .inspect_err(|e| {
+                        tracing::warn!("Http3 connection error: {:?}", e);
+                    })
+                    .instrument(tracing::info_span!("connection", peer = %peer_addr)),
+            );
+        }
+    }
+}
+
+async fn serve_connection(incoming: quinn::Incoming, service: ArcHyperService, gateway_name: Arc<str>) -> BoxResult<()> {
+    let connection = incoming.await?;
+    let peer = connection.remote_address();
+    let peer_certificate = connection
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .and_then(|certs| certs.first().cloned())
+        .and_then(|cert| PeerCertificate::from_der(cert).inspect_err(|e| tracing::warn!(%peer, "[Sg.Http3] fail to parse peer certificate: {e}")).ok());
+    let service = HyperServiceAdapter::with_gateway_name(service, peer, gateway_name).with_peer_certificate(peer_certificate);
+    let mut connection = h3::server::Connection::<_, Bytes>::new(h3_quinn::Connection::new(connection)).await?;
+    loop {
+        match connection.accept().await {
+            Ok(Some(resolver)) => {
+                let service = service.clone();
+                let _task = tokio::spawn(
+                    async move {
+                        let (req, stream) = resolver.resolve_request().await?;
+                        serve_request(req, stream, service).await
+                    }
+                    .inspect_err(|e| tracing::debug!("Http3 request error: {:?}", e)),
+                );
+            }
+            Ok(None) => return Ok(()),
+            Err(e) if e.is_h3_no_error() => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+async fn serve_request(req: Request<()>, stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>, service: HyperServiceAdapter<ArcHyperService>) -> BoxResult<()> {
+    let (mut send, mut recv) = stream.split();
+    let (tx, rx) = mpsc::channel(4);
+    let _recv_task = tokio::spawn(async move {
+        loop {
+            let frame = match recv.recv_data().await {
+                Ok(Some(mut data)) => Ok(Frame::data(data.copy_to_bytes(data.remaining()))),
+                Ok(None) => match recv.recv_trailers().await {
+                    Ok(Some(trailers)) => Ok(Frame::trailers(trailers)),
+                    Ok(None) => return,
+                    Err(e) => Err(e.into()),
+                },
+                Err(e) => Err(e.into()),
+            };
+            let end = !matches!(&frame, Ok(frame) if frame.is_data());
+            if tx.send(frame).await.is_err() || end {
+                return;
+            }
+        }
+    });
+    let content_length = req.headers().get(CONTENT_LENGTH).and_then(|value| value.to_str().ok()?.trim().parse().ok());
+    let resp = service.call(req.map(|()| Http3RequestBody::new(rx, content_length))).await.expect("infallible");
+    let (mut parts, mut body) = resp.into_parts();
+    for header in &CONNECTION_SPECIFIC_HEADERS {
+        parts.headers.remove(header);
+    }
+    send.send_response(Response::from_parts(parts, ())).await?;
+    while let Some(frame) = body.frame().await {
+        match frame?.into_data() {
+            Ok(data) => send.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    send.send_trailers(trailers).await?;
+                }
+            }
+        }
+    }
+    send.finish().await?;
+    Ok(())
+}
+
+/// Body of a HTTP/3 request, the frames are received from the QUIC stream by another task.
+struct Http3RequestBody {
+    rx: mpsc::Receiver<Result<Frame<Bytes>, BoxError>>,
+    /// bytes not received yet, by the `content-length` header
+    remaining: Option<u64>,
+    ended: bool,
+}
+
+impl Http3RequestBody {
+    fn new(rx: mpsc::Receiver<Result<Frame<Bytes>, BoxError>>, content_length: Option<u64>) -> Self {
+        Self {
+            rx,
+            remaining: content_length,
+            ended: false,
+        }
+    }
+}
+
+impl Body for Http3RequestBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if self.ended {
+            return Poll::Ready(None);
+        }
+        let frame = ready!(self.rx.poll_recv(cx));
+        match &frame {
+            Some(Ok(frame)) if frame.is_data() => {
+                let len = frame.data_ref().map_or(0, |data| data.len() as u64);
+                self.remaining = self.remaining.map(|remaining| remaining.saturating_sub(len));
+            }
+            // trailers and errors are the last frames
+            _ => self.ended = true,
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.ended
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.remaining.map(SizeHint::with_exact).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn request_body_ends_and_hints_content_length() -> BoxResult<()> {
+        let (tx, rx) = mpsc::channel(4);
+        let mut body = Http3RequestBody::new(rx, Some(5));
+        assert_eq!(body.size_hint().exact(), Some(5));
+        tx.send(Ok(Frame::data(Bytes::from_static(b"hel")))).await?;
+        tx.send(Ok(Frame::data(Bytes::from_static(b"lo")))).await?;
+        drop(tx);
+        assert!(body.frame().await.is_some());
+        assert_eq!(body.size_hint().exact(), Some(2));
+        assert!(!body.is_end_stream());
+        assert!(body.frame().await.is_some());
+        assert!(body.frame().await.is_none());
+        assert!(body.is_end_stream());
+        assert_eq!(body.size_hint().exact(), Some(0));
+
+        let (tx, rx) = mpsc::channel(4);
+        let mut body = Http3RequestBody::new(rx, None);
+        assert_eq!(body.size_hint().exact(), None);
+        tx.send(Ok(Frame::trailers(hyper::HeaderMap::new()))).await?;
+        assert!(body.frame().await.is_some());
+        assert!(body.is_end_stream());
+        Ok(())
+    }
+}
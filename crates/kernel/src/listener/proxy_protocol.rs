@@ -0,0 +1,292 @@
+//! PROXY protocol
+//!
+//! Load balancers working at layer 4 prepend a PROXY protocol header to the connection, which carries the address of the original client.
+//!
+//! Reference: [The PROXY protocol Versions 1 & 2](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt)
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use ipnet::IpNet;
+use tokio::{io::AsyncReadExt, net::TcpStream};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// The longest v1 header is `PROXY TCP6 <39 bytes> <39 bytes> 65535 65535\r\n`.
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LEN: usize = 16;
+const V2_VERSION: u8 = 0x2;
+const V2_COMMAND_LOCAL: u8 = 0x0;
+const V2_COMMAND_PROXY: u8 = 0x1;
+const V2_FAMILY_UNSPEC: u8 = 0x0;
+const V2_FAMILY_INET: u8 = 0x1;
+const V2_FAMILY_INET6: u8 = 0x2;
+const V2_FAMILY_UNIX: u8 = 0x3;
+
+const DEFAULT_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+/// Wait for the rest of the header when it's not fully received.
+const PEEK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A parsed PROXY protocol header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// Length of the header in bytes.
+    pub len: usize,
+    /// Address of the original client.
+    ///
+    /// It's `None` for `UNKNOWN` connections of v1, `LOCAL` connections of v2 and unix sockets.
+    pub source: Option<SocketAddr>,
+    /// Address the original client connected to.
+    pub destination: Option<SocketAddr>,
+}
+
+/// Parse a PROXY protocol header of version 1 or 2 at the start of the buffer.
+///
+/// Returns `Ok(None)` if the buffer doesn't contain the whole header yet.
+///
+/// # Errors
+/// If the buffer doesn't start with a valid PROXY protocol header.
+pub fn parse_proxy_header(buf: &[u8]) -> io::Result<Option<ProxyHeader>> {
+    if buf.starts_with(V2_SIGNATURE) {
+        parse_v2(buf)
+    } else if buf.starts_with(V1_PREFIX) {
+        parse_v1(buf)
+    } else if V2_SIGNATURE.starts_with(buf) || V1_PREFIX.starts_with(buf) {
+        Ok(None)
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+fn parse_v1(buf: &[u8]) -> io::Result<Option<ProxyHeader>> {
+    let Some(end) = buf.windows(2).take(V1_MAX_LEN - 1).position(|w| w == b"\r\n") else {
+        return if buf.len() >= V1_MAX_LEN {
+            Err(invalid("PROXY protocol v1 header is too long"))
+        } else {
+            Ok(None)
+        };
+    };
+    let line = buf.get(..end).and_then(|line| std::str::from_utf8(line).ok()).ok_or_else(|| invalid("PROXY protocol v1 header is not ascii"))?;
+    let mut parts = line.split(' ').skip(1);
+    let (source, destination) = match parts.next() {
+        Some("UNKNOWN") => (None, None),
+        Some(protocol @ ("TCP4" | "TCP6")) => {
+            let mut next = || parts.next().ok_or_else(|| invalid("PROXY protocol v1 header is incomplete"));
+            let (source_ip, destination_ip) = if protocol == "TCP4" {
+                (IpAddr::V4(parse_v1_field::<Ipv4Addr>(next()?)?), IpAddr::V4(parse_v1_field::<Ipv4Addr>(next()?)?))
+            } else {
+                (IpAddr::V6(parse_v1_field::<Ipv6Addr>(next()?)?), IpAddr::V6(parse_v1_field::<Ipv6Addr>(next()?)?))
+            };
+            let source_port = parse_v1_field::<u16>(next()?)?;
+            let destination_port = parse_v1_field::<u16>(next()?)?;
+            if parts.next().is_some() {
+                return Err(invalid("PROXY protocol v1 header has unexpected fields"));
+            }
+            (Some(SocketAddr::new(source_ip, source_port)), Some(SocketAddr::new(destination_ip, destination_port)))
+        }
+        _ => return Err(invalid("unknown protocol in PROXY protocol v1 header")),
+    };
+    Ok(Some(ProxyHeader {
+        len: end + 2,
+        source,
+        destination,
+    }))
+}
+
+fn parse_v1_field<T: std::str::FromStr>(field: &str) -> io::Result<T> {
+    field.parse().map_err(|_| invalid("invalid field in PROXY protocol v1 header"))
+}
+
+fn parse_v2(buf: &[u8]) -> io::Result<Option<ProxyHeader>> {
+    let Some(&[version_command, family, len_hi, len_lo]) = buf.get(V2_SIGNATURE.len()..V2_HEADER_LEN) else {
+        return Ok(None);
+    };
+    if version_command >> 4 != V2_VERSION {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    let len = V2_HEADER_LEN + u16::from_be_bytes([len_hi, len_lo]) as usize;
+    let Some(addresses) = buf.get(V2_HEADER_LEN..len) else {
+        return Ok(None);
+    };
+    let (source, destination) = match (version_command & 0x0f, family >> 4) {
+        (V2_COMMAND_LOCAL, _) | (V2_COMMAND_PROXY, V2_FAMILY_UNSPEC | V2_FAMILY_UNIX) => (None, None),
+        (V2_COMMAND_PROXY, V2_FAMILY_INET) => {
+            let (Some(source), Some(destination), Some(source_port), Some(destination_port)) =
+                (array::<4>(addresses, 0), array::<4>(addresses, 4), array::<2>(addresses, 8), array::<2>(addresses, 10))
+            else {
+                return Err(invalid("PROXY protocol v2 address block is too short"));
+            };
+            (
+                Some(SocketAddr::new(Ipv4Addr::from(source).into(), u16::from_be_bytes(source_port))),
+                Some(SocketAddr::new(Ipv4Addr::from(destination).into(), u16::from_be_bytes(destination_port))),
+            )
+        }
+        (V2_COMMAND_PROXY, V2_FAMILY_INET6) => {
+            let (Some(source), Some(destination), Some(source_port), Some(destination_port)) =
+                (array::<16>(addresses, 0), array::<16>(addresses, 16), array::<2>(addresses, 32), array::<2>(addresses, 34))
+            else {
+                return Err(invalid("PROXY protocol v2 address block is too short"));
+            };
+            (
+                Some(SocketAddr::new(Ipv6Addr::from(source).into(), u16::from_be_bytes(source_port))),
+                Some(SocketAddr::new(Ipv6Addr::from(destination).into(), u16::from_be_bytes(destination_port))),
+            )
+        }
+        (V2_COMMAND_PROXY, _) => return Err(invalid("unknown address family in PROXY protocol v2 header")),
+        _ => return Err(invalid("unknown command in PROXY protocol v2 header")),
+    };
+    Ok(Some(ProxyHeader { len, source, destination }))
+}
+
+fn array<const N: usize>(buf: &[u8], at: usize) -> Option<[u8; N]> {
+    buf.get(at..at + N)?.try_into().ok()
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Accept PROXY protocol headers on a listener.
+#[derive(Debug, Clone)]
+pub struct ProxyProtocol {
+    /// Only the connections from these networks are expected to start with a PROXY protocol header, empty for no source.
+    ///
+    /// Other connections are served as is, with their own peer address.
+    pub trusted_sources: Vec<IpNet>,
+    /// How long to wait for the header.
+    pub header_timeout: Duration,
+}
+
+impl Default for ProxyProtocol {
+    fn default() -> Self {
+        Self {
+            trusted_sources: Vec::new(),
+            header_timeout: DEFAULT_HEADER_TIMEOUT,
+        }
+    }
+}
+
+impl ProxyProtocol {
+    pub fn new(trusted_sources: impl IntoIterator<Item = IpNet>) -> Self {
+        Self {
+            trusted_sources: trusted_sources.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_header_timeout(mut self, header_timeout: Duration) -> Self {
+        self.header_timeout = header_timeout;
+        self
+    }
+
+    /// Whether the connections from this address should start with a PROXY protocol header.
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.trusted_sources.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Read and consume the PROXY protocol header from the stream, the data after the header is left in the stream.
+    ///
+    /// # Errors
+    /// If the header is invalid, the connection is closed, or the header is not received in time.
+    pub async fn read_header(&self, stream: &mut TcpStream) -> io::Result<ProxyHeader> {
+        tokio::time::timeout(self.header_timeout, read_header(stream)).await.map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timeout reading PROXY protocol header"))?
+    }
+}
+
+async fn read_header(stream: &mut TcpStream) -> io::Result<ProxyHeader> {
+    let mut buf = vec![0u8; V1_MAX_LEN];
+    loop {
+        let peeked = stream.peek(&mut buf).await?;
+        if peeked == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if let Some(header) = parse_proxy_header(buf.get(..peeked).unwrap_or_default())? {
+            let mut header_buf = vec![0u8; header.len];
+            stream.read_exact(&mut header_buf).await?;
+            return Ok(header);
+        }
+        if peeked == buf.len() {
+            // a v2 header with long TLVs
+            buf.resize(buf.len() * 2, 0);
+        } else {
+            tokio::time::sleep(PEEK_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_v1() {
+        let header = parse_proxy_header(b"PROXY TCP4 192.168.1.10 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n").expect("valid header").expect("complete header");
+        assert_eq!(header.len, 44);
+        assert_eq!(header.source, Some("192.168.1.10:56324".parse().expect("addr")));
+        assert_eq!(header.destination, Some("10.0.0.1:443".parse().expect("addr")));
+
+        let header = parse_proxy_header(b"PROXY TCP6 2001:db8::1 ::1 56324 443\r\n").expect("valid header").expect("complete header");
+        assert_eq!(header.source, Some("[2001:db8::1]:56324".parse().expect("addr")));
+
+        let header = parse_proxy_header(b"PROXY UNKNOWN\r\n").expect("valid header").expect("complete header");
+        assert_eq!((header.len, header.source), (15, None));
+
+        assert!(parse_proxy_header(b"PROXY TCP4 192.168.1.10 10.0").expect("valid prefix").is_none());
+        assert!(parse_proxy_header(b"PRO").expect("valid prefix").is_none());
+        assert!(parse_proxy_header(b"PROXY TCP4 ::1 10.0.0.1 56324 443\r\n").is_err());
+        assert!(parse_proxy_header(b"PROXY TCP4 192.168.1.10 10.0.0.1 56324\r\n").is_err());
+        assert!(parse_proxy_header(&[b'a'; V1_MAX_LEN]).is_err());
+        assert!(parse_proxy_header(b"GET / HTTP/1.1\r\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let mut buf = V2_SIGNATURE.to_vec();
+        // PROXY, TCP over IPv4, with a 3 bytes TLV
+        buf.extend_from_slice(&[0x21, 0x11, 0, 15]);
+        buf.extend_from_slice(&[192, 168, 1, 10, 10, 0, 0, 1, 0xdc, 0x04, 0x01, 0xbb, 0x04, 0, 0]);
+        let complete = buf.len();
+        buf.extend_from_slice(b"\x16\x03\x01");
+        let header = parse_proxy_header(&buf).expect("valid header").expect("complete header");
+        assert_eq!(header.len, complete);
+        assert_eq!(header.source, Some("192.168.1.10:56324".parse().expect("addr")));
+        assert_eq!(header.destination, Some("10.0.0.1:443".parse().expect("addr")));
+        assert!(parse_proxy_header(buf.get(..complete - 1).expect("short")).expect("valid prefix").is_none());
+
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.extend_from_slice(&[0x21, 0x21, 0, 36]);
+        buf.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().expect("ip").octets());
+        buf.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        buf.extend_from_slice(&[0xdc, 0x04, 0x01, 0xbb]);
+        let header = parse_proxy_header(&buf).expect("valid header").expect("complete header");
+        assert_eq!(header.source, Some("[2001:db8::1]:56324".parse().expect("addr")));
+
+        // LOCAL, health checks from the load balancer itself
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        let header = parse_proxy_header(&buf).expect("valid header").expect("complete header");
+        assert_eq!((header.len, header.source), (16, None));
+
+        // version 1 in binary format
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.extend_from_slice(&[0x11, 0x11, 0, 0]);
+        assert!(parse_proxy_header(&buf).is_err());
+        // address block is shorter than the family requires
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.extend_from_slice(&[0x21, 0x11, 0, 4, 192, 168, 1, 10]);
+        assert!(parse_proxy_header(&buf).is_err());
+    }
+
+    #[test]
+    fn test_trusted_sources() {
+        let proxy_protocol = ProxyProtocol::new(["10.0.0.0/8".parse().expect("cidr")]);
+        assert!(proxy_protocol.is_trusted("10.1.2.3".parse().expect("ip")));
+        assert!(!proxy_protocol.is_trusted("192.168.1.10".parse().expect("ip")));
+        // a forged header could replace the peer address, so no source is trusted by default
+        assert!(!ProxyProtocol::default().is_trusted("192.168.1.10".parse().expect("ip")));
+    }
+}
@@ -0,0 +1,260 @@
+//! UDP listener
+//!
+//! Datagrams from the same client address belong to one session, which is forwarded to one backend through its own socket,
+//! so that the replies of the backend could be sent back to the client.
+//!
+//! A session is closed after it's idle for the idle timeout of its route, or when the listener reaches its max sessions
+//! and the session is the least recently active one.
+
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures_util::TryFutureExt;
+use hyper::body::Bytes;
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{self, error::TrySendError},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{instrument, Instrument};
+
+use crate::{
+    service::stream_proxy::{StreamBackend, UdpProxy},
+    BoxError, BoxResult,
+};
+
+/// Max size of a UDP payload.
+const MAX_DATAGRAM_SIZE: usize = 65535;
+/// Datagrams are dropped if a session falls behind this many datagrams.
+const SESSION_QUEUE_SIZE: usize = 64;
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Default max sessions of a listener.
+pub const DEFAULT_MAX_SESSIONS: usize = 10000;
+
+/// Listener which receives UDP datagrams and forwards them by the routes of its [`UdpProxy`].
+#[derive(Debug, Clone)]
+pub struct SgUdpListen {
+    pub socket_addr: SocketAddr,
+    /// Name of the listener, which is used to find the route.
+    pub listener_id: String,
+    /// Max concurrent sessions, the least recently active session is closed for a new client beyond it.
+    pub max_sessions: usize,
+    proxy: UdpProxy,
+    cancel_token: CancellationToken,
+}
+
+impl SgUdpListen {
+    pub fn new(socket_addr: SocketAddr, proxy: UdpProxy, cancel_token: CancellationToken) -> Self {
+        Self {
+            socket_addr,
+            listener_id: Default::default(),
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            proxy,
+            cancel_token,
+        }
+    }
+
+    pub fn with_listener_id(mut self, listener_id: impl Into<String>) -> Self {
+        self.listener_id = listener_id.into();
+        self
+    }
+
+    /// Set the max concurrent sessions, it's at least 1.
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = max_sessions.max(1);
+        self
+    }
+
+    /// Spawn the listener on the tokio runtime.
+    ///
+    /// It's a shortcut for `tokio::spawn(listener.listen())`.
+    pub fn spawn(self) -> tokio::task::JoinHandle<Result<(), BoxError>> {
+        tokio::spawn(self.listen())
+    }
+
+    /// Listen on the udp socket address.
+    ///
+    /// # Errors
+    /// If fail to bind the socket.
+    #[instrument(skip(self), fields(bind=%self.socket_addr))]
+    pub async fn listen(self) -> Result<(), BoxError> {
+        tracing::debug!("start binding...");
+        #[cfg(all(unix, feature = "socket-handoff"))]
+        let (socket, _active) = {
+            let (socket, active) = super::handoff::SocketRegistry::global().bind_udp(self.socket_addr)?;
+            (UdpSocket::from_std(socket)?, active)
+        };
+        #[cfg(not(all(unix, feature = "socket-handoff")))]
+        let socket = UdpSocket::bind(self.socket_addr).await?;
+        let socket = Arc::new(socket);
+        tracing::debug!("start listening...");
+        let mut sessions = HashMap::<SocketAddr, Session>::new();
+        let epoch = Instant::now();
+        let (closed_tx, mut closed_rx) = mpsc::unbounded_channel::<SocketAddr>();
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        loop {
+            let received = tokio::select! {
+                () = self.cancel_token.cancelled() => {
+                    tracing::warn!("cancelled");
+                    return Ok(());
+                },
+                Some(peer) = closed_rx.recv() => {
+                    if sessions.get(&peer).is_some_and(|session| session.sender.is_closed()) {
+                        sessions.remove(&peer);
+                    }
+                    continue;
+                }
+                received = socket.recv_from(&mut buf) => received
+            };
+            let (len, peer) = match received {
+                Ok(received) => received,
+                Err(e) => {
+                    tracing::warn!("Receive udp datagram error: {:?}", e);
+                    continue;
+                }
+            };
+            let datagram = Bytes::copy_from_slice(buf.get(..len).unwrap_or_default());
+            let datagram = match sessions.get(&peer) {
+                Some(session) => match session.sender.try_send(datagram) {
+                    Ok(()) => {
+                        session.activity.touch();
+                        continue;
+                    }
+                    Err(TrySendError::Full(_)) => {
+                        tracing::trace!(%peer, "[Sg.UdpProxy] session is busy, datagram dropped");
+                        continue;
+                    }
+                    // the session is closed for being idle
+                    Err(TrySendError::Closed(datagram)) => datagram,
+                },
+                None => datagram,
+            };
+            let (route_name, backend, idle_timeout) = match self.proxy.pick(&self.listener_id) {
+                Ok(picked) => picked,
+                Err(e) => {
+                    tracing::debug!(%peer, "[Sg.UdpProxy] datagram dropped: {e}");
+                    continue;
+                }
+            };
+            tracing::debug!(gateway = %self.proxy.gateway_name(), %peer, route = %route_name, backend = %backend.host, port = backend.port, "[Sg.UdpProxy] new session");
+            if sessions.len() >= self.max_sessions && !sessions.contains_key(&peer) {
+                // the session is closed after its queued datagrams are sent, since its sender is dropped
+                if let Some(idlest) = sessions.iter().min_by_key(|(_, session)| session.activity.last()).map(|(peer, _)| *peer) {
+                    tracing::debug!(peer = %idlest, "[Sg.UdpProxy] too many sessions, close the least recently active session");
+                    sessions.remove(&idlest);
+                }
+            }
+            let (tx, rx) = mpsc::channel(SESSION_QUEUE_SIZE);
+            let _ = tx.try_send(datagram);
+            let activity = Activity::new(epoch);
+            sessions.insert(
+                peer,
+                Session {
+                    sender: tx,
+                    activity: activity.clone(),
+                },
+            );
+            let socket = socket.clone();
+            let closed_tx = closed_tx.clone();
+            let _task = tokio::spawn(
+                async move {
+                    let result = serve_session(socket, peer, backend, idle_timeout.unwrap_or(DEFAULT_IDLE_TIMEOUT), rx, activity).await;
+                    let _ = closed_tx.send(peer);
+                    result
+                }
+                .inspect_err(|e| {
+                    tracing::warn!("Udp session error: {:?}", e);
+                })
+                .instrument(tracing::info_span!("session", peer = %peer)),
+            );
+        }
+    }
+}
+
+/// A session of the listener, it's closed once the sender is dropped.
+struct Session {
+    sender: mpsc::Sender<Bytes>,
+    activity: Activity,
+}
+
+/// Time of the last datagram of a session in either direction, in milliseconds since the listener started.
+#[derive(Debug, Clone)]
+struct Activity {
+    epoch: Instant,
+    last: Arc<AtomicU64>,
+}
+
+impl Activity {
+    fn new(epoch: Instant) -> Self {
+        let activity = Self {
+            epoch,
+            last: Arc::new(AtomicU64::new(0)),
+        };
+        activity.touch();
+        activity
+    }
+    fn touch(&self) {
+        self.last.store(self.epoch.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+    fn last(&self) -> u64 {
+        self.last.load(Ordering::Relaxed)
+    }
+}
+
+async fn serve_session(
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+    backend: StreamBackend,
+    idle_timeout: Duration,
+    mut rx: mpsc::Receiver<Bytes>,
+    activity: Activity,
+) -> BoxResult<()> {
+    let backend_addr = tokio::net::lookup_host((backend.host.as_str(), backend.port))
+        .await?
+        .next()
+        .ok_or_else(|| BoxError::from(format!("fail to resolve udp backend {}:{}", backend.host, backend.port)))?;
+    let bind_addr = if backend_addr.is_ipv4() {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    };
+    let upstream = UdpSocket::bind(bind_addr).await?;
+    upstream.connect(backend_addr).await?;
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let event = tokio::time::timeout(idle_timeout, async {
+            tokio::select! {
+                datagram = rx.recv() => SessionEvent::Downstream(datagram),
+                received = upstream.recv(&mut buf) => SessionEvent::Upstream(received),
+            }
+        })
+        .await;
+        match event {
+            Err(_) => {
+                tracing::trace!("[Sg.UdpProxy] session idle timeout");
+                return Ok(());
+            }
+            // the listener is closed
+            Ok(SessionEvent::Downstream(None)) => return Ok(()),
+            Ok(SessionEvent::Downstream(Some(datagram))) => {
+                upstream.send(&datagram).await?;
+            }
+            Ok(SessionEvent::Upstream(received)) => {
+                activity.touch();
+                socket.send_to(buf.get(..received?).unwrap_or_default(), peer).await?;
+            }
+        }
+    }
+}
+
+enum SessionEvent {
+    Downstream(Option<Bytes>),
+    Upstream(std::io::Result<usize>),
+}
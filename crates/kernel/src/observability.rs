@@ -306,6 +306,57 @@ fn http_instruments() -> &'static HttpInstruments {
     })
 }
 
+/// Counts of a forwarded websocket connection in one direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WebSocketDirectionStats {
+    /// Text and binary messages.
+    pub messages: u64,
+    /// All frames received, control frames included.
+    pub frames: u64,
+    /// Payload bytes of the messages.
+    pub bytes: u64,
+}
+
+/// Counts of a forwarded websocket connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WebSocketStats {
+    pub upstream: WebSocketDirectionStats,
+    pub downstream: WebSocketDirectionStats,
+}
+
+pub fn record_websocket_connection(gateway: impl Into<String>, duration: Duration, stats: &WebSocketStats) {
+    let gateway = gateway.into();
+    let instruments = websocket_instruments();
+    instruments.duration.record(duration.as_secs_f64(), &[KeyValue::new("gateway", gateway.clone())]);
+    for (direction, stats) in [("upstream", stats.upstream), ("downstream", stats.downstream)] {
+        let attrs = [KeyValue::new("gateway", gateway.clone()), KeyValue::new("direction", direction)];
+        instruments.messages.add(stats.messages, &attrs);
+        instruments.frames.add(stats.frames, &attrs);
+        instruments.bytes.add(stats.bytes, &attrs);
+    }
+}
+
+#[derive(Debug)]
+struct WebSocketInstruments {
+    duration: opentelemetry::metrics::Histogram<f64>,
+    messages: opentelemetry::metrics::Counter<u64>,
+    frames: opentelemetry::metrics::Counter<u64>,
+    bytes: opentelemetry::metrics::Counter<u64>,
+}
+
+fn websocket_instruments() -> &'static WebSocketInstruments {
+    static INSTRUMENTS: OnceLock<WebSocketInstruments> = OnceLock::new();
+    INSTRUMENTS.get_or_init(|| {
+        let meter = global::meter("spacegate_kernel");
+        WebSocketInstruments {
+            duration: meter.f64_histogram("websocket.connection.duration").with_unit("s").build(),
+            messages: meter.u64_counter("websocket.messages").with_unit("{message}").build(),
+            frames: meter.u64_counter("websocket.frames").with_unit("{frame}").build(),
+            bytes: meter.u64_counter("websocket.bytes").with_unit("By").build(),
+        }
+    })
+}
+
 pub fn http_protocol_version(version: Version) -> String {
     match version {
         Version::HTTP_10 => "1.0",
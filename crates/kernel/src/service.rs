@@ -1,39 +1,58 @@
-use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
 
 use futures_util::future::BoxFuture;
-use hyper::{body::Incoming, Request, Response};
-use hyper_util::rt::TokioIo;
+use http_body_util::combinators::BoxBody;
+use hyper::{body::Bytes, Request, Response};
 use opentelemetry::trace::TraceContextExt;
 use tokio::net::TcpStream;
 use tokio_rustls::rustls;
+use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
-    extension::{BackendHost, EnterTime, PeerAddr, Reflect, RouteName},
+    extension::{BackendHost, EnterTime, PeerAddr, PeerCertificate, Reflect, RouteName},
     observability::{
         access_log_fields, client_ip, content_length, header_value, http_protocol_version, record_http_server_active_request, record_http_server_metrics_with_labels,
         telemetry_json, AccessLogContext, HttpMetricLabels, TelemetryContext,
     },
-    ArcHyperService, BoxResult, SgBody,
+    ArcHyperService, BoxError, BoxResult, SgBody,
 };
 
 pub mod http_route;
 
 pub mod http_gateway;
 
+pub mod tls_cert;
+
+pub mod tls_passthrough;
+
+pub mod stream_proxy;
+
+pub mod repository;
+
+mod http_connection;
+use http_connection::{serve_connection, ActiveBody, ConnectionActivity, ConnectionBuilder, HttpConnectionTimeouts};
+
 pub trait TcpService: 'static + Send + Sync {
     fn protocol_name(&self) -> &str;
     fn sniff_peek_size(&self) -> usize;
     fn sniff(&self, peek_buf: &[u8]) -> bool;
     fn handle(&self, stream: TcpStream, peer: SocketAddr) -> BoxFuture<'static, BoxResult<()>>;
+    /// Handle the connection, and try to finish it gracefully once `shutdown` is cancelled, when the listener is draining.
+    ///
+    /// By default the signal is ignored, and the connection is aborted when the grace period of the listener ends.
+    fn handle_graceful(&self, stream: TcpStream, peer: SocketAddr, shutdown: CancellationToken) -> BoxFuture<'static, BoxResult<()>> {
+        let _ = shutdown;
+        self.handle(stream, peer)
+    }
 }
-type ConnectionBuilder = hyper_util::server::conn::auto::Builder<hyper_util::rt::TokioExecutor>;
 
 #[derive(Debug)]
 pub struct Http {
     inner_service: ArcHyperService,
     gateway_name: Arc<str>,
+    timeouts: HttpConnectionTimeouts,
     connection_builder: ConnectionBuilder,
 }
 
@@ -46,9 +65,25 @@ impl Http {
         Self {
             inner_service: service,
             gateway_name,
+            timeouts: HttpConnectionTimeouts::default(),
             connection_builder: ConnectionBuilder::new(Default::default()),
         }
     }
+
+    /// Close the http1 connection if the client doesn't send the whole request header in time, including the wait for the next request.
+    ///
+    /// It only applies to http1, http2 connections are not covered.
+    pub fn with_header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.header_read_timeout = Some(timeout);
+        self.connection_builder = self.timeouts.connection_builder();
+        self
+    }
+
+    /// Close the connection gracefully if no request is in flight for this period.
+    pub fn with_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.keep_alive_timeout = Some(timeout);
+        self
+    }
 }
 
 impl TcpService for Http {
@@ -71,13 +106,13 @@ impl TcpService for Http {
             || peeked.starts_with(b"PRI * HTTP/2.0")
     }
     fn handle(&self, stream: TcpStream, peer: SocketAddr) -> BoxFuture<'static, BoxResult<()>> {
-        let io = TokioIo::new(stream);
+        self.handle_graceful(stream, peer, CancellationToken::new())
+    }
+    fn handle_graceful(&self, stream: TcpStream, peer: SocketAddr, shutdown: CancellationToken) -> BoxFuture<'static, BoxResult<()>> {
         let service = HyperServiceAdapter::with_gateway_name(self.inner_service.clone(), peer, self.gateway_name.clone());
         let builder = self.connection_builder.clone();
-        Box::pin(async move {
-            let conn = builder.serve_connection_with_upgrades(io, service);
-            conn.await
-        })
+        let keep_alive_timeout = self.timeouts.keep_alive_timeout;
+        Box::pin(serve_connection(builder, stream, service, keep_alive_timeout, shutdown))
     }
 }
 #[derive(Debug)]
@@ -85,6 +120,7 @@ pub struct Https {
     inner_service: ArcHyperService,
     gateway_name: Arc<str>,
     tls_config: Arc<rustls::ServerConfig>,
+    timeouts: HttpConnectionTimeouts,
     connection_builder: ConnectionBuilder,
 }
 
@@ -98,9 +134,25 @@ impl Https {
             inner_service: service,
             gateway_name,
             tls_config: Arc::new(tls_config),
+            timeouts: HttpConnectionTimeouts::default(),
             connection_builder: ConnectionBuilder::new(Default::default()),
         }
     }
+
+    /// Close the http1 connection if the client doesn't send the whole request header in time, including the wait for the next request.
+    ///
+    /// It only applies to http1, http2 connections are not covered.
+    pub fn with_header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.header_read_timeout = Some(timeout);
+        self.connection_builder = self.timeouts.connection_builder();
+        self
+    }
+
+    /// Close the connection gracefully if no request is in flight for this period.
+    pub fn with_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.keep_alive_timeout = Some(timeout);
+        self
+    }
 }
 
 impl TcpService for Https {
@@ -114,13 +166,20 @@ impl TcpService for Https {
         peeked.starts_with(b"\x16\x03")
     }
     fn handle(&self, stream: TcpStream, peer: SocketAddr) -> BoxFuture<'static, BoxResult<()>> {
+        self.handle_graceful(stream, peer, CancellationToken::new())
+    }
+    fn handle_graceful(&self, stream: TcpStream, peer: SocketAddr, shutdown: CancellationToken) -> BoxFuture<'static, BoxResult<()>> {
         let service = HyperServiceAdapter::with_gateway_name(self.inner_service.clone(), peer, self.gateway_name.clone());
         let builder = self.connection_builder.clone();
+        let keep_alive_timeout = self.timeouts.keep_alive_timeout;
         let connector = tokio_rustls::TlsAcceptor::from(self.tls_config.clone());
         Box::pin(async move {
             let accepted = connector.accept(stream).await?;
-            let conn = builder.serve_connection_with_upgrades(TokioIo::new(accepted), service);
-            conn.await
+            let peer_certificate = accepted.get_ref().1.peer_certificates().and_then(|certs| certs.first()).and_then(|cert| {
+                PeerCertificate::from_der(cert.clone().into_owned()).inspect_err(|e| tracing::warn!(%peer, "[Sg.Https] fail to parse peer certificate: {e}")).ok()
+            });
+            let service = service.with_peer_certificate(peer_certificate);
+            serve_connection(builder, accepted, service, keep_alive_timeout, shutdown).await
         })
     }
 }
@@ -134,6 +193,8 @@ where
     service: S,
     peer: SocketAddr,
     gateway_name: Arc<str>,
+    peer_certificate: Option<PeerCertificate>,
+    activity: Option<Arc<ConnectionActivity>>,
 }
 
 impl<S> HyperServiceAdapter<S>
@@ -146,25 +207,45 @@ where
     }
 
     pub fn with_gateway_name(service: S, peer: SocketAddr, gateway_name: Arc<str>) -> Self {
-        Self { service, peer, gateway_name }
+        Self {
+            service,
+            peer,
+            gateway_name,
+            peer_certificate: None,
+            activity: None,
+        }
+    }
+
+    /// Attach the certificate of the tls client to every request of this connection.
+    pub fn with_peer_certificate(mut self, peer_certificate: Option<PeerCertificate>) -> Self {
+        self.peer_certificate = peer_certificate;
+        self
     }
 
     pub fn gateway_name(&self) -> &str {
         self.gateway_name.as_ref()
     }
+
+    /// Track the requests in flight, for the keep-alive idle timeout.
+    pub(crate) fn with_activity(mut self, activity: Option<Arc<ConnectionActivity>>) -> Self {
+        self.activity = activity;
+        self
+    }
 }
 
-impl<S> hyper::service::Service<Request<Incoming>> for HyperServiceAdapter<S>
+impl<S, B> hyper::service::Service<Request<B>> for HyperServiceAdapter<S>
 where
     S: hyper::service::Service<Request<SgBody>, Error = Infallible, Response = Response<SgBody>> + Clone + Send + 'static,
     S::Future: Send + 'static,
+    B: hyper::body::Body<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Into<BoxError> + 'static,
 {
     type Response = Response<SgBody>;
     type Error = Infallible;
     type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     #[inline]
-    fn call(&self, mut req: Request<Incoming>) -> Self::Future {
+    fn call(&self, mut req: Request<B>) -> Self::Future {
         req.extensions_mut().insert(self.peer);
         // here we will clone underlying service,
         // so it's important that underlying service is cheap to clone.
@@ -173,6 +254,7 @@ where
         // especially the router is big and the too many plugins are installed.
         // so we should avoid that
         let enter_time = EnterTime::new();
+        let activity_guard = self.activity.as_ref().map(ConnectionActivity::enter);
         let service = self.service.clone();
         let mut req = req.map(SgBody::new);
         let method = req.method().clone();
@@ -216,13 +298,22 @@ where
         reflect.insert(enter_time);
         req.extensions_mut().insert(reflect);
         req.extensions_mut().insert(PeerAddr(self.peer));
+        if let Some(peer_certificate) = &self.peer_certificate {
+            req.extensions_mut().insert(peer_certificate.clone());
+        }
         req.extensions_mut().insert(enter_time);
         req.extensions_mut().insert(telemetry_context.clone());
         req.extensions_mut().insert(access_log_context.clone());
         let span_for_recording = span.clone();
         Box::pin(
             async move {
-                let resp = service.call(req).await.expect("infallible");
+                let mut resp = service.call(req).await.expect("infallible");
+                if let Some(guard) = activity_guard {
+                    resp = resp.map(|body| SgBody {
+                        body: BoxBody::new(ActiveBody::new(body.body, guard)),
+                        dump: body.dump,
+                    });
+                }
                 // if method != hyper::Method::HEAD && method != hyper::Method::OPTIONS && method != hyper::Method::CONNECT {
                 //     with_length_or_chunked(&mut resp);
                 // }
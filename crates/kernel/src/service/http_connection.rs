@@ -0,0 +1,172 @@
+//! Serve a http connection with keep-alive idle timeout and graceful shutdown.
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hyper::body::{Body, Bytes, Frame, SizeHint};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use pin_project_lite::pin_project;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{ArcHyperService, BoxError, BoxResult};
+
+use super::HyperServiceAdapter;
+
+pub(crate) type ConnectionBuilder = hyper_util::server::conn::auto::Builder<TokioExecutor>;
+
+/// Timeouts of http connections.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct HttpConnectionTimeouts {
+    /// only applies to http1
+    pub header_read_timeout: Option<Duration>,
+    pub keep_alive_timeout: Option<Duration>,
+}
+
+impl HttpConnectionTimeouts {
+    pub fn connection_builder(&self) -> ConnectionBuilder {
+        let mut builder = ConnectionBuilder::new(TokioExecutor::new());
+        if let Some(header_read_timeout) = self.header_read_timeout {
+            builder.http1().timer(hyper_util::rt::TokioTimer::new()).header_read_timeout(header_read_timeout);
+        }
+        builder
+    }
+}
+
+/// Requests in flight and the last time a request finished, of a connection.
+#[derive(Debug)]
+pub(crate) struct ConnectionActivity {
+    in_flight: AtomicUsize,
+    last_active: Mutex<Instant>,
+}
+
+impl Default for ConnectionActivity {
+    fn default() -> Self {
+        Self {
+            in_flight: AtomicUsize::new(0),
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl ConnectionActivity {
+    /// Mark a request in flight until the guard is dropped, which is moved into the response body.
+    pub fn enter(self: &Arc<Self>) -> ActivityGuard {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        ActivityGuard(self.clone())
+    }
+
+    fn last_active(&self) -> Instant {
+        *self.last_active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Resolve when no request is in flight for `timeout`.
+    async fn idle(&self, timeout: Duration) {
+        loop {
+            tokio::time::sleep_until(self.last_active() + timeout).await;
+            if self.in_flight.load(Ordering::Acquire) > 0 {
+                tokio::time::sleep(timeout).await;
+            } else if self.last_active().elapsed() >= timeout {
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ActivityGuard(Arc<ConnectionActivity>);
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        *self.0.last_active.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+        self.0.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+pin_project! {
+    /// A response body which keeps the request in flight until it's dropped.
+    pub(crate) struct ActiveBody<B> {
+        #[pin]
+        inner: B,
+        guard: ActivityGuard,
+    }
+}
+
+impl<B> ActiveBody<B> {
+    pub fn new(inner: B, guard: ActivityGuard) -> Self {
+        Self { inner, guard }
+    }
+}
+
+impl<B> Body for ActiveBody<B>
+where
+    B: Body<Data = Bytes, Error = BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.project().inner.poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Serve the connection until it's closed.
+///
+/// The connection is shutdown gracefully, by `GOAWAY` for http2 or `Connection: close` for http1,
+/// if it's idle for the keep-alive timeout, or `shutdown` is cancelled.
+pub(crate) async fn serve_connection<I>(
+    builder: ConnectionBuilder,
+    io: I,
+    service: HyperServiceAdapter<ArcHyperService>,
+    keep_alive_timeout: Option<Duration>,
+    shutdown: CancellationToken,
+) -> BoxResult<()>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let activity = keep_alive_timeout.map(|_| Arc::new(ConnectionActivity::default()));
+    let service = service.with_activity(activity.clone());
+    let conn = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+    tokio::pin!(conn);
+    let idle = async {
+        match (&activity, keep_alive_timeout) {
+            (Some(activity), Some(timeout)) => activity.idle(timeout).await,
+            _ => std::future::pending().await,
+        }
+    };
+    tokio::pin!(idle);
+    let mut shutting_down = false;
+    loop {
+        tokio::select! {
+            result = conn.as_mut() => return result,
+            () = shutdown.cancelled(), if !shutting_down => {
+                tracing::trace!("[Sg.Http] shutdown connection gracefully");
+                conn.as_mut().graceful_shutdown();
+                shutting_down = true;
+            }
+            () = &mut idle, if !shutting_down => {
+                tracing::trace!("[Sg.Http] close idle connection");
+                conn.as_mut().graceful_shutdown();
+                shutting_down = true;
+            }
+        }
+    }
+}
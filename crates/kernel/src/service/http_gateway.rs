@@ -1,10 +1,12 @@
 pub mod builder;
+pub mod path_tree;
 use std::{collections::HashMap, ops::Index, sync::Arc};
 
 use crate::{
     backend_service::ArcHyperService,
     extension::{GatewayName, MatchedSgRouter, Reflect, RouteName},
     helper_layers::{
+        function::FnLayer,
         map_request::{add_extension::add_extension, MapRequestLayer},
         reload::Reloader,
         route::{Router, RouterService},
@@ -19,7 +21,9 @@ use hyper::{header::HOST, Request};
 use tower_layer::Layer;
 use tracing::{debug, instrument};
 
-use super::http_route::{match_hostname::HostnameTree, match_request::MatchRequest, HttpRoute, HttpRouter};
+use self::path_tree::{PathTree, RoutePrecedence};
+
+use super::http_route::{match_hostname::HostnameTree, HttpRoute, HttpRouter};
 
 /****************************************************************************************
 
@@ -36,6 +40,7 @@ pub struct Gateway {
     pub http_plugins: Vec<BoxLayer>,
     pub http_fallback: ArcHyperService,
     pub http_route_reloader: Reloader<HttpRouterService>,
+    pub http_route_precedence: RoutePrecedence,
     pub ext: hyper::http::Extensions,
 }
 
@@ -51,7 +56,7 @@ impl Gateway {
         let add_gateway_name_layer = MapRequestLayer::new(add_extension(gateway_name, true));
         let gateway_plugins = self.http_plugins.iter();
         let http_routes = self.http_routes.values();
-        let route = create_http_router(http_routes, self.http_fallback.clone());
+        let route = create_http_router(http_routes, self.http_fallback.clone(), self.http_route_precedence);
         #[cfg(feature = "reload")]
         let service = {
             let reloader = self.http_route_reloader.clone();
@@ -71,7 +76,7 @@ pub struct HttpRoutedService {
 #[derive(Debug, Clone)]
 pub struct GatewayRouter {
     pub routers: Arc<[HttpRouter]>,
-    pub hostname_tree: Arc<HostnameTree<Vec<(usize, i16)>>>,
+    pub hostname_tree: Arc<HostnameTree<PathTree>>,
 }
 
 impl Index<(usize, usize)> for HttpRoutedService {
@@ -86,46 +91,48 @@ impl Router for GatewayRouter {
     #[instrument(skip_all, fields(http.host =? req.headers().get(HOST) ))]
     /// Route the request to the corresponding service.
     ///
-    /// (Maybe it will be radix tree in the future.)
+    /// Rules are looked up in the compiled [`PathTree`] of the hostname, see [`path_tree`] for the priority.
     fn route(&self, req: &mut Request<SgBody>) -> Option<Self::Index> {
         let host = req.uri().host().or(req.headers().get(HOST).and_then(|x| x.to_str().ok()))?;
-        let indices = self.hostname_tree.get(host)?;
-        for (route_index, _p) in indices {
-            for (idx1, matches) in self.routers.as_ref().index(*route_index).rules.iter().enumerate() {
-                // tracing::trace!("try match {match:?} [{route_index},{idx1}:{_p}]");
-                let index = (*route_index, idx1);
-                if let Some(ref matches) = matches {
-                    for m in matches.as_ref() {
-                        if m.match_request(req) {
-                            insert_route_name(req, self.routers.as_ref()[*route_index].name.clone());
-                            req.extensions_mut().insert(MatchedSgRouter(m.clone()));
-                            tracing::trace!("matches {m:?} [{route_index},{idx1}:{_p}]");
-                            if let Err(e) = m.rewrite(req) {
-                                tracing::warn!("rewrite failed: {e:?}");
-                                return None;
-                            }
-                            return Some(index);
-                        }
-                    }
-                    continue;
-                } else {
-                    insert_route_name(req, self.routers.as_ref()[*route_index].name.clone());
-                    tracing::trace!("matches wildcard [{route_index},{idx1}:{_p}]");
-                    return Some(index);
-                }
+        let tree = self.hostname_tree.get(host)?;
+        let Some(candidate) = tree.find(req) else {
+            tracing::trace!("no rule matched");
+            return None;
+        };
+        let (route_index, rule_index) = (candidate.route, candidate.rule);
+        insert_route_name(req, self.routers.as_ref().index(route_index).name.clone());
+        if let Some(ref m) = candidate.r#match {
+            req.extensions_mut().insert(MatchedSgRouter(m.clone()));
+            tracing::trace!("matches {m:?} [{route_index},{rule_index}]");
+            if let Err(e) = m.rewrite(req) {
+                tracing::warn!("rewrite failed: {e:?}");
+                return None;
             }
+        } else {
+            tracing::trace!("matches wildcard [{route_index},{rule_index}]");
         }
-        tracing::trace!("no rule matched");
-
-        None
+        Some((route_index, rule_index))
     }
 }
 
-pub fn create_http_router<'a>(routes: impl Iterator<Item = &'a HttpRoute>, fallback: ArcHyperService) -> RouterService<HttpRoutedService, GatewayRouter, ArcHyperService> {
+pub fn create_http_router<'a>(
+    routes: impl Iterator<Item = &'a HttpRoute>,
+    fallback: ArcHyperService,
+    precedence: RoutePrecedence,
+) -> RouterService<HttpRoutedService, GatewayRouter, ArcHyperService> {
     let mut services = Vec::new();
     let mut routers = Vec::new();
-    let mut hostname_tree = HostnameTree::<Vec<_>>::new();
-    for (idx, route) in routes.enumerate() {
+    let mut hostname_tree = HostnameTree::<PathTree>::new();
+    let routes = routes.collect::<Vec<_>>();
+    // errors of the gateway are mapped into `grpc-status` only if there are gRPC routes,
+    // the fallback is mapped as well so that gRPC clients get `UNIMPLEMENTED` for unknown methods
+    let grpc_status = BoxLayer::new(FnLayer::new_closure(crate::utils::grpc::grpc_status));
+    let fallback = if routes.iter().any(|route| route.uses_grpc()) {
+        grpc_status.layer_shared(fallback)
+    } else {
+        fallback
+    };
+    for (idx, route) in routes.into_iter().enumerate() {
         let priority = route.priority;
         let idx_with_priority = (idx, priority);
         // let route_plugins = route.plugins.iter().map(SgRefLayer::new).collect::<SgRefLayer>();
@@ -133,21 +140,22 @@ pub fn create_http_router<'a>(routes: impl Iterator<Item = &'a HttpRoute>, fallb
         let mut rules_router = Vec::with_capacity(route.rules.len());
         for rule in route.rules.iter() {
             let rule_service = fold_layers(route.plugins.iter(), ArcHyperService::new(rule.as_service()));
+            let rule_service = if route.uses_grpc() { grpc_status.layer_shared(rule_service) } else { rule_service };
             rules_services.push(rule_service);
             rules_router.push(rule.r#match.clone());
         }
         if route.hostnames.is_empty() {
-            if let Some(indices) = hostname_tree.get_mut("*") {
-                indices.push(idx_with_priority)
+            if let Some(tree) = hostname_tree.get_mut("*") {
+                tree.push(idx, priority)
             } else {
-                hostname_tree.set("*", vec![idx_with_priority]);
+                hostname_tree.set("*", PathTree::new(vec![idx_with_priority]));
             }
         } else {
             for hostname in route.hostnames.iter() {
-                if let Some(indices) = hostname_tree.get_mut(hostname) {
-                    indices.push(idx_with_priority)
+                if let Some(tree) = hostname_tree.get_mut(hostname) {
+                    tree.push(idx, priority)
                 } else {
-                    hostname_tree.set("*", vec![idx_with_priority]);
+                    hostname_tree.set("*", PathTree::new(vec![idx_with_priority]));
                 }
             }
         }
@@ -160,9 +168,9 @@ pub fn create_http_router<'a>(routes: impl Iterator<Item = &'a HttpRoute>, fallb
         });
     }
 
-    // sort the indices by priority
+    // sort the indices by priority and compile the rules
     // we put the highest priority at the front of the vector
-    hostname_tree.iter_mut().for_each(|indices| indices.sort_unstable_by_key(|(_, p)| -*p));
+    hostname_tree.iter_mut().for_each(|tree| tree.compile(&routers, precedence));
     debug!("hostname_tree: {hostname_tree:?}");
     RouterService::new(
         HttpRoutedService { services: services.into() },
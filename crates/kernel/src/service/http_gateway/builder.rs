@@ -5,11 +5,11 @@ use hyper::{service::service_fn, Response};
 use crate::{
     helper_layers::{function::FnLayer, reload::Reloader},
     service::http_route::HttpRoute,
-    utils::Snowflake,
+    utils::{BodySizeLimit, Snowflake},
     ArcHyperService, BoxLayer, SgBody,
 };
 
-use super::{Gateway, HttpRouterService};
+use super::{path_tree::RoutePrecedence, Gateway, HttpRouterService};
 #[derive(Debug)]
 pub struct GatewayBuilder {
     pub gateway_name: Arc<str>,
@@ -19,6 +19,8 @@ pub struct GatewayBuilder {
     pub http_route_reloader: Reloader<HttpRouterService>,
     pub extensions: hyper::http::Extensions,
     pub x_request_id: bool,
+    pub body_size_limit: BodySizeLimit,
+    pub http_route_precedence: RoutePrecedence,
 }
 
 /// return empty 404 not found
@@ -38,12 +40,24 @@ impl GatewayBuilder {
             http_route_reloader: Default::default(),
             extensions: hyper::http::Extensions::default(),
             x_request_id: true,
+            body_size_limit: BodySizeLimit::default(),
+            http_route_precedence: RoutePrecedence::default(),
         }
     }
     pub fn x_request_id(mut self, enable: bool) -> Self {
         self.x_request_id = enable;
         self
     }
+    /// Default body size limit for every request of this gateway, it's not enabled if both limits are `None`.
+    pub fn body_size_limit(mut self, limit: BodySizeLimit) -> Self {
+        self.body_size_limit = limit;
+        self
+    }
+    /// How http routes take precedence over each other, default is by priority.
+    pub fn http_route_precedence(mut self, precedence: RoutePrecedence) -> Self {
+        self.http_route_precedence = precedence;
+        self
+    }
     pub fn http_router(mut self, route: HttpRoute) -> Self {
         self.http_routers.insert(route.name.clone(), route);
         self
@@ -76,10 +90,13 @@ impl GatewayBuilder {
         self
     }
     pub fn build(self) -> Gateway {
-        let mut plugins = vec![];
+        let mut plugins = Vec::new();
         if self.x_request_id {
             plugins.push(BoxLayer::new(FnLayer::new_closure(crate::utils::x_request_id::<Snowflake>)));
         }
+        if self.body_size_limit != BodySizeLimit::default() {
+            plugins.push(BoxLayer::new(FnLayer::new(self.body_size_limit)));
+        }
         plugins.extend(self.http_plugins);
         Gateway {
             gateway_name: self.gateway_name,
@@ -87,6 +104,7 @@ impl GatewayBuilder {
             http_plugins: plugins,
             http_fallback: self.http_fallback,
             http_route_reloader: self.http_route_reloader,
+            http_route_precedence: self.http_route_precedence,
             ext: self.extensions,
         }
     }
@@ -0,0 +1,281 @@
+//! # Path Tree
+//!
+//! The rules of the routes of a hostname are compiled into a tree, so that a request only tries the rules which could match its path.
+//!
+//! | Path match      |  Stored in                      |
+//! |:----------------|:--------------------------------|
+//! | Exact           |  a hash map of paths            |
+//! | Prefix          |  a radix tree of path segments  |
+//! | Template        |  the radix tree, by the literal segments before the first parameter |
+//! | RegExp or none  |  the fallback bucket            |
+//!
+//! Each candidate is ranked by its position in a linear scan, which follows the [`RoutePrecedence`].
+//! Candidates are tried by rank, so the first matched one is always the one a linear scan would find.
+
+use std::{borrow::Cow, cmp::Reverse, collections::HashMap, sync::Arc};
+
+use hyper::Request;
+
+use crate::{
+    service::http_route::{
+        match_request::{HttpPathMatchRewrite, HttpRouteMatch, MatchRequest},
+        HttpRouter,
+    },
+    SgBody,
+};
+
+/// How routes take precedence over each other when more than one matches a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoutePrecedence {
+    /// By the route priority, then the order of rules and matches.
+    #[default]
+    Priority,
+    /// By the route priority, then ties are broken as the Gateway API specifies:
+    ///
+    /// 1. exact path over regular expression or template over prefix,
+    /// 2. the longest path,
+    /// 3. with a method match,
+    /// 4. the most header matches,
+    /// 5. the most query param matches,
+    /// 6. the route name in alphabetical order,
+    /// 7. the order of rules and matches.
+    ///
+    /// A match without path is taken as the prefix `/`.
+    GatewayApi,
+}
+
+/// The order of a match in [`RoutePrecedence::GatewayApi`], the less the prior.
+type GatewayApiOrder = (u8, Reverse<usize>, Reverse<bool>, Reverse<usize>, Reverse<usize>);
+
+fn gateway_api_order(r#match: Option<&HttpRouteMatch>) -> GatewayApiOrder {
+    let Some(r#match) = r#match else {
+        return (2, Reverse(1), Reverse(false), Reverse(0), Reverse(0));
+    };
+    let (kind, length) = match &r#match.path {
+        Some(HttpPathMatchRewrite::Exact(path, _)) => (0, path.len()),
+        Some(HttpPathMatchRewrite::RegExp(regex, _)) => (1, regex.as_str().len()),
+        Some(HttpPathMatchRewrite::Template(template, _)) => (1, template.as_str().len()),
+        Some(HttpPathMatchRewrite::Prefix(prefix, _)) => (2, prefix.trim_end_matches('/').len().max(1)),
+        None => (2, 1),
+    };
+    (
+        kind,
+        Reverse(length),
+        Reverse(r#match.method.as_ref().is_some_and(|methods| !methods.is_empty())),
+        Reverse(r#match.header.as_ref().map_or(0, Vec::len)),
+        Reverse(r#match.query.as_ref().map_or(0, Vec::len)),
+    )
+}
+
+/// A rule match which a request could be routed to.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    rank: usize,
+    /// the index of the route
+    pub route: usize,
+    /// the index of the rule in the route
+    pub rule: usize,
+    /// `None` for a rule without matches, it matches all requests.
+    pub r#match: Option<Arc<HttpRouteMatch>>,
+}
+
+impl Candidate {
+    pub fn match_request(&self, req: &Request<SgBody>) -> bool {
+        self.r#match.iter().all(|m| m.match_request(req))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct PrefixNode {
+    candidates: Vec<Candidate>,
+    /// children keyed by the lowercase path segment, prefixes are matched case-insensitively
+    children: HashMap<String, PrefixNode>,
+}
+
+impl PrefixNode {
+    fn insert<'a>(&mut self, segments: impl Iterator<Item = &'a str>, candidate: Candidate) {
+        let node = segments.fold(self, |node, segment| node.children.entry(segment.to_ascii_lowercase()).or_default());
+        node.candidates.push(candidate);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PathTree {
+    /// The routes of this hostname with their priorities, the highest priority is at the front once it's compiled.
+    pub indices: Vec<(usize, i16)>,
+    candidates: Vec<Candidate>,
+    exact: HashMap<String, Vec<Candidate>>,
+    prefix: PrefixNode,
+    fallback: Vec<Candidate>,
+}
+
+impl PathTree {
+    pub fn new(indices: Vec<(usize, i16)>) -> Self {
+        Self { indices, ..Default::default() }
+    }
+    pub fn push(&mut self, route: usize, priority: i16) {
+        self.indices.push((route, priority));
+    }
+    /// Sort the routes by priority and compile their rules into the tree.
+    pub fn compile(&mut self, routers: &[HttpRouter], precedence: RoutePrecedence) {
+        // a stable sort, routes of the same priority are tried in the order they're pushed
+        self.indices.sort_by_key(|(_, priority)| Reverse(*priority));
+        let mut candidates = Vec::new();
+        for &(route, _) in &self.indices {
+            let Some(router) = routers.get(route) else {
+                continue;
+            };
+            for (rule, matches) in router.rules.iter().enumerate() {
+                let mut push = |r#match: Option<Arc<HttpRouteMatch>>| {
+                    candidates.push(Candidate {
+                        rank: candidates.len(),
+                        route,
+                        rule,
+                        r#match,
+                    })
+                };
+                match matches {
+                    Some(matches) => matches.iter().cloned().map(Some).for_each(&mut push),
+                    None => push(None),
+                }
+            }
+        }
+        if precedence == RoutePrecedence::GatewayApi {
+            let priorities = self.indices.iter().copied().collect::<HashMap<_, _>>();
+            // stable as well, so the order of rules and matches is kept
+            candidates.sort_by_cached_key(|candidate| {
+                (
+                    Reverse(priorities.get(&candidate.route).copied().unwrap_or_default()),
+                    gateway_api_order(candidate.r#match.as_deref()),
+                    routers.get(candidate.route).map(|router| router.name.clone()),
+                    candidate.route,
+                )
+            });
+            candidates.iter_mut().enumerate().for_each(|(rank, candidate)| candidate.rank = rank);
+        }
+        self.exact.clear();
+        self.prefix = PrefixNode::default();
+        self.fallback.clear();
+        for candidate in &candidates {
+            match candidate.r#match.as_ref().and_then(|m| m.path.as_ref()) {
+                Some(HttpPathMatchRewrite::Exact(path, _)) => self.exact.entry(path.clone()).or_default().push(candidate.clone()),
+                Some(HttpPathMatchRewrite::Prefix(prefix, _)) => self.prefix.insert(prefix.split('/').filter(|s| !s.is_empty()), candidate.clone()),
+                // the literal segments are matched case-sensitively later
+                Some(HttpPathMatchRewrite::Template(template, _)) => self.prefix.insert(template.literal_prefix(), candidate.clone()),
+                Some(HttpPathMatchRewrite::RegExp(..)) | None => self.fallback.push(candidate.clone()),
+            }
+        }
+        self.candidates = candidates;
+    }
+    /// Find the first candidate matching the request, only the candidates which could match the path are tried.
+    pub fn find(&self, req: &Request<SgBody>) -> Option<&Candidate> {
+        let path = req.uri().path();
+        let mut buckets: Vec<&[Candidate]> = Vec::with_capacity(4);
+        if let Some(exact) = self.exact.get(path) {
+            buckets.push(exact);
+        }
+        buckets.push(&self.fallback);
+        let mut node = &self.prefix;
+        buckets.push(&node.candidates);
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            let segment = if segment.bytes().any(|b| b.is_ascii_uppercase()) {
+                Cow::Owned(segment.to_ascii_lowercase())
+            } else {
+                Cow::Borrowed(segment)
+            };
+            let Some(child) = node.children.get(segment.as_ref()) else {
+                break;
+            };
+            node = child;
+            buckets.push(&node.candidates);
+        }
+        buckets.retain(|bucket| !bucket.is_empty());
+        // each bucket is sorted by rank, so merge them
+        loop {
+            let (index, candidate) =
+                buckets.iter().enumerate().filter_map(|(index, bucket)| bucket.first().map(|candidate| (index, candidate))).min_by_key(|(_, candidate)| candidate.rank)?;
+            if candidate.match_request(req) {
+                return Some(candidate);
+            }
+            if let Some(bucket) = buckets.get_mut(index) {
+                *bucket = bucket.get(1..).unwrap_or_default();
+            }
+        }
+    }
+    /// Find the first candidate matching the request by trying all candidates in order.
+    ///
+    /// It gives the same result as [`PathTree::find`], and it's kept as a reference.
+    pub fn find_linear(&self, req: &Request<SgBody>) -> Option<&Candidate> {
+        self.candidates.iter().find(|candidate| candidate.match_request(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::http::{HeaderName, HeaderValue};
+    use regex::Regex;
+
+    use crate::service::http_route::match_request::{HttpMethodMatch, PathTemplate, SgHttpHeaderMatchRewrite};
+
+    use super::*;
+
+    fn router(rules: Vec<Option<Vec<HttpRouteMatch>>>) -> HttpRouter {
+        HttpRouter {
+            name: "test".into(),
+            hostnames: Arc::new([]),
+            rules: rules.into_iter().map(|rule| rule.map(|matches| matches.into_iter().map(Arc::new).collect())).collect(),
+            ext: Default::default(),
+        }
+    }
+
+    fn get(path: &str) -> Request<SgBody> {
+        Request::get(path).header("x-tenant", "a").body(SgBody::empty()).expect("invalid request")
+    }
+
+    #[test]
+    fn find_by_priority() {
+        let routers = [
+            router(vec![
+                Some(vec![HttpPathMatchRewrite::prefix("/api").into()]),
+                Some(vec![HttpPathMatchRewrite::exact("/api/health").into()]),
+            ]),
+            router(vec![
+                Some(vec![
+                    HttpPathMatchRewrite::prefix("/api/v1").into(),
+                    HttpPathMatchRewrite::regex(Regex::new("^/static/").expect("invalid regex")).into(),
+                ]),
+                Some(vec![HttpRouteMatch {
+                    path: Some(HttpPathMatchRewrite::prefix("/API/v2")),
+                    method: Some(vec![HttpMethodMatch("POST".into())]),
+                    ..Default::default()
+                }]),
+            ]),
+            router(vec![
+                Some(vec![HttpPathMatchRewrite::template(PathTemplate::new("/users/{id}").expect("invalid template")).into()]),
+                Some(vec![
+                    SgHttpHeaderMatchRewrite::exact(HeaderName::from_static("x-tenant"), HeaderValue::from_static("a")).into()
+                ]),
+                None,
+            ]),
+        ];
+        let mut tree = PathTree::new(vec![(0, 0), (1, 10), (2, 0)]);
+        tree.compile(&routers, RoutePrecedence::Priority);
+        let cases = [
+            ("/api/v1/users", Some((1, 0))),
+            ("/api/health", Some((0, 0))),
+            ("/Api/v2", Some((0, 0))),
+            ("/static/logo.png", Some((1, 0))),
+            ("/other", Some((2, 1))),
+            ("/users/42", Some((2, 0))),
+            ("/USERS/42", Some((2, 1))),
+        ];
+        for (path, expect) in cases {
+            let req = get(path);
+            let found = tree.find(&req).map(|c| (c.route, c.rule));
+            assert_eq!(found, expect, "{path}");
+            assert_eq!(found, tree.find_linear(&req).map(|c| (c.route, c.rule)), "{path}");
+        }
+        let req = Request::get("/other").body(SgBody::empty()).expect("invalid request");
+        assert_eq!(tree.find(&req).map(|c| (c.route, c.rule)), Some((2, 2)));
+    }
+}
@@ -1,12 +1,23 @@
 pub mod builder;
+pub mod health_check;
 pub mod match_hostname;
 pub mod match_request;
 use std::{convert::Infallible, path::PathBuf, sync::Arc, time::Duration};
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// The timeout of routes and backends, unless it's set or disabled.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 use crate::{
-    backend_service::{get_http_backend_service, http_backend_service, static_file_service::static_file_service, ArcHyperService},
+    backend_service::{
+        get_http_backend_service, http_backend_service, http_backend_service_with_client,
+        http_client_service::{ClientRepo, ClientTlsProfile, HttpClient},
+        static_file_service::static_file_service,
+        ArcHyperService,
+    },
     extension::{BackendHost, Defer, Reflect},
-    helper_layers::balancer::{self, Balancer},
+    helper_layers::{
+        balancer::{self, Balancer},
+        function::FnLayer,
+        map_request::{add_extension::add_extension, MapRequestLayer},
+    },
     observability::AccessLogContext,
     utils::{fold_box_layers::fold_layers, schema_port::port_to_schema},
     BoxLayer, SgBody,
@@ -19,6 +30,7 @@ use tower_layer::Layer;
 
 use self::{
     builder::{HttpBackendBuilder, HttpRouteBuilder, HttpRouteRuleBuilder},
+    health_check::{HealthCheck, HealthCheckRepo, HealthCheckTarget},
     match_request::HttpRouteMatch,
 };
 
@@ -42,6 +54,10 @@ impl HttpRoute {
     pub fn builder() -> HttpRouteBuilder {
         HttpRouteBuilder::new()
     }
+    /// Check if any rule of the route matches gRPC requests.
+    pub fn uses_grpc(&self) -> bool {
+        self.rules.iter().filter_map(|rule| rule.r#match.as_ref()).flatten().any(HttpRouteMatch::uses_grpc)
+    }
 }
 #[derive(Debug, Clone)]
 pub struct HttpRouter {
@@ -62,6 +78,7 @@ pub struct HttpRouteRule {
     pub r#match: Option<Vec<HttpRouteMatch>>,
     pub plugins: Vec<BoxLayer>,
     timeout: RequestTimeout,
+    first_byte_timeout: Option<Duration>,
     backends: Vec<HttpBackend>,
     balance_policy: BalancePolicyEnum,
     pub ext: hyper::http::Extensions,
@@ -73,6 +90,8 @@ pub enum BalancePolicyEnum {
     #[default]
     IpHash,
     McpSession,
+    /// Assign requests by a key into buckets sized by the backend weights, see [`balancer::Canary`].
+    Canary(balancer::CanaryConfig),
 }
 
 impl HttpRouteRule {
@@ -80,23 +99,30 @@ impl HttpRouteRule {
         HttpRouteRuleBuilder::new()
     }
     pub fn as_service(&self) -> HttpRouteRuleService {
-        use crate::helper_layers::timeout::TimeoutLayer;
         let filter_layer = self.plugins.iter();
         let fallback = get_http_backend_service();
         let service_iter = self.backends.iter().map(HttpBackend::as_service).collect::<Vec<_>>();
-        let balanced = match self.balance_policy {
+        let health = self.backends.iter().map(HttpBackend::health_state).collect::<Vec<_>>();
+        let balanced = match &self.balance_policy {
             BalancePolicyEnum::Random => {
                 let weights = self.backends.iter().map(|x| x.weight);
-                ArcHyperService::new(Balancer::new(balancer::Random::new(weights), service_iter, fallback))
+                ArcHyperService::new(Balancer::new(balancer::Random::new(weights), service_iter, fallback).with_health(health))
+            }
+            BalancePolicyEnum::IpHash => ArcHyperService::new(Balancer::new(balancer::IpHash::default(), service_iter, fallback).with_health(health)),
+            BalancePolicyEnum::McpSession => ArcHyperService::new(Balancer::new(balancer::McpSessionHash::default(), service_iter, fallback).with_health(health)),
+            BalancePolicyEnum::Canary(config) => {
+                let weights = self.backends.iter().map(|x| x.weight);
+                let labels = self.backends.iter().map(HttpBackend::label);
+                let canary = Arc::new(balancer::Canary::new(config.clone(), weights, labels));
+                let balanced = ArcHyperService::new(Balancer::new(canary.clone(), service_iter, fallback).with_health(health));
+                if config.sticky_cookie.is_some() {
+                    ArcHyperService::new(FnLayer::new(canary).layer(balanced))
+                } else {
+                    balanced
+                }
             }
-            BalancePolicyEnum::IpHash => ArcHyperService::new(Balancer::new(balancer::IpHash::default(), service_iter, fallback)),
-            BalancePolicyEnum::McpSession => ArcHyperService::new(Balancer::new(balancer::McpSessionHash::default(), service_iter, fallback)),
-        };
-        let balanced = match self.timeout {
-            RequestTimeout::Default => ArcHyperService::new(TimeoutLayer::new(DEFAULT_TIMEOUT).layer(balanced)),
-            RequestTimeout::Duration(timeout) => ArcHyperService::new(TimeoutLayer::new(timeout).layer(balanced)),
-            RequestTimeout::Disabled => balanced,
         };
+        let balanced = self.timeout.layer(self.first_byte_timeout, balanced);
         let service = fold_layers(filter_layer, balanced);
         HttpRouteRuleService { service }
     }
@@ -108,6 +134,25 @@ pub enum RequestTimeout {
     Default,
     Disabled,
     Duration(Duration),
+    /// The deadline resets each time a frame of the request or the response flows.
+    Idle(Duration),
+}
+
+impl RequestTimeout {
+    /// Wrap the service with the timeout, and the first byte timeout which limits the time waiting for the response head.
+    pub fn layer(self, first_byte_timeout: Option<Duration>, service: ArcHyperService) -> ArcHyperService {
+        use crate::helper_layers::timeout::{IdleTimeoutLayer, TimeoutLayer};
+        let service = match self {
+            RequestTimeout::Default => ArcHyperService::new(TimeoutLayer::new(DEFAULT_TIMEOUT).layer(service)),
+            RequestTimeout::Duration(timeout) => ArcHyperService::new(TimeoutLayer::new(timeout).layer(service)),
+            RequestTimeout::Idle(idle) => ArcHyperService::new(IdleTimeoutLayer::new(idle).layer(service)),
+            RequestTimeout::Disabled => service,
+        };
+        match first_byte_timeout {
+            Some(timeout) => ArcHyperService::new(TimeoutLayer::new(timeout).layer(service)),
+            None => service,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -137,6 +182,10 @@ pub struct HttpBackend {
     pub backend: Backend,
     pub weight: u16,
     pub timeout: RequestTimeout,
+    pub first_byte_timeout: Option<Duration>,
+    /// Timeout of connecting the backend, the connections are not shared with the backends of different connect timeouts.
+    pub connect_timeout: Option<Duration>,
+    pub health_check: Option<HealthCheck>,
     pub ext: hyper::http::Extensions,
 }
 
@@ -144,19 +193,48 @@ impl HttpBackend {
     pub fn builder() -> HttpBackendBuilder {
         HttpBackendBuilder::new()
     }
+    /// The `host:port` of a http backend or the path of a file backend, it's how the backend is named by the canary override header and sticky cookie.
+    pub fn label(&self) -> String {
+        match &self.backend {
+            Backend::Http { host, port: Some(port), .. } => format!("{}:{port}", host.as_deref().unwrap_or_default()),
+            Backend::Http { host, port: None, .. } => host.clone().unwrap_or_default(),
+            Backend::File { path } => path.display().to_string(),
+        }
+    }
+    /// Get the health state of this backend, a probe will be spawned if health check is configured.
+    pub fn health_state(&self) -> balancer::HealthState {
+        match (&self.health_check, HealthCheckTarget::from_backend(&self.backend)) {
+            (Some(check), Some(target)) => HealthCheckRepo::global().get_or_spawn(target, check.clone()),
+            _ => balancer::HealthState::default(),
+        }
+    }
     pub fn as_service(&self) -> ArcHyperService {
-        use crate::helper_layers::timeout::TimeoutLayer;
+        let profile = match (&self.backend, self.connect_timeout) {
+            (Backend::Http { tls, .. }, Some(connect_timeout)) => Some(tls.clone().unwrap_or_default().with_connect_timeout(connect_timeout)),
+            (Backend::Http { tls, .. }, None) => tls.clone(),
+            _ => None,
+        };
+        let client = profile.and_then(
+            |profile| match ClientRepo::global().read().expect("failed to read global client repo").get_or_create(&profile) {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    tracing::error!("[Sg.Backend] fail to create client by tls profile, fallback to the default client: {e}");
+                    None
+                }
+            },
+        );
         let inner_service = HttpBackendService {
             backend: self.backend.clone().into(),
+            client,
         };
         let inner_service = ArcHyperService::new(inner_service);
-        let inner_service = match self.timeout {
-            RequestTimeout::Default => ArcHyperService::new(TimeoutLayer::new(DEFAULT_TIMEOUT).layer(inner_service)),
-            RequestTimeout::Duration(timeout) => ArcHyperService::new(TimeoutLayer::new(timeout).layer(inner_service)),
-            RequestTimeout::Disabled => inner_service,
-        };
+        let inner_service = self.timeout.layer(self.first_byte_timeout, inner_service);
         let filtered = fold_layers(self.plugins.iter(), inner_service);
-        filtered
+        // let plugins mounted on this backend know which backend the request goes to
+        match &self.backend {
+            Backend::Http { host: Some(host), .. } => ArcHyperService::new(MapRequestLayer::new(add_extension(BackendHost::new(host.clone()), false)).layer(filtered)),
+            _ => filtered,
+        }
     }
 }
 
@@ -167,6 +245,8 @@ pub enum Backend {
         port: Option<u16>,
         schema: Option<String>,
         version: Option<Version>,
+        /// Tls settings of the connections to this backend, the default client is used if it's `None`.
+        tls: Option<ClientTlsProfile>,
     },
     File {
         path: PathBuf,
@@ -176,6 +256,8 @@ pub enum Backend {
 #[derive(Clone, Debug)]
 pub struct HttpBackendService {
     pub backend: Arc<Backend>,
+    /// The client to send requests, the global default client is used if it's `None`.
+    pub client: Option<HttpClient>,
 }
 
 impl HttpBackendService {
@@ -186,7 +268,9 @@ impl HttpBackendService {
                 port: None,
                 schema: None,
                 version: None,
+                tls: None,
             }),
+            client: None,
         }
     }
 }
@@ -203,9 +287,10 @@ impl hyper::service::Service<Request<SgBody>> for HttpBackendService {
                 port: None,
                 schema: None,
                 version: None,
+                ..
             }
             | Backend::File { .. } => req,
-            Backend::Http { host, port, schema, version } => {
+            Backend::Http { host, port, schema, version, .. } => {
                 if let Some(ref host) = host {
                     if let Some(context) = req.extensions().get::<AccessLogContext>() {
                         context.set_upstream_host(host.clone());
@@ -245,6 +330,7 @@ impl hyper::service::Service<Request<SgBody>> for HttpBackendService {
             }
         };
         let backend = self.backend.clone();
+        let client = self.client.clone();
         let req = if let Some(defer) = req.extensions().get::<Defer>().cloned() {
             defer.apply(req)
         } else {
@@ -254,7 +340,10 @@ impl hyper::service::Service<Request<SgBody>> for HttpBackendService {
         Box::pin(async move {
             unsafe {
                 let mut response = match backend.as_ref() {
-                    Backend::Http { .. } => http_backend_service(req).await.unwrap_unchecked(),
+                    Backend::Http { .. } => match client {
+                        Some(client) => http_backend_service_with_client(req, client).await.unwrap_unchecked(),
+                        None => http_backend_service(req).await.unwrap_unchecked(),
+                    },
                     Backend::File { path } => static_file_service(req, path).await,
                 };
                 response.extensions_mut().insert(crate::extension::FromBackend::new());
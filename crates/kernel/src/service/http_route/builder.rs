@@ -2,9 +2,9 @@ use std::{fmt::Debug, path::PathBuf, time::Duration};
 
 use hyper::Version;
 
-use crate::BoxLayer;
+use crate::{backend_service::http_client_service::ClientTlsProfile, BoxLayer};
 
-use super::{match_request::HttpRouteMatch, Backend, BalancePolicyEnum, HttpBackend, HttpRoute, HttpRouteRule, RequestTimeout};
+use super::{health_check::HealthCheck, match_request::HttpRouteMatch, Backend, BalancePolicyEnum, HttpBackend, HttpRoute, HttpRouteRule, RequestTimeout};
 
 #[derive(Debug)]
 pub struct HttpRouteBuilder {
@@ -85,6 +85,7 @@ pub struct HttpRouteRuleBuilder {
     r#match: Option<Vec<HttpRouteMatch>>,
     pub plugins: Vec<BoxLayer>,
     timeout: RequestTimeout,
+    first_byte_timeout: Option<Duration>,
     backends: Vec<HttpBackend>,
     pub extensions: hyper::http::Extensions,
     pub balance_policy: BalancePolicyEnum,
@@ -101,6 +102,7 @@ impl HttpRouteRuleBuilder {
             r#match: None,
             plugins: Vec::new(),
             timeout: RequestTimeout::Default,
+            first_byte_timeout: None,
             backends: Vec::new(),
             extensions: Default::default(),
             balance_policy: BalancePolicyEnum::default(),
@@ -137,6 +139,16 @@ impl HttpRouteRuleBuilder {
         self.timeout = RequestTimeout::Disabled;
         self
     }
+    /// Timeout when no frame of the request or the response flows in `idle`, for streaming responses.
+    pub fn idle_timeout(mut self, idle: Duration) -> Self {
+        self.timeout = RequestTimeout::Idle(idle);
+        self
+    }
+    /// Timeout of waiting for the response head, it works with any timeout mode.
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.first_byte_timeout = Some(timeout);
+        self
+    }
     pub fn backend(mut self, backend: HttpBackend) -> Self {
         self.backends.push(backend);
         self
@@ -154,6 +166,7 @@ impl HttpRouteRuleBuilder {
             r#match: self.r#match,
             plugins: self.plugins,
             timeout: self.timeout,
+            first_byte_timeout: self.first_byte_timeout,
             backends: self.backends,
             ext: self.extensions,
             balance_policy: self.balance_policy,
@@ -172,7 +185,10 @@ pub struct HttpBackendBuilder<B: BackendKindBuilder = HttpBackendKindBuilder> {
     backend: B,
     pub plugins: Vec<BoxLayer>,
     timeout: RequestTimeout,
+    first_byte_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
     weight: u16,
+    health_check: Option<HealthCheck>,
     pub extensions: hyper::http::Extensions,
 }
 
@@ -182,6 +198,7 @@ pub struct HttpBackendKindBuilder {
     pub port: Option<u16>,
     pub schema: Option<String>,
     pub version: Option<Version>,
+    pub tls: Option<ClientTlsProfile>,
 }
 
 impl BackendKindBuilder for HttpBackendKindBuilder {
@@ -191,6 +208,7 @@ impl BackendKindBuilder for HttpBackendKindBuilder {
             port: self.port,
             schema: self.schema,
             version: self.version,
+            tls: self.tls,
         }
     }
 }
@@ -212,7 +230,10 @@ impl<B: BackendKindBuilder> Default for HttpBackendBuilder<B> {
             backend: B::default(),
             plugins: Vec::new(),
             timeout: RequestTimeout::Default,
+            first_byte_timeout: None,
+            connect_timeout: None,
             weight: 1,
+            health_check: None,
             extensions: Default::default(),
         }
     }
@@ -251,6 +272,10 @@ impl HttpBackendBuilder<HttpBackendKindBuilder> {
         };
         self
     }
+    pub fn tls(mut self, tls: ClientTlsProfile) -> Self {
+        self.backend = HttpBackendKindBuilder { tls: Some(tls), ..self.backend };
+        self
+    }
 }
 
 impl<B: BackendKindBuilder> HttpBackendBuilder<B> {
@@ -273,16 +298,38 @@ impl<B: BackendKindBuilder> HttpBackendBuilder<B> {
         self.timeout = RequestTimeout::Disabled;
         self
     }
+    /// Timeout when no frame of the request or the response flows in `idle`, for streaming responses.
+    pub fn idle_timeout(mut self, idle: Duration) -> Self {
+        self.timeout = RequestTimeout::Idle(idle);
+        self
+    }
+    /// Timeout of waiting for the response head, it works with any timeout mode.
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.first_byte_timeout = Some(timeout);
+        self
+    }
+    /// Timeout of connecting the backend.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
     pub fn weight(mut self, weight: u16) -> Self {
         self.weight = weight;
         self
     }
+    pub fn health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = Some(health_check);
+        self
+    }
     pub fn http(self) -> HttpBackendBuilder<HttpBackendKindBuilder> {
         HttpBackendBuilder {
             backend: HttpBackendKindBuilder::default(),
             plugins: self.plugins,
             timeout: self.timeout,
+            first_byte_timeout: self.first_byte_timeout,
+            connect_timeout: self.connect_timeout,
             weight: self.weight,
+            health_check: self.health_check,
             extensions: self.extensions,
         }
     }
@@ -291,7 +338,10 @@ impl<B: BackendKindBuilder> HttpBackendBuilder<B> {
             backend: FileBackendKindBuilder::default(),
             plugins: self.plugins,
             timeout: self.timeout,
+            first_byte_timeout: self.first_byte_timeout,
+            connect_timeout: self.connect_timeout,
             weight: self.weight,
+            health_check: self.health_check,
             extensions: self.extensions,
         }
     }
@@ -304,7 +354,10 @@ impl<B: BackendKindBuilder> HttpBackendBuilder<B> {
             backend: self.backend.build(),
             plugins: self.plugins,
             timeout: self.timeout,
+            first_byte_timeout: self.first_byte_timeout,
+            connect_timeout: self.connect_timeout,
             weight: self.weight,
+            health_check: self.health_check,
             ext: self.extensions,
         }
     }
@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use hyper::{Method, Request};
+
+use crate::{
+    backend_service::http_client_service::{ClientRepo, ClientTlsProfile},
+    helper_layers::balancer::{health::WeakHealthState, HealthState},
+    utils::schema_port::schema_to_port,
+    SgBody,
+};
+
+use super::Backend;
+
+/// How to probe a backend.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum HealthCheckProbe {
+    /// Send a `GET` request to the path, a `2xx` response means healthy.
+    Http { path: String },
+    /// Try to open a tcp connection.
+    #[default]
+    Tcp,
+}
+
+/// Active health check config for a backend.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HealthCheck {
+    pub probe: HealthCheckProbe,
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// Consecutive successes required to mark an unhealthy backend healthy.
+    pub healthy_threshold: u32,
+    /// Consecutive failures required to mark a healthy backend unhealthy.
+    pub unhealthy_threshold: u32,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            probe: HealthCheckProbe::default(),
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(3),
+            healthy_threshold: 2,
+            unhealthy_threshold: 3,
+        }
+    }
+}
+
+/// The backend address to be probed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HealthCheckTarget {
+    pub schema: String,
+    pub host: String,
+    pub port: u16,
+    /// Tls settings of the backend, so that https probes connect the backend the same way as requests.
+    pub tls: Option<ClientTlsProfile>,
+}
+
+impl HealthCheckTarget {
+    /// Get the probe target of a backend, only http backends with a specified host could be probed.
+    pub fn from_backend(backend: &Backend) -> Option<Self> {
+        match backend {
+            Backend::Http {
+                host: Some(host),
+                port,
+                schema,
+                tls,
+                ..
+            } => {
+                let schema = schema.clone().unwrap_or_else(|| "http".to_string());
+                let port = port.or_else(|| schema_to_port(&schema)).unwrap_or(80);
+                Some(Self {
+                    schema,
+                    host: host.clone(),
+                    port,
+                    tls: tls.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for HealthCheckTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}:{}", self.schema, self.host, self.port)
+    }
+}
+
+/// A snapshot of a running probe.
+#[derive(Debug, Clone)]
+pub struct HealthCheckReport {
+    pub target: HealthCheckTarget,
+    pub check: HealthCheck,
+    pub healthy: bool,
+    pub consecutive_successes: u32,
+    pub consecutive_failures: u32,
+}
+
+type ProbeKey = (HealthCheckTarget, HealthCheck);
+
+/// Repository of running probes.
+///
+/// Backends with the same target and health check config share one probe. A probe stops once all balancers using its state are dropped,
+/// e.g. after the routes are reloaded.
+#[derive(Debug, Default)]
+pub struct HealthCheckRepo {
+    probes: Mutex<HashMap<ProbeKey, WeakHealthState>>,
+}
+
+static GLOBAL: OnceLock<Arc<HealthCheckRepo>> = OnceLock::new();
+
+impl HealthCheckRepo {
+    pub fn global() -> Arc<Self> {
+        GLOBAL.get_or_init(Default::default).clone()
+    }
+
+    /// Get the health state of the target, spawn a new probe if there isn't one running.
+    ///
+    /// If it's not called inside a tokio runtime, no probe will be spawned and the state stays healthy.
+    pub fn get_or_spawn(&self, target: HealthCheckTarget, check: HealthCheck) -> HealthState {
+        let mut probes = self.probes.lock().expect("failed to lock health check repo");
+        probes.retain(|_, state| state.upgrade().is_some());
+        let key = (target, check);
+        if let Some(state) = probes.get(&key).and_then(WeakHealthState::upgrade) {
+            return state;
+        }
+        let state = HealthState::new();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let (target, check) = key.clone();
+            handle.spawn(probe_loop(target, check, state.downgrade()));
+        } else {
+            tracing::warn!(target = %key.0, "no tokio runtime found, health check is disabled");
+        }
+        probes.insert(key, state.downgrade());
+        state
+    }
+
+    /// Reports of all running probes.
+    pub fn reports(&self) -> Vec<HealthCheckReport> {
+        let probes = self.probes.lock().expect("failed to lock health check repo");
+        probes
+            .iter()
+            .filter_map(|((target, check), state)| {
+                let state = state.upgrade()?;
+                Some(HealthCheckReport {
+                    target: target.clone(),
+                    check: check.clone(),
+                    healthy: state.is_healthy(),
+                    consecutive_successes: state.consecutive_successes(),
+                    consecutive_failures: state.consecutive_failures(),
+                })
+            })
+            .collect()
+    }
+}
+
+async fn probe_loop(target: HealthCheckTarget, check: HealthCheck, state: WeakHealthState) {
+    let mut interval = tokio::time::interval(check.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if state.upgrade().is_none() {
+            break;
+        }
+        let success = probe(&target, &check).await;
+        let Some(state) = state.upgrade() else {
+            break;
+        };
+        if state.record(success, check.healthy_threshold, check.unhealthy_threshold) {
+            if state.is_healthy() {
+                tracing::info!(%target, "[Sg.HealthCheck] backend turns healthy");
+            } else {
+                tracing::warn!(%target, "[Sg.HealthCheck] backend turns unhealthy");
+            }
+        }
+    }
+    tracing::debug!(%target, "[Sg.HealthCheck] probe stopped");
+}
+
+async fn probe(target: &HealthCheckTarget, check: &HealthCheck) -> bool {
+    match &check.probe {
+        HealthCheckProbe::Http { path } => {
+            let uri = if path.starts_with('/') { format!("{target}{path}") } else { format!("{target}/{path}") };
+            let req = match Request::builder().method(Method::GET).uri(&uri).body(SgBody::empty()) {
+                Ok(req) => req,
+                Err(e) => {
+                    tracing::warn!(%uri, "[Sg.HealthCheck] invalid probe uri: {e}");
+                    return false;
+                }
+            };
+            let client = ClientRepo::global().read().expect("failed to read global client repo").get_or_create(target.tls.as_ref().unwrap_or(&ClientTlsProfile::default()));
+            let mut client = match client {
+                Ok(client) => client,
+                Err(e) => {
+                    tracing::warn!(%target, "[Sg.HealthCheck] fail to create client: {e}");
+                    return false;
+                }
+            };
+            let resp = client.request_timeout(req, check.timeout).await;
+            resp.status().is_success()
+        }
+        HealthCheckProbe::Tcp => matches!(
+            tokio::time::timeout(check.timeout, tokio::net::TcpStream::connect((target.host.as_str(), target.port))).await,
+            Ok(Ok(_))
+        ),
+    }
+}
@@ -1,10 +1,18 @@
+use std::collections::HashMap;
+
 use hyper::{
+    header::COOKIE,
     http::{HeaderName, HeaderValue},
     Uri,
 };
+use ipnet::IpNet;
 use regex::Regex;
 
-use crate::{utils::query_kv::QueryKvIter, BoxError, Request, SgBody};
+use crate::{
+    extension::{PathParams, PeerAddr},
+    utils::query_kv::QueryKvIter,
+    BoxError, Request, SgBody,
+};
 
 /// PathMatchType specifies the semantics of how HTTP paths should be compared.
 #[derive(Debug, Clone)]
@@ -16,6 +24,76 @@ pub enum HttpPathMatchRewrite {
     Prefix(String, Option<String>),
     /// Matches if the URL path matches the given regular expression with case sensitivity.
     RegExp(Regex, Option<String>),
+    /// Matches if the URL path matches the template with case sensitivity, the parameters are inserted as [`PathParams`].
+    ///
+    /// Parameters in the replacement, e.g. `/v2/users/{id}`, are replaced with the captured values.
+    Template(PathTemplate, Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplateSegment {
+    Literal(String),
+    Param(String),
+}
+
+/// A path with named parameters, e.g. `/users/{id}/orders/{order_id}`.
+///
+/// Each parameter takes a whole path segment, and a path matches only if it has as many segments as the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    template: String,
+    segments: Vec<TemplateSegment>,
+}
+
+impl PathTemplate {
+    /// # Errors
+    /// A parameter doesn't take a whole segment, or it's unnamed.
+    pub fn new(template: impl Into<String>) -> Result<Self, BoxError> {
+        let template = template.into();
+        let segments = template
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) if !name.is_empty() && !name.contains(['{', '}']) => Ok(TemplateSegment::Param(name.to_string())),
+                None if !segment.contains(['{', '}']) => Ok(TemplateSegment::Literal(segment.to_string())),
+                _ => Err(format!("invalid path template segment `{segment}` in `{template}`")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { template, segments })
+    }
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+    /// The literal segments before the first parameter.
+    pub fn literal_prefix(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().map_while(|segment| match segment {
+            TemplateSegment::Literal(literal) => Some(literal.as_str()),
+            TemplateSegment::Param(_) => None,
+        })
+    }
+    /// Call `f` with each parameter and its value, returns false if the path doesn't match.
+    fn visit<'a>(&'a self, path: &'a str, mut f: impl FnMut(&'a str, &'a str)) -> bool {
+        let mut path_segments = path.split('/').filter(|s| !s.is_empty());
+        for segment in &self.segments {
+            match (segment, path_segments.next()) {
+                (TemplateSegment::Literal(literal), Some(path_segment)) if literal == path_segment => {}
+                (TemplateSegment::Param(name), Some(value)) => f(name, value),
+                _ => return false,
+            }
+        }
+        path_segments.next().is_none()
+    }
+    pub fn is_match(&self, path: &str) -> bool {
+        self.visit(path, |_, _| {})
+    }
+    /// Capture the parameters if the path matches.
+    pub fn captures(&self, path: &str) -> Option<PathParams> {
+        let mut params = HashMap::new();
+        self.visit(path, |name, value| {
+            params.insert(name.to_string(), value.to_string());
+        })
+        .then_some(PathParams(params))
+    }
 }
 
 impl HttpPathMatchRewrite {
@@ -28,11 +106,15 @@ impl HttpPathMatchRewrite {
     pub fn regex(re: Regex) -> Self {
         Self::RegExp(re, None)
     }
+    pub fn template(template: PathTemplate) -> Self {
+        Self::Template(template, None)
+    }
     pub fn replace_with(self, replace: impl Into<String>) -> Self {
         match self {
             HttpPathMatchRewrite::Exact(path, _) => HttpPathMatchRewrite::Exact(path, Some(replace.into())),
             HttpPathMatchRewrite::Prefix(path, _) => HttpPathMatchRewrite::Prefix(path, Some(replace.into())),
             HttpPathMatchRewrite::RegExp(re, _) => HttpPathMatchRewrite::RegExp(re, Some(replace.into())),
+            HttpPathMatchRewrite::Template(template, _) => HttpPathMatchRewrite::Template(template, Some(replace.into())),
         }
     }
     pub fn rewrite(&self, path: &str) -> Option<String> {
@@ -85,6 +167,10 @@ impl HttpPathMatchRewrite {
                 }
             }
             HttpPathMatchRewrite::RegExp(re, Some(replace)) => Some(re.replace(path, replace).to_string()),
+            HttpPathMatchRewrite::Template(template, Some(replace)) => {
+                let params = template.captures(path)?;
+                Some(params.iter().fold(replace.clone(), |replaced, (name, value)| replaced.replace(&format!("{{{name}}}"), value)))
+            }
             _ => None,
         }
     }
@@ -155,10 +241,50 @@ pub struct HttpQueryMatch {
     pub policy: SgHttpQueryMatchPolicy,
 }
 
+#[derive(Debug, Clone)]
+pub enum SgHttpCookieMatchPolicy {
+    /// Matches the cookie value exactly and with case sensitivity.
+    Exact(String),
+    /// Matches if the cookie value matches the given regular expression with case sensitivity.
+    Regular(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpCookieMatch {
+    pub name: String,
+    pub policy: SgHttpCookieMatchPolicy,
+}
+
 #[derive(Default, Debug, Clone)]
 
 pub struct HttpMethodMatch(pub String);
 
+/// GRPCMethodMatch describes how to select a gRPC route by matching the gRPC request's service and method.
+///
+/// An omitted service or method matches all.
+#[derive(Debug, Clone)]
+pub enum GrpcMethodMatch {
+    /// Matches the service and method exactly and with case sensitivity.
+    Exact { service: Option<String>, method: Option<String> },
+    /// Matches if the service and method match the given regular expressions with case sensitivity.
+    RegExp { service: Option<Regex>, method: Option<Regex> },
+}
+
+impl GrpcMethodMatch {
+    pub fn exact(service: impl Into<String>, method: impl Into<String>) -> Self {
+        Self::Exact {
+            service: Some(service.into()),
+            method: Some(method.into()),
+        }
+    }
+    pub fn service(service: impl Into<String>) -> Self {
+        Self::Exact {
+            service: Some(service.into()),
+            method: None,
+        }
+    }
+}
+
 /// HTTPRouteMatch defines the predicate used to match requests to a given action.
 /// Multiple match types are ANDed together, i.e. the match will evaluate to true only if all conditions are satisfied.
 #[derive(Default, Debug, Clone)]
@@ -175,13 +301,45 @@ pub struct HttpRouteMatch {
     /// Method specifies HTTP method matcher.
     /// When specified, this route will be matched only if the request has the specified method.
     pub method: Option<Vec<HttpMethodMatch>>,
+    /// Grpc specifies a gRPC service and method matcher.
+    /// When specified, this route will be matched only by gRPC requests of the service and method.
+    pub grpc: Option<GrpcMethodMatch>,
+    /// Cookie specifies HTTP cookie matchers.
+    /// Multiple match values are ANDed together, meaning, a request must match all the specified cookies to select the route.
+    pub cookie: Option<Vec<HttpCookieMatch>>,
+    /// SourceIp specifies the networks of clients, the client ip is the [`PeerAddr`] of the connection.
+    /// When specified, this route will be matched only if the client ip is in any of the networks.
+    ///
+    /// `X-Forwarded-For` and `X-Real-IP` are never trusted since any client could send them,
+    /// the peer address is replaced only by the PROXY protocol header from a trusted source.
+    pub source_ip: Option<Vec<IpNet>>,
+    /// Not specifies a match which the request must not match.
+    pub not: Option<Box<HttpRouteMatch>>,
+    /// Any specifies matches which the request must match at least one of.
+    pub any: Option<Vec<HttpRouteMatch>>,
 }
 
 impl HttpRouteMatch {
-    /// rewrite request path and headers
+    /// Check if gRPC requests are matched, by itself or by a nested match.
+    pub fn uses_grpc(&self) -> bool {
+        self.grpc.is_some() || self.not.as_deref().is_some_and(HttpRouteMatch::uses_grpc) || self.any.iter().flatten().any(HttpRouteMatch::uses_grpc)
+    }
+
+    /// The [`PathParams`] of the path template, or of the first matched branch of `any` with a path template.
+    fn path_params(&self, req: &Request<SgBody>) -> Option<PathParams> {
+        if let Some(HttpPathMatchRewrite::Template(template, _)) = self.path.as_ref() {
+            return template.captures(req.uri().path());
+        }
+        self.any.iter().flatten().filter(|branch| branch.match_request(req)).find_map(|branch| branch.path_params(req))
+    }
+
+    /// rewrite request path and headers, and insert the [`PathParams`] of a path template
     /// # Errors
     /// Rewritten path is invalid.
     pub fn rewrite(&self, req: &mut Request<SgBody>) -> Result<(), BoxError> {
+        if let Some(params) = self.path_params(req) {
+            req.extensions_mut().insert(params);
+        }
         if let Some(headers_match) = self.header.as_ref() {
             for header_match in headers_match {
                 if let (Some(replace), Some(v)) = (header_match.rewrite(req), req.headers_mut().get_mut(&header_match.header_name)) {
@@ -235,6 +393,11 @@ impl From<HttpPathMatchRewrite> for HttpRouteMatch {
             header: None,
             query: None,
             method: None,
+            grpc: None,
+            cookie: None,
+            source_ip: None,
+            not: None,
+            any: None,
         }
     }
 }
@@ -246,6 +409,11 @@ impl From<SgHttpHeaderMatchRewrite> for HttpRouteMatch {
             header: Some(vec![value]),
             query: None,
             method: None,
+            grpc: None,
+            cookie: None,
+            source_ip: None,
+            not: None,
+            any: None,
         }
     }
 }
@@ -257,6 +425,11 @@ impl From<HttpQueryMatch> for HttpRouteMatch {
             header: None,
             query: Some(vec![value]),
             method: None,
+            grpc: None,
+            cookie: None,
+            source_ip: None,
+            not: None,
+            any: None,
         }
     }
 }
@@ -268,6 +441,11 @@ impl From<HttpMethodMatch> for HttpRouteMatch {
             header: None,
             query: None,
             method: Some(vec![value]),
+            grpc: None,
+            cookie: None,
+            source_ip: None,
+            not: None,
+            any: None,
         }
     }
 }
@@ -292,6 +470,7 @@ impl MatchRequest for HttpPathMatchRewrite {
                 }
             }
             HttpPathMatchRewrite::RegExp(path, _) => path.is_match(req.uri().path()),
+            HttpPathMatchRewrite::Template(template, _) => template.is_match(req.uri().path()),
         }
     }
 }
@@ -307,15 +486,68 @@ impl MatchRequest for SgHttpHeaderMatchRewrite {
     }
 }
 
+impl MatchRequest for HttpCookieMatch {
+    fn match_request(&self, req: &Request<SgBody>) -> bool {
+        req.headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|cookies| cookies.to_str().ok())
+            .flat_map(|cookies| cookies.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .any(|(name, value)| {
+                name == self.name
+                    && match &self.policy {
+                        SgHttpCookieMatchPolicy::Exact(expect) => value == expect,
+                        SgHttpCookieMatchPolicy::Regular(re) => re.is_match(value),
+                    }
+            })
+    }
+}
+
+impl MatchRequest for IpNet {
+    fn match_request(&self, req: &Request<SgBody>) -> bool {
+        req.extensions().get::<PeerAddr>().is_some_and(|peer| self.contains(&peer.0.ip().to_canonical()))
+    }
+}
+
 impl MatchRequest for HttpMethodMatch {
     fn match_request(&self, req: &Request<SgBody>) -> bool {
         req.method().as_str().eq_ignore_ascii_case(&self.0)
     }
 }
 
+impl MatchRequest for GrpcMethodMatch {
+    fn match_request(&self, req: &Request<SgBody>) -> bool {
+        if !crate::utils::grpc::is_grpc_request(req) && !crate::utils::grpc::is_grpc_web_content_type(req.headers()) {
+            return false;
+        }
+        let Some((service, method)) = crate::utils::grpc::service_and_method(req.uri().path()) else {
+            return false;
+        };
+        match self {
+            GrpcMethodMatch::Exact {
+                service: expect_service,
+                method: expect_method,
+            } => expect_service.iter().all(|s| s == service) && expect_method.iter().all(|m| m == method),
+            GrpcMethodMatch::RegExp {
+                service: service_re,
+                method: method_re,
+            } => service_re.iter().all(|re| re.is_match(service)) && method_re.iter().all(|re| re.is_match(method)),
+        }
+    }
+}
+
 impl MatchRequest for HttpRouteMatch {
     fn match_request(&self, req: &Request<SgBody>) -> bool {
-        self.path.match_request(req) && self.header.match_request(req) && self.query.match_request(req) && self.method.match_request(req)
+        self.path.match_request(req)
+            && self.header.match_request(req)
+            && self.query.match_request(req)
+            && self.method.match_request(req)
+            && self.grpc.match_request(req)
+            && self.cookie.iter().flatten().all(|cookie| cookie.match_request(req))
+            && self.source_ip.match_request(req)
+            && !self.not.as_ref().is_some_and(|not| not.match_request(req))
+            && self.any.match_request(req)
     }
 }
 
@@ -342,3 +574,95 @@ fn test_match_path() {
     let req = Request::builder().uri("https://localhost:8080/child/subApp").body(SgBody::empty()).expect("invalid request");
     assert!(HttpPathMatchRewrite::Prefix("/child/subApp".into(), None).match_request(&req));
 }
+
+#[test]
+fn test_match_grpc() {
+    let grpc_request = |path: &str, content_type: &str| Request::builder().uri(path).header("content-type", content_type).body(SgBody::empty()).expect("invalid request");
+    let say_hello = grpc_request("/helloworld.Greeter/SayHello", "application/grpc");
+    assert!(GrpcMethodMatch::exact("helloworld.Greeter", "SayHello").match_request(&say_hello));
+    assert!(GrpcMethodMatch::service("helloworld.Greeter").match_request(&say_hello));
+    assert!(GrpcMethodMatch::service("helloworld.Greeter").match_request(&grpc_request("/helloworld.Greeter/SayHello", "application/grpc-web+proto")));
+    assert!(!GrpcMethodMatch::exact("helloworld.Greeter", "SayBye").match_request(&say_hello));
+    assert!(!GrpcMethodMatch::service("helloworld.Greeter").match_request(&grpc_request("/helloworld.Greeter/SayHello", "application/json")));
+    assert!(!GrpcMethodMatch::service("helloworld.Greeter").match_request(&grpc_request("/helloworld.Greeter/SayHello/more", "application/grpc")));
+    let regex = GrpcMethodMatch::RegExp {
+        service: Some(Regex::new(r"^helloworld\.").expect("invalid regex")),
+        method: Some(Regex::new("^Say").expect("invalid regex")),
+    };
+    assert!(regex.match_request(&say_hello));
+    assert!(!regex.match_request(&grpc_request("/helloworld.Greeter/Ping", "application/grpc")));
+}
+
+#[test]
+fn test_match_path_template() {
+    let template = PathTemplate::new("/users/{id}/orders/{order_id}").expect("invalid template");
+    assert!(template.is_match("/users/42/orders/7"));
+    assert!(!template.is_match("/users/42/orders"));
+    assert!(!template.is_match("/users/42/orders/7/items"));
+    assert!(!template.is_match("/Users/42/orders/7"));
+    assert_eq!(template.literal_prefix().collect::<Vec<_>>(), ["users"]);
+    assert!(PathTemplate::new("/users/{id").is_err());
+    assert!(PathTemplate::new("/users/{}").is_err());
+    assert!(PathTemplate::new("/files/{name}.png").is_err());
+
+    let route_match = HttpRouteMatch::from(HttpPathMatchRewrite::template(template).replace_with("/v2/orders/{order_id}/users/{id}"));
+    let mut req = Request::builder().uri("http://localhost/users/42/orders/7?page=1").body(SgBody::empty()).expect("invalid request");
+    assert!(route_match.match_request(&req));
+    route_match.rewrite(&mut req).expect("fail to rewrite");
+    let params = req.extensions().get::<PathParams>().expect("path params should be inserted");
+    assert_eq!((params.get("id"), params.get("order_id")), (Some("42"), Some("7")));
+    assert_eq!(req.uri().path_and_query().map(|pq| pq.as_str()), Some("/v2/orders/7/users/42?page=1"));
+}
+
+#[test]
+fn test_match_cookie_source_ip_and_combinators() {
+    let mut req = Request::builder()
+        .uri("http://localhost/api")
+        .header(COOKIE, "session=abc; canary=true")
+        .header("x-real-ip", "192.168.1.1")
+        .body(SgBody::empty())
+        .expect("invalid request");
+    req.extensions_mut().insert(PeerAddr("10.1.2.3:4567".parse().expect("invalid address")));
+    let cookie = |name: &str, policy: SgHttpCookieMatchPolicy| HttpRouteMatch {
+        cookie: Some(vec![HttpCookieMatch { name: name.to_string(), policy }]),
+        ..Default::default()
+    };
+    assert!(cookie("canary", SgHttpCookieMatchPolicy::Exact("true".to_string())).match_request(&req));
+    assert!(cookie("session", SgHttpCookieMatchPolicy::Regular(Regex::new("^a").expect("invalid regex"))).match_request(&req));
+    assert!(!cookie("canary", SgHttpCookieMatchPolicy::Exact("false".to_string())).match_request(&req));
+    assert!(!cookie("user", SgHttpCookieMatchPolicy::Exact("abc".to_string())).match_request(&req));
+
+    let source_ip = |nets: &[&str]| HttpRouteMatch {
+        source_ip: Some(nets.iter().map(|net| net.parse().expect("invalid net")).collect()),
+        ..Default::default()
+    };
+    assert!(source_ip(&["192.168.0.0/16", "10.0.0.0/8"]).match_request(&req));
+    // the forged header is ignored
+    assert!(!source_ip(&["192.168.0.0/16"]).match_request(&req));
+
+    let not = HttpRouteMatch {
+        not: Some(Box::new(source_ip(&["10.0.0.0/8"]))),
+        ..Default::default()
+    };
+    assert!(!not.match_request(&req));
+    let any = HttpRouteMatch {
+        path: Some(HttpPathMatchRewrite::prefix("/api")),
+        any: Some(vec![source_ip(&["192.168.0.0/16"]), cookie("canary", SgHttpCookieMatchPolicy::Exact("true".to_string()))]),
+        ..Default::default()
+    };
+    assert!(any.match_request(&req));
+}
+
+#[test]
+fn test_path_params_of_any() {
+    let template = |template: &str| HttpRouteMatch::from(HttpPathMatchRewrite::template(PathTemplate::new(template).expect("invalid template")));
+    let any = HttpRouteMatch {
+        any: Some(vec![template("/users/{id}"), template("/accounts/{account}/users/{id}")]),
+        ..Default::default()
+    };
+    let mut req = Request::builder().uri("http://localhost/accounts/a/users/42").body(SgBody::empty()).expect("invalid request");
+    assert!(any.match_request(&req));
+    any.rewrite(&mut req).expect("fail to rewrite");
+    let params = req.extensions().get::<PathParams>().expect("path params should be inserted");
+    assert_eq!((params.get("account"), params.get("id")), (Some("a"), Some("42")));
+}
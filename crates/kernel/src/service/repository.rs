@@ -0,0 +1,180 @@
+//! Tcp service repository
+//!
+//! Libraries register their [`TcpService`]s by code, so that listeners could reference them in config with a json spec,
+//! and multiplex them with http and https on the same port by [`TcpService::sniff`].
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use serde_json::Value as JsonValue;
+
+use crate::BoxError;
+
+use super::TcpService;
+
+/// A [`TcpService`] which could be created from the spec in config.
+///
+/// # Example
+/// ```rust
+/// # use spacegate_kernel::{service::{repository::{ConfigurableTcpService, TcpServiceRepository}, TcpService}, BoxError, BoxResult};
+/// # use futures_util::future::BoxFuture;
+/// pub struct Greeting {
+///     text: String,
+/// }
+///
+/// impl TcpService for Greeting {
+///     fn protocol_name(&self) -> &str {
+///         "greeting"
+///     }
+///     fn sniff_peek_size(&self) -> usize {
+///         5
+///     }
+///     fn sniff(&self, peeked: &[u8]) -> bool {
+///         peeked.starts_with(b"HELLO")
+///     }
+///     fn handle(&self, mut stream: tokio::net::TcpStream, _peer: std::net::SocketAddr) -> BoxFuture<'static, BoxResult<()>> {
+///         let text = self.text.clone();
+///         Box::pin(async move {
+///             tokio::io::AsyncWriteExt::write_all(&mut stream, text.as_bytes()).await?;
+///             Ok(())
+///         })
+///     }
+/// }
+///
+/// impl ConfigurableTcpService for Greeting {
+///     const CODE: &'static str = "greeting";
+///     fn create(spec: serde_json::Value) -> Result<Self, BoxError> {
+///         let text = spec.get("text").and_then(|text| text.as_str()).unwrap_or("hello");
+///         Ok(Self { text: text.to_string() })
+///     }
+/// }
+///
+/// TcpServiceRepository::global().register::<Greeting>();
+/// ```
+pub trait ConfigurableTcpService: TcpService + Sized {
+    /// Code of the service, it should be unique repository-wise.
+    ///
+    /// It's **recommended** to use a **kebab-case** string.
+    const CODE: &'static str;
+    /// Create the service from its spec in config.
+    ///
+    /// # Errors
+    /// If the spec is invalid.
+    fn create(spec: JsonValue) -> Result<Self, BoxError>;
+}
+
+/// Tcp service maker, which receives the spec of the service and returns a [`TcpService`].
+pub type MakeTcpService = dyn Fn(JsonValue) -> Result<Arc<dyn TcpService>, BoxError> + Send + Sync + 'static;
+
+/// # Tcp Service Repository
+/// A repository of tcp service makers, keyed by code.
+///
+/// You can get a global instance through [`TcpServiceRepository::global`].
+#[derive(Default, Clone)]
+pub struct TcpServiceRepository {
+    makers: Arc<RwLock<HashMap<String, Arc<MakeTcpService>>>>,
+}
+
+impl Debug for TcpServiceRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpServiceRepository").field("codes", &self.codes()).finish()
+    }
+}
+
+impl TcpServiceRepository {
+    /// Get a global instance of this repository.
+    pub fn global() -> &'static Self {
+        static INIT: OnceLock<TcpServiceRepository> = OnceLock::new();
+        INIT.get_or_init(TcpServiceRepository::new)
+    }
+
+    /// create a new empty repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// register by [`ConfigurableTcpService`] trait
+    pub fn register<S: ConfigurableTcpService>(&self) {
+        self.register_custom(S::CODE, |spec| Ok(Arc::new(S::create(spec)?) as Arc<dyn TcpService>))
+    }
+
+    /// register a custom maker, the previous one of the same code is replaced
+    pub fn register_custom<F>(&self, code: impl Into<String>, make: F)
+    where
+        F: Fn(JsonValue) -> Result<Arc<dyn TcpService>, BoxError> + Send + Sync + 'static,
+    {
+        self.makers.write().unwrap_or_else(|e| e.into_inner()).insert(code.into(), Arc::new(make));
+    }
+
+    /// Create a service by the maker registered with the code.
+    ///
+    /// # Errors
+    /// If the code is not registered, or the spec is invalid.
+    pub fn create(&self, code: &str, spec: JsonValue) -> Result<Arc<dyn TcpService>, BoxError> {
+        let make = self.makers.read().unwrap_or_else(|e| e.into_inner()).get(code).cloned();
+        let Some(make) = make else {
+            return Err(format!("[Sg.TcpService] unregistered tcp service {code}").into());
+        };
+        make(spec).map_err(|e| format!("[Sg.TcpService] fail to create tcp service {code}: {e}").into())
+    }
+
+    /// Codes of the registered services.
+    pub fn codes(&self) -> Vec<String> {
+        let mut codes = self.makers.read().unwrap_or_else(|e| e.into_inner()).keys().cloned().collect::<Vec<_>>();
+        codes.sort();
+        codes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use futures_util::future::BoxFuture;
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::BoxResult;
+
+    struct Echo {
+        magic: String,
+    }
+
+    impl TcpService for Echo {
+        fn protocol_name(&self) -> &str {
+            "echo"
+        }
+        fn sniff_peek_size(&self) -> usize {
+            self.magic.len()
+        }
+        fn sniff(&self, peeked: &[u8]) -> bool {
+            peeked.starts_with(self.magic.as_bytes())
+        }
+        fn handle(&self, _stream: TcpStream, _peer: SocketAddr) -> BoxFuture<'static, BoxResult<()>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    impl ConfigurableTcpService for Echo {
+        const CODE: &'static str = "echo";
+        fn create(spec: JsonValue) -> Result<Self, BoxError> {
+            let magic = spec.get("magic").and_then(JsonValue::as_str).ok_or("missing magic")?;
+            Ok(Self { magic: magic.to_string() })
+        }
+    }
+
+    #[test]
+    fn create_registered_service() {
+        let repo = TcpServiceRepository::new();
+        repo.register::<Echo>();
+        assert_eq!(repo.codes(), vec!["echo".to_string()]);
+        let echo = repo.create("echo", serde_json::json!({ "magic": "ECHO" })).expect("fail to create echo");
+        assert_eq!(echo.sniff_peek_size(), 4);
+        assert!(echo.sniff(b"ECHO hello"));
+        assert!(repo.create("echo", serde_json::json!({})).is_err());
+        assert!(repo.create("socks5", serde_json::json!({})).is_err());
+    }
+}
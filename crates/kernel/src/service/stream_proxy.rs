@@ -0,0 +1,259 @@
+//! Stream proxy
+//!
+//! Forward TCP connections and UDP datagrams to backends as raw bytes, the backend is chosen by weight.
+//! Routes attach to listeners by name, see [`StreamRouter::route`].
+
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use crossbeam_utils::sync::ShardedLock;
+use futures_util::future::BoxFuture;
+use rand::distr::{weighted::WeightedIndex, Distribution};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{helper_layers::reload::Reloader, BoxError, BoxResult};
+
+use super::TcpService;
+
+const BUFFER_SIZE: usize = 8 * 1024;
+/// Give up connecting the backend after this period.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamBackend {
+    pub host: String,
+    pub port: u16,
+    pub weight: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamRoute {
+    pub name: String,
+    /// Name of the listener this route attaches to, `None` for all listeners.
+    pub listener: Option<String>,
+    pub backends: Vec<StreamBackend>,
+    /// Close the connection or session if nothing is transferred for this period.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl StreamRoute {
+    /// Pick a backend by weight.
+    pub fn pick(&self) -> Option<&StreamBackend> {
+        let index = match WeightedIndex::new(self.backends.iter().map(|b| b.weight)) {
+            Ok(picker) => picker.sample(&mut rand::rng()),
+            // all weights are zero, pick the first one
+            Err(_) => 0,
+        };
+        self.backends.get(index)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StreamRouter {
+    routes: Arc<[StreamRoute]>,
+}
+
+impl StreamRouter {
+    pub fn new(routes: impl IntoIterator<Item = StreamRoute>) -> Self {
+        Self {
+            routes: routes.into_iter().collect(),
+        }
+    }
+
+    /// Find the route of a listener, the routes attached to the listener by name take precedence over those attached to all listeners.
+    ///
+    /// If several routes are attached at the same level, the first one is chosen.
+    pub fn route(&self, listener: &str) -> Option<&StreamRoute> {
+        self.routes.iter().find(|route| route.listener.as_deref() == Some(listener)).or_else(|| self.routes.iter().find(|route| route.listener.is_none()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+/// A [`TcpService`] which forwards all connections of a listener to the backend of its route.
+#[derive(Debug, Clone)]
+pub struct TcpProxy {
+    router: Arc<ShardedLock<StreamRouter>>,
+    gateway_name: Arc<str>,
+    listener: Arc<str>,
+}
+
+impl TcpProxy {
+    pub fn new(router: StreamRouter) -> Self {
+        Self::with_gateway_name(router, Arc::<str>::from("unknown"))
+    }
+
+    pub fn with_gateway_name(router: StreamRouter, gateway_name: Arc<str>) -> Self {
+        Self {
+            router: Arc::new(ShardedLock::new(router)),
+            gateway_name,
+            listener: Arc::<str>::from(""),
+        }
+    }
+
+    /// Let the reloader replace the router of this service.
+    pub fn with_reloader(self, reloader: &Reloader<StreamRouter>) -> Self {
+        reloader.setup(self.router.clone());
+        self
+    }
+
+    /// The name of the listener to find the route, the router is shared with the clones.
+    pub fn with_listener(mut self, listener: impl Into<Arc<str>>) -> Self {
+        self.listener = listener.into();
+        self
+    }
+}
+
+impl TcpService for TcpProxy {
+    fn protocol_name(&self) -> &str {
+        "tcp-proxy"
+    }
+    fn sniff_peek_size(&self) -> usize {
+        0
+    }
+    fn sniff(&self, _peeked: &[u8]) -> bool {
+        true
+    }
+    fn handle(&self, mut stream: TcpStream, peer: SocketAddr) -> BoxFuture<'static, BoxResult<()>> {
+        let router = self.router.clone();
+        let gateway_name = self.gateway_name.clone();
+        let listener = self.listener.clone();
+        Box::pin(async move {
+            let (route_name, backend, idle_timeout) = {
+                let router = router.read().map_err(|_| BoxError::from("stream router lock poisoned"))?;
+                let route = router.route(&listener).ok_or_else(|| BoxError::from(format!("no tcp route attaches to listener {listener}")))?;
+                let backend = route.pick().ok_or_else(|| BoxError::from(format!("no backend in tcp route {}", route.name)))?;
+                (route.name.clone(), backend.clone(), route.idle_timeout)
+            };
+            tracing::debug!(gateway = %gateway_name, %peer, route = %route_name, backend = %backend.host, port = backend.port, "[Sg.TcpProxy] connect backend");
+            let mut upstream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((backend.host.as_str(), backend.port)))
+                .await
+                .map_err(|_| BoxError::from(format!("connect tcp backend {}:{} timeout", backend.host, backend.port)))??;
+            let (sent, received) = copy_bidirectional(&mut stream, &mut upstream, idle_timeout).await?;
+            tracing::trace!(sent, received, "[Sg.TcpProxy] connection closed");
+            Ok(())
+        })
+    }
+}
+
+/// Like [`tokio::io::copy_bidirectional`], but gives up when nothing is transferred in either direction for `idle_timeout`.
+async fn copy_bidirectional(downstream: &mut TcpStream, upstream: &mut TcpStream, idle_timeout: Option<Duration>) -> io::Result<(u64, u64)> {
+    let Some(idle_timeout) = idle_timeout else {
+        return tokio::io::copy_bidirectional(downstream, upstream).await;
+    };
+    let (mut downstream_read, mut downstream_write) = downstream.split();
+    let (mut upstream_read, mut upstream_write) = upstream.split();
+    let mut downstream_buf = vec![0u8; BUFFER_SIZE];
+    let mut upstream_buf = vec![0u8; BUFFER_SIZE];
+    let (mut sent, mut received) = (0u64, 0u64);
+    let (mut downstream_open, mut upstream_open) = (true, true);
+    while downstream_open || upstream_open {
+        let read = tokio::time::timeout(idle_timeout, async {
+            tokio::select! {
+                read = downstream_read.read(&mut downstream_buf), if downstream_open => (true, read),
+                read = upstream_read.read(&mut upstream_buf), if upstream_open => (false, read),
+            }
+        })
+        .await;
+        let Ok((from_downstream, read)) = read else {
+            tracing::debug!(?idle_timeout, "[Sg.TcpProxy] connection idle timeout");
+            break;
+        };
+        let read = read?;
+        let (buf, writer, open, transferred) = if from_downstream {
+            (&downstream_buf, &mut upstream_write, &mut downstream_open, &mut sent)
+        } else {
+            (&upstream_buf, &mut downstream_write, &mut upstream_open, &mut received)
+        };
+        if read == 0 {
+            *open = false;
+            writer.shutdown().await?;
+        } else {
+            writer.write_all(buf.get(..read).unwrap_or_default()).await?;
+            *transferred += read as u64;
+        }
+    }
+    Ok((sent, received))
+}
+
+/// The router of UDP listeners.
+///
+/// Unlike [`TcpProxy`], it's not a [`TcpService`], datagrams are received by [`SgUdpListen`](crate::listener::SgUdpListen).
+#[derive(Debug, Clone)]
+pub struct UdpProxy {
+    router: Arc<ShardedLock<StreamRouter>>,
+    gateway_name: Arc<str>,
+}
+
+impl UdpProxy {
+    pub fn new(router: StreamRouter) -> Self {
+        Self::with_gateway_name(router, Arc::<str>::from("unknown"))
+    }
+
+    pub fn with_gateway_name(router: StreamRouter, gateway_name: Arc<str>) -> Self {
+        Self {
+            router: Arc::new(ShardedLock::new(router)),
+            gateway_name,
+        }
+    }
+
+    /// Let the reloader replace the router of this proxy.
+    pub fn with_reloader(self, reloader: &Reloader<StreamRouter>) -> Self {
+        reloader.setup(self.router.clone());
+        self
+    }
+
+    pub fn gateway_name(&self) -> &Arc<str> {
+        &self.gateway_name
+    }
+
+    /// Pick a backend for a new session on the listener, with the idle timeout of the route.
+    ///
+    /// # Errors
+    /// If no route attaches to the listener, or the route has no backend.
+    pub fn pick(&self, listener: &str) -> BoxResult<(String, StreamBackend, Option<Duration>)> {
+        let router = self.router.read().map_err(|_| BoxError::from("stream router lock poisoned"))?;
+        let route = router.route(listener).ok_or_else(|| BoxError::from(format!("no udp route attaches to listener {listener}")))?;
+        let backend = route.pick().ok_or_else(|| BoxError::from(format!("no backend in udp route {}", route.name)))?;
+        Ok((route.name.clone(), backend.clone(), route.idle_timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(name: &str, listener: Option<&str>) -> StreamRoute {
+        StreamRoute {
+            name: name.to_string(),
+            listener: listener.map(str::to_string),
+            backends: vec![
+                StreamBackend {
+                    host: name.to_string(),
+                    port: 5432,
+                    weight: 0,
+                },
+                StreamBackend {
+                    host: format!("{name}-weighted"),
+                    port: 5432,
+                    weight: 1,
+                },
+            ],
+            idle_timeout: None,
+        }
+    }
+
+    #[test]
+    fn route_by_listener() {
+        let router = StreamRouter::new([route("all", None), route("postgres", Some("postgres")), route("postgres-2", Some("postgres"))]);
+        let name = |listener: &str| router.route(listener).map(|r| r.name.clone());
+        assert_eq!(name("postgres").as_deref(), Some("postgres"));
+        assert_eq!(name("redis").as_deref(), Some("all"));
+        assert!(StreamRouter::new([route("postgres", Some("postgres"))]).route("redis").is_none());
+        assert_eq!(route("all", None).pick().map(|b| b.host.as_str()), Some("all-weighted"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Server certificates of https listeners.
+
+use std::sync::Arc;
+
+use crossbeam_utils::sync::ShardedLock;
+use tokio_rustls::rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::CertificateDer,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    RootCertStore,
+};
+
+use crate::{helper_layers::reload::Reloader, BoxResult};
+
+use super::http_route::match_hostname::HostnameTree;
+
+/// Resolve the server certificate by the SNI of the client.
+///
+/// Hostnames could be exact, or wildcard like `*.example.com`, the exact one takes precedence.
+/// The default certificate is used when the client sends no SNI or no hostname matches.
+#[derive(Debug, Default)]
+pub struct SniCertResolver {
+    certs: HostnameTree<Arc<CertifiedKey>>,
+}
+
+impl SniCertResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a certificate for a hostname, `*` will set the default certificate.
+    pub fn add(&mut self, hostname: &str, cert: Arc<CertifiedKey>) {
+        self.certs.set(&hostname.to_ascii_lowercase(), cert);
+    }
+
+    pub fn with_cert<'a>(mut self, hostnames: impl IntoIterator<Item = &'a str>, cert: Arc<CertifiedKey>) -> Self {
+        for hostname in hostnames {
+            self.add(hostname, cert.clone());
+        }
+        self
+    }
+
+    pub fn set_default(&mut self, cert: Arc<CertifiedKey>) {
+        self.certs.fallback = Some(cert);
+    }
+
+    pub fn with_default(mut self, cert: Arc<CertifiedKey>) -> Self {
+        self.set_default(cert);
+        self
+    }
+
+    pub fn get(&self, server_name: Option<&str>) -> Option<&Arc<CertifiedKey>> {
+        match server_name {
+            Some(server_name) => self.certs.get(&server_name.to_ascii_lowercase()),
+            None => self.certs.fallback.as_ref(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certs.iter().next().is_none()
+    }
+}
+
+impl ResolvesServerCert for SniCertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.get(client_hello.server_name()).cloned()
+    }
+}
+
+/// A [`SniCertResolver`] which could be replaced by a [`Reloader`] at runtime.
+///
+/// The certificates are rotated without restarting the listener, established connections are not affected.
+#[derive(Debug, Clone, Default)]
+pub struct ReloadableCertResolver {
+    resolver: Arc<ShardedLock<SniCertResolver>>,
+}
+
+impl ReloadableCertResolver {
+    pub fn new(resolver: SniCertResolver) -> Self {
+        Self {
+            resolver: Arc::new(ShardedLock::new(resolver)),
+        }
+    }
+
+    /// Let the reloader replace the certificates of this resolver.
+    pub fn with_reloader(self, reloader: &Reloader<SniCertResolver>) -> Self {
+        reloader.setup(self.resolver.clone());
+        self
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let resolver = self.resolver.read().ok()?;
+        resolver.get(client_hello.server_name()).cloned()
+    }
+}
+
+/// Load a certificate chain and its private key from PEM.
+///
+/// # Errors
+/// If there is no certificate or private key in the PEM, or the private key is not supported.
+pub fn certified_key_from_pem(cert: &[u8], key: &[u8]) -> BoxResult<CertifiedKey> {
+    let certs = rustls_pemfile::certs(&mut &*cert).collect::<Result<Vec<CertificateDer<'static>>, _>>()?;
+    if certs.is_empty() {
+        return Err("no certificate found in pem".into());
+    }
+    let key = rustls_pemfile::private_key(&mut &*key)?.ok_or("no private key found in pem")?;
+    let provider = CryptoProvider::get_default().cloned().unwrap_or_else(|| Arc::new(ring::default_provider()));
+    let signing_key = provider.key_provider.load_private_key(key)?;
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+/// Load the trusted CA certificates from a PEM bundle.
+///
+/// # Errors
+/// If there is no certificate in the PEM or any certificate is invalid.
+pub fn root_cert_store_from_pem(pem: &[u8]) -> BoxResult<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &*pem) {
+        roots.add(cert?)?;
+    }
+    if roots.is_empty() {
+        return Err("no ca certificate found in pem".into());
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &str = include_str!("../../tests/test_https/.cert");
+    const KEY: &str = include_str!("../../tests/test_https/.key");
+
+    #[test]
+    fn resolve_by_sni() {
+        let cert = |name: &str| {
+            let mut cert = certified_key_from_pem(CERT.as_bytes(), KEY.as_bytes()).expect("load certificate");
+            cert.ocsp = Some(name.as_bytes().to_vec());
+            Arc::new(cert)
+        };
+        let resolver =
+            SniCertResolver::new().with_cert(["api.example.com"], cert("exact")).with_cert(["*.example.com", "example.org"], cert("wildcard")).with_default(cert("default"));
+        let resolved = |name: Option<&str>| resolver.get(name).and_then(|cert| cert.ocsp.clone()).map(|name| String::from_utf8_lossy(&name).to_string());
+        assert_eq!(resolved(Some("API.example.com")).as_deref(), Some("exact"));
+        assert_eq!(resolved(Some("web.example.com")).as_deref(), Some("wildcard"));
+        assert_eq!(resolved(Some("example.org")).as_deref(), Some("wildcard"));
+        assert_eq!(resolved(Some("other.net")).as_deref(), Some("default"));
+        assert_eq!(resolved(None).as_deref(), Some("default"));
+        assert!(SniCertResolver::new().with_cert(["api.example.com"], cert("exact")).get(Some("other.net")).is_none());
+    }
+
+    #[test]
+    fn reload_certificates() {
+        let cert = |name: &str| {
+            let mut cert = certified_key_from_pem(CERT.as_bytes(), KEY.as_bytes()).expect("load certificate");
+            cert.ocsp = Some(name.as_bytes().to_vec());
+            Arc::new(cert)
+        };
+        let reloader = Reloader::<SniCertResolver>::default();
+        let resolver = ReloadableCertResolver::new(SniCertResolver::new().with_default(cert("old"))).with_reloader(&reloader);
+        let resolved = || resolver.resolver.read().ok().and_then(|resolver| resolver.get(None).and_then(|cert| cert.ocsp.clone()));
+        assert_eq!(resolved().as_deref(), Some(b"old".as_slice()));
+        reloader.reload(SniCertResolver::new().with_default(cert("new")));
+        assert_eq!(resolved().as_deref(), Some(b"new".as_slice()));
+    }
+}
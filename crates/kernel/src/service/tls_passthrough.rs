@@ -0,0 +1,240 @@
+//! TLS passthrough
+//!
+//! Route TLS connections by the SNI of the ClientHello to backends, and proxy the raw TCP stream without terminating TLS.
+//! So the backends could own their certificates.
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use crossbeam_utils::sync::ShardedLock;
+use futures_util::future::BoxFuture;
+use rand::distr::{weighted::WeightedIndex, Distribution};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    helper_layers::reload::Reloader,
+    utils::{client_hello_message_sni, reassemble_client_hello},
+    BoxError, BoxResult,
+};
+
+use super::{http_route::match_hostname::HostnameTree, TcpService};
+
+/// Max length of the ClientHello handshake message, which may span several TLS records.
+const MAX_CLIENT_HELLO_LEN: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsBackend {
+    pub host: String,
+    pub port: u16,
+    pub weight: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsRoute {
+    pub name: String,
+    /// Hostnames to be matched against the SNI, `*.example.com` is supported. An empty list matches all connections.
+    pub hostnames: Vec<String>,
+    pub backends: Vec<TlsBackend>,
+    pub priority: i16,
+}
+
+impl TlsRoute {
+    /// Pick a backend by weight.
+    pub fn pick(&self) -> Option<&TlsBackend> {
+        let index = match WeightedIndex::new(self.backends.iter().map(|b| b.weight)) {
+            Ok(picker) => picker.sample(&mut rand::rng()),
+            // all weights are zero, pick the first one
+            Err(_) => 0,
+        };
+        self.backends.get(index)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsRouter {
+    routes: Arc<[TlsRoute]>,
+    hostname_tree: HostnameTree<Vec<(usize, i16)>>,
+}
+
+impl TlsRouter {
+    pub fn new(routes: impl IntoIterator<Item = TlsRoute>) -> Self {
+        let routes: Arc<[TlsRoute]> = routes.into_iter().collect();
+        let mut indices = HashMap::<&str, Vec<(usize, i16)>>::new();
+        for (index, route) in routes.iter().enumerate() {
+            if route.hostnames.is_empty() {
+                indices.entry("*").or_default().push((index, route.priority));
+            }
+            for hostname in &route.hostnames {
+                indices.entry(hostname.as_str()).or_default().push((index, route.priority));
+            }
+        }
+        let mut hostname_tree = HostnameTree::new();
+        for (hostname, mut indices) in indices {
+            // we put the highest priority at the front of the vector
+            indices.sort_by_key(|(_, priority)| -priority);
+            hostname_tree.set(&hostname.to_ascii_lowercase(), indices);
+        }
+        Self { routes, hostname_tree }
+    }
+
+    /// Find the route by SNI, connections without SNI could only match the routes without hostnames.
+    pub fn route(&self, sni: Option<&str>) -> Option<&TlsRoute> {
+        let indices = match sni {
+            Some(sni) => self.hostname_tree.get(sni),
+            None => self.hostname_tree.fallback.as_ref(),
+        }?;
+        indices.first().and_then(|(index, _)| self.routes.get(*index))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+/// A [`TcpService`] which proxies TLS connections to the backend chosen by SNI.
+#[derive(Debug, Clone)]
+pub struct TlsPassthrough {
+    router: Arc<ShardedLock<TlsRouter>>,
+    gateway_name: Arc<str>,
+    client_hello_timeout: Duration,
+    connect_timeout: Duration,
+}
+
+impl TlsPassthrough {
+    pub fn new(router: TlsRouter) -> Self {
+        Self::with_gateway_name(router, Arc::<str>::from("unknown"))
+    }
+
+    pub fn with_gateway_name(router: TlsRouter, gateway_name: Arc<str>) -> Self {
+        Self {
+            router: Arc::new(ShardedLock::new(router)),
+            gateway_name,
+            client_hello_timeout: Duration::from_secs(10),
+            connect_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Let the reloader replace the router of this service.
+    pub fn with_reloader(self, reloader: &Reloader<TlsRouter>) -> Self {
+        reloader.setup(self.router.clone());
+        self
+    }
+
+    /// Timeout of receiving the ClientHello, default is 10 seconds.
+    pub fn with_client_hello_timeout(mut self, timeout: Duration) -> Self {
+        self.client_hello_timeout = timeout;
+        self
+    }
+
+    /// Timeout of connecting the backend, default is 10 seconds.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+}
+
+/// Read the TLS records which carry the ClientHello.
+///
+/// Returns the bytes read, which are forwarded to the backend as they are, and the reassembled ClientHello message.
+async fn read_client_hello<S: AsyncRead + Unpin>(stream: &mut S) -> BoxResult<(Vec<u8>, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(1024);
+    loop {
+        if let Some((_, client_hello)) = reassemble_client_hello(&buf, MAX_CLIENT_HELLO_LEN)? {
+            return Ok((buf, client_hello));
+        }
+        if stream.read_buf(&mut buf).await? == 0 {
+            return Err(BoxError::from("connection closed before client hello"));
+        }
+    }
+}
+
+impl TcpService for TlsPassthrough {
+    fn protocol_name(&self) -> &str {
+        "tls-passthrough"
+    }
+    fn sniff_peek_size(&self) -> usize {
+        5
+    }
+    fn sniff(&self, peeked: &[u8]) -> bool {
+        peeked.starts_with(b"\x16\x03")
+    }
+    fn handle(&self, mut stream: TcpStream, peer: SocketAddr) -> BoxFuture<'static, BoxResult<()>> {
+        let router = self.router.clone();
+        let gateway_name = self.gateway_name.clone();
+        let client_hello_timeout = self.client_hello_timeout;
+        let connect_timeout = self.connect_timeout;
+        Box::pin(async move {
+            let (records, client_hello) =
+                tokio::time::timeout(client_hello_timeout, read_client_hello(&mut stream)).await.map_err(|_| BoxError::from("timeout reading client hello"))??;
+            let sni = client_hello_message_sni(&client_hello);
+            let (route_name, backend) = {
+                let router = router.read().map_err(|_| BoxError::from("tls router lock poisoned"))?;
+                let route = router.route(sni.as_deref()).ok_or_else(|| BoxError::from(format!("no tls route matches sni {sni:?}")))?;
+                let backend = route.pick().ok_or_else(|| BoxError::from(format!("no backend in tls route {}", route.name)))?;
+                (route.name.clone(), backend.clone())
+            };
+            tracing::debug!(gateway = %gateway_name, %peer, ?sni, route = %route_name, backend = %backend.host, port = backend.port, "[Sg.TlsPassthrough] connect backend");
+            let mut upstream = tokio::time::timeout(connect_timeout, TcpStream::connect((backend.host.as_str(), backend.port)))
+                .await
+                .map_err(|_| BoxError::from(format!("connect tls backend {}:{} timeout", backend.host, backend.port)))??;
+            upstream.write_all(&records).await?;
+            let (sent, received) = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+            tracing::trace!(sent = sent + records.len() as u64, received, "[Sg.TlsPassthrough] connection closed");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(name: &str, hostnames: &[&str], priority: i16) -> TlsRoute {
+        TlsRoute {
+            name: name.to_string(),
+            hostnames: hostnames.iter().map(|h| h.to_string()).collect(),
+            backends: vec![TlsBackend {
+                host: name.to_string(),
+                port: 443,
+                weight: 1,
+            }],
+            priority,
+        }
+    }
+
+    #[test]
+    fn route_by_sni() {
+        let router = TlsRouter::new([
+            route("exact", &["api.example.com"], 1),
+            route("wildcard", &["*.example.com"], 1),
+            route("fallback", &[], 1),
+            route("fallback-high", &[], 2),
+        ]);
+        let name = |sni: Option<&str>| router.route(sni).map(|r| r.name.clone());
+        assert_eq!(name(Some("api.example.com")).as_deref(), Some("exact"));
+        assert_eq!(name(Some("web.example.com")).as_deref(), Some("wildcard"));
+        assert_eq!(name(Some("other.org")).as_deref(), Some("fallback-high"));
+        assert_eq!(name(None).as_deref(), Some("fallback-high"));
+        assert!(TlsRouter::new([route("exact", &["api.example.com"], 1)]).route(Some("other.org")).is_none());
+    }
+
+    #[tokio::test]
+    async fn read_fragmented_client_hello() {
+        // a ClientHello without extensions, fragmented into two records
+        let mut message = vec![0x01, 0x00, 0x00, 0x26, 0x03, 0x03];
+        message.extend_from_slice(&[0u8; 32]);
+        message.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        let mut records = Vec::new();
+        for fragment in message.chunks(20) {
+            records.extend_from_slice(&[0x16, 0x03, 0x01, 0x00, fragment.len() as u8]);
+            records.extend_from_slice(fragment);
+        }
+        let (read, client_hello) = read_client_hello(&mut records.as_slice()).await.expect("read client hello");
+        assert_eq!(read, records);
+        assert_eq!(client_hello, message);
+        let mut truncated = records.get(..records.len() - 1).expect("truncated");
+        assert!(read_client_hello(&mut truncated).await.is_err());
+    }
+}
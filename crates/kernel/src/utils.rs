@@ -17,9 +17,14 @@ mod h2_downgrade;
 pub use h2_downgrade::h2_downgrade;
 mod dump;
 pub use dump::dump;
+mod body_limit;
+pub mod grpc;
+pub use body_limit::BodySizeLimit;
 mod path;
 pub use path::PathIter;
 mod auth;
 pub use auth::{basic::Basic, bearer::Bearer, Authorization};
 mod str_extract;
 pub use str_extract::{StrExtractorKind, StrExtractorPartsKind};
+mod client_hello;
+pub use client_hello::{client_hello_message_sni, client_hello_sni, reassemble_client_hello, tls_record_len};
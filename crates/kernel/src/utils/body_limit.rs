@@ -0,0 +1,70 @@
+use std::sync::atomic::Ordering;
+
+use hyper::{header::CONTENT_LENGTH, HeaderMap, Request, Response, StatusCode};
+
+use crate::{helper_layers::function::Inner, SgBody, SgResponseExt};
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// # Body size limit
+/// Limit the size of request and response bodies, without buffering them.
+///
+/// - A request with a `Content-Length` over the limit is rejected with `413 Payload Too Large` before it's forwarded.
+/// - A streaming request body is cut off once it exceeds the limit, and the response is replaced with `413 Payload Too Large`.
+/// - A response with a `Content-Length` over the limit is replaced with `502 Bad Gateway`.
+/// - A streaming response body is aborted once it exceeds the limit, since the head has been sent.
+///
+/// It can be used as a [`FnLayerMethod`](crate::helper_layers::function::FnLayerMethod).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BodySizeLimit {
+    /// max request body size in bytes
+    pub request: Option<u64>,
+    /// max response body size in bytes
+    pub response: Option<u64>,
+}
+
+impl BodySizeLimit {
+    pub const fn new(request: Option<u64>, response: Option<u64>) -> Self {
+        Self { request, response }
+    }
+
+    pub async fn limit(&self, req: Request<SgBody>, inner: Inner) -> Response<SgBody> {
+        let mut request_exceeded = None;
+        let req = match self.request {
+            Some(limit) => {
+                if content_length(req.headers()).is_some_and(|length| length > limit) {
+                    return Response::with_code_message(StatusCode::PAYLOAD_TOO_LARGE, format!("[Sg.BodyLimit] request body exceeds the limit of {limit} bytes"));
+                }
+                let (parts, body) = req.into_parts();
+                let (body, exceeded) = body.limited(limit);
+                request_exceeded = Some(exceeded);
+                Request::from_parts(parts, body)
+            }
+            None => req,
+        };
+        let resp = inner.call(req).await;
+        if let (Some(limit), Some(exceeded)) = (self.request, request_exceeded) {
+            if exceeded.load(Ordering::Acquire) {
+                return Response::with_code_message(StatusCode::PAYLOAD_TOO_LARGE, format!("[Sg.BodyLimit] request body exceeds the limit of {limit} bytes"));
+            }
+        }
+        match self.response {
+            Some(limit) => {
+                if content_length(resp.headers()).is_some_and(|length| length > limit) {
+                    return Response::with_code_message(StatusCode::BAD_GATEWAY, format!("[Sg.BodyLimit] response body exceeds the limit of {limit} bytes"));
+                }
+                let (parts, body) = resp.into_parts();
+                Response::from_parts(parts, body.limited(limit).0)
+            }
+            None => resp,
+        }
+    }
+}
+
+impl crate::helper_layers::function::FnLayerMethod for BodySizeLimit {
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Response<SgBody> {
+        self.limit(req, inner).await
+    }
+}
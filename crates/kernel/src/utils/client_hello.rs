@@ -0,0 +1,213 @@
+//! Extract the server name indication from a TLS ClientHello.
+//!
+//! A ClientHello may be fragmented into several handshake records, [`reassemble_client_hello`] joins them.
+//!
+//! Reference: [RFC 8446 section 4.1.2](https://datatracker.ietf.org/doc/html/rfc8446#section-4.1.2), [RFC 6066 section 3](https://datatracker.ietf.org/doc/html/rfc6066#section-3)
+
+use crate::BoxResult;
+
+const RECORD_HEADER_LEN: usize = 5;
+/// Max length of a TLS record, the header included.
+const MAX_RECORD_LEN: usize = RECORD_HEADER_LEN + (1 << 14);
+const HANDSHAKE_HEADER_LEN: usize = 4;
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 0x01;
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+const SERVER_NAME_TYPE_HOST_NAME: u8 = 0x00;
+
+/// The whole length of the first TLS record (header included), `None` if it's not a handshake record or the header is incomplete.
+pub fn tls_record_len(buf: &[u8]) -> Option<usize> {
+    let mut reader = Reader(buf);
+    if reader.u8()? != CONTENT_TYPE_HANDSHAKE {
+        return None;
+    }
+    let _version = reader.u16()?;
+    let len = reader.u16()?;
+    Some(RECORD_HEADER_LEN + len as usize)
+}
+
+/// Reassemble the ClientHello handshake message from the handshake records at the start of the buffer.
+///
+/// Returns the length of the records carrying the message and the message itself, or `None` if more bytes are needed.
+///
+/// # Errors
+/// The buffer doesn't start with handshake records, or the message is longer than `max_len`.
+pub fn reassemble_client_hello(buf: &[u8], max_len: usize) -> BoxResult<Option<(usize, Vec<u8>)>> {
+    let mut message = Vec::new();
+    let mut offset = 0;
+    loop {
+        let rest = buf.get(offset..).unwrap_or_default();
+        let Some(record_len) = tls_record_len(rest) else {
+            if rest.len() >= RECORD_HEADER_LEN {
+                return Err("not a tls handshake".into());
+            }
+            return Ok(None);
+        };
+        if record_len > MAX_RECORD_LEN {
+            return Err("tls record too large".into());
+        }
+        let Some(fragment) = rest.get(RECORD_HEADER_LEN..record_len) else {
+            return Ok(None);
+        };
+        message.extend_from_slice(fragment);
+        offset += record_len;
+        if let Some(message_len) = Reader(message.get(1..HANDSHAKE_HEADER_LEN).unwrap_or_default()).u24() {
+            let message_len = HANDSHAKE_HEADER_LEN + message_len as usize;
+            if message_len > max_len {
+                return Err("client hello too large".into());
+            }
+            if message.len() >= message_len {
+                message.truncate(message_len);
+                return Ok(Some((offset, message)));
+            }
+        }
+    }
+}
+
+/// Extract the SNI from a buffer which starts with a complete TLS record of ClientHello.
+///
+/// Returns `None` if there is no SNI or the record is malformed.
+pub fn client_hello_sni(buf: &[u8]) -> Option<String> {
+    let record_len = tls_record_len(buf)?;
+    client_hello_message_sni(buf.get(RECORD_HEADER_LEN..record_len)?)
+}
+
+/// Extract the SNI from a complete ClientHello handshake message.
+///
+/// Returns `None` if there is no SNI or the message is malformed.
+pub fn client_hello_message_sni(message: &[u8]) -> Option<String> {
+    let mut record = Reader(message);
+    if record.u8()? != HANDSHAKE_TYPE_CLIENT_HELLO {
+        return None;
+    }
+    let _handshake_len = record.u24()?;
+    // legacy_version and random
+    record.skip(2 + 32)?;
+    // legacy_session_id
+    let len = record.u8()?;
+    record.skip(len as usize)?;
+    // cipher_suites
+    let len = record.u16()?;
+    record.skip(len as usize)?;
+    // legacy_compression_methods
+    let len = record.u8()?;
+    record.skip(len as usize)?;
+    let len = record.u16()?;
+    let mut extensions = Reader(record.take(len as usize)?);
+    while !extensions.0.is_empty() {
+        let extension_type = extensions.u16()?;
+        let len = extensions.u16()?;
+        let mut extension = Reader(extensions.take(len as usize)?);
+        if extension_type != EXTENSION_SERVER_NAME {
+            continue;
+        }
+        let len = extension.u16()?;
+        let mut server_names = Reader(extension.take(len as usize)?);
+        while !server_names.0.is_empty() {
+            let name_type = server_names.u8()?;
+            let len = server_names.u16()?;
+            let name = server_names.take(len as usize)?;
+            if name_type == SERVER_NAME_TYPE_HOST_NAME {
+                return std::str::from_utf8(name).ok().map(str::to_ascii_lowercase);
+            }
+        }
+        return None;
+    }
+    None
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1)?.first().copied()
+    }
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take(2)?;
+        Some(u16::from_be_bytes(bytes.try_into().ok()?))
+    }
+    fn u24(&mut self) -> Option<u32> {
+        let bytes = self.take(3)?;
+        Some(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_hello(server_name: Option<&str>) -> Vec<u8> {
+        let mut extensions = Vec::new();
+        // an unrelated extension before the sni: supported_versions
+        extensions.extend_from_slice(&[0x00, 0x2b, 0x00, 0x03, 0x02, 0x03, 0x04]);
+        if let Some(name) = server_name {
+            let name = name.as_bytes();
+            let list_len = name.len() + 3;
+            extensions.extend_from_slice(&[0x00, 0x00]);
+            extensions.extend_from_slice(&((list_len + 2) as u16).to_be_bytes());
+            extensions.extend_from_slice(&(list_len as u16).to_be_bytes());
+            extensions.push(SERVER_NAME_TYPE_HOST_NAME);
+            extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            extensions.extend_from_slice(name);
+        }
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0u8; 32]);
+        // session id, cipher suites, compression methods
+        body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+        let mut handshake = vec![HANDSHAKE_TYPE_CLIENT_HELLO];
+        handshake.extend_from_slice((body.len() as u32).to_be_bytes().get(1..).unwrap_or_default());
+        handshake.extend_from_slice(&body);
+        let mut record = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn extract_sni() {
+        let record = client_hello(Some("Example.COM"));
+        assert_eq!(tls_record_len(&record), Some(record.len()));
+        assert_eq!(client_hello_sni(&record).as_deref(), Some("example.com"));
+        assert_eq!(client_hello_sni(&client_hello(None)), None);
+        // incomplete record
+        assert_eq!(client_hello_sni(record.get(..record.len() - 1).unwrap_or_default()), None);
+        assert_eq!(tls_record_len(b"GET / HTTP/1.1"), None);
+    }
+
+    #[test]
+    fn reassemble_fragmented_client_hello() {
+        let record = client_hello(Some("example.com"));
+        let message = record.get(RECORD_HEADER_LEN..).expect("record body").to_vec();
+        // split the message into records of 16 bytes at most
+        let mut records = Vec::new();
+        for fragment in message.chunks(16) {
+            records.extend_from_slice(&[CONTENT_TYPE_HANDSHAKE, 0x03, 0x01]);
+            records.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            records.extend_from_slice(fragment);
+        }
+        for end in [3, RECORD_HEADER_LEN + 16, records.len() - 1] {
+            assert!(reassemble_client_hello(records.get(..end).expect("prefix"), 1024).expect("valid prefix").is_none());
+        }
+        let mut with_data = records.clone();
+        with_data.extend_from_slice(b"early data");
+        let (len, reassembled) = reassemble_client_hello(&with_data, 1024).expect("valid records").expect("complete message");
+        assert_eq!(len, records.len());
+        assert_eq!(reassembled, message);
+        assert_eq!(client_hello_message_sni(&reassembled).as_deref(), Some("example.com"));
+        assert!(reassemble_client_hello(&records, 16).is_err());
+        assert!(reassemble_client_hello(b"GET / HTTP/1.1", 1024).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! gRPC helpers
+//!
+//! Errors of the gateway itself, e.g. timeouts and no route, are plain http responses,
+//! which are mapped to the `grpc-status` of a [trailers-only](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md#responses) response for gRPC clients.
+//! The mapping is installed on the routes matching gRPC requests, and on the fallback of a gateway having such routes.
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    HeaderMap, Request, Response, StatusCode,
+};
+
+use crate::{helper_layers::function::Inner, SgBody};
+
+pub const GRPC_CONTENT_TYPE: &str = "application/grpc";
+pub const GRPC_WEB_CONTENT_TYPE: &str = "application/grpc-web";
+pub const GRPC_WEB_TEXT_CONTENT_TYPE: &str = "application/grpc-web-text";
+pub const GRPC_STATUS: &str = "grpc-status";
+pub const GRPC_MESSAGE: &str = "grpc-message";
+
+/// gRPC status codes, see [status codes](https://grpc.github.io/grpc/core/md_doc_statuscodes.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum GrpcCode {
+    Ok = 0,
+    Cancelled = 1,
+    Unknown = 2,
+    InvalidArgument = 3,
+    DeadlineExceeded = 4,
+    NotFound = 5,
+    AlreadyExists = 6,
+    PermissionDenied = 7,
+    ResourceExhausted = 8,
+    FailedPrecondition = 9,
+    Aborted = 10,
+    OutOfRange = 11,
+    Unimplemented = 12,
+    Internal = 13,
+    Unavailable = 14,
+    DataLoss = 15,
+    Unauthenticated = 16,
+}
+
+impl GrpcCode {
+    /// Map a http status to gRPC status, see [http to gRPC status code mapping](https://github.com/grpc/grpc/blob/master/doc/http-grpc-status-mapping.md).
+    ///
+    /// Besides the mapping, `504 Gateway Timeout` is mapped to `DEADLINE_EXCEEDED`, since it's responded when the deadline of the gateway is exceeded,
+    /// and `413 Payload Too Large` is mapped to `RESOURCE_EXHAUSTED`.
+    pub fn from_http_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::OK => GrpcCode::Ok,
+            StatusCode::BAD_REQUEST => GrpcCode::Internal,
+            StatusCode::UNAUTHORIZED => GrpcCode::Unauthenticated,
+            StatusCode::FORBIDDEN => GrpcCode::PermissionDenied,
+            StatusCode::NOT_FOUND => GrpcCode::Unimplemented,
+            StatusCode::PAYLOAD_TOO_LARGE => GrpcCode::ResourceExhausted,
+            StatusCode::GATEWAY_TIMEOUT => GrpcCode::DeadlineExceeded,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => GrpcCode::Unavailable,
+            _ => GrpcCode::Unknown,
+        }
+    }
+
+    pub fn as_header_value(self) -> HeaderValue {
+        HeaderValue::from(self as u16)
+    }
+}
+
+/// Check if the content type is `expected`, with an optional `+{codec}` or `;{parameters}` suffix.
+fn content_type_is(headers: &HeaderMap, expected: &str) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.as_bytes().strip_prefix(expected.as_bytes()))
+        .is_some_and(|suffix| matches!(suffix.first(), None | Some(b'+' | b';')))
+}
+
+/// Check if the content type is gRPC, gRPC-Web is not included.
+pub fn is_grpc_content_type(headers: &HeaderMap) -> bool {
+    content_type_is(headers, GRPC_CONTENT_TYPE)
+}
+
+/// Check if the content type is gRPC-Web or gRPC-Web-Text.
+pub fn is_grpc_web_content_type(headers: &HeaderMap) -> bool {
+    content_type_is(headers, GRPC_WEB_CONTENT_TYPE) || content_type_is(headers, GRPC_WEB_TEXT_CONTENT_TYPE)
+}
+
+/// Check if the request is a gRPC request.
+pub fn is_grpc_request(req: &Request<SgBody>) -> bool {
+    is_grpc_content_type(req.headers())
+}
+
+/// Split the path of a gRPC request `/{service}/{method}` into service and method.
+pub fn service_and_method(path: &str) -> Option<(&str, &str)> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    if service.is_empty() || method.is_empty() || method.contains('/') {
+        return None;
+    }
+    Some((service, method))
+}
+
+/// Percent encode the `grpc-message`.
+fn encode_grpc_message(message: &str) -> HeaderValue {
+    let mut encoded = String::with_capacity(message.len());
+    for b in message.bytes() {
+        if (0x20..=0x7e).contains(&b) && b != b'%' {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    HeaderValue::from_str(&encoded).expect("percent encoded message should be valid header value")
+}
+
+/// Create a trailers-only response, the status is in the headers and the body is empty.
+pub fn grpc_error_response(code: GrpcCode, message: &str, content_type: &'static str) -> Response<SgBody> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, content_type)
+        .header(GRPC_STATUS, code.as_header_value())
+        .header(GRPC_MESSAGE, encode_grpc_message(message))
+        .body(SgBody::empty())
+        .expect("invalid grpc response")
+}
+
+/// Map a response which is not a gRPC response, into a trailers-only response.
+///
+/// gRPC responses are returned as they are.
+pub fn map_grpc_error_response(resp: Response<SgBody>, content_type: &'static str) -> Response<SgBody> {
+    if is_grpc_content_type(resp.headers()) {
+        return resp;
+    }
+    let status = resp.status();
+    let message = resp.body().get_dumped().and_then(|body| std::str::from_utf8(body).ok()).filter(|message| !message.is_empty());
+    let message = message.or(status.canonical_reason()).unwrap_or_default();
+    let code = match GrpcCode::from_http_status(status) {
+        // a http 200 response is not a valid gRPC response
+        GrpcCode::Ok => GrpcCode::Unknown,
+        code => code,
+    };
+    grpc_error_response(code, message, content_type)
+}
+
+/// The content type of error responses to the request, gRPC-Web clients expect a gRPC-Web content type.
+pub fn error_content_type(req: &Request<SgBody>) -> &'static str {
+    match req.headers().get(CONTENT_TYPE).map(HeaderValue::as_bytes) {
+        Some(content_type) if content_type.starts_with(GRPC_WEB_TEXT_CONTENT_TYPE.as_bytes()) => GRPC_WEB_TEXT_CONTENT_TYPE,
+        Some(content_type) if content_type.starts_with(GRPC_WEB_CONTENT_TYPE.as_bytes()) => GRPC_WEB_CONTENT_TYPE,
+        _ => GRPC_CONTENT_TYPE,
+    }
+}
+
+/// Map the errors of gRPC requests into `grpc-status`, gRPC-Web requests are mapped by the `grpc-web` plugin.
+pub async fn grpc_status(req: Request<SgBody>, inner: Inner) -> Response<SgBody> {
+    if !is_grpc_request(&req) {
+        return inner.call(req).await;
+    }
+    let content_type = error_content_type(&req);
+    map_grpc_error_response(inner.call(req).await, content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SgResponseExt;
+
+    #[test]
+    fn map_error_response() {
+        let resp = map_grpc_error_response(Response::with_code_message(StatusCode::GATEWAY_TIMEOUT, "request timeout"), GRPC_CONTENT_TYPE);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(CONTENT_TYPE).expect("content type"), GRPC_CONTENT_TYPE);
+        assert_eq!(resp.headers().get(GRPC_STATUS).expect("grpc status"), "4");
+        assert_eq!(resp.headers().get(GRPC_MESSAGE).expect("grpc message"), "request timeout");
+
+        let resp = map_grpc_error_response(Response::with_code_empty(StatusCode::NOT_FOUND), GRPC_WEB_CONTENT_TYPE);
+        assert_eq!(resp.headers().get(GRPC_STATUS).expect("grpc status"), "12");
+        assert_eq!(resp.headers().get(GRPC_MESSAGE).expect("grpc message"), "Not Found");
+
+        let grpc = grpc_error_response(GrpcCode::Unavailable, "100% 不可用", GRPC_CONTENT_TYPE);
+        assert_eq!(grpc.headers().get(GRPC_MESSAGE).expect("grpc message"), "100%25 %E4%B8%8D%E5%8F%AF%E7%94%A8");
+        let grpc = map_grpc_error_response(grpc, GRPC_CONTENT_TYPE);
+        assert_eq!(grpc.headers().get(GRPC_STATUS).expect("grpc status"), "14");
+    }
+
+    #[test]
+    fn match_content_type() {
+        let headers = |content_type: &'static str| HeaderMap::from_iter([(CONTENT_TYPE, HeaderValue::from_static(content_type))]);
+        assert!(is_grpc_content_type(&headers("application/grpc")));
+        assert!(is_grpc_content_type(&headers("application/grpc+proto")));
+        assert!(is_grpc_content_type(&headers("application/grpc;charset=utf-8")));
+        assert!(!is_grpc_content_type(&headers("application/grpc-web")));
+        assert!(!is_grpc_content_type(&headers("application/grpcx")));
+        assert!(is_grpc_web_content_type(&headers("application/grpc-web+proto")));
+        assert!(is_grpc_web_content_type(&headers("application/grpc-web-text")));
+        assert!(!is_grpc_web_content_type(&headers("application/grpc")));
+    }
+
+    #[test]
+    fn split_service_and_method() {
+        assert_eq!(service_and_method("/helloworld.Greeter/SayHello"), Some(("helloworld.Greeter", "SayHello")));
+        assert_eq!(service_and_method("/helloworld.Greeter/"), None);
+        assert_eq!(service_and_method("/helloworld.Greeter"), None);
+        assert_eq!(service_and_method("/a/b/c"), None);
+    }
+}
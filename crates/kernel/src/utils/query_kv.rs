@@ -4,11 +4,13 @@
 /// ```rust
 /// # use spacegate_kernel::utils::QueryKvIter;
 /// # fn main() {
-/// let query = "a=1&b=2&c";
+/// let query = "a=1&b=2&c&d=4";
 /// let mut iter = QueryKvIter::new(query);
 /// assert_eq!(iter.next(), Some(("a", Some("1"))));
 /// assert_eq!(iter.next(), Some(("b", Some("2"))));
 /// assert_eq!(iter.next(), Some(("c", None)));
+/// assert_eq!(iter.next(), Some(("d", Some("4"))));
+/// assert_eq!(iter.next(), None);
 /// # }
 /// ```
 #[derive(Debug)]
@@ -29,19 +31,29 @@ impl<'a> Iterator for QueryKvIter<'a> {
         if self.inner.is_empty() {
             return None;
         }
-        match self.inner.split_once('&') {
-            Some((k, v)) => {
-                self.inner = v;
-                match k.split_once('=') {
-                    Some((k, v)) => Some((k, Some(v))),
-                    None => Some((k, None)),
-                }
-            }
-            None => {
-                let k = self.inner;
-                self.inner = "";
-                Some((k, None))
+        let kv = match self.inner.split_once('&') {
+            Some((kv, rest)) => {
+                self.inner = rest;
+                kv
             }
+            None => std::mem::take(&mut self.inner),
+        };
+        match kv.split_once('=') {
+            Some((k, v)) => Some((k, Some(v))),
+            None => Some((kv, None)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_pair_has_value() {
+        assert_eq!(QueryKvIter::new("a=1").collect::<Vec<_>>(), vec![("a", Some("1"))]);
+        assert_eq!(QueryKvIter::new("a=1&b=2").collect::<Vec<_>>(), vec![("a", Some("1")), ("b", Some("2"))]);
+        assert_eq!(QueryKvIter::new("a=1&b=").collect::<Vec<_>>(), vec![("a", Some("1")), ("b", Some(""))]);
+        assert_eq!(QueryKvIter::new("a=1&b").collect::<Vec<_>>(), vec![("a", Some("1")), ("b", None)]);
+    }
+}
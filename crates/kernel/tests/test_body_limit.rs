@@ -0,0 +1,62 @@
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use http_body_util::BodyExt;
+use hyper::{service::service_fn, Response, StatusCode};
+use spacegate_kernel::{helper_layers::function::FnLayer, listener::SgListen, utils::BodySizeLimit, ArcHyperService, SgBody};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_util::sync::CancellationToken;
+use tower_layer::Layer;
+
+/// An upstream which reads the whole body, and responds with its size.
+fn service() -> ArcHyperService {
+    let upstream = service_fn(|req: hyper::Request<SgBody>| async move {
+        Ok(match req.into_body().collect().await {
+            Ok(body) => Response::new(SgBody::full(body.to_bytes().len().to_string())),
+            Err(e) => Response::builder().status(StatusCode::BAD_GATEWAY).body(SgBody::full(e.to_string())).expect("invalid response"),
+        })
+    });
+    ArcHyperService::new(FnLayer::new(BodySizeLimit::new(Some(1024), None)).layer(upstream))
+}
+
+async fn read_status(stream: &mut TcpStream) -> String {
+    let mut response = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !response.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf)).await.expect("timeout").expect("fail to read");
+        assert!(read > 0, "connection closed: {}", String::from_utf8_lossy(&response));
+        response.extend_from_slice(&buf[..read]);
+    }
+    String::from_utf8_lossy(&response).lines().next().unwrap_or_default().to_string()
+}
+
+#[tokio::test]
+async fn test_body_limit() {
+    let cancel = CancellationToken::default();
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9459").expect("invalid host"), cancel.child_token()).with_service(service().http());
+    tokio::spawn(listen.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // rejected by content-length before the body is sent
+    let mut stream = TcpStream::connect("127.0.0.1:9459").await.expect("fail to connect");
+    stream.write_all(b"POST / HTTP/1.1\r\nhost: localhost\r\ncontent-length: 1048576\r\n\r\n").await.expect("fail to write");
+    assert!(read_status(&mut stream).await.contains("413"));
+
+    // a chunked body is cut off once it exceeds the limit, the rest is never sent
+    let mut stream = TcpStream::connect("127.0.0.1:9459").await.expect("fail to connect");
+    stream.write_all(b"POST / HTTP/1.1\r\nhost: localhost\r\ntransfer-encoding: chunked\r\n\r\n").await.expect("fail to write");
+    for _ in 0..3 {
+        stream.write_all(format!("200\r\n{}\r\n", "x".repeat(512)).as_bytes()).await.expect("fail to write");
+    }
+    assert!(read_status(&mut stream).await.contains("413"));
+
+    // a chunked body within the limit is forwarded
+    let mut stream = TcpStream::connect("127.0.0.1:9459").await.expect("fail to connect");
+    stream.write_all(b"POST / HTTP/1.1\r\nhost: localhost\r\ntransfer-encoding: chunked\r\n\r\n").await.expect("fail to write");
+    stream.write_all(format!("200\r\n{}\r\n0\r\n\r\n", "x".repeat(512)).as_bytes()).await.expect("fail to write");
+    assert!(read_status(&mut stream).await.contains("200"));
+    cancel.cancel();
+}
@@ -0,0 +1,131 @@
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use hyper::{service::service_fn, Response};
+use spacegate_kernel::{listener::SgListen, ArcHyperService, SgBody};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_util::sync::CancellationToken;
+
+const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n";
+const SLOW_REQUEST: &[u8] = b"GET /slow HTTP/1.1\r\nhost: localhost\r\n\r\n";
+
+fn service() -> ArcHyperService {
+    ArcHyperService::new(service_fn(|req: hyper::Request<SgBody>| async move {
+        if req.uri().path() == "/slow" {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+        Ok(Response::new(SgBody::full("ok")))
+    }))
+}
+
+/// Read a response with the body `ok`, returns the head.
+async fn read_response(stream: &mut TcpStream) -> String {
+    let mut response = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !response.ends_with(b"\r\n\r\nok") {
+        let read = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf)).await.expect("timeout").expect("fail to read");
+        assert!(read > 0, "connection closed: {}", String::from_utf8_lossy(&response));
+        response.extend_from_slice(&buf[..read]);
+    }
+    String::from_utf8_lossy(&response).to_lowercase()
+}
+
+async fn is_closed(stream: &mut TcpStream) -> bool {
+    let mut buf = [0u8; 1024];
+    matches!(tokio::time::timeout(Duration::from_secs(1), stream.read(&mut buf)).await, Ok(Ok(0) | Err(_)))
+}
+
+#[tokio::test]
+async fn test_max_connections() {
+    let cancel = CancellationToken::default();
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9454").expect("invalid host"), cancel.child_token()).with_service(service().http()).with_max_connections(2);
+    tokio::spawn(listen.listen());
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9455").expect("invalid host"), cancel.child_token()).with_service(service().http()).with_max_connections_per_ip(1);
+    tokio::spawn(listen.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    for (port, limit) in [(9454, 2), (9455, 1)] {
+        let mut streams = Vec::new();
+        for _ in 0..limit {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).await.expect("fail to connect");
+            stream.write_all(REQUEST).await.expect("fail to write");
+            read_response(&mut stream).await;
+            streams.push(stream);
+        }
+        let mut rejected = TcpStream::connect(("127.0.0.1", port)).await.expect("fail to connect");
+        let _ = rejected.write_all(REQUEST).await;
+        assert!(is_closed(&mut rejected).await, "connection over limit on {port} is not closed");
+
+        // the slot is released after a connection is closed
+        drop(streams.pop());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.expect("fail to connect");
+        stream.write_all(REQUEST).await.expect("fail to write");
+        read_response(&mut stream).await;
+    }
+    cancel.cancel();
+}
+
+#[tokio::test]
+async fn test_keep_alive_timeout() {
+    let cancel = CancellationToken::default();
+    let service = service().http().with_keep_alive_timeout(Duration::from_millis(200));
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9456").expect("invalid host"), cancel.child_token()).with_service(service);
+    tokio::spawn(listen.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:9456").await.expect("fail to connect");
+    // a slow request is not idle
+    stream.write_all(SLOW_REQUEST).await.expect("fail to write");
+    read_response(&mut stream).await;
+    stream.write_all(REQUEST).await.expect("fail to write");
+    read_response(&mut stream).await;
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert!(is_closed(&mut stream).await);
+    cancel.cancel();
+}
+
+#[tokio::test]
+async fn test_graceful_drain() {
+    let cancel = CancellationToken::default();
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9457").expect("invalid host"), cancel.child_token())
+        .with_service(service().http())
+        .with_grace_period(Duration::from_secs(2));
+    let listening = tokio::spawn(listen.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:9457").await.expect("fail to connect");
+    stream.write_all(SLOW_REQUEST).await.expect("fail to write");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cancel.cancel();
+
+    // the request in flight is finished, and the client is told to close the connection
+    let response = read_response(&mut stream).await;
+    assert!(response.contains("connection: close"), "{response}");
+    assert!(is_closed(&mut stream).await);
+    tokio::time::timeout(Duration::from_secs(1), listening).await.expect("drain timeout").expect("join error").expect("listen error");
+    assert!(TcpStream::connect("127.0.0.1:9457").await.is_err());
+}
+
+#[tokio::test]
+async fn test_grace_period_expired() {
+    let cancel = CancellationToken::default();
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9458").expect("invalid host"), cancel.child_token())
+        .with_service(service().http())
+        .with_grace_period(Duration::from_millis(100));
+    let listening = tokio::spawn(listen.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:9458").await.expect("fail to connect");
+    stream.write_all(SLOW_REQUEST).await.expect("fail to write");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    cancel.cancel();
+    tokio::time::timeout(Duration::from_millis(250), listening).await.expect("drain timeout").expect("join error").expect("listen error");
+    assert!(is_closed(&mut stream).await);
+}
@@ -0,0 +1,103 @@
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::{
+    body::{Bytes, Frame},
+    service::service_fn,
+    HeaderMap, Request, Response,
+};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::{TokioExecutor, TokioIo},
+};
+use spacegate_kernel::{
+    listener::SgListen,
+    service::{
+        http_gateway,
+        http_route::{
+            match_request::{GrpcMethodMatch, HttpRouteMatch},
+            HttpBackend, HttpRoute, HttpRouteRule,
+        },
+    },
+    BoxError, SgBody,
+};
+use tokio_util::sync::CancellationToken;
+
+const MESSAGE: &[u8] = b"\x00\x00\x00\x00\x05hello";
+
+async fn echo(req: Request<hyper::body::Incoming>) -> Result<Response<SgBody>, BoxError> {
+    if req.uri().path() == "/test.Echo/Slow" {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    let message = req.into_body().collect().await?.to_bytes();
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", "0".parse()?);
+    let frames = [Ok::<_, BoxError>(Frame::data(message)), Ok(Frame::trailers(trailers))];
+    Ok(Response::builder().header("content-type", "application/grpc").body(SgBody::new(StreamBody::new(futures_util::stream::iter(frames))))?)
+}
+
+/// A cleartext http2 gRPC server which echoes the request message.
+async fn grpc_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:9461").await.expect("fail to bind");
+    loop {
+        let (stream, _) = listener.accept().await.expect("fail to accept");
+        tokio::spawn(hyper::server::conn::http2::Builder::new(TokioExecutor::new()).serve_connection(TokioIo::new(stream), service_fn(echo)));
+    }
+}
+
+#[tokio::test]
+async fn test_grpc() {
+    let cancel = CancellationToken::default();
+    let route_match = HttpRouteMatch {
+        grpc: Some(GrpcMethodMatch::service("test.Echo")),
+        ..Default::default()
+    };
+    let gateway = http_gateway::Gateway::builder("test_grpc")
+        .http_routers([(
+            "test_grpc".to_string(),
+            HttpRoute::builder()
+                .rule(
+                    HttpRouteRule::builder()
+                        .match_item(route_match)
+                        .backend(HttpBackend::builder().host("127.0.0.1").port(9461).build())
+                        .timeout(Duration::from_millis(300))
+                        .build(),
+                )
+                .build(),
+        )])
+        .build();
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9460").expect("invalid host"), cancel.child_token()).with_service(gateway.as_service().http());
+    tokio::spawn(listen.listen());
+    tokio::spawn(grpc_server());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let client = Client::builder(TokioExecutor::new()).http2_only(true).build::<_, Full<Bytes>>(HttpConnector::new());
+    let call = |path: &'static str| {
+        let client = client.clone();
+        async move {
+            let req = Request::post(format!("http://127.0.0.1:9460{path}"))
+                .header("content-type", "application/grpc")
+                .header("te", "trailers")
+                .body(Full::new(Bytes::from_static(MESSAGE)))
+                .expect("invalid request");
+            let (parts, body) = client.request(req).await.expect("fail to send").into_parts();
+            (parts, body.collect().await.expect("fail to read body"))
+        }
+    };
+
+    // the message and the trailers are forwarded
+    let (parts, body) = call("/test.Echo/Echo").await;
+    assert_eq!(parts.status, 200);
+    let trailers = body.trailers().cloned().expect("no trailers");
+    assert_eq!(trailers.get("grpc-status").expect("no grpc status"), "0");
+    assert_eq!(body.to_bytes().as_ref(), MESSAGE);
+
+    // errors of the gateway are responded as trailers-only responses
+    let (parts, _) = call("/test.Echo/Slow").await;
+    assert_eq!(parts.status, 200);
+    assert_eq!(parts.headers.get("grpc-status").expect("no grpc status"), "4");
+    let (parts, _) = call("/test.Other/Echo").await;
+    assert_eq!(parts.headers.get("grpc-status").expect("no grpc status"), "12");
+    cancel.cancel();
+}
@@ -0,0 +1,60 @@
+use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+
+use http_body_util::BodyExt;
+use hyper::{body::Buf, service::service_fn, Request, Response};
+use spacegate_kernel::{
+    listener::{http3::alt_svc, SgHttp3Listen},
+    service::tls_cert::{certified_key_from_pem, root_cert_store_from_pem, SniCertResolver},
+    ArcHyperService, BoxError, SgBody,
+};
+use tokio_rustls::rustls::{ClientConfig, ServerConfig};
+use tokio_util::sync::CancellationToken;
+
+const CA: &[u8] = include_bytes!("test_mtls/ca.crt");
+const SERVER_CERT: &[u8] = include_bytes!("test_mtls/server.crt");
+const SERVER_KEY: &[u8] = include_bytes!("test_mtls/server.key");
+
+#[tokio::test]
+async fn test_h3() {
+    let _ = tokio_rustls::rustls::crypto::ring::default_provider().install_default();
+    let cancel = CancellationToken::default();
+    tokio::spawn(gateway(cancel.child_token()));
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let (status, body) = request("hello h3").await.expect("fail to request over http3");
+    assert_eq!(status, 200);
+    assert_eq!(body, "HTTP/3.0 POST /echo hello h3");
+    assert_eq!(alt_svc(9447), "h3=\":9447\"; ma=86400");
+    cancel.cancel();
+}
+
+async fn request(body: &'static str) -> Result<(u16, String), BoxError> {
+    let mut tls_config = ClientConfig::builder().with_root_certificates(root_cert_store_from_pem(CA)?).with_no_client_auth();
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+    let mut endpoint = quinn::Endpoint::client(SocketAddr::from_str("[::]:0")?)?;
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(quinn::crypto::rustls::QuicClientConfig::try_from(tls_config)?)));
+    let connection = endpoint.connect(SocketAddr::from_str("[::1]:9447")?, "localhost")?.await?;
+    let (mut driver, mut send_request) = h3::client::new(h3_quinn::Connection::new(connection)).await?;
+    tokio::spawn(async move { driver.wait_idle().await });
+    let mut stream = send_request.send_request(Request::post("https://localhost:9447/echo").body(())?).await?;
+    stream.send_data(hyper::body::Bytes::from_static(body.as_bytes())).await?;
+    stream.finish().await?;
+    let response = stream.recv_response().await?;
+    let mut body = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await? {
+        body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+    Ok((response.status().as_u16(), String::from_utf8(body)?))
+}
+
+async fn gateway(cancel: CancellationToken) {
+    let service = ArcHyperService::new(service_fn(|req: hyper::Request<SgBody>| async move {
+        let head = format!("{:?} {} {}", req.version(), req.method(), req.uri().path());
+        let body = req.into_body().collect().await.map(|body| body.to_bytes()).unwrap_or_default();
+        Ok(Response::new(SgBody::full(format!("{head} {}", String::from_utf8_lossy(&body)))))
+    }));
+    let cert = certified_key_from_pem(SERVER_CERT, SERVER_KEY).expect("fail to load server certificate");
+    let tls_config = ServerConfig::builder().with_no_client_auth().with_cert_resolver(Arc::new(SniCertResolver::new().with_default(Arc::new(cert))));
+    let listener = SgHttp3Listen::new(SocketAddr::from_str("[::]:9447").expect("invalid host"), service, tls_config, cancel);
+    listener.listen().await.expect("fail to listen");
+}
@@ -0,0 +1,76 @@
+use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+
+use hyper::{service::service_fn, Response};
+use spacegate_kernel::{
+    extension::PeerCertificate,
+    listener::SgListen,
+    service::tls_cert::{certified_key_from_pem, root_cert_store_from_pem, SniCertResolver},
+    ArcHyperService, SgBody,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, server::WebPkiClientVerifier, ClientConfig, ServerConfig},
+    TlsConnector,
+};
+use tokio_util::sync::CancellationToken;
+
+const CA: &[u8] = include_bytes!("test_mtls/ca.crt");
+const SERVER_CERT: &[u8] = include_bytes!("test_mtls/server.crt");
+const SERVER_KEY: &[u8] = include_bytes!("test_mtls/server.key");
+const CLIENT_CERT: &[u8] = include_bytes!("test_mtls/client.crt");
+const CLIENT_KEY: &[u8] = include_bytes!("test_mtls/client.key");
+
+#[tokio::test]
+async fn test_mtls() {
+    let _ = tokio_rustls::rustls::crypto::ring::default_provider().install_default();
+    let cancel = CancellationToken::default();
+    tokio::spawn(gateway(cancel.child_token(), "[::]:9444", false));
+    tokio::spawn(gateway(cancel.child_token(), "[::]:9445", true));
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // required: client with certificate
+    let identity = request(9444, true).await.expect("fail to request with client certificate");
+    assert!(identity.contains("CN=client"), "{identity}");
+    assert!(identity.contains("spiffe://cluster.local/ns/default/sa/client"), "{identity}");
+    // required: client without certificate
+    assert!(request(9444, false).await.is_err());
+    // optional: client without certificate
+    let identity = request(9445, false).await.expect("fail to request without client certificate");
+    assert_eq!(identity, "anonymous");
+    cancel.cancel();
+}
+
+async fn request(port: u16, with_client_cert: bool) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let builder = ClientConfig::builder().with_root_certificates(root_cert_store_from_pem(CA)?);
+    let config = if with_client_cert {
+        let certs = rustls_pemfile::certs(&mut &*CLIENT_CERT).collect::<Result<Vec<_>, _>>()?;
+        let key = rustls_pemfile::private_key(&mut &*CLIENT_KEY)?.ok_or("no client key")?;
+        builder.with_client_auth_cert(certs, key)?
+    } else {
+        builder.with_no_client_auth()
+    };
+    let stream = tokio::net::TcpStream::connect(("localhost", port)).await?;
+    let mut stream = TlsConnector::from(Arc::new(config)).connect(ServerName::try_from("localhost")?, stream).await?;
+    stream.write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response.split_once("\r\n\r\n").ok_or("bad response")?;
+    Ok(body.to_string())
+}
+
+async fn gateway(cancel: CancellationToken, addr: &str, optional: bool) {
+    let service = ArcHyperService::new(service_fn(|req: hyper::Request<SgBody>| async move {
+        let identity = match req.extensions().get::<PeerCertificate>() {
+            Some(cert) => format!("{} {:?}", cert.subject(), cert.subject_alt_names()),
+            None => "anonymous".to_string(),
+        };
+        Ok(Response::new(SgBody::full(identity)))
+    }));
+    let cert = certified_key_from_pem(SERVER_CERT, SERVER_KEY).expect("fail to load server certificate");
+    let verifier = WebPkiClientVerifier::builder(Arc::new(root_cert_store_from_pem(CA).expect("fail to load ca")));
+    let verifier = if optional { verifier.allow_unauthenticated() } else { verifier }.build().expect("fail to build verifier");
+    let tls_config = ServerConfig::builder().with_client_cert_verifier(verifier).with_cert_resolver(Arc::new(SniCertResolver::new().with_default(Arc::new(cert))));
+    let listener = SgListen::new(SocketAddr::from_str(addr).expect("invalid host"), cancel).with_service(service.https(tls_config));
+    listener.listen().await.expect("fail to listen");
+}
@@ -0,0 +1,53 @@
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use hyper::{service::service_fn, Response};
+use spacegate_kernel::{extension::PeerAddr, listener::ProxyProtocol, listener::SgListen, ArcHyperService, SgBody};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n";
+
+#[tokio::test]
+async fn test_proxy_protocol() {
+    let cancel = CancellationToken::default();
+    tokio::spawn(gateway(cancel.child_token()));
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let response = request(&[b"PROXY TCP4 192.168.1.10 10.0.0.1 56324 9448\r\n", REQUEST].concat()).await;
+    assert!(response.ends_with("192.168.1.10:56324"), "{response}");
+
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    v2.extend_from_slice(&[0x21, 0x11, 0, 12, 172, 16, 0, 3, 10, 0, 0, 1, 0xdc, 0x04, 0x24, 0xf8]);
+    let response = request(&[v2.as_slice(), REQUEST].concat()).await;
+    assert!(response.ends_with("172.16.0.3:56324"), "{response}");
+
+    // LOCAL connections keep the peer address of the load balancer
+    let mut local = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    local.extend_from_slice(&[0x20, 0x00, 0, 0]);
+    let response = request(&[local.as_slice(), REQUEST].concat()).await;
+    assert!(response.contains("\r\n\r\n127.0.0.1:"), "{response}");
+
+    // the header is required from trusted sources
+    assert!(request(REQUEST).await.is_empty());
+    cancel.cancel();
+}
+
+async fn request(data: &[u8]) -> String {
+    let mut stream = tokio::net::TcpStream::connect("127.0.0.1:9448").await.expect("fail to connect");
+    stream.write_all(data).await.expect("fail to write");
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    String::from_utf8_lossy(&response).to_string()
+}
+
+async fn gateway(cancel: CancellationToken) {
+    let service = ArcHyperService::new(service_fn(|req: hyper::Request<SgBody>| async move {
+        let peer = req.extensions().get::<PeerAddr>().map(|peer| peer.0.to_string()).unwrap_or_default();
+        Ok(Response::new(SgBody::full(peer)))
+    }));
+    let listener = SgListen::new(SocketAddr::from_str("127.0.0.1:9448").expect("invalid host"), cancel)
+        .with_service(service.http())
+        .with_proxy_protocol(ProxyProtocol::new(["127.0.0.0/8".parse().expect("invalid cidr")]));
+    listener.listen().await.expect("fail to listen");
+}
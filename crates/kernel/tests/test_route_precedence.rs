@@ -0,0 +1,211 @@
+//! Precedence of http routes, the cases are derived from the Gateway API conformance tests
+//! `HTTPRouteMatchingAcrossRoutes`, `HTTPRoutePathMatchOrder`, `HTTPRouteMethodMatching`, `HTTPRouteHeaderMatching` and `HTTPRouteQueryParamMatching`.
+use hyper::{
+    http::{HeaderName, HeaderValue},
+    Method, Request,
+};
+use spacegate_kernel::{
+    backend_service::ArcHyperService,
+    extension::RouteName,
+    helper_layers::route::Router,
+    service::{
+        http_gateway::{builder::default_gateway_route_fallback, create_http_router, path_tree::RoutePrecedence, GatewayRouter},
+        http_route::{
+            match_request::{HttpMethodMatch, HttpPathMatchRewrite, HttpQueryMatch, HttpRouteMatch, SgHttpHeaderMatchRewrite, SgHttpQueryMatchPolicy},
+            HttpRoute, HttpRouteRule,
+        },
+    },
+    SgBody,
+};
+
+fn rule(r#match: HttpRouteMatch) -> HttpRouteRule {
+    HttpRouteRule::builder().match_item(r#match).build()
+}
+
+fn prefix(path: &str) -> HttpRouteMatch {
+    HttpPathMatchRewrite::prefix(path).into()
+}
+
+fn exact(path: &str) -> HttpRouteMatch {
+    HttpPathMatchRewrite::exact(path).into()
+}
+
+fn headers(mut r#match: HttpRouteMatch, headers: &[(&'static str, &'static str)]) -> HttpRouteMatch {
+    r#match.header = Some(headers.iter().map(|(name, value)| SgHttpHeaderMatchRewrite::exact(HeaderName::from_static(name), HeaderValue::from_static(value))).collect());
+    r#match
+}
+
+fn route(name: &str, rules: impl IntoIterator<Item = HttpRouteRule>) -> HttpRoute {
+    HttpRoute::builder().name(name).rules(rules).build()
+}
+
+fn router(routes: &[HttpRoute], precedence: RoutePrecedence) -> GatewayRouter {
+    let fallback: ArcHyperService = default_gateway_route_fallback();
+    create_http_router(routes.iter(), fallback, precedence).router().clone()
+}
+
+struct Case {
+    method: Method,
+    path: &'static str,
+    headers: &'static [(&'static str, &'static str)],
+    expect: Option<(&'static str, usize)>,
+}
+
+fn get(path: &'static str, headers: &'static [(&'static str, &'static str)], expect: (&'static str, usize)) -> Case {
+    Case {
+        method: Method::GET,
+        path,
+        headers,
+        expect: Some(expect),
+    }
+}
+
+fn assert_cases(router: &GatewayRouter, cases: impl IntoIterator<Item = Case>) {
+    for case in cases {
+        let mut req = Request::builder().method(case.method.clone()).uri(format!("http://example.com{}", case.path));
+        for (name, value) in case.headers {
+            req = req.header(*name, *value);
+        }
+        let mut req = req.body(SgBody::empty()).expect("invalid request");
+        let routed = router.route(&mut req).map(|(_, rule)| (req.extensions().get::<RouteName>().expect("route name").to_string(), rule));
+        assert_eq!(
+            routed.as_ref().map(|(name, rule)| (name.as_str(), *rule)),
+            case.expect,
+            "{} {} {:?}",
+            case.method,
+            case.path,
+            case.headers
+        );
+    }
+}
+
+#[test]
+fn matching_across_routes() {
+    let routes = [
+        route("matching-part1", [rule(prefix("/")), rule(prefix("/v2"))]),
+        route(
+            "matching-part2",
+            [
+                rule(headers(prefix("/v2"), &[("version", "one")])),
+                rule(headers(HttpRouteMatch::default(), &[("version", "two")])),
+            ],
+        ),
+    ];
+    let router = router(&routes, RoutePrecedence::GatewayApi);
+    assert_cases(
+        &router,
+        [
+            get("/", &[], ("matching-part1", 0)),
+            get("/example", &[], ("matching-part1", 0)),
+            get("/v2", &[], ("matching-part1", 1)),
+            get("/v2/example", &[], ("matching-part1", 1)),
+            get("/", &[("version", "one")], ("matching-part1", 0)),
+            // same prefix, more headers
+            get("/v2", &[("version", "one")], ("matching-part2", 0)),
+            get("/", &[("version", "two")], ("matching-part2", 1)),
+            // longer prefix goes before headers
+            get("/v2", &[("version", "two")], ("matching-part1", 1)),
+        ],
+    );
+}
+
+#[test]
+fn path_match_order() {
+    let routes = [route(
+        "path-order",
+        [
+            rule(prefix("/match")),
+            rule(prefix("/match/prefix")),
+            rule(prefix("/match/prefix/one")),
+            rule(exact("/match/exact")),
+            rule(exact("/match/exact/one")),
+            rule(exact("/match")),
+        ],
+    )];
+    let router = router(&routes, RoutePrecedence::GatewayApi);
+    assert_cases(
+        &router,
+        [
+            get("/match", &[], ("path-order", 5)),
+            get("/match/", &[], ("path-order", 0)),
+            get("/match/prefix", &[], ("path-order", 1)),
+            get("/match/prefix/one/extra", &[], ("path-order", 2)),
+            get("/match/prefix/two", &[], ("path-order", 1)),
+            get("/match/exact", &[], ("path-order", 3)),
+            get("/match/exact/one", &[], ("path-order", 4)),
+            get("/match/exact/two", &[], ("path-order", 0)),
+            get("/match/prefixextra", &[], ("path-order", 0)),
+        ],
+    );
+    let mut not_found = get("/other", &[], ("path-order", 0));
+    not_found.expect = None;
+    assert_cases(&router, [not_found]);
+    // the first rule always wins by priority
+    let router = self::router(&routes, RoutePrecedence::Priority);
+    assert_cases(&router, [get("/match", &[], ("path-order", 0)), get("/match/exact/one", &[], ("path-order", 0))]);
+}
+
+#[test]
+fn method_header_and_query_order() {
+    let query = |mut r#match: HttpRouteMatch| {
+        r#match.query = Some(vec![HttpQueryMatch {
+            name: "q".to_string(),
+            policy: SgHttpQueryMatchPolicy::Exact("1".to_string()),
+        }]);
+        r#match
+    };
+    let post = |mut r#match: HttpRouteMatch| {
+        r#match.method = Some(vec![HttpMethodMatch("POST".to_string())]);
+        r#match
+    };
+    let routes = [route(
+        "precedence",
+        [
+            rule(prefix("/api")),
+            rule(query(prefix("/api"))),
+            rule(headers(prefix("/api"), &[("x-c", "1")])),
+            rule(headers(prefix("/api"), &[("x-a", "1"), ("x-b", "1")])),
+            rule(post(prefix("/api"))),
+        ],
+    )];
+    let router = router(&routes, RoutePrecedence::GatewayApi);
+    let post = |path: &'static str, headers: &'static [(&'static str, &'static str)], expect: (&'static str, usize)| Case {
+        method: Method::POST,
+        ..get(path, headers, expect)
+    };
+    assert_cases(
+        &router,
+        [
+            get("/api", &[], ("precedence", 0)),
+            post("/api", &[], ("precedence", 4)),
+            // method goes before headers
+            post("/api", &[("x-a", "1"), ("x-b", "1")], ("precedence", 4)),
+            get("/api", &[("x-c", "1")], ("precedence", 2)),
+            get("/api", &[("x-a", "1"), ("x-b", "1")], ("precedence", 3)),
+            get("/api?q=1", &[], ("precedence", 1)),
+            // headers go before query params
+            get("/api?q=1", &[("x-c", "1")], ("precedence", 2)),
+        ],
+    );
+}
+
+#[test]
+fn ties_across_routes() {
+    let routes = [
+        route("b-route", [rule(prefix("/same"))]),
+        route("a-route", [rule(prefix("/same"))]),
+        route("c-route", [rule(exact("/same/exact"))]),
+        HttpRoute::builder().name("z-route").priority(1).rule(rule(prefix("/same/priority"))).build(),
+    ];
+    let router = router(&routes, RoutePrecedence::GatewayApi);
+    assert_cases(
+        &router,
+        [
+            // the route name breaks the tie
+            get("/same", &[], ("a-route", 0)),
+            get("/same/exact", &[], ("c-route", 0)),
+            // the priority is still the first
+            get("/same/priority/exact", &[], ("z-route", 0)),
+        ],
+    );
+}
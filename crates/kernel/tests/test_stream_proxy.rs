@@ -0,0 +1,136 @@
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use spacegate_kernel::{
+    listener::{SgListen, SgUdpListen},
+    service::stream_proxy::{StreamBackend, StreamRoute, StreamRouter, TcpProxy, UdpProxy},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+};
+use tokio_util::sync::CancellationToken;
+
+fn router(listener: &str, port: u16, idle_timeout: Option<Duration>) -> StreamRouter {
+    StreamRouter::new([StreamRoute {
+        name: "echo".to_string(),
+        listener: Some(listener.to_string()),
+        backends: vec![StreamBackend {
+            host: "127.0.0.1".to_string(),
+            port,
+            weight: 1,
+        }],
+        idle_timeout,
+    }])
+}
+
+#[tokio::test]
+async fn test_tcp_proxy() {
+    let cancel = CancellationToken::default();
+    // the server speaks first
+    let upstream = TcpListener::bind("127.0.0.1:9449").await.expect("fail to bind upstream");
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                stream.write_all(b"hello").await?;
+                let mut buf = [0u8; 1024];
+                loop {
+                    let read = stream.read(&mut buf).await?;
+                    if read == 0 {
+                        return std::io::Result::Ok(());
+                    }
+                    stream.write_all(&buf[..read]).await?;
+                }
+            });
+        }
+    });
+    let proxy = TcpProxy::new(router("tcp", 9449, Some(Duration::from_millis(300)))).with_listener("tcp");
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9450").expect("invalid host"), cancel.child_token()).with_service(proxy);
+    tokio::spawn(listen.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:9450").await.expect("fail to connect");
+    let mut buf = [0u8; 5];
+    stream.read_exact(&mut buf).await.expect("fail to read greeting");
+    assert_eq!(&buf, b"hello");
+    stream.write_all(b"world").await.expect("fail to write");
+    stream.read_exact(&mut buf).await.expect("fail to read echo");
+    assert_eq!(&buf, b"world");
+
+    // the idle connection is closed by the gateway
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(stream.read(&mut buf).await.expect("fail to read eof"), 0);
+    cancel.cancel();
+}
+
+#[tokio::test]
+async fn test_udp_proxy() {
+    let cancel = CancellationToken::default();
+    let upstream = UdpSocket::bind("127.0.0.1:9451").await.expect("fail to bind upstream");
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1024];
+        while let Ok((len, peer)) = upstream.recv_from(&mut buf).await {
+            let _ = upstream.send_to(&[b"echo ", &buf[..len]].concat(), peer).await;
+        }
+    });
+    let proxy = UdpProxy::new(router("udp", 9451, None));
+    let listen = SgUdpListen::new(SocketAddr::from_str("127.0.0.1:9452").expect("invalid host"), proxy.clone(), cancel.child_token()).with_listener_id("udp");
+    tokio::spawn(listen.listen());
+    // no route attaches to this listener
+    let unrouted = SgUdpListen::new(SocketAddr::from_str("127.0.0.1:9453").expect("invalid host"), proxy, cancel.child_token()).with_listener_id("other");
+    tokio::spawn(unrouted.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await.expect("fail to bind client");
+    client.connect("127.0.0.1:9452").await.expect("fail to connect");
+    let mut buf = [0u8; 1024];
+    for message in ["first", "second"] {
+        client.send(message.as_bytes()).await.expect("fail to send");
+        let len = tokio::time::timeout(Duration::from_secs(1), client.recv(&mut buf)).await.expect("timeout").expect("fail to receive");
+        assert_eq!(&buf[..len], format!("echo {message}").as_bytes());
+    }
+
+    client.connect("127.0.0.1:9453").await.expect("fail to connect");
+    client.send(b"dropped").await.expect("fail to send");
+    assert!(tokio::time::timeout(Duration::from_millis(300), client.recv(&mut buf)).await.is_err());
+    cancel.cancel();
+}
+
+#[tokio::test]
+async fn test_udp_max_sessions() {
+    let cancel = CancellationToken::default();
+    // reply with the port of the session socket
+    let upstream = UdpSocket::bind("127.0.0.1:9466").await.expect("fail to bind upstream");
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1024];
+        while let Ok((_, peer)) = upstream.recv_from(&mut buf).await {
+            let _ = upstream.send_to(peer.port().to_string().as_bytes(), peer).await;
+        }
+    });
+    let proxy = UdpProxy::new(router("udp", 9466, None));
+    let listen = SgUdpListen::new(SocketAddr::from_str("127.0.0.1:9467").expect("invalid host"), proxy, cancel.child_token()).with_listener_id("udp").with_max_sessions(1);
+    tokio::spawn(listen.listen());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let session_port = |client: UdpSocket| async move {
+        client.send(b"ping").await.expect("fail to send");
+        let mut buf = [0u8; 16];
+        let len = tokio::time::timeout(Duration::from_secs(1), client.recv(&mut buf)).await.expect("timeout").expect("fail to receive");
+        (client, String::from_utf8_lossy(&buf[..len]).to_string())
+    };
+    let client = || async {
+        let client = UdpSocket::bind("127.0.0.1:0").await.expect("fail to bind client");
+        client.connect("127.0.0.1:9467").await.expect("fail to connect");
+        client
+    };
+    let (first, port) = session_port(client().await).await;
+    let (first, same_port) = session_port(first).await;
+    assert_eq!(port, same_port);
+    // the session of the first client is closed for the second client
+    let (_second, _) = session_port(client().await).await;
+    let (_, new_port) = session_port(first).await;
+    assert_ne!(port, new_port);
+    cancel.cancel();
+}
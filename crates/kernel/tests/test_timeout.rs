@@ -0,0 +1,113 @@
+use std::{
+    net::SocketAddr,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use http_body_util::{BodyExt, StreamBody};
+use hyper::{
+    body::{Bytes, Frame},
+    service::service_fn,
+    Request, Response,
+};
+use hyper_util::rt::TokioIo;
+use spacegate_kernel::{
+    backend_service::http_client_service::get_client,
+    listener::SgListen,
+    service::{
+        http_gateway,
+        http_route::{match_request::HttpPathMatchRewrite, HttpBackend, HttpRoute, HttpRouteRule},
+    },
+    BoxError, SgBody,
+};
+use tokio_util::sync::CancellationToken;
+
+/// A body which yields a chunk after each delay, like server-sent events.
+fn events(delays: &'static [u64]) -> SgBody {
+    SgBody::new(StreamBody::new(futures_util::stream::unfold(delays.iter(), |mut delays| async move {
+        let delay = delays.next()?;
+        tokio::time::sleep(Duration::from_millis(*delay)).await;
+        Some((Ok::<_, BoxError>(Frame::data(Bytes::from_static(b"data: ping\n\n"))), delays))
+    })))
+}
+
+async fn upstream(req: Request<hyper::body::Incoming>) -> Result<Response<SgBody>, BoxError> {
+    let body = match req.uri().path() {
+        "/sse" => events(&[200, 200, 200, 200, 200]),
+        "/stall" => events(&[0, 2000]),
+        _ => {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            SgBody::empty()
+        }
+    };
+    Ok(Response::builder().header("content-type", "text/event-stream").body(body)?)
+}
+
+async fn upstream_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:9463").await.expect("fail to bind");
+    loop {
+        let (stream, _) = listener.accept().await.expect("fail to accept");
+        tokio::spawn(hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service_fn(upstream)));
+    }
+}
+
+#[tokio::test]
+async fn test_timeout() {
+    let cancel = CancellationToken::default();
+    let backend = || HttpBackend::builder().host("127.0.0.1").port(9463).build();
+    let gateway = http_gateway::Gateway::builder("test_timeout")
+        .http_routers([(
+            "test_timeout".to_string(),
+            HttpRoute::builder()
+                .rule(HttpRouteRule::builder().match_item(HttpPathMatchRewrite::prefix("/sse")).backend(backend()).idle_timeout(Duration::from_millis(500)).build())
+                .rule(HttpRouteRule::builder().match_item(HttpPathMatchRewrite::prefix("/stall")).backend(backend()).idle_timeout(Duration::from_millis(500)).build())
+                .rule(
+                    HttpRouteRule::builder()
+                        .match_item(HttpPathMatchRewrite::prefix("/slow"))
+                        .backend(backend())
+                        .idle_timeout(Duration::from_secs(5))
+                        .first_byte_timeout(Duration::from_millis(300))
+                        .build(),
+                )
+                .rule(
+                    HttpRouteRule::builder()
+                        .match_item(HttpPathMatchRewrite::prefix("/unreachable"))
+                        .backend(HttpBackend::builder().host("10.255.255.1").port(80).connect_timeout(Duration::from_millis(300)).build())
+                        .build(),
+                )
+                .build(),
+        )])
+        .build();
+    let listen = SgListen::new(SocketAddr::from_str("127.0.0.1:9462").expect("invalid host"), cancel.child_token()).with_service(gateway.as_service().http());
+    tokio::spawn(listen.listen());
+    tokio::spawn(upstream_server());
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut client = get_client();
+    let get = |path: &str| Request::get(format!("http://127.0.0.1:9462{path}")).body(SgBody::empty()).expect("invalid request");
+
+    // the stream lasts longer than the idle timeout, but it's never idle
+    let resp = client.request(get("/sse")).await;
+    assert_eq!(resp.status(), 200);
+    let body = resp.into_body().collect().await.expect("stream should not be aborted").to_bytes();
+    assert_eq!(body.len(), 5 * b"data: ping\n\n".len());
+
+    // the stream is aborted once it's idle
+    let resp = client.request(get("/stall")).await;
+    assert_eq!(resp.status(), 200);
+    let start = Instant::now();
+    assert!(resp.into_body().collect().await.is_err());
+    assert!(start.elapsed() < Duration::from_millis(1500));
+
+    // the response head doesn't arrive in time
+    let resp = client.request(get("/slow")).await;
+    assert_eq!(resp.status(), 504);
+
+    // connecting an unreachable backend gives up soon
+    let start = Instant::now();
+    let resp = client.request(get("/unreachable")).await;
+    assert!(resp.status().is_server_error());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    cancel.cancel();
+}
@@ -0,0 +1,74 @@
+use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+
+use hyper::{service::service_fn, Request, Response, StatusCode};
+use spacegate_kernel::{
+    backend_service::http_client_service::{ClientRepo, ClientTlsProfile, HttpClient},
+    extension::PeerCertificate,
+    listener::SgListen,
+    service::tls_cert::{certified_key_from_pem, root_cert_store_from_pem, SniCertResolver},
+    ArcHyperService, BoxResult, SgBody,
+};
+use tokio_rustls::rustls::{server::WebPkiClientVerifier, ServerConfig};
+use tokio_util::sync::CancellationToken;
+
+const CA: &[u8] = include_bytes!("test_mtls/ca.crt");
+const SERVER_CERT: &[u8] = include_bytes!("test_mtls/server.crt");
+const SERVER_KEY: &[u8] = include_bytes!("test_mtls/server.key");
+const CLIENT_CERT: &[u8] = include_bytes!("test_mtls/client.crt");
+const CLIENT_KEY: &[u8] = include_bytes!("test_mtls/client.key");
+
+#[tokio::test]
+async fn test_upstream_mtls() {
+    let _ = tokio_rustls::rustls::crypto::ring::default_provider().install_default();
+    let cancel = CancellationToken::default();
+    tokio::spawn(upstream(cancel.child_token(), "[::]:9446"));
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mtls = ClientTlsProfile::default().with_ca(CA).with_client_cert(CLIENT_CERT, CLIENT_KEY).with_sni("localhost");
+    let (status, identity) = request(client(&mtls).expect("fail to create mtls client")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(identity.contains("CN=client"), "{identity}");
+
+    // the upstream requires a client certificate
+    let without_cert = ClientTlsProfile::default().with_ca(CA).with_sni("localhost");
+    let (status, _) = request(client(&without_cert).expect("fail to create client")).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+
+    // the upstream certificate is not issued by the native roots
+    let native_roots = ClientTlsProfile::default().with_client_cert(CLIENT_CERT, CLIENT_KEY);
+    let (status, _) = request(client(&native_roots).expect("fail to create client")).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+
+    // the server name doesn't match the upstream certificate
+    let wrong_sni = ClientTlsProfile::default().with_ca(CA).with_client_cert(CLIENT_CERT, CLIENT_KEY).with_sni("other.example.com");
+    let (status, _) = request(client(&wrong_sni).expect("fail to create client")).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+
+    assert!(client(&ClientTlsProfile::default().with_ca(b"not a pem".as_slice())).is_err());
+    cancel.cancel();
+}
+
+fn client(profile: &ClientTlsProfile) -> BoxResult<HttpClient> {
+    ClientRepo::global().read().expect("client repo").get_or_create(profile)
+}
+
+async fn request(mut client: HttpClient) -> (StatusCode, String) {
+    let req = Request::builder().uri("https://127.0.0.1:9446/").body(SgBody::empty()).expect("invalid request");
+    let resp = client.request_timeout(req, Duration::from_secs(5)).await;
+    let status = resp.status();
+    let body = resp.into_body().dump().await.expect("fail to read body");
+    (status, String::from_utf8_lossy(body.get_dumped().expect("no body")).to_string())
+}
+
+async fn upstream(cancel: CancellationToken, addr: &str) {
+    let service = ArcHyperService::new(service_fn(|req: hyper::Request<SgBody>| async move {
+        let identity = req.extensions().get::<PeerCertificate>().map(|cert| cert.subject().to_string()).unwrap_or_default();
+        Ok(Response::new(SgBody::full(identity)))
+    }));
+    let cert = certified_key_from_pem(SERVER_CERT, SERVER_KEY).expect("fail to load server certificate");
+    let verifier = WebPkiClientVerifier::builder(Arc::new(root_cert_store_from_pem(CA).expect("fail to load ca"))).build().expect("fail to build verifier");
+    let tls_config = ServerConfig::builder().with_client_cert_verifier(verifier).with_cert_resolver(Arc::new(SniCertResolver::new().with_default(Arc::new(cert))));
+    let listener = SgListen::new(SocketAddr::from_str(addr).expect("invalid host"), cancel).with_service(service.https(tls_config));
+    listener.listen().await.expect("fail to listen");
+}
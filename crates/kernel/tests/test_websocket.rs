@@ -54,3 +54,83 @@ async fn ws_server() {
         });
     }
 }
+
+#[tokio::test]
+async fn test_ws_forward() {
+    use spacegate_kernel::{
+        extension::{TextMessageAction, WebSocketForward, WsDirection},
+        helper_layers::map_request::MapRequestLayer,
+        BoxLayer, SgRequest,
+    };
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, protocol::frame::coding::CloseCode, Message};
+
+    let cancel = CancellationToken::default();
+    let forward = MapRequestLayer::new(|mut req: SgRequest| {
+        let forward = req.extensions_mut().get_or_insert_default::<WebSocketForward>();
+        forward.max_message_size = Some(512);
+        forward.idle_timeout = Some(Duration::from_millis(400));
+        forward.add_text_hook(|direction, text: &str| match direction {
+            WsDirection::Upstream if text.contains("admin") => TextMessageAction::Reject("forbidden".to_string()),
+            WsDirection::Downstream => TextMessageAction::Replace(text.replace("secret", "******")),
+            _ => TextMessageAction::Forward,
+        });
+        req
+    });
+    let gateway = http_gateway::Gateway::builder("test_ws_forward")
+        .http_routers([(
+            "ws".to_string(),
+            HttpRoute::builder()
+                .rule(HttpRouteRule::builder().match_all().plugin(BoxLayer::new(forward)).backend(HttpBackend::builder().host("127.0.0.1").port(9465).build()).build())
+                .build(),
+        )])
+        .build();
+    let listener = SgListen::new(SocketAddr::from_str("127.0.0.1:9464").expect("invalid host"), cancel.child_token()).with_service(Http::new(gateway.as_service()));
+    tokio::spawn(listener.listen());
+    tokio::spawn(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:9465").await.expect("fail to bind");
+        while let Ok((stream, _peer)) = listener.accept().await {
+            tokio::spawn(async move {
+                // the signature is given by tungstenite
+                #[allow(clippy::result_large_err)]
+                let check_extensions = |req: &tokio_tungstenite::tungstenite::handshake::server::Request, resp| {
+                    assert!(!req.headers().contains_key("sec-websocket-extensions"), "extensions should not be forwarded");
+                    Ok(resp)
+                };
+                let Ok(mut ws_stream) = tokio_tungstenite::accept_hdr_async(stream, check_extensions).await else {
+                    return;
+                };
+                while let Some(Ok(msg)) = ws_stream.next().await {
+                    if msg.is_text() || msg.is_binary() {
+                        let _result = ws_stream.send(msg).await;
+                    }
+                }
+            });
+        }
+    });
+    // wait for startup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let close_code = |msg: Option<Result<Message, _>>| match msg {
+        Some(Ok(Message::Close(Some(frame)))) => frame.code,
+        msg => panic!("expect a close frame, got {msg:?}"),
+    };
+
+    // text messages are transformed, and rejected by the hook
+    let mut request = "ws://127.0.0.1:9464/ws".into_client_request().expect("invalid request");
+    request.headers_mut().insert("sec-websocket-extensions", "permessage-deflate".parse().expect("invalid header"));
+    let (mut stream, _resp) = tokio_tungstenite::connect_async(request).await.expect("fail to connect");
+    stream.send(Message::Text("my secret".into())).await.expect("fail to send");
+    assert_eq!(stream.next().await.expect("no message").expect("fail to receive").to_text().expect("not text"), "my ******");
+    stream.send(Message::Text("subscribe admin".into())).await.expect("fail to send");
+    assert_eq!(close_code(stream.next().await), CloseCode::Policy);
+
+    // messages are limited in size
+    let (mut stream, _resp) = tokio_tungstenite::connect_async("ws://127.0.0.1:9464/ws").await.expect("fail to connect");
+    stream.send(Message::Binary(vec![0u8; 1024].into())).await.expect("fail to send");
+    assert_eq!(close_code(stream.next().await), CloseCode::Size);
+
+    // idle connections are closed
+    let (mut stream, _resp) = tokio_tungstenite::connect_async("ws://127.0.0.1:9464/ws").await.expect("fail to connect");
+    let msg = tokio::time::timeout(Duration::from_secs(2), stream.next()).await.expect("idle connection should be closed");
+    assert_eq!(close_code(msg), CloseCode::Away);
+    cancel.cancel();
+}
@@ -16,6 +16,9 @@ pub const GATEWAY_ANNOTATION_LOG_LEVEL: &str = "log_level";
 pub const GATEWAY_ANNOTATION_LANGUAGE: &str = "lang";
 pub const GATEWAY_ANNOTATION_IGNORE_TLS_VERIFICATION: &str = "ignore_tls_verification";
 pub const GATEWAY_ANNOTATION_ENABLE_X_REQUEST_ID: &str = "enable_x_request_id";
+pub const GATEWAY_ANNOTATION_MAX_REQUEST_BODY_SIZE: &str = "max_request_body_size";
+pub const GATEWAY_ANNOTATION_MAX_RESPONSE_BODY_SIZE: &str = "max_response_body_size";
+pub const GATEWAY_ANNOTATION_ROUTE_PRECEDENCE: &str = "route_precedence";
 pub const GATEWAY_ANNOTATION_OTEL_ENABLED: &str = "spacegate.io/otel-enabled";
 pub const GATEWAY_ANNOTATION_OTEL_SERVICE_NAME: &str = "spacegate.io/otel-service-name";
 pub const GATEWAY_ANNOTATION_OTEL_ENDPOINT: &str = "spacegate.io/otel-endpoint";
@@ -173,6 +173,8 @@ pub struct HttpRouteRule {
 
     pub timeout_ms: Option<u32>,
     pub timeout_mode: Option<crate::TimeoutMode>,
+    pub connect_timeout_ms: Option<u32>,
+    pub first_byte_timeout_ms: Option<u32>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
@@ -226,6 +228,8 @@ pub struct BackendRef {
 
     pub timeout_ms: Option<u32>,
     pub timeout_mode: Option<crate::TimeoutMode>,
+    pub connect_timeout_ms: Option<u32>,
+    pub first_byte_timeout_ms: Option<u32>,
 
     /// whether to downgrade http2 to http1.1
     pub downgrade_http2: Option<bool>,
@@ -271,6 +275,8 @@ impl From<HttpRoute> for HttpSpaceroute {
                                             },
                                             downgrade_http2: None,
                                             timeout_mode: None,
+                                            connect_timeout_ms: None,
+                                            first_byte_timeout_ms: None,
                                         }),
                                         filters: http_backend_ref.filters,
                                     })
@@ -278,6 +284,8 @@ impl From<HttpRoute> for HttpSpaceroute {
                             }),
                             timeout_ms: None,
                             timeout_mode: None,
+                            connect_timeout_ms: None,
+                            first_byte_timeout_ms: None,
                         })
                         .collect()
                 }),
@@ -27,6 +27,8 @@ pub struct McpRouteSpec {
     pub backend_refs: Vec<HttpBackendRef>,
     #[serde(default = "default_mcp_timeout_mode")]
     pub timeout_mode: McpTimeoutMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u32>,
     #[serde(default)]
     pub session_affinity: McpSessionAffinity,
 }
@@ -8,7 +8,7 @@ use super::{observability::ObservabilityConfig, plugin::PluginBinding};
 /// by binding Listeners to a set of IP addresses.
 ///
 /// Reference: [Kubernetes Gateway](https://gateway-api.sigs.k8s.io/references/spec/#gateway.networking.k8s.io/v1beta1.Gateway)
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
 #[serde(default)]
 /// Name of the Gateway. Global Unique.
@@ -47,10 +47,30 @@ impl<P> SgGateway<P> {
             plugins: self.plugins.into_iter().map(f).collect(),
         }
     }
+
+    /// Check if two gateways are the same except for the tls certificates and keys of listeners.
+    pub fn eq_ignore_tls_material(&self, other: &Self) -> bool
+    where
+        P: PartialEq,
+    {
+        fn strip(listeners: &[SgListener]) -> Vec<SgListener> {
+            listeners
+                .iter()
+                .cloned()
+                .map(|mut listener| {
+                    if let SgProtocolConfig::Https { ref mut tls } = listener.protocol {
+                        *tls = tls.without_material();
+                    }
+                    listener
+                })
+                .collect()
+        }
+        self.name == other.name && self.parameters == other.parameters && self.plugins == other.plugins && strip(&self.listeners) == strip(&other.listeners)
+    }
 }
 
 /// Gateway parameter configuration.
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
 #[serde(default)]
 pub struct SgParameters {
@@ -69,11 +89,56 @@ pub struct SgParameters {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Add request id for every request
     pub enable_x_request_id: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Default max request body size in bytes, larger requests are rejected with 413
+    pub max_request_body_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Default max response body size in bytes
+    pub max_response_body_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// How http routes take precedence over each other, default is by priority
+    pub route_precedence: Option<SgRoutePrecedence>,
     pub observability: ObservabilityConfig,
 }
 
+/// How http routes take precedence over each other when more than one matches a request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "snake_case")]
+pub enum SgRoutePrecedence {
+    /// By the priority of routes, then the order of rules and matches.
+    #[default]
+    Priority,
+    /// By the priority of routes, then ties are broken as the Gateway API specifies:
+    /// exact path over prefix, longest prefix, method, most headers and most query params, then the route name.
+    ///
+    /// Reference: [HTTPRouteRule](https://gateway-api.sigs.k8s.io/reference/spec/#gateway.networking.k8s.io/v1.HTTPRouteRule)
+    GatewayApi,
+}
+
+impl Display for SgRoutePrecedence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SgRoutePrecedence::Priority => write!(f, "priority"),
+            SgRoutePrecedence::GatewayApi => write!(f, "gateway_api"),
+        }
+    }
+}
+
+impl std::str::FromStr for SgRoutePrecedence {
+    type Err = crate::BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "priority" => Ok(SgRoutePrecedence::Priority),
+            "gateway_api" => Ok(SgRoutePrecedence::GatewayApi),
+            _ => Err(format!("invalid route precedence: {s}").into()),
+        }
+    }
+}
+
 /// Listener embodies the concept of a logical endpoint where a Gateway accepts network connections.
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
 #[serde(default)]
 pub struct SgListener {
@@ -89,6 +154,69 @@ pub struct SgListener {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// `HostName` is used to define the host on which the listener accepts requests.
     pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Accept PROXY protocol headers from layer 4 load balancers, so that the address of the original client is used as the peer address.
+    pub proxy_protocol: Option<SgProxyProtocol>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Limit the connections of the listener, and how they are closed.
+    pub connection_limits: Option<SgConnectionLimits>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// Custom tcp services on the port, which are registered by libraries and tried in order before the service of the protocol.
+    ///
+    /// A connection is served by the first service recognizing its first bytes.
+    pub tcp_services: Vec<SgTcpService>,
+}
+
+/// A custom tcp service of a listener, created by the factory registered with the code.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgTcpService {
+    /// Code of the registered factory, e.g. `socks5`.
+    pub code: String,
+    /// Spec passed to the factory.
+    #[serde(default)]
+    pub spec: serde_json::Value,
+}
+
+/// PROXY protocol (v1 and v2) of a listener.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgProxyProtocol {
+    /// CIDRs of the load balancers which send the PROXY protocol header, e.g. `10.0.0.0/8`, it must not be empty.
+    ///
+    /// Connections from other sources are served as is.
+    #[serde(default)]
+    pub trusted_cidrs: Vec<String>,
+}
+
+/// Connection limits and timeouts of a listener.
+///
+/// The connection limits and the drain timeout only cover tcp connections, http3 connections aren't limited nor drained,
+/// and udp sessions are limited by `max_udp_sessions` only.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(default)]
+pub struct SgConnectionLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Max concurrent connections of the listener, new connections beyond it are closed at once. It can't be 0, default is unlimited.
+    pub max_connections: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Max concurrent connections from one client ip. It can't be 0, default is unlimited.
+    pub max_connections_per_ip: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Close the http/1 connection if the request header is not received in time, including the next request of a keep-alive connection.
+    ///
+    /// It only applies to http/1, http/2 connections are not covered, their idle connections are closed by `keep_alive_timeout_ms`.
+    pub header_read_timeout_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Close the http connection if there is no request in flight for this period.
+    pub keep_alive_timeout_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// How long to wait for the connections to finish when the gateway is shut down or restarted. Default is 10 seconds.
+    pub drain_timeout_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Max concurrent client sessions of a udp listener, the least recently active session is closed for a new client beyond it. It can't be 0, default is 10000.
+    pub max_udp_sessions: Option<usize>,
 }
 
 #[non_exhaustive]
@@ -130,6 +258,10 @@ pub enum SgProtocolConfig {
         /// This field is required if the Protocol field is “HTTPS” or “TLS”. It is invalid to set this field if the Protocol field is “HTTP”, “TCP”, or “UDP”.
         tls: SgTlsConfig,
     },
+    /// Accepts TCP connections which are forwarded by `TCPRoute` as raw streams. It's not supported by the k8s config backend yet.
+    Tcp,
+    /// Accepts UDP datagrams which are forwarded by `UDPRoute`. It's not supported by the k8s config backend yet.
+    Udp,
 }
 
 impl Display for SgProtocolConfig {
@@ -137,6 +269,8 @@ impl Display for SgProtocolConfig {
         match self {
             SgProtocolConfig::Http => write!(f, "http"),
             SgProtocolConfig::Https { .. } => write!(f, "https"),
+            SgProtocolConfig::Tcp => write!(f, "tcp"),
+            SgProtocolConfig::Udp => write!(f, "udp"),
         }
     }
 }
@@ -146,9 +280,101 @@ impl Display for SgProtocolConfig {
 #[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
 pub struct SgTlsConfig {
     pub mode: SgTlsMode,
+    #[serde(default)]
     pub key: String,
+    #[serde(default)]
     pub cert: String,
+    /// Path of the PEM file of `key`, it takes precedence over `key`, and the file will be watched by the file config backend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<String>,
+    /// Path of the PEM file of `cert`, it takes precedence over `cert`, and the file will be watched by the file config backend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_path: Option<String>,
     pub http2: Option<bool>,
+    /// Serve HTTP/3 on the same port over UDP, and advertise it by the `Alt-Svc` header of https responses.
+    ///
+    /// It only works in `Terminate` mode, and the gateway should be built with the `http3` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http3: Option<bool>,
+    /// Certificates selected by the SNI of the client, the `key` and `cert` above will be the default one when no certificate matches.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub certs: Vec<SgTlsCertificate>,
+    /// Authenticate clients by their certificates, aka mutual tls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_auth: Option<SgTlsClientAuth>,
+}
+
+/// Client certificate authentication of a tls listener.
+#[derive(Debug, Serialize, PartialEq, Eq, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgTlsClientAuth {
+    #[serde(default)]
+    pub mode: SgTlsClientAuthMode,
+    /// PEM bundle of the CAs which issue client certificates.
+    #[serde(default)]
+    pub ca: String,
+    /// Path of the PEM file of `ca`, it takes precedence over `ca`.
+    ///
+    /// The file is watched like the certificates, the gateway restarts when its content changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Deserialize, Clone, Default, Eq, Copy)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "lowercase")]
+pub enum SgTlsClientAuthMode {
+    /// Reject the clients without a valid certificate.
+    #[default]
+    Require,
+    /// Accept the clients without certificate, but reject the ones with an invalid certificate.
+    Optional,
+}
+
+/// A certificate served for a set of hostnames.
+#[derive(Debug, Serialize, PartialEq, Eq, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgTlsCertificate {
+    /// Hostnames to be matched against the SNI, wildcard like `*.example.com` is supported.
+    pub hostnames: Vec<String>,
+    #[serde(default)]
+    pub key: String,
+    #[serde(default)]
+    pub cert: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_path: Option<String>,
+}
+
+impl SgTlsConfig {
+    /// Paths of the PEM files referenced by this config.
+    pub fn pem_paths(&self) -> impl Iterator<Item = &str> {
+        [&self.key_path, &self.cert_path]
+            .into_iter()
+            .chain(self.certs.iter().flat_map(|cert| [&cert.key_path, &cert.cert_path]))
+            .chain(self.client_auth.iter().map(|auth| &auth.ca_path))
+            .filter_map(|path| path.as_deref())
+    }
+
+    /// A copy of this config without certificates, keys and client CAs.
+    pub fn without_material(&self) -> Self {
+        Self {
+            mode: self.mode,
+            http2: self.http2,
+            http3: self.http3,
+            key: String::new(),
+            cert: String::new(),
+            key_path: None,
+            cert_path: None,
+            certs: Vec::new(),
+            client_auth: self.client_auth.as_ref().map(|auth| SgTlsClientAuth {
+                mode: auth.mode,
+                ca: String::new(),
+                ca_path: None,
+            }),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, PartialEq, Deserialize, Clone, Default, Eq, Copy)]
@@ -12,6 +12,9 @@ use super::{gateway::SgBackendProtocol, PluginBinding};
 #[serde(untagged)]
 pub enum SgRoute<P = PluginBinding> {
     Mcp(SgMcpRoute<P>),
+    Tls(SgTlsRoute),
+    Tcp(SgTcpRoute),
+    Udp(SgUdpRoute),
     Http(SgHttpRoute<P>),
 }
 
@@ -19,6 +22,9 @@ impl<P> SgRoute<P> {
     pub fn route_name(&self) -> &str {
         match self {
             SgRoute::Mcp(route) => &route.route_name,
+            SgRoute::Tls(route) => &route.route_name,
+            SgRoute::Tcp(route) => &route.route_name,
+            SgRoute::Udp(route) => &route.route_name,
             SgRoute::Http(route) => &route.route_name,
         }
     }
@@ -29,6 +35,9 @@ impl<P> SgRoute<P> {
     {
         match self {
             SgRoute::Mcp(route) => SgRoute::Mcp(route.map_plugins(&mut f)),
+            SgRoute::Tls(route) => SgRoute::Tls(route),
+            SgRoute::Tcp(route) => SgRoute::Tcp(route),
+            SgRoute::Udp(route) => SgRoute::Udp(route),
             SgRoute::Http(route) => SgRoute::Http(route.map_plugins(&mut f)),
         }
     }
@@ -72,6 +81,9 @@ impl Default for SgMcpTransport {
 #[serde(rename_all = "snake_case")]
 pub enum TimeoutMode {
     Request,
+    // the timeout is the longest idle time between body frames, the deadline resets each time a frame of the request or the response flows,
+    // it's for streaming responses like server-sent events
+    Idle,
     Disabled,
 }
 
@@ -109,6 +121,48 @@ pub enum SgBalancePolicy {
     Random,
     IpHash,
     McpSession,
+    /// Assign requests by a key into buckets sized by the backend weights, so a user stays on one version.
+    Canary(SgCanaryPolicy),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(default)]
+pub struct SgCanaryPolicy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Key defines where the key of a request is read from, requests without the key are assigned randomly by weight.
+    pub key: Option<SgCanaryKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// OverrideHeader defines a header to force the backend, its value is the `host:port` or the index of a backend.
+    pub override_header: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// StickyCookie defines a cookie to keep the session on the assigned backend, its value is the `host:port` of the backend.
+    pub sticky_cookie: Option<SgStickyCookie>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SgCanaryKey {
+    Header {
+        name: String,
+    },
+    Cookie {
+        name: String,
+    },
+    /// A claim of the JWT in the `Authorization: Bearer` header, the token is not verified.
+    JwtClaim {
+        claim: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgStickyCookie {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// MaxAge of the cookie in seconds, it's a session cookie if it's absent.
+    pub max_age_s: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -139,6 +193,9 @@ pub struct SgMcpRoute<P = PluginBinding> {
     pub plugins: Vec<P>,
     #[serde(default = "default_mcp_timeout_mode")]
     pub timeout_mode: McpTimeoutMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Timeout of the requests in milliseconds, it takes effect unless the timeout mode is `disabled`.
+    pub timeout_ms: Option<u32>,
     #[serde(default)]
     pub session_affinity: McpSessionAffinity,
 }
@@ -158,11 +215,110 @@ impl<P> SgMcpRoute<P> {
             backends: self.backends.into_iter().map(|backend| backend.map_plugins(&mut f)).collect(),
             plugins: self.plugins.into_iter().map(&mut f).collect(),
             timeout_mode: self.timeout_mode,
+            timeout_ms: self.timeout_ms,
             session_affinity: self.session_affinity,
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub enum SgTlsRouteKind {
+    #[serde(rename = "TLSRoute")]
+    TlsRoute,
+}
+
+/// TLSRoute routes TLS connections by SNI to backends, the connections are passed through without being terminated.
+///
+/// It works on the listeners of `https` protocol in `Passthrough` mode.
+///
+/// Reference: [Kubernetes Gateway](https://gateway-api.sigs.k8s.io/reference/spec/#gateway.networking.k8s.io/v1alpha2.TLSRoute)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgTlsRoute {
+    pub kind: SgTlsRouteKind,
+    /// Route name
+    pub route_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Hostnames to be matched against the SNI, wildcard like `*.example.com` is supported.
+    ///
+    /// If it's empty, all connections will be matched, including those without SNI.
+    pub hostnames: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backends: Vec<SgStreamBackendRef>,
+    #[serde(default = "default_tls_route_priority")]
+    /// Rule priority, the route of higher priority will be chosen.
+    pub priority: i16,
+}
+
+fn default_tls_route_priority() -> i16 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub enum SgTcpRouteKind {
+    #[serde(rename = "TCPRoute")]
+    TcpRoute,
+}
+
+/// TCPRoute forwards TCP connections to backends as raw streams.
+///
+/// It works on the listeners of `tcp` protocol. It's not supported by the k8s config backend yet, use the file or redis config backend for it.
+///
+/// Reference: [Kubernetes Gateway](https://gateway-api.sigs.k8s.io/reference/spec/#gateway.networking.k8s.io/v1alpha2.TCPRoute)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgTcpRoute {
+    pub kind: SgTcpRouteKind,
+    /// Route name
+    pub route_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Name of the listener this route attaches to, like the `sectionName` of a parent reference.
+    ///
+    /// If it's empty, the route attaches to all `tcp` listeners which no other route attaches to by name.
+    pub listener: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backends: Vec<SgStreamBackendRef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Close the connection if no data is transferred in either direction for this period, in milliseconds.
+    ///
+    /// If it's empty, the connection is never closed for being idle, and it must not be 0.
+    pub idle_timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub enum SgUdpRouteKind {
+    #[serde(rename = "UDPRoute")]
+    UdpRoute,
+}
+
+/// UDPRoute forwards UDP datagrams to backends, the datagrams from the same client are kept in one session with the same backend.
+///
+/// It works on the listeners of `udp` protocol. It's not supported by the k8s config backend yet, use the file or redis config backend for it.
+///
+/// Reference: [Kubernetes Gateway](https://gateway-api.sigs.k8s.io/reference/spec/#gateway.networking.k8s.io/v1alpha2.UDPRoute)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgUdpRoute {
+    pub kind: SgUdpRouteKind,
+    /// Route name
+    pub route_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Name of the listener this route attaches to, like the `sectionName` of a parent reference.
+    ///
+    /// If it's empty, the route attaches to all `udp` listeners which no other route attaches to by name.
+    pub listener: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backends: Vec<SgStreamBackendRef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Close the session of a client if no datagram is transferred in either direction for this period, in milliseconds.
+    ///
+    /// If it's empty, it's 60 seconds, and it must not be 0.
+    pub idle_timeout_ms: Option<u64>,
+}
+
 /// HTTPRoute provides a way to route HTTP requests.
 ///
 /// Reference: [Kubernetes Gateway](https://gateway-api.sigs.k8s.io/references/spec/#gateway.networking.k8s.io%2fv1beta1.HTTPRoute)
@@ -232,6 +388,12 @@ pub struct SgHttpRouteRule<P = PluginBinding> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout_mode: Option<TimeoutMode>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    /// Timeout of connecting the backends, it's the default of the backends in this rule.
+    pub connect_timeout_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Timeout of waiting for the response head, regardless of the timeout mode.
+    pub first_byte_timeout_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub balance_policy: Option<SgBalancePolicy>,
 }
 
@@ -246,6 +408,8 @@ impl<P> SgHttpRouteRule<P> {
             backends: self.backends.into_iter().map(|backend| backend.map_plugins(&mut f)).collect(),
             timeout_ms: self.timeout_ms,
             timeout_mode: self.timeout_mode,
+            connect_timeout_ms: self.connect_timeout_ms,
+            first_byte_timeout_ms: self.first_byte_timeout_ms,
             balance_policy: self.balance_policy,
         }
     }
@@ -259,6 +423,8 @@ impl<P> Default for SgHttpRouteRule<P> {
             backends: Default::default(),
             timeout_ms: Default::default(),
             timeout_mode: Default::default(),
+            connect_timeout_ms: Default::default(),
+            first_byte_timeout_ms: Default::default(),
             balance_policy: Default::default(),
         }
     }
@@ -280,6 +446,12 @@ pub struct SgBackendRef<P = PluginBinding> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout_mode: Option<TimeoutMode>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    /// Timeout of connecting the referenced backend.
+    pub connect_timeout_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Timeout of waiting for the response head from the referenced backend, regardless of the timeout mode.
+    pub first_byte_timeout_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     // Protocol specifies the protocol used to talk to the referenced backend.
     pub protocol: Option<SgBackendProtocol>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -297,6 +469,12 @@ pub struct SgBackendRef<P = PluginBinding> {
     /// # Notice!
     /// this field is ordered, the first plugin will be the outermost plugin.
     pub plugins: Vec<P>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// HealthCheck defines how to actively probe the referenced backend, unhealthy backends will be skipped by the balancer.
+    pub health_check: Option<SgHealthCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Tls defines how to connect the referenced backend over https, the certificate of backend is verified by the system trust roots if it's absent.
+    pub tls: Option<SgBackendTls>,
 }
 
 impl<P> SgBackendRef<P> {
@@ -309,10 +487,14 @@ impl<P> SgBackendRef<P> {
             port: self.port,
             timeout_ms: self.timeout_ms,
             timeout_mode: self.timeout_mode,
+            connect_timeout_ms: self.connect_timeout_ms,
+            first_byte_timeout_ms: self.first_byte_timeout_ms,
             protocol: self.protocol,
             downgrade_http2: self.downgrade_http2,
             weight: self.weight,
             plugins: self.plugins.into_iter().map(f).collect(),
+            health_check: self.health_check,
+            tls: self.tls,
         }
     }
 
@@ -328,14 +510,116 @@ impl<P> Default for SgBackendRef<P> {
             port: Default::default(),
             timeout_ms: Default::default(),
             timeout_mode: Default::default(),
+            connect_timeout_ms: Default::default(),
+            first_byte_timeout_ms: Default::default(),
             downgrade_http2: Default::default(),
             protocol: Default::default(),
             weight: Default::default(),
             plugins: Default::default(),
+            health_check: Default::default(),
+            tls: Default::default(),
+        }
+    }
+}
+
+/// BackendTls defines the tls settings used to connect a backend.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(default)]
+pub struct SgBackendTls {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// PEM bundle of the CAs which issue the backend certificate, it replaces the system trust roots.
+    pub ca: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Path of the PEM file of `ca`, it takes precedence over `ca`.
+    pub ca_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// PEM encoded client certificate chain, presented to backends which require mutual tls.
+    pub cert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Path of the PEM file of `cert`, it takes precedence over `cert`.
+    pub cert_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// PEM encoded private key of the client certificate.
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Path of the PEM file of `key`, it takes precedence over `key`.
+    pub key_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Server name sent by SNI and verified against the backend certificate, the backend host is used if it's absent.
+    pub sni: Option<String>,
+    /// Verify the backend certificate, default is true.
+    pub verify: bool,
+}
+
+impl Default for SgBackendTls {
+    fn default() -> Self {
+        Self {
+            ca: None,
+            ca_path: None,
+            cert: None,
+            cert_path: None,
+            key: None,
+            key_path: None,
+            sni: None,
+            verify: true,
+        }
+    }
+}
+
+/// A backend of stream routes, the connections are forwarded as raw bytes.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub struct SgStreamBackendRef {
+    /// Name is the kubernetes service name OR url host.
+    pub host: BackendHost,
+    /// Port specifies the destination port number to use for this resource.
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Weight specifies the proportion of connections forwarded to the referenced backend.
+    pub weight: Option<u16>,
+}
+
+/// HealthCheck defines an active health check for a backend.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(default)]
+pub struct SgHealthCheck {
+    /// Probe defines how to check the backend.
+    pub probe: SgHealthCheckProbe,
+    /// Interval between two probes, in milliseconds.
+    pub interval_ms: u32,
+    /// Timeout of a single probe, in milliseconds.
+    pub timeout_ms: u32,
+    /// Consecutive successes required to mark an unhealthy backend healthy, it can't be 0.
+    pub healthy_threshold: u32,
+    /// Consecutive failures required to mark a healthy backend unhealthy, it can't be 0.
+    pub unhealthy_threshold: u32,
+}
+
+impl Default for SgHealthCheck {
+    fn default() -> Self {
+        Self {
+            probe: Default::default(),
+            interval_ms: 10000,
+            timeout_ms: 3000,
+            healthy_threshold: 2,
+            unhealthy_threshold: 3,
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+#[serde(tag = "kind")]
+pub enum SgHealthCheckProbe {
+    /// Send a `GET` request to the path, a `2xx` response means healthy.
+    Http { path: String },
+    /// Try to open a tcp connection.
+    #[default]
+    Tcp,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
 pub struct K8sServiceData {
@@ -23,6 +23,13 @@ pub enum SgHttpPathMatch {
         #[serde(skip_serializing_if = "Option::is_none")]
         replace: Option<String>,
     },
+    /// Matches if the URL path matches the template with named parameters, e.g. `/users/{id}`, with case sensitivity.
+    /// Each parameter takes a whole path segment, the parameters are exposed to plugins, and could be used in the replacement, e.g. `/v2/users/{id}`.
+    Template {
+        value: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        replace: Option<String>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -45,6 +52,16 @@ pub enum SgHttpHeaderMatch {
     },
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub enum SgHttpCookieMatch {
+    /// Matches the cookie value exactly and with case sensitivity.
+    Exact { name: String, value: String },
+    /// Matches if the cookie value matches the given regular expression with case sensitivity.
+    RegExp { name: String, re: String },
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "kind", content = "value", rename_all = "snake_case")]
 #[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
@@ -55,6 +72,30 @@ pub enum SgHttpQueryMatch {
     Regular { key: String, re: String },
 }
 
+/// GRPCMethodMatch describes how to select a gRPC route by matching the gRPC request's service and method.
+///
+/// gRPC requests are matched by the `/{service}/{method}` path and the `application/grpc` content type.
+/// At least one of `service` and `method` should be specified, an omitted one matches all.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "PascalCase")]
+#[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
+pub enum SgGrpcMethodMatch {
+    /// Matches the service and method exactly and with case sensitivity, e.g. service `helloworld.Greeter` and method `SayHello`.
+    Exact {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        service: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        method: Option<String>,
+    },
+    /// Matches if the service and method match the given regular expressions with case sensitivity.
+    RegExp {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        service: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        method: Option<String>,
+    },
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "typegen", derive(ts_rs::TS), ts(export))]
 #[serde(transparent)]
@@ -83,4 +124,23 @@ pub struct SgHttpRouteMatch {
     /// Method specifies HTTP method matcher.
     /// When specified, this route will be matched only if the request has the specified method.
     pub method: Option<Vec<SgHttpMethodMatch>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Grpc specifies a gRPC service and method matcher.
+    /// When specified, this route will be matched only by gRPC requests of the service and method.
+    pub grpc: Option<SgGrpcMethodMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Cookie specifies HTTP cookie matchers.
+    /// Multiple match values are ANDed together, meaning, a request must match all the specified cookies to select the route.
+    pub cookie: Option<Vec<SgHttpCookieMatch>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// SourceIp specifies the CIDRs or IPs of clients, e.g. `10.0.0.0/8`.
+    /// When specified, this route will be matched only if the client ip is in any of them.
+    /// The client ip is the peer address of the connection, or the source in the PROXY protocol header from a trusted source, forwarded headers are not trusted.
+    pub source_ip: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Not specifies a match which the request must not match.
+    pub not: Option<Box<SgHttpRouteMatch>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Any specifies matches which the request must match at least one of.
+    pub any: Option<Vec<SgHttpRouteMatch>>,
 }
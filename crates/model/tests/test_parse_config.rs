@@ -1,4 +1,4 @@
-use spacegate_model::{Config, McpSessionAffinity, McpTimeoutMode, SgMcpTransport, SgRoute};
+use spacegate_model::{Config, McpSessionAffinity, McpTimeoutMode, SgMcpTransport, SgProtocolConfig, SgRoute};
 
 #[test]
 fn test_parse_config() {
@@ -151,6 +151,24 @@ fn local_otel_json_config_shape_can_be_parsed() {
     assert!(gateway.routes.contains_key("root"));
 }
 
+#[test]
+fn gateway_body_size_limits_can_be_parsed() {
+    let file = r#"
+{
+  "name": "local",
+  "parameters": {
+    "max_request_body_size": 10485760
+  },
+  "listeners": []
+}
+"#;
+
+    let gateway = serde_json::from_str::<spacegate_model::SgGateway>(file).expect("parse gateway with body size limits");
+
+    assert_eq!(gateway.parameters.max_request_body_size, Some(10 * 1024 * 1024));
+    assert_eq!(gateway.parameters.max_response_body_size, None);
+}
+
 #[test]
 fn legacy_http_route_without_kind_is_wrapped_as_http_route() {
     let file = r#"
@@ -177,7 +195,7 @@ fn legacy_http_route_without_kind_is_wrapped_as_http_route() {
 
     match route {
         SgRoute::Http(route) => assert_eq!(route.route_name, "root"),
-        SgRoute::Mcp(_) => panic!("legacy HTTP route should parse as SgRoute::Http"),
+        SgRoute::Mcp(_) | SgRoute::Tls(_) | SgRoute::Tcp(_) | SgRoute::Udp(_) => panic!("legacy HTTP route should parse as SgRoute::Http"),
     }
 }
 
@@ -224,6 +242,219 @@ fn mcp_route_parses_with_streamable_defaults() {
             assert_eq!(route.session_affinity, McpSessionAffinity::McpSession);
             assert_eq!(route.backends.len(), 1);
         }
-        SgRoute::Http(_) => panic!("MCPRoute should parse as SgRoute::Mcp"),
+        SgRoute::Http(_) | SgRoute::Tls(_) | SgRoute::Tcp(_) | SgRoute::Udp(_) => panic!("MCPRoute should parse as SgRoute::Mcp"),
+    }
+}
+
+#[test]
+fn tls_route_parses_as_tls_variant() {
+    let file = r#"
+{
+  "gateways": {
+    "local": {
+      "gateway": {
+        "name": "local",
+        "listeners": []
+      },
+      "routes": {
+        "tls": {
+          "kind": "TLSRoute",
+          "route_name": "tls",
+          "hostnames": ["*.example.com"],
+          "backends": [
+            {
+              "host": {
+                "kind": "Host",
+                "host": "127.0.0.1"
+              },
+              "port": 8443
+            }
+          ]
+        }
+      }
+    }
+  }
+}
+"#;
+
+    let config = serde_json::from_str::<Config>(file).expect("parse tls route");
+    let route = config.gateways["local"].routes.get("tls").expect("tls route");
+
+    match route {
+        SgRoute::Tls(route) => {
+            assert_eq!(route.route_name, "tls");
+            assert_eq!(route.priority, 1);
+            assert_eq!(route.backends.len(), 1);
+        }
+        SgRoute::Http(_) | SgRoute::Mcp(_) | SgRoute::Tcp(_) | SgRoute::Udp(_) => panic!("TLSRoute should parse as SgRoute::Tls"),
+    }
+}
+
+#[test]
+fn stream_routes_parse_as_tcp_and_udp_variants() {
+    let file = r#"
+{
+  "gateways": {
+    "local": {
+      "gateway": {
+        "name": "local",
+        "listeners": [
+          { "name": "postgres", "port": 5432, "protocol": { "type": "tcp" } },
+          { "name": "dns", "port": 53, "protocol": { "type": "udp" } }
+        ]
+      },
+      "routes": {
+        "postgres": {
+          "kind": "TCPRoute",
+          "route_name": "postgres",
+          "listener": "postgres",
+          "idle_timeout_ms": 600000,
+          "backends": [
+            { "host": { "kind": "Host", "host": "10.0.0.1" }, "port": 5432, "weight": 2 },
+            { "host": { "kind": "Host", "host": "10.0.0.2" }, "port": 5432 }
+          ]
+        },
+        "dns": {
+          "kind": "UDPRoute",
+          "route_name": "dns",
+          "backends": [
+            { "host": { "kind": "Host", "host": "10.0.0.53" }, "port": 53 }
+          ]
+        }
+      }
+    }
+  }
+}
+"#;
+
+    let config = serde_json::from_str::<Config>(file).expect("parse stream routes");
+    let item = &config.gateways["local"];
+    assert_eq!(item.gateway.listeners[0].protocol, SgProtocolConfig::Tcp);
+    assert_eq!(item.gateway.listeners[1].protocol, SgProtocolConfig::Udp);
+
+    match item.routes.get("postgres").expect("tcp route") {
+        SgRoute::Tcp(route) => {
+            assert_eq!(route.listener.as_deref(), Some("postgres"));
+            assert_eq!(route.idle_timeout_ms, Some(600000));
+            assert_eq!(route.backends.len(), 2);
+            assert_eq!(route.backends[0].weight, Some(2));
+        }
+        _ => panic!("TCPRoute should parse as SgRoute::Tcp"),
+    }
+    match item.routes.get("dns").expect("udp route") {
+        SgRoute::Udp(route) => {
+            assert_eq!(route.listener, None);
+            assert_eq!(route.backends.len(), 1);
+        }
+        _ => panic!("UDPRoute should parse as SgRoute::Udp"),
+    }
+}
+
+#[test]
+fn listener_tcp_services_parse_with_spec() {
+    let listener = r#"
+{
+  "name": "http",
+  "port": 80,
+  "protocol": { "type": "http" },
+  "tcp_services": [
+    { "code": "socks5", "spec": { "auth": false } },
+    { "code": "banner" }
+  ]
+}
+"#;
+
+    let listener = serde_json::from_str::<spacegate_model::SgListener>(listener).expect("parse listener");
+    assert_eq!(listener.tcp_services.len(), 2);
+    assert_eq!(listener.tcp_services[0].code, "socks5");
+    assert_eq!(listener.tcp_services[0].spec["auth"], false);
+    assert!(listener.tcp_services[1].spec.is_null());
+    assert!(!serde_json::to_string(&spacegate_model::SgListener::default()).expect("serialize listener").contains("tcp_services"));
+}
+
+#[test]
+fn grpc_method_match_parses_service_and_method() {
+    let route_match = r#"
+{
+  "grpc": { "kind": "Exact", "service": "helloworld.Greeter", "method": "SayHello" }
+}
+"#;
+
+    let route_match = serde_json::from_str::<spacegate_model::SgHttpRouteMatch>(route_match).expect("parse route match");
+    match route_match.grpc {
+        Some(spacegate_model::SgGrpcMethodMatch::Exact { service, method }) => {
+            assert_eq!(service.as_deref(), Some("helloworld.Greeter"));
+            assert_eq!(method.as_deref(), Some("SayHello"));
+        }
+        _ => panic!("grpc match should parse as exact"),
+    }
+    let route_match = serde_json::from_str::<spacegate_model::SgHttpRouteMatch>(r#"{ "grpc": { "kind": "RegExp", "service": "^helloworld\\." } }"#).expect("parse route match");
+    assert!(matches!(route_match.grpc, Some(spacegate_model::SgGrpcMethodMatch::RegExp { method: None, .. })));
+}
+
+#[test]
+fn idle_timeout_mode_parses_with_connect_and_first_byte_timeouts() {
+    let rule = r#"
+{
+  "timeout_ms": 30000,
+  "timeout_mode": "idle",
+  "connect_timeout_ms": 1000,
+  "first_byte_timeout_ms": 10000,
+  "backends": [{ "host": { "kind": "Host", "host": "127.0.0.1" }, "connect_timeout_ms": 500 }]
+}
+"#;
+
+    let rule = serde_json::from_str::<spacegate_model::SgHttpRouteRule>(rule).expect("parse route rule");
+    assert_eq!(rule.timeout_mode, Some(spacegate_model::TimeoutMode::Idle));
+    assert_eq!(rule.connect_timeout_ms, Some(1000));
+    assert_eq!(rule.first_byte_timeout_ms, Some(10000));
+    assert_eq!(rule.backends[0].connect_timeout_ms, Some(500));
+    assert_eq!(rule.backends[0].first_byte_timeout_ms, None);
+}
+
+#[test]
+fn cookie_source_ip_template_and_combinator_matches_parse() {
+    let route_match = r#"
+{
+  "path": { "kind": "Template", "value": "/users/{id}", "replace": "/v2/users/{id}" },
+  "cookie": [{ "kind": "exact", "name": "canary", "value": "true" }],
+  "source_ip": ["10.0.0.0/8", "192.168.1.1"],
+  "not": { "method": ["DELETE"] },
+  "any": [{ "header": [{ "kind": "exact", "name": "x-tenant", "value": "a" }] }, { "cookie": [{ "kind": "reg_exp", "name": "tenant", "re": "^a" }] }]
+}
+"#;
+
+    let route_match = serde_json::from_str::<spacegate_model::SgHttpRouteMatch>(route_match).expect("parse route match");
+    assert!(matches!(route_match.path, Some(spacegate_model::SgHttpPathMatch::Template { ref value, .. }) if value == "/users/{id}"));
+    assert!(matches!(route_match.cookie.as_deref(), Some([spacegate_model::SgHttpCookieMatch::Exact { name, .. }]) if name == "canary"));
+    assert_eq!(route_match.source_ip.as_ref().map(Vec::len), Some(2));
+    assert!(route_match.not.is_some_and(|not| not.method.is_some()));
+    assert_eq!(route_match.any.as_ref().map(Vec::len), Some(2));
+    assert!(!serde_json::to_string(&spacegate_model::SgHttpRouteMatch::default()).expect("serialize route match").contains("cookie"));
+}
+
+#[test]
+fn canary_balance_policy_parses() {
+    let rule = r#"
+{
+  "backends": [{ "host": { "kind": "Host", "host": "stable" }, "weight": 90 }, { "host": { "kind": "Host", "host": "canary" }, "weight": 10 }],
+  "balance_policy": {
+    "canary": {
+      "key": { "kind": "jwt_claim", "claim": "sub" },
+      "override_header": "x-canary",
+      "sticky_cookie": { "name": "sg-canary", "max_age_s": 3600 }
     }
+  }
+}
+"#;
+
+    let rule = serde_json::from_str::<spacegate_model::SgHttpRouteRule>(rule).expect("parse route rule");
+    let Some(spacegate_model::SgBalancePolicy::Canary(canary)) = rule.balance_policy else {
+        panic!("balance policy should be canary");
+    };
+    assert_eq!(canary.key, Some(spacegate_model::SgCanaryKey::JwtClaim { claim: "sub".to_string() }));
+    assert_eq!(canary.override_header.as_deref(), Some("x-canary"));
+    assert_eq!(canary.sticky_cookie.and_then(|cookie| cookie.max_age_s), Some(3600));
+    let policy = serde_json::from_str::<spacegate_model::SgBalancePolicy>(r#""ip_hash""#).expect("parse balance policy");
+    assert_eq!(policy, spacegate_model::SgBalancePolicy::IpHash);
 }
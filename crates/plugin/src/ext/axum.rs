@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+#[cfg(feature = "schema")]
 use serde::{Deserialize, Serialize};
 use spacegate_ext_axum::{
     axum::{self, extract::Query, Router},
@@ -23,6 +24,11 @@ pub async fn register_plugin_routes() {
             {
                 router = router.route("/plugin-schema", axum::routing::get(plugin_schema));
             }
+            #[cfg(feature = "status")]
+            {
+                use crate::plugins::status::server;
+                router = router.route("/plugin-status", axum::routing::get(server::mono_status_page)).route("/plugin-status/{name}", axum::routing::get(server::status_page));
+            }
             router
         })
         .await
@@ -16,6 +16,7 @@ fn redis_format_key(req: &Request<SgBody>, matched: &MatchedSgRouter, header: &H
             HttpPathMatchRewrite::Exact(path, _) => path,
             HttpPathMatchRewrite::Prefix(path, _) => path,
             HttpPathMatchRewrite::RegExp(regex, _) => regex.as_str(),
+            HttpPathMatchRewrite::Template(template, _) => template.as_str(),
         })
         .unwrap_or("*");
     let header = req.headers().get(header).and_then(|v| v.to_str().ok())?;
@@ -263,8 +263,10 @@ impl PluginRepository {
         self.register::<plugins::limit::RateLimitPlugin>();
         #[cfg(feature = "redirect")]
         self.register::<plugins::redirect::RedirectPlugin>();
-        // #[cfg(feature = "retry")]
-        // self.register::<plugins::retry::RetryPlugin>();
+        #[cfg(feature = "retry")]
+        self.register::<plugins::retry::RetryPlugin>();
+        #[cfg(feature = "breaker")]
+        self.register::<plugins::breaker::BreakerPlugin>();
         #[cfg(feature = "header-modifier")]
         self.register::<plugins::header_modifier::HeaderModifierPlugin>();
         #[cfg(feature = "inject")]
@@ -273,10 +275,10 @@ impl PluginRepository {
         self.register::<plugins::rewrite::RewritePlugin>();
         #[cfg(feature = "maintenance")]
         self.register::<plugins::maintenance::MaintenancePlugin>();
-        // #[cfg(feature = "status")]
-        // self.register::<plugins::status::StatusPlugin>();
-        // #[cfg(feature = "decompression")]
-        // self.register::<plugins::decompression::DecompressionPlugin>();
+        #[cfg(feature = "status")]
+        self.register::<plugins::status::StatusPlugin>();
+        #[cfg(feature = "compression")]
+        self.register::<plugins::compression::CompressionPlugin>();
         #[cfg(feature = "set-version")]
         self.register::<plugins::set_version::SetVersionPlugin>();
         #[cfg(feature = "set-scheme")]
@@ -290,6 +292,14 @@ impl PluginRepository {
         }
         #[cfg(feature = "east-west-traffic-white-list")]
         self.register::<plugins::east_west_traffic_white_list::EastWestTrafficWhiteListPlugin>();
+        #[cfg(feature = "body-limit")]
+        self.register::<plugins::body_limit::BodyLimitPlugin>();
+        #[cfg(feature = "grpc-web")]
+        self.register::<plugins::grpc_web::GrpcWebPlugin>();
+        #[cfg(feature = "websocket")]
+        self.register::<plugins::websocket::WebSocketPlugin>();
+        #[cfg(feature = "mirror")]
+        self.register::<plugins::mirror::MirrorPlugin>();
     }
 
     /// create a new empty repository
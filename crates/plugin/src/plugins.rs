@@ -1,5 +1,11 @@
-// #[cfg(feature = "decompression")]
-// pub mod decompression;
+#[cfg(feature = "body-limit")]
+pub mod body_limit;
+#[cfg(feature = "breaker")]
+pub mod breaker;
+#[cfg(feature = "compression")]
+pub mod compression;
+#[cfg(feature = "grpc-web")]
+pub mod grpc_web;
 #[cfg(feature = "header-modifier")]
 pub mod header_modifier;
 #[cfg(feature = "inject")]
@@ -8,14 +14,16 @@ pub mod inject;
 pub mod limit;
 #[cfg(feature = "maintenance")]
 pub mod maintenance;
+#[cfg(feature = "mirror")]
+pub mod mirror;
 #[cfg(feature = "redirect")]
 pub mod redirect;
-// #[cfg(feature = "retry")]
-// pub mod retry;
+#[cfg(feature = "retry")]
+pub mod retry;
 #[cfg(feature = "rewrite")]
 pub mod rewrite;
-// #[cfg(feature = "status")]
-// pub mod status;
+#[cfg(feature = "status")]
+pub mod status;
 
 #[cfg(feature = "east-west-traffic-white-list")]
 pub mod east_west_traffic_white_list;
@@ -24,3 +32,5 @@ pub mod set_scheme;
 #[cfg(feature = "set-version")]
 pub mod set_version;
 pub mod static_resource;
+#[cfg(feature = "websocket")]
+pub mod websocket;
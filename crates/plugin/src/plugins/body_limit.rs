@@ -0,0 +1,99 @@
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use spacegate_kernel::{helper_layers::function::Inner, utils::BodySizeLimit, BoxError, SgBody};
+
+use crate::Plugin;
+
+/// Request bodies over the limit are rejected with `413 Payload Too Large`, even if they're chunked.
+/// Response bodies over the limit are replaced with `502 Bad Gateway` if the length is known, or aborted while streaming.
+///
+/// Bodies are never buffered for the limit.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(title = "请求体大小限制插件配置"))]
+#[serde(default)]
+pub struct BodyLimitPluginConfig {
+    /// max request body size in bytes
+    #[cfg_attr(feature = "schema", schemars(title = "最大请求体大小"))]
+    pub max_request_body_size: Option<u64>,
+    /// max response body size in bytes
+    #[cfg_attr(feature = "schema", schemars(title = "最大响应体大小"))]
+    pub max_response_body_size: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BodyLimitPlugin {
+    pub limit: BodySizeLimit,
+}
+
+impl Plugin for BodyLimitPlugin {
+    const CODE: &'static str = "body-limit";
+
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        Ok(self.limit.limit(req, inner).await)
+    }
+
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let config = serde_json::from_value::<BodyLimitPluginConfig>(config.spec)?;
+        Ok(Self {
+            limit: BodySizeLimit::new(config.max_request_body_size, config.max_response_body_size),
+        })
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
+    }
+}
+
+#[cfg(feature = "schema")]
+crate::schema!(BodyLimitPlugin, BodyLimitPluginConfig);
+
+#[cfg(test)]
+mod test {
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::{body::Frame, service::service_fn, StatusCode};
+    use spacegate_kernel::ArcHyperService;
+
+    use super::*;
+
+    fn echo() -> Inner {
+        Inner::new(ArcHyperService::new(service_fn(|req: Request<SgBody>| async move {
+            Ok(match req.into_body().dump().await {
+                Ok(body) => Response::new(body),
+                Err(e) => Response::builder().status(StatusCode::BAD_GATEWAY).body(SgBody::full(e.to_string())).expect("invalid response"),
+            })
+        })))
+    }
+
+    fn chunked(size: usize) -> SgBody {
+        let chunks = (0..size / 10).map(|_| Ok::<_, BoxError>(Frame::data(hyper::body::Bytes::from_static(b"0123456789"))));
+        SgBody::new(StreamBody::new(futures_util::stream::iter(chunks)))
+    }
+
+    #[tokio::test]
+    async fn limit_body_size() {
+        let plugin = BodyLimitPlugin::create(crate::PluginConfig::new(
+            crate::PluginInstanceId::new(BodyLimitPlugin::CODE, crate::PluginInstanceName::mono()),
+            serde_json::json!({ "max_request_body_size": 50, "max_response_body_size": 30 }),
+        ))
+        .expect("fail to create plugin");
+
+        let req = Request::builder().header("content-length", "100").body(SgBody::full(vec![b'0'; 100])).expect("invalid request");
+        let resp = plugin.call(req, echo()).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let resp = plugin.call(Request::new(chunked(100)), echo()).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let resp = plugin.call(Request::new(chunked(20)), echo()).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.into_body().collect().await.expect("within limit").to_bytes().len(), 20);
+
+        // the echoed body is over the response limit
+        let resp = plugin.call(Request::new(chunked(40)), echo()).await.expect("infallible");
+        assert!(resp.into_body().collect().await.is_err());
+    }
+}
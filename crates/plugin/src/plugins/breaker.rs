@@ -0,0 +1,442 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+use spacegate_kernel::{
+    extension::{BackendHost, RouteName},
+    helper_layers::function::Inner,
+    BoxError, SgBody,
+};
+
+use crate::{error::code, Plugin, PluginError};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(title = "熔断插件配置"))]
+#[serde(default)]
+pub struct BreakerPluginConfig {
+    /// open the circuit after this many consecutive failures, 0 to disable
+    #[cfg_attr(feature = "schema", schemars(title = "连续失败次数"))]
+    pub consecutive_failures: u32,
+    /// open the circuit when failures / requests in the window reaches this ratio, 0 to disable
+    #[cfg_attr(feature = "schema", schemars(title = "错误率阈值"))]
+    pub error_ratio: f64,
+    /// minimum requests in the window before the error ratio is checked
+    #[cfg_attr(feature = "schema", schemars(title = "最小请求数"))]
+    pub min_requests: u32,
+    /// statistic window in milliseconds
+    #[cfg_attr(feature = "schema", schemars(title = "统计窗口(毫秒)"))]
+    pub window_ms: u64,
+    /// how long the circuit stays open before it turns half-open, in milliseconds
+    #[cfg_attr(feature = "schema", schemars(title = "熔断时长(毫秒)"))]
+    pub cool_down_ms: u64,
+    /// trial requests allowed while half-open, the circuit closes after all of them succeed
+    #[cfg_attr(feature = "schema", schemars(title = "半开试探请求数"))]
+    pub half_open_requests: u32,
+    /// share the open state among gateway nodes through redis
+    #[cfg_attr(feature = "schema", schemars(title = "通过Redis共享状态"))]
+    pub shared: bool,
+}
+
+impl Default for BreakerPluginConfig {
+    fn default() -> Self {
+        Self {
+            consecutive_failures: 5,
+            error_ratio: 0.5,
+            min_requests: 20,
+            window_ms: 10_000,
+            cool_down_ms: 30_000,
+            half_open_requests: 1,
+            shared: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitState {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { in_flight: u32, successes: u32 },
+}
+
+#[derive(Debug, Clone)]
+struct Circuit {
+    state: CircuitState,
+    window_start: Instant,
+    requests: u32,
+    failures: u32,
+    consecutive_failures: u32,
+}
+
+impl Circuit {
+    fn new(now: Instant) -> Self {
+        Self {
+            state: CircuitState::Closed,
+            window_start: now,
+            requests: 0,
+            failures: 0,
+            consecutive_failures: 0,
+        }
+    }
+    fn reset_counters(&mut self, now: Instant) {
+        self.window_start = now;
+        self.requests = 0;
+        self.failures = 0;
+        self.consecutive_failures = 0;
+    }
+}
+
+/// Circuit states of all backends behind a breaker.
+///
+/// Circuits are keyed by [`BackendHost`] if the breaker is mounted on backends.
+/// On a route the backend is not picked yet, so all backends of the route share one circuit keyed by the route name,
+/// one failing backend opens the circuit of the whole route, and on a gateway all requests share one circuit.
+/// The breaker warns once if it's not mounted on backends.
+#[derive(Debug, Clone)]
+pub struct Circuits {
+    config: Arc<BreakerPluginConfig>,
+    circuits: Arc<Mutex<HashMap<Arc<str>, Circuit>>>,
+}
+
+impl Circuits {
+    pub fn new(config: BreakerPluginConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            circuits: Default::default(),
+        }
+    }
+
+    /// Try to let a request pass, returns `None` if the circuit is open.
+    ///
+    /// The result should be reported by the permit, a permit dropped without a report counts as a failure if it's a half-open trial.
+    pub fn acquire(&self, key: &Arc<str>, now: Instant) -> Option<CircuitPermit> {
+        self.try_acquire(key, now).map(|trial| CircuitPermit {
+            circuits: self.clone(),
+            key: key.clone(),
+            trial,
+            reported: false,
+        })
+    }
+
+    /// Returns whether the request is a half-open trial, or `None` if the circuit is open.
+    fn try_acquire(&self, key: &Arc<str>, now: Instant) -> Option<bool> {
+        let mut circuits = self.circuits.lock().expect("failed to lock circuits");
+        let circuit = circuits.entry(key.clone()).or_insert_with(|| Circuit::new(now));
+        match circuit.state {
+            CircuitState::Closed => Some(false),
+            CircuitState::Open { until } if now >= until => {
+                circuit.state = CircuitState::HalfOpen { in_flight: 1, successes: 0 };
+                Some(true)
+            }
+            CircuitState::Open { .. } => None,
+            CircuitState::HalfOpen { in_flight, successes } => {
+                if in_flight + successes < self.config.half_open_requests.max(1) {
+                    circuit.state = CircuitState::HalfOpen {
+                        in_flight: in_flight + 1,
+                        successes,
+                    };
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Report the result of a request, returns `true` if the circuit turns open.
+    fn report(&self, key: &Arc<str>, success: bool, trial: bool, now: Instant) -> bool {
+        let config = &self.config;
+        let mut circuits = self.circuits.lock().expect("failed to lock circuits");
+        let circuit = circuits.entry(key.clone()).or_insert_with(|| Circuit::new(now));
+        let open_until = now + Duration::from_millis(config.cool_down_ms);
+        match circuit.state {
+            CircuitState::Closed => {
+                if now.duration_since(circuit.window_start) > Duration::from_millis(config.window_ms) {
+                    let consecutive_failures = circuit.consecutive_failures;
+                    circuit.reset_counters(now);
+                    circuit.consecutive_failures = consecutive_failures;
+                }
+                circuit.requests += 1;
+                if success {
+                    circuit.consecutive_failures = 0;
+                } else {
+                    circuit.failures += 1;
+                    circuit.consecutive_failures += 1;
+                }
+                let too_many_consecutive = config.consecutive_failures > 0 && circuit.consecutive_failures >= config.consecutive_failures;
+                let ratio_exceeded =
+                    config.error_ratio > 0.0 && circuit.requests >= config.min_requests.max(1) && f64::from(circuit.failures) / f64::from(circuit.requests) >= config.error_ratio;
+                if too_many_consecutive || ratio_exceeded {
+                    circuit.state = CircuitState::Open { until: open_until };
+                    circuit.reset_counters(now);
+                    true
+                } else {
+                    false
+                }
+            }
+            // requests sent while the circuit was closed are not trials
+            CircuitState::HalfOpen { .. } if !trial => false,
+            CircuitState::HalfOpen { in_flight, successes } => {
+                if success {
+                    let successes = successes + 1;
+                    if successes >= config.half_open_requests.max(1) {
+                        circuit.state = CircuitState::Closed;
+                        circuit.reset_counters(now);
+                    } else {
+                        circuit.state = CircuitState::HalfOpen {
+                            in_flight: in_flight.saturating_sub(1),
+                            successes,
+                        };
+                    }
+                    false
+                } else {
+                    circuit.state = CircuitState::Open { until: open_until };
+                    true
+                }
+            }
+            // requests sent before the circuit opened
+            CircuitState::Open { .. } => false,
+        }
+    }
+}
+
+/// A request passing the breaker, a half-open trial holds its slot until it's reported or dropped.
+#[derive(Debug)]
+pub struct CircuitPermit {
+    circuits: Circuits,
+    key: Arc<str>,
+    trial: bool,
+    reported: bool,
+}
+
+impl CircuitPermit {
+    /// Report the result of the request, returns `true` if the circuit turns open.
+    pub fn report(mut self, success: bool, now: Instant) -> bool {
+        self.reported = true;
+        self.circuits.report(&self.key, success, self.trial, now)
+    }
+}
+
+impl Drop for CircuitPermit {
+    fn drop(&mut self) {
+        if self.reported || !self.trial {
+            return;
+        }
+        // the trial request is cancelled, e.g. the client disconnected or it timed out
+        let mut circuits = self.circuits.circuits.lock().expect("failed to lock circuits");
+        if let Some(circuit) = circuits.get_mut(&self.key) {
+            if matches!(circuit.state, CircuitState::HalfOpen { .. }) {
+                circuit.state = CircuitState::Open {
+                    until: Instant::now() + Duration::from_millis(self.circuits.config.cool_down_ms),
+                };
+            }
+        }
+    }
+}
+
+/// Any key for requests whose backend is unknown when passing the breaker.
+const UNKNOWN_BACKEND: &str = "*";
+
+#[derive(Debug, Clone)]
+pub struct BreakerPlugin {
+    pub circuits: Circuits,
+    pub unknown_backend: Arc<str>,
+    /// whether it has warned that it's not mounted on backends
+    pub shared_circuit_warned: Arc<AtomicBool>,
+    #[cfg(feature = "redis")]
+    pub redis_prefix: String,
+}
+
+impl BreakerPlugin {
+    fn is_failure(resp: &Response<SgBody>) -> bool {
+        // connect errors and timeouts are reported as 502 and 504 by the backend service
+        resp.status().is_server_error()
+    }
+    #[cfg(feature = "redis")]
+    async fn is_shared_open(&self, req: &Request<SgBody>, key: &str) -> bool {
+        use spacegate_ext_redis::redis::AsyncCommands;
+        use spacegate_kernel::SgRequestExt;
+        let Some(client) = req.get_redis_client_by_gateway_name() else {
+            return false;
+        };
+        let mut conn = client.get_conn().await;
+        conn.exists(format!("{}:{key}", self.redis_prefix)).await.unwrap_or_else(|e| {
+            tracing::warn!("[Sg.Plugin.Breaker] failed to read shared state: {e}");
+            false
+        })
+    }
+    #[cfg(feature = "redis")]
+    async fn share_open(&self, client: Option<spacegate_ext_redis::RedisClient>, key: &str) {
+        use spacegate_ext_redis::redis::AsyncCommands;
+        let Some(client) = client else {
+            return;
+        };
+        let mut conn = client.get_conn().await;
+        let result: Result<(), _> = conn.pset_ex(format!("{}:{key}", self.redis_prefix), 1, self.circuits.config.cool_down_ms).await;
+        if let Err(e) = result {
+            tracing::warn!("[Sg.Plugin.Breaker] failed to write shared state: {e}");
+        }
+    }
+}
+
+impl Plugin for BreakerPlugin {
+    const CODE: &'static str = "breaker";
+    fn meta() -> spacegate_model::PluginMetaData {
+        crate::plugin_meta!(
+            description: "Circuit breaker plugin, it's recommended to mount it on backends."
+        )
+    }
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        let key = match (req.extensions().get::<BackendHost>(), req.extensions().get::<RouteName>()) {
+            (Some(host), _) => host.0.clone(),
+            (None, Some(route)) => format!("route:{}", route.0).into(),
+            (None, None) => self.unknown_backend.clone(),
+        };
+        if req.extensions().get::<BackendHost>().is_none() && !self.shared_circuit_warned.swap(true, Ordering::Relaxed) {
+            tracing::warn!(circuit = %key, "[Sg.Plugin.Breaker] not mounted on backends, all backends share one circuit, one failing backend opens it for all of them");
+        }
+        #[cfg(feature = "redis")]
+        if self.circuits.config.shared && self.is_shared_open(&req, &key).await {
+            return Ok(PluginError::status::<Self, { code::SERVICE_UNAVAILABLE }>(format!("circuit of backend {key} is open")).into());
+        }
+        let Some(permit) = self.circuits.acquire(&key, Instant::now()) else {
+            return Ok(PluginError::status::<Self, { code::SERVICE_UNAVAILABLE }>(format!("circuit of backend {key} is open")).into());
+        };
+        #[cfg(feature = "redis")]
+        let redis_client = {
+            use spacegate_kernel::SgRequestExt;
+            req.get_redis_client_by_gateway_name()
+        };
+        let resp = inner.call(req).await;
+        if permit.report(!Self::is_failure(&resp), Instant::now()) {
+            tracing::warn!(backend = %key, "[Sg.Plugin.Breaker] circuit opened");
+            #[cfg(feature = "redis")]
+            if self.circuits.config.shared {
+                self.share_open(redis_client, &key).await;
+            }
+        }
+        Ok(resp)
+    }
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let spec = serde_json::from_value::<BreakerPluginConfig>(config.spec)?;
+        if !(0.0..=1.0).contains(&spec.error_ratio) {
+            return Err("error_ratio should be in range [0, 1]".into());
+        }
+        Ok(Self {
+            circuits: Circuits::new(spec),
+            unknown_backend: UNKNOWN_BACKEND.into(),
+            shared_circuit_warned: Default::default(),
+            #[cfg(feature = "redis")]
+            redis_prefix: config.id.redis_prefix(),
+        })
+    }
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
+    }
+}
+
+#[cfg(feature = "schema")]
+crate::schema!(BreakerPlugin, BreakerPluginConfig);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn circuits() -> Circuits {
+        Circuits::new(BreakerPluginConfig {
+            consecutive_failures: 3,
+            error_ratio: 0.5,
+            min_requests: 10,
+            window_ms: 10_000,
+            cool_down_ms: 1_000,
+            half_open_requests: 1,
+            shared: false,
+        })
+    }
+
+    #[test]
+    fn open_on_consecutive_failures_and_recover() {
+        let circuits = circuits();
+        let key: Arc<str> = "backend".into();
+        let now = Instant::now();
+        for _ in 0..2 {
+            assert!(!circuits.acquire(&key, now).expect("closed").report(false, now));
+        }
+        assert!(circuits.acquire(&key, now).expect("closed").report(false, now));
+        assert!(circuits.acquire(&key, now).is_none());
+
+        // half open, only one trial request
+        let later = now + Duration::from_millis(1_000);
+        let trial = circuits.acquire(&key, later).expect("half open");
+        assert!(circuits.acquire(&key, later).is_none());
+        assert!(!trial.report(true, later));
+        assert!(circuits.acquire(&key, later).is_some());
+    }
+
+    #[test]
+    fn open_on_error_ratio() {
+        let circuits = circuits();
+        let key: Arc<str> = "backend".into();
+        let now = Instant::now();
+        let mut opened = false;
+        for index in 0..10 {
+            opened = circuits.acquire(&key, now).expect("closed").report(index % 2 == 0, now);
+        }
+        assert!(opened);
+        assert!(circuits.acquire(&key, now).is_none());
+    }
+
+    #[test]
+    fn half_open_failure_reopens() {
+        let circuits = circuits();
+        let key: Arc<str> = "backend".into();
+        let now = Instant::now();
+        for _ in 0..3 {
+            circuits.acquire(&key, now).expect("closed").report(false, now);
+        }
+        let later = now + Duration::from_millis(1_000);
+        assert!(circuits.acquire(&key, later).expect("half open").report(false, later));
+        assert!(circuits.acquire(&key, later).is_none());
+    }
+
+    #[test]
+    fn dropped_trial_releases_half_open() {
+        let circuits = circuits();
+        let key: Arc<str> = "backend".into();
+        let now = Instant::now();
+        for _ in 0..3 {
+            circuits.acquire(&key, now).expect("closed").report(false, now);
+        }
+        // the trial request is cancelled, it counts as a failure
+        drop(circuits.acquire(&key, now + Duration::from_millis(1_000)).expect("half open"));
+        let later = Instant::now() + Duration::from_millis(1_000);
+        assert!(!circuits.acquire(&key, later).expect("half open again").report(true, later));
+        assert!(circuits.acquire(&key, later).is_some());
+    }
+
+    #[test]
+    fn dropped_closed_permit_keeps_half_open() {
+        let circuits = circuits();
+        let key: Arc<str> = "backend".into();
+        let now = Instant::now();
+        let slow = circuits.acquire(&key, now).expect("closed");
+        for _ in 0..3 {
+            circuits.acquire(&key, now).expect("closed").report(false, now);
+        }
+        let later = now + Duration::from_millis(1_000);
+        let trial = circuits.acquire(&key, later).expect("half open");
+        // the request sent while closed is cancelled during the trial
+        drop(slow);
+        assert!(!trial.report(true, later));
+        assert!(circuits.acquire(&key, later).is_some());
+    }
+}
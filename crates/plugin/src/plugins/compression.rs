@@ -0,0 +1,498 @@
+//! Compress responses according to the request's accept encoding, and decompress compressed request bodies.
+//!
+//! Both directions work on the body stream, so the whole body is never buffered.
+//!
+//! To let other plugins inspect decompressed request bodies, mount this plugin with a higher priority than them.
+//!
+//! The `ETag` of a compressed response is weakened, since the compressed bytes differ from the original representation.
+//!
+//! see also:
+//! - https://developer.mozilla.org/zh-CN/docs/Web/HTTP/Headers/Accept-Encoding
+//! - https://developer.mozilla.org/zh-CN/docs/Web/HTTP/Headers/Content-Encoding
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use async_compression::{
+    tokio::bufread::{BrotliDecoder, BrotliEncoder, DeflateDecoder, DeflateEncoder, GzipDecoder, GzipEncoder, ZstdDecoder, ZstdEncoder},
+    Level,
+};
+use futures_util::TryStreamExt;
+use http_body_util::{BodyDataStream, StreamBody};
+use hyper::{
+    body::{Body, Frame},
+    header::{HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, VARY},
+    HeaderMap, Method, Request, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use spacegate_kernel::{helper_layers::function::Inner, BoxError, SgBody, SgResponseExt};
+use tokio::io::AsyncRead;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::Plugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+    #[serde(alias = "brotli")]
+    Br,
+    Zstd,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Br => "br",
+            Encoding::Zstd => "zstd",
+        }
+    }
+    pub fn from_coding(coding: &str) -> Option<Self> {
+        [Encoding::Gzip, Encoding::Deflate, Encoding::Br, Encoding::Zstd].into_iter().find(|encoding| coding.eq_ignore_ascii_case(encoding.as_str())).or_else(|| {
+            // `x-gzip` should be treated as `gzip`, see rfc9110 section 8.4.1.3
+            coding.eq_ignore_ascii_case("x-gzip").then_some(Encoding::Gzip)
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionLevel {
+    Fastest,
+    #[default]
+    Default,
+    Best,
+}
+
+impl From<CompressionLevel> for Level {
+    fn from(value: CompressionLevel) -> Self {
+        match value {
+            CompressionLevel::Fastest => Level::Fastest,
+            CompressionLevel::Default => Level::Default,
+            CompressionLevel::Best => Level::Best,
+        }
+    }
+}
+
+const DEFAULT_MAX_DECOMPRESSED_SIZE: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(title = "压缩插件配置"))]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Encodings used to compress responses, the former is preferred when the client accepts several of them equally.
+    #[cfg_attr(feature = "schema", schemars(title = "压缩算法"))]
+    pub encodings: Vec<Encoding>,
+    #[cfg_attr(feature = "schema", schemars(title = "压缩级别"))]
+    pub level: CompressionLevel,
+    /// Responses smaller than this (in bytes) won't be compressed, responses with unknown size are always compressed.
+    #[cfg_attr(feature = "schema", schemars(title = "最小压缩大小(字节)"))]
+    pub min_size: u64,
+    /// Content types to be compressed, `type/*` matches all subtypes.
+    #[cfg_attr(feature = "schema", schemars(title = "压缩内容类型"))]
+    pub content_types: Vec<String>,
+    #[cfg_attr(feature = "schema", schemars(title = "压缩响应"))]
+    pub compress_response: bool,
+    /// Decompress request bodies encoded by gzip, deflate, br or zstd.
+    #[cfg_attr(feature = "schema", schemars(title = "解压请求"))]
+    pub decompress_request: bool,
+    /// Max size (in bytes) of a decompressed request body, the request is rejected with `413 Payload Too Large` beyond it.
+    /// Default is 10 MiB, `null` means unlimited.
+    #[cfg_attr(feature = "schema", schemars(title = "最大解压大小(字节)"))]
+    pub max_decompressed_size: Option<u64>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            encodings: vec![Encoding::Zstd, Encoding::Br, Encoding::Gzip],
+            level: CompressionLevel::default(),
+            min_size: 1024,
+            content_types: [
+                "text/*",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "application/wasm",
+                "image/svg+xml",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            compress_response: true,
+            decompress_request: true,
+            max_decompressed_size: Some(DEFAULT_MAX_DECOMPRESSED_SIZE),
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Pick an encoding from the `Accept-Encoding` headers, by the quality value and then by the configured order.
+    pub fn negotiate(&self, headers: &HeaderMap) -> Option<Encoding> {
+        let mut picked: Option<(Encoding, u16)> = None;
+        for encoding in &self.encodings {
+            let Some(quality) = accept_quality(headers, encoding.as_str()) else {
+                continue;
+            };
+            let better = match picked {
+                Some((_, picked_quality)) => quality > picked_quality,
+                None => quality > 0,
+            };
+            if better {
+                picked = Some((*encoding, quality));
+            }
+        }
+        picked.map(|(encoding, _)| encoding)
+    }
+
+    fn is_compressible_type(&self, content_type: &str) -> bool {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        self.content_types.iter().any(|allowed| match allowed.strip_suffix('*') {
+            Some(prefix) => essence.get(..prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(prefix)),
+            None => essence.eq_ignore_ascii_case(allowed),
+        })
+    }
+
+    fn should_compress(&self, resp: &Response<SgBody>) -> bool {
+        let status = resp.status();
+        if status.is_informational() || status == hyper::StatusCode::NO_CONTENT || status == hyper::StatusCode::NOT_MODIFIED || status == hyper::StatusCode::PARTIAL_CONTENT {
+            return false;
+        }
+        let headers = resp.headers();
+        if headers.contains_key(CONTENT_ENCODING) || headers.contains_key(CONTENT_RANGE) {
+            return false;
+        }
+        if headers.get_all(CACHE_CONTROL).iter().filter_map(|v| v.to_str().ok()).flat_map(|v| v.split(',')).any(|directive| directive.trim().eq_ignore_ascii_case("no-transform")) {
+            return false;
+        }
+        if !headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).is_some_and(|content_type| self.is_compressible_type(content_type)) {
+            return false;
+        }
+        let size = resp.body().size_hint().exact().or_else(|| headers.get(CONTENT_LENGTH).and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<u64>().ok()));
+        !matches!(size, Some(size) if size < self.min_size)
+    }
+}
+
+/// Add `Vary: accept-encoding` to the headers, unless it's already there.
+fn vary_by_accept_encoding(headers: &mut HeaderMap) {
+    let varied = headers.get_all(VARY).iter().filter_map(|v| v.to_str().ok()).flat_map(|v| v.split(',')).any(|field| {
+        let field = field.trim();
+        field == "*" || field.eq_ignore_ascii_case("accept-encoding")
+    });
+    if !varied {
+        headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    }
+}
+
+/// Quality value of the coding in `Accept-Encoding`, in thousandths. `None` if the coding is not mentioned.
+fn accept_quality(headers: &HeaderMap, coding: &str) -> Option<u16> {
+    let mut wildcard = None;
+    for item in headers.get_all(ACCEPT_ENCODING).iter().filter_map(|v| v.to_str().ok()).flat_map(|v| v.split(',')) {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let quality = params
+            .filter_map(|param| param.trim().strip_prefix("q=").or_else(|| param.trim().strip_prefix("Q=")))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .map(|q| (q.clamp(0.0, 1.0) * 1000.0) as u16)
+            .unwrap_or(1000);
+        if name.eq_ignore_ascii_case(coding) {
+            return Some(quality);
+        } else if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    wildcard
+}
+
+type BoxAsyncRead = Pin<Box<dyn AsyncRead + Send + Sync>>;
+
+fn body_reader(body: SgBody) -> StreamReader<impl futures_util::Stream<Item = io::Result<hyper::body::Bytes>> + Send + Sync, hyper::body::Bytes> {
+    StreamReader::new(BodyDataStream::new(body).map_err(io::Error::other))
+}
+
+fn reader_body(reader: BoxAsyncRead) -> SgBody {
+    SgBody::new(StreamBody::new(ReaderStream::new(reader).map_ok(Frame::data)))
+}
+
+/// Compress the body stream.
+pub fn encode_body(body: SgBody, encoding: Encoding, level: Level) -> SgBody {
+    let reader = body_reader(body);
+    let encoded: BoxAsyncRead = match encoding {
+        Encoding::Gzip => Box::pin(GzipEncoder::with_quality(reader, level)),
+        Encoding::Deflate => Box::pin(DeflateEncoder::with_quality(reader, level)),
+        Encoding::Br => Box::pin(BrotliEncoder::with_quality(reader, level)),
+        Encoding::Zstd => Box::pin(ZstdEncoder::with_quality(reader, level)),
+    };
+    reader_body(encoded)
+}
+
+/// Decompress the body stream.
+pub fn decode_body(body: SgBody, encoding: Encoding) -> SgBody {
+    let reader = body_reader(body);
+    let decoded: BoxAsyncRead = match encoding {
+        Encoding::Gzip => Box::pin(GzipDecoder::new(reader)),
+        Encoding::Deflate => Box::pin(DeflateDecoder::new(reader)),
+        Encoding::Br => Box::pin(BrotliDecoder::new(reader)),
+        Encoding::Zstd => Box::pin(ZstdDecoder::new(reader)),
+    };
+    reader_body(decoded)
+}
+
+#[derive(Debug, Clone)]
+pub struct CompressionPlugin {
+    pub config: Arc<CompressionConfig>,
+}
+
+impl CompressionPlugin {
+    /// Decompress the request body, the returned flag is set once the decompressed body exceeds the limit.
+    fn decompress_request(&self, req: Request<SgBody>) -> (Request<SgBody>, Option<Arc<AtomicBool>>) {
+        // only a single known coding could be decoded, otherwise leave the request to the backend
+        let Some(encoding) = req.headers().get(CONTENT_ENCODING).and_then(|v| v.to_str().ok()).and_then(|v| Encoding::from_coding(v.trim())) else {
+            return (req, None);
+        };
+        let (mut parts, body) = req.into_parts();
+        parts.headers.remove(CONTENT_ENCODING);
+        parts.headers.remove(CONTENT_LENGTH);
+        let body = decode_body(body, encoding);
+        match self.config.max_decompressed_size {
+            Some(limit) => {
+                let (body, exceeded) = body.limited(limit);
+                (Request::from_parts(parts, body), Some(exceeded))
+            }
+            None => (Request::from_parts(parts, body), None),
+        }
+    }
+
+    fn compress_response(&self, resp: Response<SgBody>, encoding: Encoding) -> Response<SgBody> {
+        let (mut parts, body) = resp.into_parts();
+        parts.headers.remove(CONTENT_LENGTH);
+        parts.headers.remove(ACCEPT_RANGES);
+        parts.headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+        // the encoded bytes differ from the original ones, so the strong validator doesn't hold anymore
+        if let Some(etag) = parts.headers.get(ETAG).filter(|etag| !etag.as_bytes().starts_with(b"W/")) {
+            let mut weak = b"W/".to_vec();
+            weak.extend_from_slice(etag.as_bytes());
+            if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+                parts.headers.insert(ETAG, weak);
+            }
+        }
+        Response::from_parts(parts, encode_body(body, encoding, self.config.level.into()))
+    }
+}
+
+impl Plugin for CompressionPlugin {
+    const CODE: &'static str = "compression";
+    fn meta() -> spacegate_model::PluginMetaData {
+        crate::plugin_meta!(
+            description: "Compress responses with gzip, brotli or zstd, and decompress compressed requests."
+        )
+    }
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        let config = &self.config;
+        let (req, decompressed_exceeded) = if config.decompress_request { self.decompress_request(req) } else { (req, None) };
+        let encoding = if config.compress_response && req.method() != Method::HEAD {
+            config.negotiate(req.headers())
+        } else {
+            None
+        };
+        let resp = inner.call(req).await;
+        if decompressed_exceeded.is_some_and(|exceeded| exceeded.load(Ordering::Acquire)) {
+            let limit = config.max_decompressed_size.unwrap_or_default();
+            return Ok(Response::with_code_message(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("[SG.Plugin.Compression] decompressed request body exceeds the limit of {limit} bytes"),
+            ));
+        }
+        if !config.compress_response || !config.should_compress(&resp) {
+            return Ok(resp);
+        }
+        let mut resp = match encoding {
+            Some(encoding) => self.compress_response(resp, encoding),
+            None => resp,
+        };
+        // the response is compressed or not by the accept-encoding of the request, caches should know it either way
+        vary_by_accept_encoding(resp.headers_mut());
+        Ok(resp)
+    }
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let config = serde_json::from_value::<CompressionConfig>(config.spec)?;
+        Ok(Self { config: Arc::new(config) })
+    }
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
+    }
+}
+
+#[cfg(feature = "schema")]
+crate::schema!(CompressionPlugin, CompressionConfig);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use spacegate_kernel::BoxResult;
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn negotiate_by_quality_and_order() {
+        let config = CompressionConfig::default();
+        assert_eq!(config.negotiate(&accept("gzip, deflate, br, zstd")), Some(Encoding::Zstd));
+        assert_eq!(config.negotiate(&accept("gzip;q=1.0, br;q=0.8")), Some(Encoding::Gzip));
+        assert_eq!(config.negotiate(&accept("*;q=0.5, zstd;q=0")), Some(Encoding::Br));
+        assert_eq!(config.negotiate(&accept("deflate")), None);
+        assert_eq!(config.negotiate(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn content_type_allowlist() {
+        let config = CompressionConfig::default();
+        assert!(config.is_compressible_type("text/html; charset=utf-8"));
+        assert!(config.is_compressible_type("application/json"));
+        assert!(!config.is_compressible_type("image/png"));
+    }
+
+    #[tokio::test]
+    async fn compress_decompress_roundtrip() -> BoxResult<()> {
+        let message = "hello from spacegate ".repeat(128);
+        for encoding in [Encoding::Gzip, Encoding::Deflate, Encoding::Br, Encoding::Zstd] {
+            let encoded = encode_body(SgBody::full(message.clone()), encoding, Level::Default).dump().await?;
+            let encoded = encoded.get_dumped().cloned().unwrap_or_default();
+            assert!(encoded.len() < message.len());
+            let decoded = decode_body(SgBody::full(encoded), encoding).dump().await?;
+            assert_eq!(decoded.get_dumped().map(|b| b.as_ref()), Some(message.as_bytes()));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compress_response_through_plugin() -> BoxResult<()> {
+        let plugin = CompressionPlugin {
+            config: Arc::new(CompressionConfig {
+                min_size: 16,
+                ..Default::default()
+            }),
+        };
+        // echo the request body as plain text
+        let inner = Inner::new(spacegate_kernel::ArcHyperService::new(hyper::service::service_fn(|req: Request<SgBody>| async move {
+            Ok::<_, std::convert::Infallible>(Response::builder().header(CONTENT_TYPE, "text/plain").body(req.into_body()).expect("invalid response"))
+        })));
+        let message = "hello from spacegate ".repeat(8);
+        let req =
+            Request::builder().header(ACCEPT_ENCODING, "gzip").header(CONTENT_ENCODING, "br").body(encode_body(SgBody::full(message.clone()), Encoding::Br, Level::Default))?;
+        let resp = plugin.call(req, inner).await?;
+        assert_eq!(resp.headers().get(CONTENT_ENCODING), Some(&HeaderValue::from_static("gzip")));
+        let body = decode_body(resp.into_body(), Encoding::Gzip).dump().await?;
+        assert_eq!(body.get_dumped().map(|b| b.as_ref()), Some(message.as_bytes()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn vary_compressible_response() -> BoxResult<()> {
+        let plugin = CompressionPlugin {
+            config: Arc::new(CompressionConfig {
+                min_size: 16,
+                ..Default::default()
+            }),
+        };
+        let message = "hello from spacegate ".repeat(8);
+        for (accept_encoding, vary, expected_vary) in [
+            (Some("gzip"), None, vec!["accept-encoding"]),
+            // not compressed for this request, but it would be for others
+            (None, None, vec!["accept-encoding"]),
+            (Some("gzip"), Some("Accept-Encoding"), vec!["Accept-Encoding"]),
+            (None, Some("origin"), vec!["origin", "accept-encoding"]),
+        ] {
+            let message = message.clone();
+            let inner = Inner::new(spacegate_kernel::ArcHyperService::new(hyper::service::service_fn(move |_: Request<SgBody>| {
+                let mut resp = Response::builder().header(CONTENT_TYPE, "text/plain");
+                if let Some(vary) = vary {
+                    resp = resp.header(VARY, vary);
+                }
+                let resp = resp.body(SgBody::full(message.clone())).expect("invalid response");
+                async move { Ok::<_, std::convert::Infallible>(resp) }
+            })));
+            let mut req = Request::builder();
+            if let Some(accept_encoding) = accept_encoding {
+                req = req.header(ACCEPT_ENCODING, accept_encoding);
+            }
+            let resp = plugin.call(req.body(SgBody::empty())?, inner).await?;
+            assert_eq!(resp.headers().contains_key(CONTENT_ENCODING), accept_encoding.is_some());
+            assert_eq!(
+                resp.headers().get_all(VARY).iter().map(|v| v.to_str().expect("invalid vary")).collect::<Vec<_>>(),
+                expected_vary
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn weaken_etag_of_compressed_response() -> BoxResult<()> {
+        let plugin = CompressionPlugin {
+            config: Arc::new(CompressionConfig {
+                min_size: 16,
+                ..Default::default()
+            }),
+        };
+        let message = "hello from spacegate ".repeat(8);
+        for (etag, expected) in [("\"v1\"", "W/\"v1\""), ("W/\"v2\"", "W/\"v2\"")] {
+            let message = message.clone();
+            let inner = Inner::new(spacegate_kernel::ArcHyperService::new(hyper::service::service_fn(move |_: Request<SgBody>| {
+                let message = message.clone();
+                async move {
+                    Ok::<_, std::convert::Infallible>(
+                        Response::builder().header(CONTENT_TYPE, "text/plain").header(ETAG, etag).body(SgBody::full(message)).expect("invalid response"),
+                    )
+                }
+            })));
+            let req = Request::builder().header(ACCEPT_ENCODING, "gzip").body(SgBody::empty())?;
+            let resp = plugin.call(req, inner).await?;
+            assert_eq!(resp.headers().get(ETAG), Some(&HeaderValue::from_static(expected)));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_oversized_decompressed_request() -> BoxResult<()> {
+        let plugin = CompressionPlugin {
+            config: Arc::new(CompressionConfig {
+                max_decompressed_size: Some(1024),
+                ..Default::default()
+            }),
+        };
+        // consume the request body like a backend
+        let inner = Inner::new(spacegate_kernel::ArcHyperService::new(hyper::service::service_fn(|req: Request<SgBody>| async move {
+            let status = match req.into_body().dump().await {
+                Ok(_) => StatusCode::OK,
+                Err(_) => StatusCode::BAD_GATEWAY,
+            };
+            Ok::<_, std::convert::Infallible>(Response::builder().status(status).body(SgBody::empty()).expect("invalid response"))
+        })));
+        let bomb = encode_body(SgBody::full(vec![0u8; 64 * 1024]), Encoding::Gzip, Level::Default).dump().await?;
+        let req = Request::builder().header(CONTENT_ENCODING, "gzip").body(bomb)?;
+        let resp = plugin.call(req, inner.clone()).await?;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let small = encode_body(SgBody::full(vec![0u8; 512]), Encoding::Gzip, Level::Default).dump().await?;
+        let req = Request::builder().header(CONTENT_ENCODING, "gzip").body(small)?;
+        let resp = plugin.call(req, inner).await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        Ok(())
+    }
+}
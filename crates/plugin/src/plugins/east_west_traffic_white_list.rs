@@ -5,7 +5,7 @@ use crate::schema;
 use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use spacegate_kernel::{
-    extension::{IsEastWestTraffic, OriginalIpAddr},
+    extension::{IsEastWestTraffic, OriginalIpAddr, PeerCertificate},
     SgRequestExt as _,
 };
 
@@ -21,10 +21,17 @@ schema!(EastWestTrafficWhiteListPlugin, EastWestTrafficWhiteListConfig);
 pub struct EastWestTrafficWhiteListConfig {
     #[cfg_attr(feature = "schema", schemars(title = "IP白名单列表"))]
     pub ip_list: Vec<String>,
+    /// Identities of client certificates, typed by their prefixes: `cn:`, `subject:`, `dns:`, `uri:`, `email:` or `ip:`,
+    /// e.g. `uri:spiffe://cluster.local/ns/default/sa/client`. An identity without a prefix only matches the dns and uri subject alternative names.
+    ///
+    /// It requires the listener to authenticate clients by certificates.
+    #[cfg_attr(feature = "schema", schemars(title = "客户端证书身份白名单列表"))]
+    pub identity_list: Vec<String>,
 }
 
 pub struct EastWestTrafficWhiteListPlugin {
     pub ip_list: Vec<IpNet>,
+    pub identity_list: Vec<String>,
 }
 
 impl From<EastWestTrafficWhiteListConfig> for EastWestTrafficWhiteListPlugin {
@@ -35,7 +42,10 @@ impl From<EastWestTrafficWhiteListConfig> for EastWestTrafficWhiteListPlugin {
             ip_list.push(net)
         }
 
-        EastWestTrafficWhiteListPlugin { ip_list }
+        EastWestTrafficWhiteListPlugin {
+            ip_list,
+            identity_list: value.identity_list,
+        }
     }
 }
 
@@ -43,6 +53,10 @@ impl EastWestTrafficWhiteListPlugin {
     fn match_(&self, ip: &IpAddr) -> bool {
         self.ip_list.iter().any(|net| net.contains(ip))
     }
+
+    fn match_identity(&self, cert: &PeerCertificate) -> bool {
+        self.identity_list.iter().any(|identity| cert.has_identity(identity))
+    }
 }
 
 impl Plugin for EastWestTrafficWhiteListPlugin {
@@ -65,9 +79,60 @@ impl Plugin for EastWestTrafficWhiteListPlugin {
         inner: spacegate_kernel::helper_layers::function::Inner,
     ) -> Result<spacegate_kernel::SgResponse, spacegate_kernel::BoxError> {
         let original_addr = req.extract::<OriginalIpAddr>().into_inner();
-        if self.match_(&original_addr) {
+        let identity_matched = req.extensions().get::<PeerCertificate>().is_some_and(|cert| self.match_identity(cert));
+        if identity_matched || self.match_(&original_addr) {
             req.extensions_mut().insert(IsEastWestTraffic::new(true));
         }
         Ok(inner.call(req).await)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_certificate() -> PeerCertificate {
+        let pem = include_str!("../../../kernel/tests/test_mtls/client.crt");
+        let der = rustls_pemfile::certs(&mut pem.as_bytes()).next().and_then(Result::ok).expect("certificate in pem");
+        PeerCertificate::from_der(der).expect("parse certificate")
+    }
+
+    fn plugin(identity_list: &[&str]) -> EastWestTrafficWhiteListPlugin {
+        EastWestTrafficWhiteListConfig {
+            identity_list: identity_list.iter().map(|identity| identity.to_string()).collect(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn match_identity_list() {
+        let cert = client_certificate();
+        assert!(plugin(&["uri:spiffe://cluster.local/ns/default/sa/client"]).match_identity(&cert));
+        assert!(plugin(&["spiffe://cluster.local/ns/default/sa/client"]).match_identity(&cert));
+        assert!(plugin(&["dns:other", "cn:client"]).match_identity(&cert));
+        // the common name is not a dns name
+        assert!(!plugin(&["client", "dns:client"]).match_identity(&cert));
+        assert!(!plugin(&["uri:spiffe://cluster.local/ns/default/sa/other"]).match_identity(&cert));
+        assert!(!plugin(&[]).match_identity(&cert));
+    }
+
+    #[tokio::test]
+    async fn mark_east_west_traffic_by_identity() -> Result<(), spacegate_kernel::BoxError> {
+        use hyper::{service::service_fn, Request, Response};
+        use spacegate_kernel::{extension::OriginalIpAddr, helper_layers::function::Inner, ArcHyperService, SgBody};
+        for (identity, allowed) in [("uri:spiffe://cluster.local/ns/default/sa/client", true), ("dns:client", false)] {
+            let inner = Inner::new(ArcHyperService::new(service_fn(|req: Request<SgBody>| async move {
+                let is_east_west = req.extensions().get::<IsEastWestTraffic>().is_some_and(|is_east_west| **is_east_west);
+                Ok::<_, std::convert::Infallible>(Response::new(SgBody::full(is_east_west.to_string())))
+            })));
+            let mut req = Request::builder().body(SgBody::empty())?;
+            req.extensions_mut().insert(OriginalIpAddr([10, 0, 0, 1].into()));
+            req.extensions_mut().insert(client_certificate());
+            let resp = plugin(&[identity]).call(req, inner).await?;
+            let body = resp.into_body().dump().await?;
+            assert_eq!(body.get_dumped().map(|body| body.as_ref()), Some(allowed.to_string().as_bytes()), "{identity}");
+        }
+        Ok(())
+    }
+}
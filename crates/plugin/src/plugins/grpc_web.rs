@@ -0,0 +1,328 @@
+//! Translate gRPC-Web requests into gRPC, so that browsers could call gRPC services.
+//!
+//! - Requests of `application/grpc-web` and `application/grpc-web-text` are sent to backends as `application/grpc` over http2.
+//! - The trailers of gRPC responses are encoded into the body as a trailer frame, since browsers can't read http trailers.
+//! - Other requests are passed through.
+//!
+//! see also: https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md
+
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::{
+    body::{Body, Bytes, Frame},
+    header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, TE},
+    HeaderMap, Request, Response, Version,
+};
+use serde::{Deserialize, Serialize};
+use spacegate_kernel::{
+    helper_layers::function::Inner,
+    utils::grpc::{map_grpc_error_response, GRPC_CONTENT_TYPE, GRPC_WEB_CONTENT_TYPE, GRPC_WEB_TEXT_CONTENT_TYPE},
+    BoxError, SgBody,
+};
+
+use crate::Plugin;
+
+/// Flag of the trailer frame in a gRPC-Web response body.
+const TRAILER_FRAME_FLAG: u8 = 0x80;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(title = "gRPC-Web转换插件配置"))]
+#[serde(default)]
+pub struct GrpcWebPluginConfig {
+    /// headers exposed to browsers by `access-control-expose-headers`, besides `grpc-status` and `grpc-message`
+    #[cfg_attr(feature = "schema", schemars(title = "暴露的响应头"))]
+    pub expose_headers: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcWebPlugin {
+    pub expose_headers: HeaderValue,
+}
+
+impl GrpcWebPlugin {
+    pub fn new(expose_headers: impl IntoIterator<Item = String>) -> Result<Self, BoxError> {
+        let expose_headers = ["grpc-status".to_string(), "grpc-message".to_string()].into_iter().chain(expose_headers).collect::<Vec<_>>().join(",");
+        Ok(Self {
+            expose_headers: HeaderValue::from_str(&expose_headers)?,
+        })
+    }
+}
+
+/// Content type of the request, `None` if it's not a gRPC-Web request.
+fn grpc_web_mode(headers: &HeaderMap) -> Option<(bool, HeaderValue)> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let (text, suffix) = if let Some(suffix) = content_type.strip_prefix(GRPC_WEB_TEXT_CONTENT_TYPE) {
+        (true, suffix)
+    } else {
+        (false, content_type.strip_prefix(GRPC_WEB_CONTENT_TYPE)?)
+    };
+    HeaderValue::from_str(&format!("{GRPC_CONTENT_TYPE}{suffix}")).ok().map(|content_type| (text, content_type))
+}
+
+/// Replace the gRPC content type of the response with gRPC-Web
+fn grpc_web_content_type(content_type: Option<&HeaderValue>, text: bool) -> HeaderValue {
+    let suffix = content_type.and_then(|content_type| content_type.to_str().ok()).and_then(|content_type| content_type.strip_prefix(GRPC_CONTENT_TYPE)).unwrap_or_default();
+    let prefix = if text { GRPC_WEB_TEXT_CONTENT_TYPE } else { GRPC_WEB_CONTENT_TYPE };
+    HeaderValue::from_str(&format!("{prefix}{suffix}")).unwrap_or(HeaderValue::from_static(GRPC_WEB_CONTENT_TYPE))
+}
+
+/// Encode trailers into a trailer frame.
+fn trailer_frame(trailers: &HeaderMap) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.push(b':');
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+    let mut frame = Vec::with_capacity(block.len() + 5);
+    frame.push(TRAILER_FRAME_FLAG);
+    frame.extend_from_slice(&(block.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&block);
+    frame
+}
+
+/// Decode base64 which may be concatenated by several padded segments.
+fn decode_base64_segments(input: &[u8]) -> Result<Vec<u8>, BoxError> {
+    let mut output = Vec::with_capacity(input.len() / 4 * 3);
+    let mut start = 0;
+    for (index, quad) in input.chunks_exact(4).enumerate() {
+        if quad.contains(&b'=') {
+            let end = (index + 1) * 4;
+            output.extend(STANDARD.decode(input.get(start..end).unwrap_or_default())?);
+            start = end;
+        }
+    }
+    output.extend(STANDARD.decode(input.get(start..).unwrap_or_default())?);
+    Ok(output)
+}
+
+/// Request body of `application/grpc-web-text`, which is decoded from base64.
+struct TextRequestBody {
+    inner: SgBody,
+    /// undecoded tail which is less than 4 bytes
+    pending: Vec<u8>,
+}
+
+impl Body for TextRequestBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        loop {
+            match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => {
+                        this.pending.extend(data.iter().filter(|b| !b.is_ascii_whitespace()));
+                        let aligned = this.pending.len() / 4 * 4;
+                        if aligned == 0 {
+                            continue;
+                        }
+                        let decoded = decode_base64_segments(this.pending.get(..aligned).unwrap_or_default());
+                        this.pending.drain(..aligned);
+                        return Poll::Ready(Some(decoded.map(|decoded| Frame::data(Bytes::from(decoded)))));
+                    }
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    if this.pending.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(Err("invalid grpc-web-text body: incomplete base64".into())));
+                }
+            }
+        }
+    }
+}
+
+/// Response body of gRPC-Web, the trailers are encoded into the body, and the body is encoded in base64 in text mode.
+struct GrpcWebResponseBody {
+    inner: SgBody,
+    text: bool,
+    /// unencoded tail which is less than 3 bytes in text mode
+    pending: Vec<u8>,
+    finished: bool,
+}
+
+impl GrpcWebResponseBody {
+    fn encode(&mut self, data: &[u8], last: bool) -> Bytes {
+        if !self.text {
+            return Bytes::copy_from_slice(data);
+        }
+        self.pending.extend_from_slice(data);
+        let aligned = if last { self.pending.len() } else { self.pending.len() / 3 * 3 };
+        let encoded = STANDARD.encode(self.pending.get(..aligned).unwrap_or_default());
+        self.pending.drain(..aligned);
+        Bytes::from(encoded)
+    }
+}
+
+impl Body for GrpcWebResponseBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        loop {
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    let data = match frame.into_data() {
+                        Ok(data) => this.encode(&data, false),
+                        Err(frame) => match frame.into_trailers() {
+                            Ok(trailers) => {
+                                this.finished = true;
+                                this.encode(&trailer_frame(&trailers), true)
+                            }
+                            Err(_) => continue,
+                        },
+                    };
+                    if data.is_empty() {
+                        continue;
+                    }
+                    return Poll::Ready(Some(Ok(Frame::data(data))));
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    this.finished = true;
+                    let data = this.encode(&[], true);
+                    if !data.is_empty() {
+                        return Poll::Ready(Some(Ok(Frame::data(data))));
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.finished
+    }
+}
+
+impl Plugin for GrpcWebPlugin {
+    const CODE: &'static str = "grpc-web";
+
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        let Some((text, content_type)) = grpc_web_mode(req.headers()) else {
+            return Ok(inner.call(req).await);
+        };
+        let (mut parts, body) = req.into_parts();
+        parts.headers.insert(CONTENT_TYPE, content_type);
+        parts.headers.insert(TE, HeaderValue::from_static("trailers"));
+        parts.version = Version::HTTP_2;
+        let body = if text {
+            parts.headers.remove(CONTENT_LENGTH);
+            SgBody::new(TextRequestBody { inner: body, pending: Vec::new() })
+        } else {
+            body
+        };
+        let resp = map_grpc_error_response(inner.call(Request::from_parts(parts, body)).await, GRPC_CONTENT_TYPE);
+        let (mut parts, body) = resp.into_parts();
+        let content_type = grpc_web_content_type(parts.headers.get(CONTENT_TYPE), text);
+        parts.headers.insert(CONTENT_TYPE, content_type);
+        parts.headers.remove(CONTENT_LENGTH);
+        parts.headers.insert(HeaderName::from_static("access-control-expose-headers"), self.expose_headers.clone());
+        let body = SgBody::new(GrpcWebResponseBody {
+            inner: body,
+            text,
+            pending: Vec::new(),
+            finished: false,
+        });
+        Ok(Response::from_parts(parts, body))
+    }
+
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let config = serde_json::from_value::<GrpcWebPluginConfig>(config.spec)?;
+        Self::new(config.expose_headers)
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
+    }
+}
+
+#[cfg(feature = "schema")]
+crate::schema!(GrpcWebPlugin, GrpcWebPluginConfig);
+
+#[cfg(test)]
+mod test {
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::{service::service_fn, StatusCode};
+    use spacegate_kernel::ArcHyperService;
+
+    use super::*;
+
+    /// A gRPC service which echoes the request message, with `grpc-status: 0` in trailers.
+    fn echo() -> Inner {
+        Inner::new(ArcHyperService::new(service_fn(|req: Request<SgBody>| async move {
+            assert_eq!(req.headers().get(CONTENT_TYPE).expect("content type"), "application/grpc+proto");
+            assert_eq!(req.headers().get(TE).expect("te"), "trailers");
+            let message = req.into_body().collect().await.expect("invalid body").to_bytes();
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", HeaderValue::from_static("0"));
+            let frames = [Ok::<_, BoxError>(Frame::data(message)), Ok(Frame::trailers(trailers))];
+            let body = SgBody::new(StreamBody::new(futures_util::stream::iter(frames)));
+            Ok(Response::builder().header(CONTENT_TYPE, "application/grpc+proto").body(body).expect("invalid response"))
+        })))
+    }
+
+    fn grpc_web_request(content_type: &str, chunks: &[&[u8]]) -> Request<SgBody> {
+        let frames = chunks.iter().map(|chunk| Ok::<_, BoxError>(Frame::data(Bytes::copy_from_slice(chunk)))).collect::<Vec<_>>();
+        let body = SgBody::new(StreamBody::new(futures_util::stream::iter(frames)));
+        Request::builder().uri("/helloworld.Greeter/SayHello").header(CONTENT_TYPE, content_type).body(body).expect("invalid request")
+    }
+
+    const MESSAGE: &[u8] = b"\x00\x00\x00\x00\x05hello";
+    const TRAILER: &[u8] = b"\x80\x00\x00\x00\x0fgrpc-status:0\r\n";
+
+    #[tokio::test]
+    async fn translate_grpc_web() {
+        let plugin = GrpcWebPlugin::new(Vec::new()).expect("invalid plugin");
+
+        let resp = plugin.call(grpc_web_request("application/grpc-web+proto", &[&MESSAGE[..3], &MESSAGE[3..]]), echo()).await.expect("infallible");
+        assert_eq!(resp.headers().get(CONTENT_TYPE).expect("content type"), "application/grpc-web+proto");
+        let body = resp.into_body().collect().await.expect("invalid body").to_bytes();
+        assert_eq!(body.as_ref(), [MESSAGE, TRAILER].concat());
+
+        let encoded = STANDARD.encode(MESSAGE);
+        let (head, tail) = encoded.as_bytes().split_at(5);
+        let resp = plugin.call(grpc_web_request("application/grpc-web-text+proto", &[head, tail]), echo()).await.expect("infallible");
+        assert_eq!(resp.headers().get(CONTENT_TYPE).expect("content type"), "application/grpc-web-text+proto");
+        let body = resp.into_body().collect().await.expect("invalid body").to_bytes();
+        assert_eq!(decode_base64_segments(&body).expect("invalid base64"), [MESSAGE, TRAILER].concat());
+    }
+
+    #[tokio::test]
+    async fn map_errors_to_grpc_status() {
+        let plugin = GrpcWebPlugin::new(vec!["x-request-id".to_string()]).expect("invalid plugin");
+        let unavailable = Inner::new(ArcHyperService::new(service_fn(|_| async {
+            Ok(Response::builder().status(StatusCode::SERVICE_UNAVAILABLE).body(SgBody::full("no backend")).expect("invalid response"))
+        })));
+        let resp = plugin.call(grpc_web_request("application/grpc-web", &[MESSAGE]), unavailable).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(CONTENT_TYPE).expect("content type"), "application/grpc-web");
+        assert_eq!(resp.headers().get("grpc-status").expect("grpc status"), "14");
+        assert_eq!(resp.headers().get("grpc-message").expect("grpc message"), "no backend");
+        assert_eq!(
+            resp.headers().get("access-control-expose-headers").expect("expose headers"),
+            "grpc-status,grpc-message,x-request-id"
+        );
+    }
+
+    #[test]
+    fn decode_padded_segments() {
+        let segments = [STANDARD.encode(b"a"), STANDARD.encode(b"bc"), STANDARD.encode(b"def")].concat();
+        assert_eq!(decode_base64_segments(segments.as_bytes()).expect("invalid base64"), b"abcdef");
+    }
+}
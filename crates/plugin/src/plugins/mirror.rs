@@ -0,0 +1,376 @@
+use std::{sync::Arc, time::Duration};
+
+use http_body_util::BodyExt;
+use hyper::{
+    body::Bytes,
+    header::{HeaderName, CONNECTION, HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, TE, TRAILER, TRANSFER_ENCODING, UPGRADE},
+    http::{request::Parts, uri::PathAndQuery},
+    HeaderMap, Request, Response, Uri,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, Semaphore};
+
+use spacegate_kernel::{
+    backend_service::http_client_service::get_client,
+    body::observer::{Observer, State},
+    helper_layers::function::Inner,
+    BoxError, SgBody,
+};
+
+use crate::Plugin;
+
+/// Mirror requests to shadow backends, the responses of shadow backends are discarded.
+///
+/// The body is copied while it's forwarded to the backend, and the mirrored requests are sent after the body ends,
+/// so the client never waits for shadow backends. A request is not mirrored if its body is over `max_body_size` or it's not read to the end,
+/// or if `max_in_flight` mirrored requests are still waiting for shadow backends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(title = "请求镜像插件配置"))]
+#[serde(default)]
+pub struct MirrorPluginConfig {
+    /// base urls of shadow backends, e.g. `http://shadow:8080`, the path of the request is appended to the path of the url
+    #[cfg_attr(feature = "schema", schemars(title = "镜像后端"))]
+    pub backends: Vec<String>,
+    /// percentage of requests to mirror, from 0 to 100
+    #[cfg_attr(feature = "schema", schemars(title = "采样百分比"))]
+    pub percent: f64,
+    /// max body size in bytes to mirror
+    #[cfg_attr(feature = "schema", schemars(title = "最大镜像请求体大小"))]
+    pub max_body_size: usize,
+    /// timeout of a mirrored request, it should be greater than 0
+    #[cfg_attr(feature = "schema", schemars(title = "镜像请求超时（毫秒）"))]
+    pub timeout_ms: u64,
+    /// max mirrored requests in flight, more requests are not mirrored
+    #[cfg_attr(feature = "schema", schemars(title = "最大并发镜像请求数"))]
+    pub max_in_flight: usize,
+}
+
+impl Default for MirrorPluginConfig {
+    fn default() -> Self {
+        Self {
+            backends: Vec::new(),
+            percent: 100.0,
+            max_body_size: 64 * 1024,
+            timeout_ms: 5000,
+            max_in_flight: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MirrorPlugin {
+    pub backends: Vec<Uri>,
+    pub percent: f64,
+    pub max_body_size: usize,
+    pub timeout: Duration,
+    pub in_flight: Arc<Semaphore>,
+}
+
+/// Copy the body until it ends, the copy is dropped once it's over the limit.
+struct MirrorBody {
+    buffer: Vec<u8>,
+    limit: usize,
+    sender: Option<oneshot::Sender<Bytes>>,
+}
+
+impl State for MirrorBody {
+    fn update_bytes(&mut self, data: &Bytes) {
+        if self.sender.is_none() {
+            return;
+        }
+        if self.buffer.len() + data.len() > self.limit {
+            self.sender = None;
+            self.buffer = Vec::new();
+        } else {
+            self.buffer.extend_from_slice(data);
+        }
+    }
+    fn finish(self) {
+        if let Some(sender) = self.sender {
+            let _ = sender.send(self.buffer.into());
+        }
+    }
+}
+
+impl MirrorPlugin {
+    fn sample(&self) -> bool {
+        self.percent >= 100.0 || (self.percent > 0.0 && rand::rng().random_range(0.0..100.0) < self.percent)
+    }
+    /// Returns the body to forward and a receiver of its copy, the sender is dropped if the body should not be mirrored.
+    fn tee(&self, body: SgBody) -> (SgBody, oneshot::Receiver<Bytes>) {
+        let (sender, receiver) = oneshot::channel();
+        if let Some(dumped) = body.get_dumped() {
+            if dumped.len() <= self.max_body_size {
+                let _ = sender.send(dumped.clone());
+            }
+            return (body, receiver);
+        }
+        if hyper::body::Body::is_end_stream(&body) {
+            let _ = sender.send(Bytes::new());
+            return (body, receiver);
+        }
+        if hyper::body::Body::size_hint(&body).lower() > self.max_body_size as u64 {
+            return (body, receiver);
+        }
+        let state = MirrorBody {
+            buffer: Vec::new(),
+            limit: self.max_body_size,
+            sender: Some(sender),
+        };
+        (Observer::new(state, body).to_sg_body(), receiver)
+    }
+    fn mirror_uri(backend: &Uri, uri: &Uri) -> Result<Uri, BoxError> {
+        let path_and_query = uri.path_and_query().map_or("/", PathAndQuery::as_str);
+        let path_and_query = format!("{}{}", backend.path().trim_end_matches('/'), path_and_query);
+        let mut parts = backend.clone().into_parts();
+        parts.path_and_query = Some(path_and_query.parse()?);
+        Ok(Uri::from_parts(parts)?)
+    }
+    /// Headers of the mirrored request, hop-by-hop headers are removed since the mirrored request is a new plain request.
+    fn mirror_headers(headers: &HeaderMap) -> HeaderMap {
+        let mut headers = headers.clone();
+        let listed = headers
+            .get_all(CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+            .collect::<Vec<_>>();
+        for name in listed {
+            headers.remove(name);
+        }
+        // the host of shadow backends is set by the client
+        for name in [CONNECTION, TE, TRAILER, TRANSFER_ENCODING, UPGRADE, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, HOST] {
+            headers.remove(name);
+        }
+        headers.remove("keep-alive");
+        headers.remove("proxy-connection");
+        headers
+    }
+    fn mirror(&self, parts: &Parts, body: oneshot::Receiver<Bytes>) {
+        let backends = self.backends.clone();
+        let (method, uri, headers) = (parts.method.clone(), parts.uri.clone(), Self::mirror_headers(&parts.headers));
+        let timeout = self.timeout;
+        let in_flight = self.in_flight.clone();
+        tokio::spawn(async move {
+            let Ok(body) = body.await else {
+                tracing::debug!("[SG.Plugin.Mirror] request {method} {uri} is not mirrored, its body is over the limit or not completed");
+                return;
+            };
+            for backend in backends {
+                let request = Self::mirror_uri(&backend, &uri).and_then(|uri| {
+                    let mut request = Request::builder().method(method.clone()).uri(uri).body(SgBody::full(body.clone()))?;
+                    *request.headers_mut() = headers.clone();
+                    Ok(request)
+                });
+                let request = match request {
+                    Ok(request) => request,
+                    Err(e) => {
+                        tracing::warn!("[SG.Plugin.Mirror] fail to build mirrored request to {backend}: {e}");
+                        continue;
+                    }
+                };
+                let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+                    tracing::debug!("[SG.Plugin.Mirror] request {method} {uri} is not mirrored to {backend}, too many mirrored requests in flight");
+                    continue;
+                };
+                tokio::spawn(async move {
+                    let response = get_client().request_timeout(request, timeout).await;
+                    tracing::trace!("[SG.Plugin.Mirror] mirrored request to {backend} responds {}", response.status());
+                    // the body is read to the end and discarded, so that the connection could be reused
+                    let mut body = response.into_body();
+                    let _ = tokio::time::timeout(timeout, async { while let Some(Ok(_)) = body.frame().await {} }).await;
+                    drop(permit);
+                });
+            }
+        });
+    }
+}
+
+impl Plugin for MirrorPlugin {
+    const CODE: &'static str = "mirror";
+
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        if self.backends.is_empty() || !self.sample() {
+            return Ok(inner.call(req).await);
+        }
+        let (parts, body) = req.into_parts();
+        let (body, mirrored) = self.tee(body);
+        self.mirror(&parts, mirrored);
+        Ok(inner.call(Request::from_parts(parts, body)).await)
+    }
+
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let config = serde_json::from_value::<MirrorPluginConfig>(config.spec)?;
+        let backends = config
+            .backends
+            .iter()
+            .map(|backend| {
+                let uri = backend.parse::<Uri>()?;
+                if uri.scheme().is_none() || uri.authority().is_none() {
+                    return Err(BoxError::from(format!("mirror backend {backend} should be an absolute url")));
+                }
+                Ok(uri)
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        if config.timeout_ms == 0 {
+            return Err("timeout_ms of mirror plugin should be greater than 0".into());
+        }
+        Ok(Self {
+            backends,
+            percent: config.percent.clamp(0.0, 100.0),
+            max_body_size: config.max_body_size,
+            timeout: Duration::from_millis(config.timeout_ms),
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight)),
+        })
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
+    }
+}
+
+#[cfg(feature = "schema")]
+crate::schema!(MirrorPlugin, MirrorPluginConfig);
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use http_body_util::StreamBody;
+    use hyper::{body::Frame, service::service_fn, Method, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use spacegate_kernel::ArcHyperService;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// A shadow backend which reports the received requests and responds slowly.
+    async fn shadow() -> (u16, mpsc::UnboundedReceiver<(Method, String, Bytes)>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("fail to bind");
+        let port = listener.local_addr().expect("no local address").port();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.expect("fail to accept");
+                let sender = sender.clone();
+                let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                    let sender = sender.clone();
+                    async move {
+                        let method = req.method().clone();
+                        let uri = req.uri().to_string();
+                        let body = req.into_body().collect().await?.to_bytes();
+                        let _ = sender.send((method, uri, body));
+                        tokio::time::sleep(Duration::from_secs(2)).await;
+                        Ok::<_, BoxError>(Response::new(SgBody::empty()))
+                    }
+                });
+                tokio::spawn(hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+            }
+        });
+        (port, receiver)
+    }
+
+    fn plugin(port: u16, percent: f64) -> MirrorPlugin {
+        MirrorPlugin::create(crate::PluginConfig::new(
+            crate::PluginInstanceId::new(MirrorPlugin::CODE, crate::PluginInstanceName::mono()),
+            serde_json::json!({ "backends": [format!("http://127.0.0.1:{port}/shadow")], "percent": percent, "max_body_size": 50 }),
+        ))
+        .expect("fail to create plugin")
+    }
+
+    fn echo() -> Inner {
+        Inner::new(ArcHyperService::new(service_fn(|req: Request<SgBody>| async move {
+            Ok(match req.into_body().dump().await {
+                Ok(body) => Response::new(body),
+                Err(e) => Response::builder().status(StatusCode::BAD_GATEWAY).body(SgBody::full(e.to_string())).expect("invalid response"),
+            })
+        })))
+    }
+
+    fn chunked(size: usize) -> SgBody {
+        let chunks = (0..size / 10).map(|_| Ok::<_, BoxError>(Frame::data(Bytes::from_static(b"0123456789"))));
+        SgBody::new(StreamBody::new(futures_util::stream::iter(chunks)))
+    }
+
+    #[tokio::test]
+    async fn mirror_requests() {
+        let (port, mut mirrored) = shadow().await;
+        let plugin = plugin(port, 100.0);
+
+        // the client doesn't wait for the slow shadow backend
+        let start = Instant::now();
+        let req = Request::post("http://example.com/api/orders?id=1").body(chunked(40)).expect("invalid request");
+        let resp = plugin.call(req, echo()).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.into_body().collect().await.expect("echo").to_bytes().len(), 40);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let (method, uri, body) = mirrored.recv().await.expect("request should be mirrored");
+        assert_eq!(method, Method::POST);
+        assert_eq!(uri, "/shadow/api/orders?id=1");
+        assert_eq!(body.len(), 40);
+
+        let resp = plugin.call(Request::get("http://example.com/health").body(SgBody::empty()).expect("invalid request"), echo()).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let (method, uri, body) = mirrored.recv().await.expect("request should be mirrored");
+        assert_eq!((method, uri.as_str(), body.len()), (Method::GET, "/shadow/health", 0));
+
+        // the body is over the limit, it's forwarded but not mirrored
+        let resp = plugin.call(Request::post("http://example.com/upload").body(chunked(100)).expect("invalid request"), echo()).await.expect("infallible");
+        assert_eq!(resp.into_body().collect().await.expect("echo").to_bytes().len(), 100);
+        // not sampled
+        let plugin = self::plugin(port, 0.0);
+        plugin.call(Request::get("http://example.com/sampled").body(SgBody::empty()).expect("invalid request"), echo()).await.expect("infallible");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(mirrored.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn limit_in_flight() {
+        let (port, mut mirrored) = shadow().await;
+        let plugin = MirrorPlugin::create(crate::PluginConfig::new(
+            crate::PluginInstanceId::new(MirrorPlugin::CODE, crate::PluginInstanceName::mono()),
+            serde_json::json!({ "backends": [format!("http://127.0.0.1:{port}")], "max_in_flight": 1 }),
+        ))
+        .expect("fail to create plugin");
+        for path in ["/first", "/second"] {
+            let req = Request::get(format!("http://example.com{path}")).body(SgBody::empty()).expect("invalid request");
+            plugin.call(req, echo()).await.expect("infallible");
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        // the first one is still waiting for the slow shadow backend
+        let (_, uri, _) = mirrored.recv().await.expect("request should be mirrored");
+        assert_eq!(uri, "/first");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(mirrored.try_recv().is_err());
+    }
+
+    #[test]
+    fn strip_hop_by_hop_headers() {
+        let req = Request::get("http://example.com/ws")
+            .header("connection", "upgrade, x-hop")
+            .header("upgrade", "websocket")
+            .header("keep-alive", "timeout=5")
+            .header("x-hop", "1")
+            .header("x-kept", "1")
+            .header("host", "example.com")
+            .body(())
+            .expect("invalid request");
+        let headers = MirrorPlugin::mirror_headers(req.headers());
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("x-kept").expect("x-kept should be kept"), "1");
+    }
+
+    #[test]
+    fn reject_zero_timeout() {
+        let config = crate::PluginConfig::new(
+            crate::PluginInstanceId::new(MirrorPlugin::CODE, crate::PluginInstanceName::mono()),
+            serde_json::json!({ "backends": ["http://127.0.0.1:8080"], "timeout_ms": 0 }),
+        );
+        assert!(MirrorPlugin::create(config).is_err());
+    }
+}
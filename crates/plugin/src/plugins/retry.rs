@@ -1,43 +1,18 @@
-use std::{convert::Infallible, future::Future, pin::Pin, sync::Arc, task::ready, time::Duration};
+use std::{sync::Arc, time::Duration};
 
-use http_body_util::BodyExt;
-use hyper::{Request, Response, StatusCode};
-use pin_project_lite::pin_project;
+use futures_util::StreamExt;
+use http_body_util::{BodyExt, BodyStream, StreamBody};
+use hyper::{
+    body::{Bytes, Frame},
+    header::CONTENT_LENGTH,
+    HeaderMap, Method, Request, Response,
+};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use tokio::time::Sleep;
-use tower_layer::Layer;
 
-use spacegate_kernel::{SgBody, SgBoxLayer, SgResponseExt};
+use spacegate_kernel::{helper_layers::function::Inner, observability::TelemetryContext, BoxError, SgBody};
 
-use crate::def_plugin;
-#[derive(Debug, Clone)]
-pub struct RetryLayer<P> {
-    policy_default: P,
-}
-
-impl<P> RetryLayer<P>
-where
-    P: Policy,
-{
-    pub fn new(policy_default: P) -> Self {
-        Self { policy_default }
-    }
-}
-
-impl<S, P> Layer<S> for RetryLayer<P>
-where
-    P: Clone,
-{
-    type Service = Retry<P, S>;
-
-    fn layer(&self, service: S) -> Self::Service {
-        Retry {
-            policy: self.policy_default.clone(),
-            service,
-        }
-    }
-}
+use crate::{error::code, Plugin, PluginError};
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
@@ -61,8 +36,19 @@ pub enum BackOff {
 pub struct SgPluginRetryConfig {
     #[cfg_attr(feature = "schema", schemars(title = "重试次数"))]
     pub retries: u16,
+    /// `*` for any method, non-idempotent methods are excluded unless `idempotent_only` is disabled
+    #[serde(alias = "retryable_methods")]
     #[cfg_attr(feature = "schema", schemars(title = "可重试方法"))]
     pub retirable_methods: Vec<String>,
+    /// Only retry idempotent methods: GET, HEAD, OPTIONS, TRACE, PUT and DELETE, it's enabled by default
+    #[cfg_attr(feature = "schema", schemars(title = "仅重试幂等方法"))]
+    pub idempotent_only: bool,
+    /// Response status codes which should be retried
+    #[cfg_attr(feature = "schema", schemars(title = "重试状态码"))]
+    pub retry_on_status: Vec<u16>,
+    /// Max request body size in bytes to be buffered for replay, requests with a larger body won't be retried
+    #[cfg_attr(feature = "schema", schemars(title = "最大重放请求体(字节)"))]
+    pub max_replay_size: usize,
     /// Backoff strategies can vary depending on the specific implementation and requirements.
     /// see [BackOff]
     #[cfg_attr(feature = "schema", schemars(title = "退避策略"))]
@@ -80,6 +66,9 @@ impl Default for SgPluginRetryConfig {
         Self {
             retries: 3,
             retirable_methods: vec!["*".to_string()],
+            idempotent_only: true,
+            retry_on_status: vec![code::INTERNAL_SERVER_ERROR, code::BAD_GATEWAY, code::SERVICE_UNAVAILABLE, code::GATEWAY_TIMEOUT],
+            max_replay_size: 64 * 1024,
             backoff: BackOff::default(),
             base_interval: 100,
             //10 seconds
@@ -88,199 +77,279 @@ impl Default for SgPluginRetryConfig {
     }
 }
 
-#[derive(Clone)]
-pub struct RetryPolicy {
-    times: usize,
-    config: Arc<SgPluginRetryConfig>,
-}
-pin_project! {
-    pub struct Delay<T> {
-        value: Option<T>,
-        #[pin]
-        sleep: Sleep,
-    }
-}
-
-impl<T> Delay<T> {
-    pub fn new(value: T, duration: Duration) -> Self {
-        Self {
-            value: Some(value),
-            sleep: tokio::time::sleep(duration),
+impl SgPluginRetryConfig {
+    fn is_retirable_method(&self, method: &Method) -> bool {
+        if self.idempotent_only && !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE) {
+            return false;
         }
+        self.retirable_methods.iter().any(|m| m == "*" || m.eq_ignore_ascii_case(method.as_str()))
     }
-}
-
-impl<T> Future for Delay<T> {
-    type Output = T;
-
-    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        let this = self.project();
-        ready!(this.sleep.poll(cx));
-        std::task::Poll::Ready(this.value.take().expect("poll after ready"))
+    /// Delay before the `times`-th retry, starts from 0.
+    fn delay(&self, times: u16) -> Duration {
+        let delay = match self.backoff {
+            BackOff::Fixed => self.base_interval,
+            BackOff::Exponential => self.base_interval.saturating_mul(2u64.saturating_pow(times.into())),
+            BackOff::Random => {
+                if self.base_interval < self.max_interval {
+                    rand::rng().random_range(self.base_interval..self.max_interval)
+                } else {
+                    self.base_interval
+                }
+            }
+        };
+        Duration::from_millis(delay.min(self.max_interval))
     }
 }
-pub trait Policy: Sized {
-    /// The [`Future`] type returned by [`Policy::retry`].
-    type Future: Future<Output = Self>;
 
-    fn retry(&self, req: &Request<SgBody>, response: &Response<SgBody>) -> Option<Self::Future>;
+/// Request body read for replay.
+enum ReplayBody {
+    /// The whole body is buffered, with its trailers.
+    Buffered(Bytes, Option<HeaderMap>),
+    /// The body is too large, it could only be sent once.
+    Oversize(SgBody),
 }
 
-impl Policy for RetryPolicy {
-    type Future = Delay<Self>;
+/// Rebuild a buffered body for an attempt.
+fn replay(data: &Bytes, trailers: &Option<HeaderMap>) -> SgBody {
+    match trailers {
+        Some(trailers) => {
+            let frames = [Frame::data(data.clone()), Frame::trailers(trailers.clone())];
+            SgBody::new(StreamBody::new(futures_util::stream::iter(frames.map(Ok::<_, BoxError>))))
+        }
+        None => SgBody::full(data.clone()),
+    }
+}
 
-    fn retry(&self, _req: &Request<SgBody>, response: &Response<SgBody>) -> Option<Self::Future> {
-        if self.times < self.config.retries.into() && response.status() == StatusCode::INTERNAL_SERVER_ERROR {
-            let delay = match self.config.backoff {
-                BackOff::Fixed => self.config.base_interval,
-                BackOff::Exponential => self.config.base_interval * 2u64.pow(self.times as u32),
-                BackOff::Random => {
-                    let mut rng = rand::rng();
-                    rng.gen_range(self.config.base_interval..self.config.max_interval)
+/// Read the body until it ends or exceeds `max_size`.
+///
+/// An oversize body is rebuilt from the frames already read and the rest stream, so it's never fully buffered.
+async fn read_replay_body(body: SgBody, max_size: usize) -> Result<ReplayBody, BoxError> {
+    let mut body = body;
+    let mut frames = Vec::new();
+    let mut size = 0;
+    while let Some(frame) = body.frame().await {
+        let frame = frame?;
+        if let Some(data) = frame.data_ref() {
+            size += data.len();
+        }
+        frames.push(frame);
+        if size > max_size {
+            let rest = BodyStream::new(body);
+            let stream = futures_util::stream::iter(frames.into_iter().map(Ok::<Frame<Bytes>, BoxError>)).chain(rest);
+            return Ok(ReplayBody::Oversize(SgBody::new(StreamBody::new(stream))));
+        }
+    }
+    let mut buffer = Vec::with_capacity(size);
+    let mut trailers: Option<HeaderMap> = None;
+    for frame in frames {
+        match frame.into_data() {
+            Ok(data) => buffer.extend_from_slice(&data),
+            Err(frame) => {
+                if let Ok(frame_trailers) = frame.into_trailers() {
+                    trailers.get_or_insert_with(HeaderMap::new).extend(frame_trailers);
                 }
-            };
-            Some(Delay::new(
-                RetryPolicy {
-                    times: self.times + 1,
-                    config: self.config.clone(),
-                },
-                Duration::from_millis(delay),
-            ))
-        } else {
-            None
+            }
         }
     }
+    Ok(ReplayBody::Buffered(buffer.into(), trailers))
 }
 
 #[derive(Debug, Clone)]
-pub struct Retry<P, S> {
-    policy: P,
-    service: S,
+pub struct RetryPlugin {
+    pub config: Arc<SgPluginRetryConfig>,
 }
 
-pin_project_lite::pin_project! {
-    pub struct RetryFuture<P, S>
-    where
-        P: Policy,
-        S: hyper::service::Service<Request<SgBody>, Response = Response<SgBody>, Error = Infallible>,
-    {
-        policy: P,
-        service: S,
-        #[pin]
-        state: RetryState<P::Future, S::Future>,
-        request: Option<Request<SgBody>>
+impl Plugin for RetryPlugin {
+    const CODE: &'static str = "retry";
+    fn meta() -> spacegate_model::PluginMetaData {
+        crate::plugin_meta!(
+            description: "Retry the request when the response status matches."
+        )
     }
-}
-
-impl<P, S> RetryFuture<P, S>
-where
-    P: Policy,
-    S: hyper::service::Service<Request<SgBody>, Response = Response<SgBody>, Error = Infallible>,
-{
-    pub fn new(policy: P, service: S, req: Request<SgBody>) -> Self {
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        let config = &self.config;
+        if config.retries == 0 || !config.is_retirable_method(req.method()) {
+            return Ok(inner.call(req).await);
+        }
+        let declared_oversize =
+            req.headers().get(CONTENT_LENGTH).and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<usize>().ok()).is_some_and(|len| len > config.max_replay_size);
+        if declared_oversize {
+            return Ok(inner.call(req).await);
+        }
         let (parts, body) = req.into_parts();
-        let body = body.collect();
-        Self {
-            policy,
-            service,
-            state: RetryState::Collecting { body, parts },
-            request: None,
+        let (body, trailers) = match read_replay_body(body, config.max_replay_size).await {
+            Ok(ReplayBody::Buffered(body, trailers)) => (body, trailers),
+            Ok(ReplayBody::Oversize(body)) => return Ok(inner.call(Request::from_parts(parts, body)).await),
+            Err(e) => return Ok(PluginError::status::<Self, { code::BAD_REQUEST }>(e).into()),
+        };
+        let mut statuses = Vec::new();
+        let mut times = 0;
+        loop {
+            let attempt = Request::from_parts(parts.clone(), replay(&body, &trailers));
+            let resp = inner.clone().call(attempt).await;
+            statuses.push(resp.status().as_u16().to_string());
+            let should_retry = times < config.retries && config.retry_on_status.contains(&resp.status().as_u16());
+            if !should_retry {
+                // record attempts into the access log
+                if let Some(context) = parts.extensions.get::<TelemetryContext>() {
+                    let _ = context.insert_namespaced("retry", "attempts", statuses.len());
+                    let _ = context.insert_namespaced("retry", "statuses", statuses.join(","));
+                }
+                return Ok(resp);
+            }
+            let delay = config.delay(times);
+            tracing::debug!(status = %resp.status(), ?delay, "[Sg.Plugin.Retry] retry request, times: {}", times + 1);
+            tokio::time::sleep(delay).await;
+            times += 1;
         }
     }
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let config = serde_json::from_value::<SgPluginRetryConfig>(config.spec)?;
+        Ok(Self { config: Arc::new(config) })
+    }
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
+    }
 }
 
-pin_project_lite::pin_project! {
-    #[project = RetryStateProj]
-    pub enum RetryState<PF, SF> {
-        Collecting {
-            #[pin]
-            body: http_body_util::combinators::Collect<SgBody>,
-            parts: hyper::http::request::Parts,
-        },
-        Requesting {
-            #[pin]
-            future: SF,
-        },
-        Retrying {
-            #[pin]
-            future: PF,
-        },
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use hyper::{service::service_fn, StatusCode};
+    use spacegate_kernel::ArcHyperService;
+
+    use super::*;
 
+    #[test]
+    fn idempotent_only_filters_methods() {
+        let config = SgPluginRetryConfig::default();
+        assert!(config.is_retirable_method(&Method::GET));
+        assert!(config.is_retirable_method(&Method::PUT));
+        assert!(!config.is_retirable_method(&Method::POST));
+        let config = SgPluginRetryConfig {
+            retirable_methods: vec!["post".to_string()],
+            idempotent_only: false,
+            ..Default::default()
+        };
+        assert!(config.is_retirable_method(&Method::POST));
+        assert!(!config.is_retirable_method(&Method::GET));
     }
-}
 
-impl<P, S> Future for RetryFuture<P, S>
-where
-    P: Policy,
-    S: hyper::service::Service<Request<SgBody>, Response = Response<SgBody>, Error = Infallible>,
-{
-    type Output = Result<Response<SgBody>, Infallible>;
+    #[tokio::test]
+    async fn replay_body_respects_max_size() {
+        let body = read_replay_body(SgBody::full("hello"), 8).await.expect("read body");
+        assert!(matches!(body, ReplayBody::Buffered(ref bytes, None) if bytes.as_ref() == b"hello"));
+        let body = read_replay_body(SgBody::full("hello world"), 8).await.expect("read body");
+        let ReplayBody::Oversize(body) = body else {
+            panic!("body should be oversize");
+        };
+        let body = body.dump().await.expect("dump body");
+        assert_eq!(body.get_dumped().map(|b| b.as_ref()), Some(b"hello world".as_ref()));
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        let mut this = self.project();
-        loop {
-            match this.state.as_mut().project() {
-                RetryStateProj::Collecting { body, parts: part } => {
-                    let body = ready!(body.poll(cx));
-                    match body {
-                        Ok(body) => {
-                            let req = Request::from_parts(part.clone(), SgBody::full(body.to_bytes()));
-                            {
-                                let req = req.clone();
-                                *this.request = Some(req);
-                            }
-                            let fut = this.service.call(req);
-                            this.state.set(RetryState::Requesting { future: fut });
-                        }
-                        Err(e) => {
-                            return std::task::Poll::Ready(Ok(Response::with_code_message(StatusCode::BAD_REQUEST, e.to_string())));
-                        }
-                    }
-                }
-                RetryStateProj::Requesting { future } => {
-                    let resp = ready!(future.poll(cx));
-                    match resp {
-                        Ok(resp) => {
-                            if let Some(fut) = this.policy.retry(this.request.as_ref().expect("status conflict"), &resp) {
-                                this.state.set(RetryState::Retrying { future: fut });
-                            } else {
-                                return std::task::Poll::Ready(Ok(resp));
-                            }
-                        }
-                        Err(_e) => {
-                            unreachable!()
-                        }
-                    }
-                }
-                RetryStateProj::Retrying { future } => {
-                    let next_p = ready!(future.poll(cx));
-                    *this.policy = next_p;
-                    // retry
-                    let fut = this.service.call(this.request.as_ref().expect("status conflict").clone());
-                    this.state.set(RetryState::Requesting { future: fut });
-                }
-            }
+    #[tokio::test]
+    async fn replay_body_keeps_trailers() {
+        let trailers = HeaderMap::from_iter([(hyper::header::HeaderName::from_static("grpc-timeout"), "1S".parse().expect("header value"))]);
+        let frames = [Frame::data(Bytes::from_static(b"hello")), Frame::trailers(trailers.clone())];
+        let body = SgBody::new(StreamBody::new(futures_util::stream::iter(frames.map(Ok::<_, BoxError>))));
+        let ReplayBody::Buffered(data, replayed) = read_replay_body(body, 8).await.expect("read body") else {
+            panic!("body should be buffered");
+        };
+        assert_eq!(replayed.as_ref(), Some(&trailers));
+        for _ in 0..2 {
+            let collected = replay(&data, &replayed).collect().await.expect("collect body");
+            assert_eq!(collected.trailers(), Some(&trailers));
+            assert_eq!(collected.to_bytes().as_ref(), b"hello");
         }
     }
-}
 
-impl<P, S> hyper::service::Service<Request<SgBody>> for Retry<P, S>
-where
-    P: Policy + Clone,
-    S: hyper::service::Service<Request<SgBody>, Response = Response<SgBody>, Error = Infallible> + Clone,
-{
-    type Response = Response<SgBody>;
+    /// Responds with the statuses in order, and the last one after that.
+    fn upstream(statuses: &'static [u16], calls: Arc<AtomicUsize>) -> Inner {
+        Inner::new(ArcHyperService::new(service_fn(move |req: Request<SgBody>| {
+            let calls = calls.clone();
+            async move {
+                let body = req.into_body().collect().await.expect("collect body").to_bytes();
+                let index = calls.fetch_add(1, Ordering::SeqCst);
+                let status = statuses.get(index).or(statuses.last()).copied().unwrap_or(200);
+                Ok(Response::builder().status(status).body(SgBody::full(body)).expect("invalid response"))
+            }
+        })))
+    }
+
+    fn plugin(config: SgPluginRetryConfig) -> RetryPlugin {
+        RetryPlugin { config: Arc::new(config) }
+    }
+
+    #[tokio::test]
+    async fn retry_on_status() {
+        let plugin = plugin(SgPluginRetryConfig {
+            base_interval: 1,
+            ..Default::default()
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let req = Request::put("http://example.com/orders").body(SgBody::full("order")).expect("invalid request");
+        let resp = plugin.call(req, upstream(&[503, 502, 200], calls.clone())).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        // the body is replayed on every attempt
+        assert_eq!(resp.into_body().collect().await.expect("collect body").to_bytes().as_ref(), b"order");
 
-    type Error = Infallible;
+        // retries are exhausted
+        let calls = Arc::new(AtomicUsize::new(0));
+        let req = Request::get("http://example.com/orders").body(SgBody::empty()).expect("invalid request");
+        let resp = plugin.call(req, upstream(&[503], calls.clone())).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
 
-    type Future = RetryFuture<P, S>;
+        // non-idempotent methods are not retried by default
+        let calls = Arc::new(AtomicUsize::new(0));
+        let req = Request::post("http://example.com/orders").body(SgBody::full("order")).expect("invalid request");
+        let resp = plugin.call(req, upstream(&[503, 200], calls.clone())).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
 
-    fn call(&self, req: Request<SgBody>) -> Self::Future {
-        RetryFuture::new(self.policy.clone(), self.service.clone(), req)
+    #[tokio::test]
+    async fn oversize_body_is_not_retried() {
+        let plugin = plugin(SgPluginRetryConfig {
+            max_replay_size: 4,
+            base_interval: 1,
+            ..Default::default()
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let req = Request::put("http://example.com/orders").body(SgBody::full("large order")).expect("invalid request");
+        let resp = plugin.call(req, upstream(&[503, 200], calls.clone())).await.expect("infallible");
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(resp.into_body().collect().await.expect("collect body").to_bytes().as_ref(), b"large order");
+    }
+
+    #[test]
+    fn backoff_delay() {
+        let config = SgPluginRetryConfig {
+            backoff: BackOff::Exponential,
+            base_interval: 100,
+            max_interval: 1000,
+            ..Default::default()
+        };
+        let delays = (0..5).map(|times| config.delay(times).as_millis()).collect::<Vec<_>>();
+        assert_eq!(delays, [100, 200, 400, 800, 1000]);
+        let config = SgPluginRetryConfig {
+            backoff: BackOff::Fixed,
+            ..config
+        };
+        assert_eq!(config.delay(3), Duration::from_millis(100));
+        let config = SgPluginRetryConfig {
+            backoff: BackOff::Random,
+            ..config
+        };
+        assert!((0..10).map(|times| config.delay(times)).all(|delay| delay >= Duration::from_millis(100) && delay < Duration::from_millis(1000)));
     }
 }
 
-def_plugin!("retry", RetryPlugin, SgPluginRetryConfig; #[cfg(feature = "schema")] schema;);
 #[cfg(feature = "schema")]
 crate::schema! {
     RetryPlugin,
@@ -1,39 +1,29 @@
 use std::sync::Arc;
 
+use chrono::Utc;
 use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+use spacegate_kernel::{extension::BackendHost, helper_layers::function::Inner, BoxError, SgBody};
+
+use crate::Plugin;
+
+use self::status_plugin::{publish_board, StatusBoard};
+
 pub mod server;
 pub mod sliding_window;
 pub mod status_plugin;
-use serde::{Deserialize, Serialize};
-use spacegate_kernel::BoxError;
-use spacegate_kernel::{
-    extension::BackendHost,
-    helper_layers::{self},
-    layers::gateway::builder::SgGatewayLayerBuilder,
-    SgBody, SgBoxLayer,
-};
-use tardis::chrono::TimeDelta;
-use tardis::{
-    chrono::{Duration, Utc},
-    tokio::{self},
-};
-
-use crate::{def_plugin, MakeSgLayer, PluginError};
 
-use self::{
-    sliding_window::SlidingWindowCounter,
-    status_plugin::{get_status, update_status},
-};
+/// Status plugin config.
+///
+/// The status page is served by the shared axum server at `/plugin-status/{name}`, or at `/plugin-status` for the mono instance.
+///
+/// With the `cache` feature, the statuses are kept in the redis of the gateway, so the gateway must have a redis client,
+/// otherwise nothing is recorded.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "schema", schemars(title = "状态检测插件配置"))]
 #[serde(default)]
 pub struct SgFilterStatusConfig {
-    #[serde(alias = "serv_addr")]
-    #[cfg_attr(feature = "schema", schemars(title = "主机地址"))]
-    pub host: String,
-    #[cfg_attr(feature = "schema", schemars(title = "端口"))]
-    pub port: u16,
     #[cfg_attr(feature = "schema", schemars(title = "标题"))]
     pub title: String,
     /// Unhealthy threshold , if server error more than this, server will be tag as unhealthy
@@ -51,8 +41,6 @@ pub struct SgFilterStatusConfig {
 impl Default for SgFilterStatusConfig {
     fn default() -> Self {
         Self {
-            host: "0.0.0.0".to_string(),
-            port: 8110,
             title: "System Status".to_string(),
             unhealthy_threshold: 3,
             interval: 5,
@@ -62,154 +50,95 @@ impl Default for SgFilterStatusConfig {
     }
 }
 
-#[cfg(not(feature = "cache"))]
 #[derive(Debug, Clone)]
-pub struct DefaultPolicy {
-    counter: Arc<RwLock<SlidingWindowCounter>>,
-    unhealthy_threshold: u16,
+pub struct StatusPlugin {
+    pub board: Arc<StatusBoard>,
+    /// whether the missing redis client has been warned, so that it's warned once instead of on every request
+    #[cfg(feature = "cache")]
+    missing_redis_warned: Arc<std::sync::atomic::AtomicBool>,
 }
 
-#[cfg(not(feature = "cache"))]
-impl spacegate_kernel::helper_layers::stat::Policy for DefaultPolicy {
-    fn on_request(&self, _req: &Request<SgBody>) {
-        // do nothing
+impl Plugin for StatusPlugin {
+    const CODE: &'static str = "status";
+    fn meta() -> spacegate_model::PluginMetaData {
+        crate::plugin_meta!(
+            description: "Track the health of backends by their server errors, and serve a status page."
+        )
     }
-
-    fn on_response(&self, resp: &Response<SgBody>) {
-        if let Some(backend_host) = resp.extensions().get::<BackendHost>() {
-            let backend_host = backend_host.0.clone();
-            let unhealthy_threshold = self.unhealthy_threshold;
-            let counter = self.counter.clone();
-            if resp.status().is_server_error() {
+    async fn call(&self, req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        #[cfg(feature = "cache")]
+        let gateway_name = req.extensions().get::<spacegate_kernel::extension::GatewayName>().map(|name| name.0.clone());
+        let resp = inner.call(req).await;
+        let Some(backend) = resp.extensions().get::<BackendHost>().map(|host| host.0.clone()) else {
+            return Ok(resp);
+        };
+        let is_error = resp.status().is_server_error();
+        #[cfg(not(feature = "cache"))]
+        self.board.record(&backend, is_error, Utc::now());
+        #[cfg(feature = "cache")]
+        match gateway_name.and_then(|gateway_name| spacegate_ext_redis::global_repo().get(&gateway_name).map(|client| (gateway_name, client))) {
+            Some((gateway_name, client)) => {
+                let board = self.board.clone();
                 let now = Utc::now();
-                tardis::tokio::spawn(async move {
-                    let mut counter = counter.write().await;
-                    let count = counter.add_and_count(now);
-                    if count >= unhealthy_threshold as u64 {
-                        update_status(&backend_host, status_plugin::Status::Major).await?;
-                    } else {
-                        update_status(&backend_host, status_plugin::Status::Minor).await?;
+                tokio::spawn(async move {
+                    if let Err(e) = board.record(&backend, is_error, now, &gateway_name, &client).await {
+                        tracing::warn!("[SG.Plugin.Status] fail to record status of {backend}: {e}");
                     }
-                    Result::<_, BoxError>::Ok(())
-                });
-            } else {
-                tardis::tokio::spawn(async move {
-                    if let Some(status) = get_status(&backend_host).await? {
-                        if status != status_plugin::Status::Good {
-                            update_status(&backend_host, status_plugin::Status::Good).await?;
-                        }
-                    }
-                    Result::<_, BoxError>::Ok(())
                 });
             }
+            None => {
+                if !self.missing_redis_warned.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                    tracing::warn!("[SG.Plugin.Status] missing redis client of the gateway, statuses are not recorded");
+                }
+            }
         }
+        Ok(resp)
     }
-}
-
-#[derive(Debug, Clone)]
-#[cfg(feature = "cache")]
-pub struct CachePolicy {
-    unhealthy_threshold: u16,
-    pub interval: TimeDelta,
-    status_cache_key: Arc<str>,
-    window_cache_key: Arc<str>,
-    gateway_name: Arc<str>,
-}
-
-impl CachePolicy {
-    pub fn get_cache_key(&self, gateway_name: &str) -> String {
-        format!("{}:{}", self.status_cache_key, gateway_name)
-    }
-}
-
-#[cfg(feature = "cache")]
-impl spacegate_kernel::helper_layers::stat::Policy for CachePolicy {
-    fn on_request(&self, _req: &Request<SgBody>) {
-        // do nothing
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let spec = serde_json::from_value::<SgFilterStatusConfig>(config.spec)?;
+        let board = Arc::new(StatusBoard::new(&spec)?);
+        publish_board(config.id.name.to_raw_str(), &board);
+        Ok(Self {
+            board,
+            #[cfg(feature = "cache")]
+            missing_redis_warned: Default::default(),
+        })
     }
-
-    fn on_response(&self, resp: &Response<SgBody>) {
-        if let Some(backend_host) = resp.extensions().get::<BackendHost>() {
-            let backend_host = backend_host.0.clone();
-            let unhealthy_threshold = self.unhealthy_threshold;
-            let cache_key = Arc::<str>::from(self.get_cache_key(&self.gateway_name));
-            let gateway_name = self.gateway_name.clone();
-            let interval = self.interval;
-            let cache_window_key = self.window_cache_key.clone();
-            if resp.status().is_server_error() {
-                let now = Utc::now();
-
-                tardis::tokio::spawn(async move {
-                    let client = spacegate_ext_redis::RedisClientRepo::global()
-                        .get(&gateway_name)
-                        .ok_or_else(|| spacegate_ext_redis::RedisClientRepoError::new(gateway_name.as_ref(), "not found"))?;
-                    let count = SlidingWindowCounter::new(interval, &cache_window_key).add_and_count(now, &client).await?;
-                    let status = if count >= unhealthy_threshold as u64 {
-                        status_plugin::Status::Major
-                    } else {
-                        status_plugin::Status::Minor
-                    };
-                    update_status(&backend_host, &cache_key, client, status).await?;
-                    Result::<_, BoxError>::Ok(())
-                });
-            } else {
-                tardis::tokio::spawn(async move {
-                    let client = spacegate_ext_redis::RedisClientRepo::global()
-                        .get(&gateway_name)
-                        .ok_or_else(|| spacegate_ext_redis::RedisClientRepoError::new(gateway_name.as_ref(), "not found"))?;
-                    if let Some(status) = get_status(&backend_host, &cache_key, &client).await? {
-                        if status != status_plugin::Status::Good {
-                            update_status(&backend_host, &cache_key, client, status_plugin::Status::Good).await?;
-                        }
-                    }
-                    Result::<_, BoxError>::Ok(())
-                });
-            }
-        }
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
     }
 }
 
-impl MakeSgLayer for SgFilterStatusConfig {
-    fn make_layer(&self) -> Result<SgBoxLayer, BoxError> {
-        Err(BoxError::from("status plugin is only supported on gateway layer"))
-    }
-    fn install_on_gateway(&self, gateway: &mut SgGatewayLayerBuilder) -> Result<(), BoxError> {
-        let gateway_name = gateway.gateway_name.clone();
-        let cancel_guard = gateway.cancel_token.clone();
-        let config = self.clone();
-        tokio::spawn(async move {
-            if let Err(e) = server::launch_status_server(&config, gateway_name, cancel_guard).await {
-                tracing::error!("[SG.Filter.Status] launch status server error: {e}");
-            }
-        });
+#[cfg(feature = "schema")]
+crate::schema!(StatusPlugin, SgFilterStatusConfig);
 
-        let gateway_name = gateway.gateway_name.clone();
-        #[cfg(feature = "cache")]
-        let layer = {
-            let policy = CachePolicy {
-                unhealthy_threshold: self.unhealthy_threshold,
-                interval: Duration::try_seconds(self.interval as i64).ok_or_else(|| format!("[SG.Filter.Status] invalid interval config[{}]", self.interval))?,
-                status_cache_key: self.status_cache_key.clone().into(),
-                window_cache_key: self.window_cache_key.clone().into(),
-                gateway_name,
-            };
-            SgBoxLayer::new(helper_layers::stat::StatLayer::new(policy))
-        };
-        #[cfg(not(feature = "cache"))]
-        let layer = {
-            let counter = Arc::new(RwLock::new(SlidingWindowCounter::new(Duration::seconds(self.interval as i64), 60)));
-            let policy = DefaultPolicy {
-                counter,
-                unhealthy_threshold: self.unhealthy_threshold,
-            };
-            SgBoxLayer::new(helper_layers::stat::StatLayer::new(policy))
-        };
-        gateway.http_plugins.push(layer);
+#[cfg(test)]
+#[cfg(feature = "cache")]
+mod tests {
+    use hyper::{service::service_fn, StatusCode};
+    use spacegate_kernel::{extension::GatewayName, ArcHyperService};
+
+    use super::*;
+    use crate::PluginInstanceName;
+
+    #[tokio::test]
+    async fn warn_once_without_redis() -> Result<(), BoxError> {
+        let plugin = StatusPlugin::create_by_spec(serde_json::json!({}), PluginInstanceName::named("status-without-redis"))?;
+        for _ in 0..2 {
+            let inner = Inner::new(ArcHyperService::new(service_fn(|_: Request<SgBody>| async move {
+                let mut resp = Response::builder().status(StatusCode::INTERNAL_SERVER_ERROR).body(SgBody::empty()).expect("invalid response");
+                resp.extensions_mut().insert(BackendHost::new("backend"));
+                Ok::<_, std::convert::Infallible>(resp)
+            })));
+            let mut req = Request::builder().body(SgBody::empty())?;
+            // a gateway which has no redis client
+            req.extensions_mut().insert(GatewayName::new("gateway-without-redis"));
+            let resp = plugin.call(req, inner).await?;
+            assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert!(plugin.missing_redis_warned.load(std::sync::atomic::Ordering::Relaxed));
+        }
         Ok(())
     }
 }
-
-def_plugin!("status", StatusPlugin, SgFilterStatusConfig; #[cfg(feature = "schema")] schema;);
-
-#[cfg(feature = "schema")]
-crate::schema!(StatusPlugin, SgFilterStatusConfig);
@@ -0,0 +1,18 @@
+local key = KEYS[1]
+
+local window_size = tonumber(ARGV[1])
+local current_time_timestamp = tonumber(ARGV[2])
+local current_time_subsec_micros = tonumber(ARGV[3])
+
+local member_score = ((current_time_timestamp % 10000000) * 1000000) + current_time_subsec_micros
+local window_expire_at = (current_time_timestamp * 1000) + window_size
+
+redis.call('ZREMRANGEBYSCORE', key, 0, (member_score - (window_size * 1000)))
+
+local current_requests_count = redis.call('ZCARD', key)
+
+redis.call('ZADD', key, member_score, member_score)
+
+redis.call('PEXPIRE', key, window_expire_at)
+
+return current_requests_count
@@ -1,81 +1,65 @@
-use tardis::basic::{error::TardisError, result::TardisResult};
 #[cfg(feature = "cache")]
-use tardis::cache::Script;
-use tardis::chrono::{DateTime, Duration, Utc};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "cache")]
+use spacegate_ext_redis::redis::Script;
 #[cfg(feature = "cache")]
-use tardis::tardis_static;
+use spacegate_kernel::BoxError;
 
 pub(super) const DEFAULT_CONF_WINDOW_KEY: &str = "sg:plugin:filter:window:key";
 
+/// Sliding window script
+///
+/// # Arguments
+///
+/// * KEYS[1]  window key
+/// * ARGV[1]  window size
+/// * ARGV[2]  current timestamp
+/// * ARGV[3]  current sub_second microsecond
+///
+/// # Return
+///
+/// * count
+///
+/// # Kernel logic
+///
+/// ```lua
+/// -- Extract the key from the KEYS array, which represents the Redis key used for the sorted set.
+/// local key = KEYS[1]
+///
+/// -- Convert the window size from the ARGV array into a numeric value.
+/// local window_size = tonumber(ARGV[1])
+///
+/// -- Convert the current time timestamp, including seconds and microseconds, from ARGV into numeric values.
+/// local current_time_timestamp = tonumber(ARGV[2])
+/// local current_time_subsec_micros = tonumber(ARGV[3])
+///
+/// -- Calculate the member_score, which combines timestamp and microseconds.
+/// local member_score = ((current_time_timestamp % 10000000) * 1000000) + current_time_subsec_micros
+///
+/// -- Calculate the timestamp when the current window should expire, in milliseconds.
+/// local window_expire_at = (current_time_timestamp * 1000) + window_size
+///
+/// -- Remove elements from the sorted set that are older than (now - window) based on member_score.
+/// redis.call('ZREMRANGEBYSCORE', key, 0, (member_score - (window_size * 1000)))
+///
+/// -- Get the number of requests in the current window by counting the elements in the sorted set.
+/// local current_requests_count = redis.call('ZCARD', key)
+///
+/// -- Add the current request's member_score to the sorted set.
+/// redis.call('ZADD', key, member_score, member_score)
+///
+/// -- Set the expiration time for the key, specifying when the window should expire.
+/// redis.call('PEXPIRE', key, window_expire_at)
+///
+/// -- Return the count of requests in the current window.
+/// return current_requests_count
+/// ```
 #[cfg(feature = "cache")]
-tardis_static! {
-    /// Sliding window script
-    ///
-    /// # Arguments
-    ///
-    /// * KEYS[1]  window key
-    /// * ARGV[1]  window size
-    /// * ARGV[2]  current timestamp
-    /// * ARGV[3]  current sub_second microsecond
-    ///
-    /// # Return
-    ///
-    /// * count
-    ///
-    /// # Kernel logic
-    ///
-    /// -- Extract the key from the KEYS array, which represents the Redis key used for the sorted set.
-    /// local key = KEYS[1]
-    ///
-    /// -- Convert the window size from the ARGV array into a numeric value.
-    /// local window_size = tonumber(ARGV[1])
-    ///
-    /// -- Convert the current time timestamp, including seconds and microseconds, from ARGV into numeric values.
-    /// local current_time_timestamp = tonumber(ARGV[2])
-    /// local current_time_subsec_micros = tonumber(ARGV[3])
-    ///
-    /// -- Calculate the member_score, which combines timestamp and microseconds.
-    /// local member_score = ((current_time_timestamp % 10000000) * 1000000) + current_time_subsec_micros
-    ///
-    /// -- Calculate the timestamp when the current window should expire, in milliseconds.
-    /// local window_expire_at = (current_time_timestamp * 1000) + window_size
-    ///
-    /// -- Remove elements from the sorted set that are older than (now - window) based on member_score.
-    /// redis.call('ZREMRANGEBYSCORE', key, 0, (member_score - (window_size * 1000)))
-    ///
-    /// -- Get the number of requests in the current window by counting the elements in the sorted set.
-    /// local current_requests_count = redis.call('ZCARD', key)
-    ///
-    /// -- Add the current request's member_score to the sorted set.
-    /// redis.call('ZADD', key, member_score, member_score)
-    ///
-    /// -- Set the expiration time for the key, specifying when the window should expire.
-    /// redis.call('PEXPIRE', key, window_expire_at)
-    ///
-    /// -- Return the count of requests in the current window.
-    /// return current_requests_count
-    pub script: Script = Script::new(
-        r"
-    local key = KEYS[1]
-
-    local window_size = tonumber(ARGV[1])
-    local current_time_timestamp = tonumber(ARGV[2])
-    local current_time_subsec_micros = tonumber(ARGV[3])
-
-    local member_score = ((current_time_timestamp % 10000000) * 1000000) + current_time_subsec_micros
-    local window_expire_at = (current_time_timestamp * 1000) + window_size
-
-    redis.call('ZREMRANGEBYSCORE', key, 0, (member_score - (window_size * 1000)))
-
-    local current_requests_count = redis.call('ZCARD', key)
-
-    redis.call('ZADD', key, member_score, member_score)
-
-    redis.call('PEXPIRE', key, window_expire_at)
-
-    return current_requests_count
-    ",
-    );
+pub fn script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| Script::new(include_str!("./sliding_window.lua")))
 }
 
 /// # SlidingWindowCounter:
@@ -94,9 +78,9 @@ tardis_static! {
 ///
 /// ## Note:
 /// - The Redis-based implementation is suitable for distributed systems
-/// and offers higher-level performance with millisecond-level accuracy.
+///   and offers higher-level performance with millisecond-level accuracy.
 /// - The Memory-based implementation is more efficient in terms of performance
-/// but lacks distributed support and offers nanosecond-level accuracy.
+///   but lacks distributed support and offers nanosecond-level accuracy.
 #[derive(Debug, Clone)]
 pub struct SlidingWindowCounter {
     window_size: Duration,
@@ -151,9 +135,9 @@ impl SlidingWindowCounter {
 
     #[cfg(not(feature = "cache"))]
     // move_index range: 1--slot_num
-    fn init_part(&mut self, move_index: i64) -> TardisResult<()> {
+    fn init_part(&mut self, move_index: i64) -> Result<(), &'static str> {
         if self.slot_num < move_index as usize {
-            return Err(TardisError::bad_request("move index out of range", ""));
+            return Err("move index out of range");
         }
 
         let last_slot_index = (self.start_slot + self.slot_num - 1) % self.slot_num;
@@ -195,15 +179,14 @@ impl SlidingWindowCounter {
     }
 
     #[cfg(feature = "cache")]
-    pub async fn add_and_count(&self, now: DateTime<Utc>, client: &spacegate_ext_redis::RedisClient) -> TardisResult<u64> {
+    pub async fn add_and_count(&self, now: DateTime<Utc>, client: &spacegate_ext_redis::RedisClient) -> Result<u64, BoxError> {
         let result: u64 = script()
             .key((if self.window_key.is_empty() { DEFAULT_CONF_WINDOW_KEY } else { &self.window_key }).to_string())
             .arg(self.window_size.num_milliseconds())
             .arg(now.timestamp())
             .arg(now.timestamp_subsec_micros())
             .invoke_async(&mut client.get_conn().await)
-            .await
-            .map_err(|e| TardisError::internal_error(&format!("[SG.Filter.Status] redis error : {e}"), ""))?;
+            .await?;
         Ok(result)
     }
 
@@ -239,129 +222,137 @@ impl Slot {
 mod tests {
     use super::*;
     #[cfg(feature = "cache")]
-    use tardis::test::test_container::TardisTestContainer;
-    #[cfg(feature = "cache")]
-    use tardis::{testcontainers, tokio};
+    use testcontainers_modules::redis::REDIS_PORT;
 
     #[test]
     #[cfg(not(feature = "cache"))]
     fn test() {
         let mut test = SlidingWindowCounter::new(Duration::seconds(60), 12);
-        test.init(DateTime::parse_from_rfc3339("2000-01-01T01:00:00.000Z").unwrap().into());
+        test.init(DateTime::parse_from_rfc3339("2000-01-01T01:00:00.000Z").expect("invalid time").into());
 
         assert_eq!(test.get_data().len(), 12);
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:01.100Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:01.200Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:01.300Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:01.100Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:01.200Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:01.300Z").expect("invalid time").into());
         assert_eq!(test.get_data()[0].count, 3);
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:59.100Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:59.200Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:59.300Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:59.100Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:59.200Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:00:59.300Z").expect("invalid time").into());
         assert_eq!(test.get_data()[11].count, 3);
 
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.000Z").unwrap().into()), 6);
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").unwrap().into()), 0);
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.000Z").expect("invalid time").into()),
+            6
+        );
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").expect("invalid time").into()),
+            0
+        );
 
         // test add out of window time
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.100Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.200Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.300Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.100Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.200Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:00.300Z").expect("invalid time").into());
         assert_eq!(test.get_data()[0].count, 3);
         assert_eq!(test.start_slot, 1);
 
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").unwrap().into()), 3);
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").expect("invalid time").into()),
+            3
+        );
 
         //slide window
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:06.100Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:06.200Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:06.300Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:06.100Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:06.200Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:06.300Z").expect("invalid time").into());
         assert_eq!(test.get_data()[1].count, 3);
         assert_eq!(test.start_slot, 2);
 
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").unwrap().into()), 6);
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").expect("invalid time").into()),
+            6
+        );
 
         //slide window
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:50.100Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:50.200Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:50.100Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:01:50.200Z").expect("invalid time").into());
         assert_eq!(test.get_data()[10].count, 2);
         assert_eq!(test.start_slot, 11);
 
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").unwrap().into()), 8);
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:02:00.000Z").expect("invalid time").into()),
+            8
+        );
 
         //test reinit
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:03:05.100Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:03:05.100Z").expect("invalid time").into());
         assert_eq!(test.get_data()[0].count, 1);
         assert_eq!(test.start_slot, 0);
 
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:03:06.000Z").unwrap().into()), 1);
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:03:06.000Z").expect("invalid time").into()),
+            1
+        );
 
         //test critical case
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:04:05.100Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:04:05.100Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:04:05.100Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:04:05.100Z").expect("invalid time").into());
         assert_eq!(test.get_data()[0].count, 2);
         assert_eq!(test.start_slot, 1);
 
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:04:05.100Z").unwrap().into()), 2);
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:04:05.100Z").expect("invalid time").into()),
+            2
+        );
 
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:05:10.100Z").unwrap().into());
-        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:05:10.100Z").unwrap().into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:05:10.100Z").expect("invalid time").into());
+        test.add_one(DateTime::parse_from_rfc3339("2000-01-01T01:05:10.100Z").expect("invalid time").into());
         assert_eq!(test.get_data()[0].count, 2);
         assert_eq!(test.start_slot, 0);
 
-        assert_eq!(test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:05:10.100Z").unwrap().into()), 2);
+        assert_eq!(
+            test.count_in_window(DateTime::parse_from_rfc3339("2000-01-01T01:05:10.100Z").expect("invalid time").into()),
+            2
+        );
     }
 
     #[tokio::test]
     #[cfg(feature = "cache")]
     async fn test() {
-        let _init = tardis::basic::tracing::TardisTracingInitializer::default().with_env_layer().with_fmt_layer().init();
         let docker = testcontainers::clients::Cli::default();
-        let redis_container = TardisTestContainer::redis_custom(&docker);
-        let port = redis_container.get_host_port_ipv4(6379);
+        let redis_container = docker.run(testcontainers_modules::redis::Redis);
+        let port = redis_container.get_host_port_ipv4(REDIS_PORT);
         let url = format!("redis://127.0.0.1:{port}/0",);
         let repo = spacegate_ext_redis::RedisClientRepo::global();
-        repo.add("test_gate1".to_owned(), url.as_str());
-        let client = std::sync::Arc::new(repo.get("test_gate1").unwrap());
-        // fn new_ctx() -> SgRoutePluginContext {
-        //     SgRoutePluginContext::new_http(
-        //         Method::GET,
-        //         Uri::from_static("http://sg.idealworld.group/iam/ct/001?name=sg"),
-        //         Version::HTTP_11,
-        //         HeaderMap::new(),
-        //         Body::empty(),
-        //         "127.0.0.1:8080".parse().unwrap(),
-        //         "test_gate1".to_string(),
-        //         None,
-        //         None,
-        //     )
-        // }
+        repo.add("test_gate1", url.as_str());
+        let client = repo.get("test_gate1").expect("missing client");
 
         let test = SlidingWindowCounter::new(Duration::seconds(60), "");
 
         assert_eq!(
-            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:00:50.100Z").unwrap().into(), &client).await.unwrap(),
+            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:00:50.100Z").expect("invalid time").into(), &client).await.expect("redis error"),
             0
         );
         assert_eq!(
-            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:00:55.100Z").unwrap().into(), &client).await.unwrap(),
+            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:00:55.100Z").expect("invalid time").into(), &client).await.expect("redis error"),
             1
         );
 
         assert_eq!(
-            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:01:50.100Z").unwrap().into(), &client).await.unwrap(),
+            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:01:50.100Z").expect("invalid time").into(), &client).await.expect("redis error"),
             1
         );
         assert_eq!(
-            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:01:55.000Z").unwrap().into(), &client).await.unwrap(),
+            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:01:55.000Z").expect("invalid time").into(), &client).await.expect("redis error"),
             2
         );
         assert_eq!(
-            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:01:55.100Z").unwrap().into(), &client).await.unwrap(),
+            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:01:55.100Z").expect("invalid time").into(), &client).await.expect("redis error"),
             2
         );
 
         assert_eq!(
-            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:05:00.100Z").unwrap().into(), &client).await.unwrap(),
+            test.add_and_count(DateTime::parse_from_rfc3339("2000-01-01T01:05:00.100Z").expect("invalid time").into(), &client).await.expect("redis error"),
             0
         );
     }
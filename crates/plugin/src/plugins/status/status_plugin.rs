@@ -1,29 +1,24 @@
-#![allow(unused_assignments)]
-use http_body_util::Full;
-use hyper::{Request, Response};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, RwLock, Weak},
+};
+
+#[cfg(feature = "cache")]
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use spacegate_kernel::BoxError;
-use std::sync::Arc;
 
-type BoxResult<T> = Result<T, BoxError>;
-#[cfg(not(feature = "cache"))]
-use std::collections::HashMap;
-#[cfg(not(feature = "cache"))]
-use tardis::tardis_static;
-#[cfg(not(feature = "cache"))]
-use tardis::tokio::sync::RwLock;
 #[cfg(feature = "cache")]
 use spacegate_ext_redis::redis::AsyncCommands;
-#[cfg(feature = "cache")]
-use tardis::TardisFuns;
-#[cfg(not(feature = "cache"))]
-tardis_static! {
-    server_status: Arc<RwLock<HashMap<String, Status>>> = <_>::default();
-}
+
+use super::sliding_window::SlidingWindowCounter;
+
 const STATUS_TEMPLATE: &str = include_str!("status.html");
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub(crate) enum Status {
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
     #[default]
     Good,
     Minor,
@@ -31,99 +26,214 @@ pub(crate) enum Status {
 }
 
 impl Status {
-    fn to_html_css_class(&self) -> String {
+    fn to_html_css_class(self) -> &'static str {
         match self {
-            Status::Good => "good".to_string(),
-            Status::Minor => "minor".to_string(),
-            Status::Major => "major".to_string(),
+            Status::Good => "good",
+            Status::Minor => "minor",
+            Status::Major => "major",
         }
     }
 }
 
-pub(crate) async fn create_status_html<B>(
-    _: Request<B>,
-    _gateway_name: Arc<str>,
-    _cache_key: Arc<str>,
-    title: Arc<str>,
-) -> Result<Response<Full<hyper::body::Bytes>>, hyper::Error> {
-    let mut keys = Vec::<String>::new();
+/// Status board of a status plugin instance.
+///
+/// Without the `cache` feature, both the error windows and the statuses are kept in memory. With the `cache` feature,
+/// they are kept in redis so that all gateway nodes share the same board.
+#[derive(Debug)]
+pub struct StatusBoard {
+    pub title: Arc<str>,
+    unhealthy_threshold: u64,
+    interval: Duration,
+    #[cfg(not(feature = "cache"))]
+    counters: Mutex<HashMap<Arc<str>, SlidingWindowCounter>>,
+    #[cfg(not(feature = "cache"))]
+    statuses: RwLock<HashMap<Arc<str>, Status>>,
     #[cfg(feature = "cache")]
-    {
-        let cache_client = crate::cache::Cache::get(_gateway_name.as_ref()).await.expect("get cache client error!");
-        keys = cache_client.hkeys(_cache_key.as_ref()).await.expect("get cache keys error!");
+    status_cache_key: Arc<str>,
+    #[cfg(feature = "cache")]
+    window_cache_key: Arc<str>,
+    /// gateways whose redis holds the statuses of this board
+    #[cfg(feature = "cache")]
+    gateways: RwLock<HashSet<Arc<str>>>,
+}
+
+impl StatusBoard {
+    pub fn new(config: &super::SgFilterStatusConfig) -> Result<Self, BoxError> {
+        let interval =
+            Duration::try_seconds(config.interval as i64).filter(|d| !d.is_zero()).ok_or_else(|| format!("[SG.Plugin.Status] invalid interval config[{}]", config.interval))?;
+        Ok(Self {
+            title: config.title.as_str().into(),
+            unhealthy_threshold: config.unhealthy_threshold.into(),
+            interval,
+            #[cfg(not(feature = "cache"))]
+            counters: Default::default(),
+            #[cfg(not(feature = "cache"))]
+            statuses: Default::default(),
+            #[cfg(feature = "cache")]
+            status_cache_key: config.status_cache_key.as_str().into(),
+            #[cfg(feature = "cache")]
+            window_cache_key: config.window_cache_key.as_str().into(),
+            #[cfg(feature = "cache")]
+            gateways: Default::default(),
+        })
+    }
+
+    fn status_by_error_count(&self, count: u64) -> Status {
+        if count >= self.unhealthy_threshold {
+            Status::Major
+        } else {
+            Status::Minor
+        }
     }
+
+    /// Record a response of the backend.
+    ///
+    /// A server error moves the backend to [`Status::Minor`], or [`Status::Major`] once the errors in the window reach the unhealthy threshold,
+    /// any other response moves it (back) to [`Status::Good`].
     #[cfg(not(feature = "cache"))]
-    {
-        let status = server_status().read().await;
-        keys = status.keys().cloned().collect::<Vec<String>>();
+    pub fn record(&self, backend: &Arc<str>, is_error: bool, now: DateTime<Utc>) {
+        let status = if is_error {
+            let mut counters = self.counters.lock().expect("poisoned");
+            let counter = counters.entry(backend.clone()).or_insert_with(|| SlidingWindowCounter::new(self.interval, 60));
+            self.status_by_error_count(counter.add_and_count(now))
+        } else {
+            if self.statuses.read().expect("poisoned").get(backend) == Some(&Status::Good) {
+                return;
+            }
+            Status::Good
+        };
+        self.statuses.write().expect("poisoned").insert(backend.clone(), status);
     }
-    let mut service_html = "".to_string();
-    for ref key in keys {
-        let status;
-        #[cfg(feature = "cache")]
-        {
-            let cache_client = crate::cache::Cache::get(_gateway_name.as_ref()).await.expect("get cache client error!");
-            status = get_status(key, _cache_key.as_ref(), &cache_client).await.expect("");
+
+    /// Record a response of the backend, see the memory implementation for the rules.
+    #[cfg(feature = "cache")]
+    pub async fn record(&self, backend: &str, is_error: bool, now: DateTime<Utc>, gateway_name: &Arc<str>, client: &spacegate_ext_redis::RedisClient) -> Result<(), BoxError> {
+        if !self.gateways.read().expect("poisoned").contains(gateway_name) {
+            self.gateways.write().expect("poisoned").insert(gateway_name.clone());
         }
-        #[cfg(not(feature = "cache"))]
-        {
-            status = get_status(&key).await.expect("");
+        let status_key = self.status_cache_key(gateway_name);
+        let status = if is_error {
+            let window_key = format!("{}:{gateway_name}:{backend}", self.window_cache_key);
+            self.status_by_error_count(SlidingWindowCounter::new(self.interval, &window_key).add_and_count(now, client).await?)
+        } else {
+            let current: Option<String> = client.get_conn().await.hget(&status_key, backend).await?;
+            if current.and_then(|status| serde_json::from_str::<Status>(&status).ok()) == Some(Status::Good) {
+                return Ok(());
+            }
+            Status::Good
+        };
+        let _: () = client.get_conn().await.hset(&status_key, backend, serde_json::to_string(&status)?).await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "cache")]
+    fn status_cache_key(&self, gateway_name: &str) -> String {
+        format!("{}:{}", self.status_cache_key, gateway_name)
+    }
+
+    #[cfg(not(feature = "cache"))]
+    pub async fn statuses(&self) -> Result<Vec<(String, Status)>, BoxError> {
+        let mut statuses = self.statuses.read().expect("poisoned").iter().map(|(backend, status)| (backend.to_string(), *status)).collect::<Vec<_>>();
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(statuses)
+    }
+
+    #[cfg(feature = "cache")]
+    pub async fn statuses(&self) -> Result<Vec<(String, Status)>, BoxError> {
+        let gateways = self.gateways.read().expect("poisoned").iter().cloned().collect::<Vec<_>>();
+        let mut statuses = HashMap::new();
+        for gateway_name in gateways {
+            let Some(client) = spacegate_ext_redis::global_repo().get(&gateway_name) else {
+                continue;
+            };
+            let board: HashMap<String, String> = client.get_conn().await.hgetall(self.status_cache_key(&gateway_name)).await?;
+            for (backend, status) in board {
+                statuses.insert(backend, serde_json::from_str::<Status>(&status)?);
+            }
         }
-        if let Some(status) = status {
-            service_html.push_str(
-                format!(
-                    r##"<div class="service">
+        let mut statuses = statuses.into_iter().collect::<Vec<_>>();
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(statuses)
+    }
+
+    pub async fn render_html(&self) -> Result<String, BoxError> {
+        let mut service_html = String::new();
+        for (backend, status) in self.statuses().await? {
+            service_html.push_str(&format!(
+                r##"<div class="service">
                             <div class="service-name">{}</div>
                             <div class="service-status {}">Status</div>
                         </div>"##,
-                    key,
-                    status.to_html_css_class()
-                )
-                .as_str(),
-            );
-        };
+                escape_html(&backend),
+                status.to_html_css_class()
+            ));
+        }
+        Ok(STATUS_TEMPLATE.replace("{title}", &escape_html(&self.title)).replace("{status}", &service_html))
     }
-    let html = STATUS_TEMPLATE.replace("{title}", title.as_ref()).replace("{status}", &service_html);
-
-    Ok(Response::new(Full::new(html.into())))
 }
 
-#[cfg(feature = "cache")]
-pub(crate) async fn update_status(server_name: &str, _cache_key: &str, client: &spacegate_ext_redis::RedisClient, status: Status) -> BoxResult<()> {
-    client.get_conn().await.hset(_cache_key, server_name, &TardisFuns::json.obj_to_string(&status)?).await?;
-    Ok(())
-}
-#[cfg(not(feature = "cache"))]
-pub(crate) async fn update_status(server_name: &str, status: Status) -> BoxResult<()> {
-    let mut server_status = server_status().write().await;
-    server_status.insert(server_name.to_string(), status);
-    Ok(())
+/// Escape the text to be put into the status page.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
-#[cfg(feature = "cache")]
-pub(crate) async fn get_status(server_name: &str, cache_key: &str, client: &spacegate_ext_redis::RedisClient) -> BoxResult<Option<Status>> {
-    match client.get_conn().await.hget(cache_key, server_name).await? {
-        Some(result) => Ok(Some(TardisFuns::json.str_to_obj(&result)?)),
-        None => Ok(None),
-    }
+/// Boards of the living status plugin instances, keyed by the instance name.
+fn boards() -> &'static Mutex<HashMap<String, Weak<StatusBoard>>> {
+    static BOARDS: OnceLock<Mutex<HashMap<String, Weak<StatusBoard>>>> = OnceLock::new();
+    BOARDS.get_or_init(Default::default)
 }
-#[cfg(not(feature = "cache"))]
-pub(crate) async fn get_status(server_name: &str) -> BoxResult<Option<Status>> {
-    let server_status = server_status().read().await;
-    Ok(server_status.get(server_name).cloned())
+
+/// Publish the board, so that the status page could find it. The board is unpublished once it's dropped.
+pub fn publish_board(name: String, board: &Arc<StatusBoard>) {
+    let mut boards = boards().lock().expect("poisoned");
+    boards.retain(|_, board| board.strong_count() > 0);
+    boards.insert(name, Arc::downgrade(board));
 }
 
-#[cfg(feature = "cache")]
-pub(crate) async fn clean_status(cache_key: &str, gateway_name: &str) -> BoxResult<()> {
-    let client = spacegate_ext_redis::RedisClientRepo::global().get(gateway_name);
-    client.del(cache_key).await?;
-    Ok(())
+pub fn get_board(name: &str) -> Option<Arc<StatusBoard>> {
+    boards().lock().expect("poisoned").get(name).and_then(Weak::upgrade)
 }
 
+#[cfg(test)]
 #[cfg(not(feature = "cache"))]
-pub(crate) async fn clean_status(cache_key: &str, gateway_name: &str) -> BoxResult<()> {
-    let mut server_status = server_status().write().await;
-    server_status.clear();
+mod tests {
+    use super::*;
 
-    Ok(())
+    #[tokio::test]
+    async fn record_by_error_count() {
+        let board = StatusBoard::new(&super::super::SgFilterStatusConfig {
+            unhealthy_threshold: 2,
+            ..Default::default()
+        })
+        .expect("invalid config");
+        let backend: Arc<str> = "backend".into();
+        let now = Utc::now();
+        board.record(&backend, true, now);
+        assert_eq!(board.statuses().await.expect("statuses"), vec![("backend".to_string(), Status::Minor)]);
+        board.record(&backend, true, now);
+        board.record(&backend, true, now);
+        assert_eq!(board.statuses().await.expect("statuses"), vec![("backend".to_string(), Status::Major)]);
+        board.record(&backend, false, now);
+        assert_eq!(board.statuses().await.expect("statuses"), vec![("backend".to_string(), Status::Good)]);
+    }
+
+    #[tokio::test]
+    async fn render_html_escapes_backend_names() {
+        let board = StatusBoard::new(&Default::default()).expect("invalid config");
+        board.record(&"<script>alert('x')</script>".into(), true, Utc::now());
+        let html = board.render_html().await.expect("render html");
+        assert!(!html.contains("<script>alert"));
+        assert!(html.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;"));
+    }
 }
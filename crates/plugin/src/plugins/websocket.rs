@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use spacegate_kernel::{extension::WebSocketForward, helper_layers::function::Inner, BoxError, SgBody};
+
+use crate::Plugin;
+
+/// Settings of forwarding websocket connections, the absent ones are left to the defaults or other plugins.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(title = "WebSocket 转发插件配置"))]
+#[serde(default)]
+pub struct WebSocketPluginConfig {
+    /// max message size in bytes, the connection is closed with 1009 if it's exceeded
+    #[cfg_attr(feature = "schema", schemars(title = "最大消息大小"))]
+    pub max_message_size: Option<usize>,
+    /// interval of sending pings to both sides
+    #[cfg_attr(feature = "schema", schemars(title = "ping 间隔（毫秒）"))]
+    pub ping_interval_ms: Option<u64>,
+    /// the connection is closed if one side sends nothing in this duration
+    #[cfg_attr(feature = "schema", schemars(title = "空闲超时（毫秒）"))]
+    pub idle_timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebSocketPlugin {
+    pub config: WebSocketPluginConfig,
+}
+
+impl Plugin for WebSocketPlugin {
+    const CODE: &'static str = "websocket";
+
+    async fn call(&self, mut req: Request<SgBody>, inner: Inner) -> Result<Response<SgBody>, BoxError> {
+        let forward = req.extensions_mut().get_or_insert_default::<WebSocketForward>();
+        if let Some(max_message_size) = self.config.max_message_size {
+            forward.max_message_size = Some(max_message_size);
+        }
+        if let Some(ping_interval) = self.config.ping_interval_ms {
+            forward.ping_interval = Some(Duration::from_millis(ping_interval));
+        }
+        if let Some(idle_timeout) = self.config.idle_timeout_ms {
+            forward.idle_timeout = Some(Duration::from_millis(idle_timeout));
+        }
+        Ok(inner.call(req).await)
+    }
+
+    fn create(config: crate::PluginConfig) -> Result<Self, BoxError> {
+        let config = serde_json::from_value::<WebSocketPluginConfig>(config.spec)?;
+        Ok(Self { config })
+    }
+
+    #[cfg(feature = "schema")]
+    fn schema_opt() -> Option<schemars::schema::RootSchema> {
+        use crate::PluginSchemaExt;
+        Some(Self::schema())
+    }
+}
+
+#[cfg(feature = "schema")]
+crate::schema!(WebSocketPlugin, WebSocketPluginConfig);
+
+#[cfg(test)]
+mod test {
+    use hyper::service::service_fn;
+    use spacegate_kernel::ArcHyperService;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn set_websocket_forward() {
+        let plugin = WebSocketPlugin::create(crate::PluginConfig::new(
+            crate::PluginInstanceId::new(WebSocketPlugin::CODE, crate::PluginInstanceName::mono()),
+            serde_json::json!({ "max_message_size": 1024, "idle_timeout_ms": 60000 }),
+        ))
+        .expect("fail to create plugin");
+        let inner = Inner::new(ArcHyperService::new(service_fn(|req: Request<SgBody>| async move {
+            let forward = req.extensions().get::<WebSocketForward>().expect("websocket forward should be set");
+            assert_eq!(forward.max_message_size, Some(1024));
+            assert_eq!(forward.ping_interval, Some(Duration::from_secs(10)));
+            assert_eq!(forward.idle_timeout, Some(Duration::from_secs(60)));
+            Ok(Response::new(SgBody::empty()))
+        })));
+        let mut req = Request::new(SgBody::empty());
+        // the settings of other plugins are kept
+        req.extensions_mut().get_or_insert_default::<WebSocketForward>().ping_interval = Some(Duration::from_secs(10));
+        let resp = plugin.call(req, inner).await.expect("infallible");
+        assert!(resp.status().is_success());
+    }
+}
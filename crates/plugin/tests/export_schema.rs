@@ -19,25 +19,21 @@ macro_rules! export_plugins {
 #[test]
 fn export_schema() {
     use plugins::{
-        header_modifier::HeaderModifierPlugin,
-        inject::InjectPlugin,
-        limit::RateLimitPlugin,
-        maintenance::MaintenancePlugin,
-        redirect::RedirectPlugin,
-        // retry::RetryPlugin,
-        rewrite::RewritePlugin,
-        static_resource::StaticResourcePlugin,
+        compression::CompressionPlugin, header_modifier::HeaderModifierPlugin, inject::InjectPlugin, limit::RateLimitPlugin, maintenance::MaintenancePlugin,
+        redirect::RedirectPlugin, retry::RetryPlugin, rewrite::RewritePlugin, static_resource::StaticResourcePlugin, status::StatusPlugin,
     };
     use redis_plugins::{redis_count::RedisCountPlugin, redis_dynamic_route::RedisDynamicRoutePlugin, redis_limit::RedisLimitPlugin, redis_time_range::RedisTimeRangePlugin};
     export_plugins!("schema":
+        CompressionPlugin
         HeaderModifierPlugin
         InjectPlugin
         RateLimitPlugin
         MaintenancePlugin
         RedirectPlugin
-        // RetryPlugin
+        RetryPlugin
         RewritePlugin
         StaticResourcePlugin
+        StatusPlugin
         RedisCountPlugin
         RedisDynamicRoutePlugin
         RedisLimitPlugin
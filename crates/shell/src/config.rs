@@ -44,6 +44,9 @@ where
             server.set_bind(std::net::SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), port)).await;
         }
         let server_addr = server.get_bind().await;
+        #[cfg(all(unix, feature = "socket-handoff"))]
+        server.start_with_listener(crate::upgrade::bind_api_server(server_addr).await?).await?;
+        #[cfg(not(all(unix, feature = "socket-handoff")))]
         server.start().await?;
 
         info!(%server_addr, "Web server started.");
@@ -51,6 +54,8 @@ where
     };
     let mut listener = ListenerWrapper(listener);
     RunningSgGateway::global_init(init_config, shutdown_signal.clone()).await;
+    #[cfg(all(unix, feature = "socket-handoff"))]
+    crate::upgrade::notify_ready().await;
     info!("[SG.Config] Entering listening");
     let mut local_queue = VecDeque::new();
     let gateway_shutdown_signal = shutdown_signal.child_token();
@@ -63,6 +68,8 @@ where
                 _ = shutdown_signal.cancelled() => {
                     tracing::info!("[SG.Config] config listener {CONFIG_LISTENER_NAME} shutdown", CONFIG_LISTENER_NAME = C::CONFIG_LISTENER_NAME);
                     // listener.shutdown();
+                    // wait for the gateways to drain their connections
+                    RunningSgGateway::global_reset().await;
                     return Ok(());
                 }
                 event = listener.next() => {
@@ -100,6 +107,18 @@ async fn handler<C: Retrieve>(event: ListenEvent, config: &C, gateway_shutdown_s
         }
         (ConfigType::Gateway { name }, ConfigEventType::Update) => {
             if let Some(config) = config.retrieve_config_item(&name).await? {
+                match RunningSgGateway::global_reload_certs(&config.gateway) {
+                    Ok(true) => {
+                        tracing::info!("[SG.Config] gateway {name} certificates reloaded", name = name);
+                        return RunningSgGateway::global_update(&name, config.routes);
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        // keep serving with the previous certificates
+                        tracing::error!("[SG.Config] gateway {name} certificates reload failed: {e}", name = name, e = e);
+                        return Err(e);
+                    }
+                }
                 tracing::info!("[SG.Config] gateway {name} updated", name = name);
                 if let Some(inst) = RunningSgGateway::global_remove(&name) {
                     inst.shutdown().await;
@@ -1,6 +1,7 @@
-use std::str::FromStr;
+use std::{net::IpAddr, str::FromStr};
 
 use hyper::header::{HeaderName, HeaderValue};
+use ipnet::IpNet;
 use regex::Regex;
 use spacegate_config::model as config;
 use spacegate_kernel::{service::http_route::match_request as kernel, BoxError};
@@ -12,6 +13,7 @@ pub(crate) fn convert_config_to_kernel(config_match: config::SgHttpRouteMatch) -
             Some(config::SgHttpPathMatch::Exact { value, replace }) => Some(kernel::HttpPathMatchRewrite::Exact(value, replace)),
             Some(config::SgHttpPathMatch::Prefix { value, replace }) => Some(kernel::HttpPathMatchRewrite::Prefix(value, replace)),
             Some(config::SgHttpPathMatch::RegExp { value, replace }) => Some(kernel::HttpPathMatchRewrite::RegExp(Regex::new(&value)?, replace)),
+            Some(config::SgHttpPathMatch::Template { value, replace }) => Some(kernel::HttpPathMatchRewrite::Template(kernel::PathTemplate::new(value)?, replace)),
             None => None,
         },
         header: match config_match.header {
@@ -51,5 +53,43 @@ pub(crate) fn convert_config_to_kernel(config_match: config::SgHttpRouteMatch) -
             None => None,
         },
         method: config_match.method.map(|method| method.into_iter().map(|x| kernel::HttpMethodMatch(x.0)).collect()),
+        grpc: match config_match.grpc {
+            Some(config::SgGrpcMethodMatch::Exact { service, method }) => Some(kernel::GrpcMethodMatch::Exact { service, method }),
+            Some(config::SgGrpcMethodMatch::RegExp { service, method }) => Some(kernel::GrpcMethodMatch::RegExp {
+                service: service.as_deref().map(Regex::new).transpose()?,
+                method: method.as_deref().map(Regex::new).transpose()?,
+            }),
+            None => None,
+        },
+        cookie: match config_match.cookie {
+            Some(cookies) => Some(
+                cookies
+                    .into_iter()
+                    .map(|cookie| match cookie {
+                        config::SgHttpCookieMatch::Exact { name, value } => Ok(kernel::HttpCookieMatch {
+                            name,
+                            policy: kernel::SgHttpCookieMatchPolicy::Exact(value),
+                        }),
+                        config::SgHttpCookieMatch::RegExp { name, re } => Ok(kernel::HttpCookieMatch {
+                            name,
+                            policy: kernel::SgHttpCookieMatchPolicy::Regular(Regex::new(&re)?),
+                        }),
+                    })
+                    .collect::<Result<Vec<_>, BoxError>>()?,
+            ),
+            None => None,
+        },
+        source_ip: match config_match.source_ip {
+            Some(source_ip) => Some(
+                source_ip.iter().map(|ip| ip.parse::<IpNet>().or(ip.parse::<IpAddr>().map(IpNet::from)).map_err(|_| format!("invalid source ip: {ip}").into())).collect::<Result<
+                    Vec<_>,
+                    BoxError,
+                >>(
+                )?,
+            ),
+            None => None,
+        },
+        not: config_match.not.map(|not| convert_config_to_kernel(*not).map(Box::new)).transpose()?,
+        any: config_match.any.map(|any| any.into_iter().map(convert_config_to_kernel).collect::<Result<Vec<_>, _>>()).transpose()?,
     })
 }
@@ -1,5 +1,10 @@
 use spacegate_config::service::ListenEvent;
-use spacegate_ext_axum::axum::{self, http, routing::post, Extension, Json, Router};
+use spacegate_ext_axum::axum::{
+    self, http,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use spacegate_kernel::service::http_route::health_check::{HealthCheckProbe, HealthCheckRepo};
 use tokio::sync::mpsc::Sender;
 #[derive(Debug, Clone)]
 pub struct App {
@@ -13,7 +18,7 @@ pub fn shell_routers(router: Router) -> Router {
 }
 
 pub fn control_routes() -> Router {
-    Router::new().route("/push_event", post(event))
+    Router::new().route("/push_event", post(event)).route("/health_check", get(health_check))
 }
 
 pub struct HttpEventListener {}
@@ -26,3 +31,26 @@ pub async fn event(state: Extension<App>, event: Json<ListenEvent>) -> Result<()
         Ok(())
     }
 }
+
+/// Probe states of all backends with health check.
+pub async fn health_check() -> Json<Vec<serde_json::Value>> {
+    let reports = HealthCheckRepo::global().reports();
+    Json(
+        reports
+            .into_iter()
+            .map(|report| {
+                let probe = match report.check.probe {
+                    HealthCheckProbe::Http { path } => serde_json::json!({ "kind": "Http", "path": path }),
+                    HealthCheckProbe::Tcp => serde_json::json!({ "kind": "Tcp" }),
+                };
+                serde_json::json!({
+                    "target": report.target.to_string(),
+                    "probe": probe,
+                    "healthy": report.healthy,
+                    "consecutive_successes": report.consecutive_successes,
+                    "consecutive_failures": report.consecutive_failures,
+                })
+            })
+            .collect(),
+    )
+}
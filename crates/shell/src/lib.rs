@@ -53,6 +53,9 @@ pub mod extension;
 pub mod observability;
 /// Spacegate service creation
 pub mod server;
+#[cfg(all(unix, feature = "socket-handoff"))]
+/// Zero-downtime upgrade by socket handoff
+pub mod upgrade;
 
 /// Extended features
 pub mod ext_features;
@@ -129,7 +132,12 @@ where
     // 启用 `plugin-wasm` 时注册 `CODE = "wasm"`；注册放在 shell 而非 `spacegate-plugin`，避免与 `plugin-wasm` crate 循环依赖。
     #[cfg(feature = "plugin-wasm")]
     spacegate_plugin_wasm::register(spacegate_plugin::PluginRepository::global());
-    config::startup_with_shutdown_signal(config, ctrl_c_cancel_token()).await
+    #[cfg(all(unix, feature = "socket-handoff"))]
+    upgrade::inherit_sockets().await?;
+    let shutdown_signal = ctrl_c_cancel_token();
+    #[cfg(all(unix, feature = "socket-handoff"))]
+    upgrade::upgrade_on_signal(shutdown_signal.clone())?;
+    config::startup_with_shutdown_signal(config, shutdown_signal).await
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -1,39 +1,65 @@
 use std::{
+    borrow::Cow,
     collections::{BTreeMap, HashMap},
     net::SocketAddr,
     sync::{Arc, Mutex, OnceLock},
 };
 
-use crate::config::{matches_convert::convert_config_to_kernel, plugin_filter_dto::global_batch_mount_plugin, PluginConfig, SgProtocolConfig, SgTlsMode};
+use crate::config::{
+    matches_convert::convert_config_to_kernel, plugin_filter_dto::global_batch_mount_plugin, PluginConfig, SgGateway, SgProtocolConfig, SgTlsClientAuthMode, SgTlsConfig, SgTlsMode,
+};
 
 use hyper::Version;
+use ipnet::IpNet;
 use spacegate_config::{
-    BackendHost, Config, ConfigItem, McpSessionAffinity, SgBalancePolicy, SgHttpMethodMatch, SgHttpPathMatch, SgHttpRouteMatch, SgMcpRoute, SgMcpTransport, SgRoute, TimeoutMode,
+    BackendHost, Config, ConfigItem, McpSessionAffinity, SgBackendTls, SgBalancePolicy, SgCanaryKey, SgCanaryPolicy, SgConnectionLimits, SgHealthCheck, SgHealthCheckProbe,
+    SgHttpMethodMatch, SgHttpPathMatch, SgHttpRouteMatch, SgMcpRoute, SgMcpTransport, SgProxyProtocol, SgRoute, SgRoutePrecedence, SgTcpService, TimeoutMode,
 };
+#[cfg(feature = "http3")]
+use spacegate_kernel::listener::SgHttp3Listen;
 use spacegate_kernel::{
+    backend_service::http_client_service::{ClientRepo, ClientTlsProfile},
+    helper_layers::balancer::{CanaryConfig, CanaryKey, StickyCookie},
     helper_layers::map_request::MapRequestLayer,
     helper_layers::reload::Reloader,
-    listener::SgListen,
-    service::http_gateway::{builder::default_gateway_route_fallback, create_http_router, HttpRouterService},
+    listener::{ProxyProtocol, SgListen, SgUdpListen},
+    service::http_gateway::{builder::default_gateway_route_fallback, create_http_router, path_tree::RoutePrecedence, HttpRouterService},
+    service::http_route::{
+        health_check::{HealthCheck, HealthCheckProbe},
+        DEFAULT_TIMEOUT,
+    },
+    service::repository::TcpServiceRepository,
+    service::stream_proxy::{StreamBackend, StreamRoute, StreamRouter, TcpProxy, UdpProxy},
+    service::tls_cert::{certified_key_from_pem, root_cert_store_from_pem, ReloadableCertResolver, SniCertResolver},
+    service::tls_passthrough::{TlsBackend, TlsPassthrough, TlsRoute, TlsRouter},
+    service::TcpService,
+    utils::BodySizeLimit,
     ArcHyperService, BoxError, BoxLayer,
 };
 use spacegate_plugin::{mount::MountPointIndex, PluginRepository};
 use std::time::Duration;
 use std::vec::Vec;
 use tokio::time::timeout;
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{error, instrument, warn};
 
-use tokio_rustls::rustls::{self, pki_types::PrivateKeyDer};
+use tokio_rustls::rustls::{
+    self,
+    server::{ResolvesServerCert, WebPkiClientVerifier},
+};
 use tokio_util::sync::CancellationToken;
 
+/// Wait at least this long for the gateway to shutdown.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 fn collect_http_route(
     gateway_name: Arc<str>,
     http_routes: impl IntoIterator<Item = (String, SgRoute)>,
+    ignore_tls_verification: bool,
 ) -> Result<HashMap<String, spacegate_kernel::service::http_route::HttpRoute>, BoxError> {
     http_routes
         .into_iter()
-        .map(|(name, route)| {
-            let (route, mcp_transport) = compile_route(route);
+        .filter_map(|(name, route)| compile_route(route).map(|compiled| (name, compiled)))
+        .map(|(name, (route, mcp_transport))| {
             let route_name: Arc<str> = name.clone().into();
             let mount_index = MountPointIndex::HttpRoute {
                 gateway: gateway_name.clone(),
@@ -67,6 +93,8 @@ fn collect_http_route(
                     } else {
                         builder.match_all()
                     };
+                    // the connect timeout of the rule is the default of its backends
+                    let connect_timeout_ms = route_rule.connect_timeout_ms;
                     let backends = route_rule
                         .backends
                         .into_iter()
@@ -96,11 +124,27 @@ fn collect_http_route(
                             if let Some(port) = backend.port {
                                 builder = builder.port(port)
                             }
-                            if let Some(timeout) = backend.timeout_ms.map(|timeout| Duration::from_millis(timeout as u64)) {
-                                builder = builder.timeout(timeout)
+                            let timeout = backend.timeout_ms.map(|timeout| Duration::from_millis(timeout as u64));
+                            match backend.timeout_mode {
+                                Some(TimeoutMode::Disabled) => builder = builder.disable_timeout(),
+                                Some(TimeoutMode::Idle) => builder = builder.idle_timeout(timeout.unwrap_or(DEFAULT_TIMEOUT)),
+                                Some(TimeoutMode::Request) | None => {
+                                    if let Some(timeout) = timeout {
+                                        builder = builder.timeout(timeout)
+                                    }
+                                }
+                            }
+                            if let Some(timeout) = backend.first_byte_timeout_ms {
+                                builder = builder.first_byte_timeout(Duration::from_millis(timeout as u64))
+                            }
+                            if let Some(timeout) = backend.connect_timeout_ms.or(connect_timeout_ms) {
+                                builder = builder.connect_timeout(Duration::from_millis(timeout as u64))
+                            }
+                            if let Some(health_check) = backend.health_check {
+                                builder = builder.health_check(convert_health_check(health_check)?)
                             }
-                            if backend.timeout_mode == Some(TimeoutMode::Disabled) {
-                                builder = builder.disable_timeout()
+                            if let Some(tls) = create_client_tls_profile(backend.tls.as_ref(), ignore_tls_verification)? {
+                                builder = builder.tls(tls)
                             }
                             let mut layer = if let BackendHost::File { path } = backend.host {
                                 builder.file().path(path).build()
@@ -119,17 +163,25 @@ fn collect_http_route(
                         })
                         .collect::<Result<Vec<_>, _>>()?;
                     builder = builder.backends(backends);
-                    if let Some(timeout) = route_rule.timeout_ms {
-                        builder = builder.timeout(Duration::from_millis(timeout as u64));
+                    let timeout = route_rule.timeout_ms.map(|timeout| Duration::from_millis(timeout as u64));
+                    match route_rule.timeout_mode {
+                        Some(TimeoutMode::Disabled) => builder = builder.disable_timeout(),
+                        Some(TimeoutMode::Idle) => builder = builder.idle_timeout(timeout.unwrap_or(DEFAULT_TIMEOUT)),
+                        Some(TimeoutMode::Request) | None => {
+                            if let Some(timeout) = timeout {
+                                builder = builder.timeout(timeout);
+                            }
+                        }
                     }
-                    if route_rule.timeout_mode == Some(TimeoutMode::Disabled) {
-                        builder = builder.disable_timeout();
+                    if let Some(timeout) = route_rule.first_byte_timeout_ms {
+                        builder = builder.first_byte_timeout(Duration::from_millis(timeout as u64));
                     }
                     if let Some(policy) = route_rule.balance_policy {
                         builder = builder.balance_policy(match policy {
                             SgBalancePolicy::Random => spacegate_kernel::service::http_route::BalancePolicyEnum::Random,
                             SgBalancePolicy::IpHash => spacegate_kernel::service::http_route::BalancePolicyEnum::IpHash,
                             SgBalancePolicy::McpSession => spacegate_kernel::service::http_route::BalancePolicyEnum::McpSession,
+                            SgBalancePolicy::Canary(canary) => spacegate_kernel::service::http_route::BalancePolicyEnum::Canary(convert_canary_policy(canary)?),
                         });
                     }
                     let mut layer = builder.build();
@@ -145,11 +197,80 @@ fn collect_http_route(
         .collect::<Result<HashMap<String, _>, _>>()
 }
 
-fn compile_route(route: SgRoute) -> (crate::SgHttpRoute, Option<String>) {
+/// Compile a route into http route, tls routes are not http routes and will be skipped.
+fn compile_route(route: SgRoute) -> Option<(crate::SgHttpRoute, Option<String>)> {
     match route {
-        SgRoute::Http(route) => (route, None),
-        SgRoute::Mcp(route) => compile_mcp_route_to_http_route(route),
+        SgRoute::Http(route) => Some((route, None)),
+        SgRoute::Mcp(route) => Some(compile_mcp_route_to_http_route(route)),
+        SgRoute::Tls(_) | SgRoute::Tcp(_) | SgRoute::Udp(_) => None,
+    }
+}
+
+fn collect_tls_route(routes: impl IntoIterator<Item = (String, SgRoute)>) -> TlsRouter {
+    let routes = routes.into_iter().filter_map(|(name, route)| {
+        let SgRoute::Tls(route) = route else {
+            return None;
+        };
+        let backends = route
+            .backends
+            .into_iter()
+            .filter_map(|backend| {
+                if let BackendHost::File { .. } = backend.host {
+                    warn!("[SG.Server] file backend is not supported by tls route {name}");
+                    return None;
+                }
+                Some(TlsBackend {
+                    host: backend.host.to_string(),
+                    port: backend.port,
+                    weight: backend.weight.unwrap_or(1),
+                })
+            })
+            .collect();
+        Some(TlsRoute {
+            name,
+            hostnames: route.hostnames.unwrap_or_default(),
+            backends,
+            priority: route.priority,
+        })
+    });
+    TlsRouter::new(routes)
+}
+
+/// Collect the routers of `tcp` and `udp` listeners.
+fn collect_stream_route(routes: impl IntoIterator<Item = (String, SgRoute)>) -> Result<(StreamRouter, StreamRouter), BoxError> {
+    let mut tcp_routes = Vec::new();
+    let mut udp_routes = Vec::new();
+    for (name, route) in routes {
+        let (routes, listener, backends, idle_timeout_ms) = match route {
+            SgRoute::Tcp(route) => (&mut tcp_routes, route.listener, route.backends, route.idle_timeout_ms),
+            SgRoute::Udp(route) => (&mut udp_routes, route.listener, route.backends, route.idle_timeout_ms),
+            _ => continue,
+        };
+        if idle_timeout_ms == Some(0) {
+            return Err(format!("the idle timeout of stream route {name} should be greater than 0").into());
+        }
+        let backends = backends
+            .into_iter()
+            .filter_map(|backend| {
+                if let BackendHost::File { .. } = backend.host {
+                    warn!("[SG.Server] file backend is not supported by stream route {name}");
+                    return None;
+                }
+                Some(StreamBackend {
+                    host: backend.host.to_string(),
+                    port: backend.port,
+                    weight: backend.weight.unwrap_or(1),
+                })
+            })
+            .collect();
+        routes.push(StreamRoute {
+            name,
+            listener,
+            backends,
+            idle_timeout: idle_timeout_ms.map(Duration::from_millis),
+        });
     }
+    Ok((StreamRouter::new(tcp_routes), StreamRouter::new(udp_routes)))
 }
 
 fn compile_mcp_route_to_http_route(route: SgMcpRoute) -> (crate::SgHttpRoute, Option<String>) {
@@ -182,8 +303,10 @@ fn compile_mcp_route_to_http_route(route: SgMcpRoute) -> (crate::SgHttpRoute, Op
                         backend
                     })
                     .collect(),
-                timeout_ms: None,
+                timeout_ms: route.timeout_ms,
                 timeout_mode: Some(timeout_mode),
+                connect_timeout_ms: None,
+                first_byte_timeout_ms: None,
                 balance_policy: (session_affinity == McpSessionAffinity::McpSession).then_some(spacegate_config::SgBalancePolicy::McpSession),
             }],
             priority: 1,
@@ -201,29 +324,230 @@ fn mcp_path_method_match(path: String, method: impl Into<String>) -> SgHttpRoute
         header: None,
         query: None,
         method: Some(vec![SgHttpMethodMatch(method.into())]),
+        grpc: None,
+        cookie: None,
+        source_ip: None,
+        not: None,
+        any: None,
+    }
+}
+
+fn convert_health_check(health_check: SgHealthCheck) -> Result<HealthCheck, BoxError> {
+    if health_check.interval_ms == 0 || health_check.timeout_ms == 0 {
+        return Err("the interval and timeout of health check should be greater than 0".into());
+    }
+    if health_check.healthy_threshold == 0 || health_check.unhealthy_threshold == 0 {
+        return Err("the healthy and unhealthy threshold of health check should be greater than 0".into());
+    }
+    Ok(HealthCheck {
+        probe: match health_check.probe {
+            SgHealthCheckProbe::Http { path } => HealthCheckProbe::Http { path },
+            SgHealthCheckProbe::Tcp => HealthCheckProbe::Tcp,
+        },
+        interval: Duration::from_millis(health_check.interval_ms as u64),
+        timeout: Duration::from_millis(health_check.timeout_ms as u64),
+        healthy_threshold: health_check.healthy_threshold,
+        unhealthy_threshold: health_check.unhealthy_threshold,
+    })
+}
+
+fn convert_canary_policy(canary: SgCanaryPolicy) -> Result<CanaryConfig, BoxError> {
+    Ok(CanaryConfig {
+        key: canary
+            .key
+            .map(|key| {
+                Result::<_, BoxError>::Ok(match key {
+                    SgCanaryKey::Header { name } => CanaryKey::Header(name.parse()?),
+                    SgCanaryKey::Cookie { name } => CanaryKey::Cookie(name),
+                    SgCanaryKey::JwtClaim { claim } => CanaryKey::JwtClaim(claim),
+                })
+            })
+            .transpose()?,
+        override_header: canary.override_header.map(|header| header.parse()).transpose()?,
+        sticky_cookie: canary.sticky_cookie.map(|cookie| StickyCookie {
+            name: cookie.name,
+            max_age: cookie.max_age_s.map(Duration::from_secs),
+        }),
+    })
+}
+
+/// Create the certificate resolver of a tls listener.
+///
+/// The certificate in `key` and `cert` is bound to the listener's hostname if there is one, otherwise it's the default certificate.
+fn create_cert_resolver(tls: &SgTlsConfig, hostname: Option<&str>) -> Result<SniCertResolver, BoxError> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let mut resolver = SniCertResolver::new();
+    if tls.cert_path.is_some() || !tls.cert.trim().is_empty() {
+        let cert = read_pem(&tls.cert, tls.cert_path.as_deref())?;
+        let key = read_pem(&tls.key, tls.key_path.as_deref())?;
+        let cert = Arc::new(certified_key_from_pem(&cert, &key)?);
+        match hostname {
+            Some(hostname) => resolver.add(hostname, cert),
+            None => resolver.set_default(cert),
+        }
+    }
+    for item in &tls.certs {
+        let cert = read_pem(&item.cert, item.cert_path.as_deref())?;
+        let key = read_pem(&item.key, item.key_path.as_deref())?;
+        let cert = Arc::new(certified_key_from_pem(&cert, &key)?);
+        for hostname in &item.hostnames {
+            resolver.add(hostname, cert.clone());
+        }
+    }
+    if resolver.is_empty() {
+        return Err("no certificate configured".into());
+    }
+    Ok(resolver)
+}
+
+/// Create the tls server config of a terminating listener, clients are authenticated if `client_auth` is configured.
+///
+/// The client CA is returned along with the config, the verifier can't be reloaded, so a changed CA requires a restart.
+fn create_tls_server_config(tls: &SgTlsConfig, resolver: Arc<dyn ResolvesServerCert>) -> Result<(rustls::ServerConfig, Option<Vec<u8>>), BoxError> {
+    let builder = rustls::ServerConfig::builder();
+    let (builder, client_ca) = if let Some(client_auth) = &tls.client_auth {
+        let ca = read_pem(&client_auth.ca, client_auth.ca_path.as_deref())?;
+        let verifier = WebPkiClientVerifier::builder(Arc::new(root_cert_store_from_pem(&ca)?));
+        let verifier = match client_auth.mode {
+            SgTlsClientAuthMode::Require => verifier.build()?,
+            SgTlsClientAuthMode::Optional => verifier.allow_unauthenticated().build()?,
+        };
+        (builder.with_client_cert_verifier(verifier), Some(ca.into_owned()))
+    } else {
+        (builder.with_no_client_auth(), None)
+    };
+    let mut tls_server_cfg = builder.with_cert_resolver(resolver);
+    tls_server_cfg.alpn_protocols = vec![b"http/1.1".to_vec(), b"h2".to_vec()];
+    tls_server_cfg.ignore_client_order = true;
+    tls_server_cfg.enable_secret_extraction = true;
+    Ok((tls_server_cfg, client_ca))
+}
+
+/// Add the `Alt-Svc` header to responses, so that clients could upgrade to http3 on the same port.
+#[cfg(feature = "http3")]
+fn advertise_http3(service: ArcHyperService, port: u16) -> ArcHyperService {
+    use spacegate_kernel::{helper_layers::map_response::MapResponseLayer, listener::http3::alt_svc, Layer};
+    let alt_svc = alt_svc(port);
+    ArcHyperService::new(
+        MapResponseLayer::new(move |mut resp: spacegate_kernel::SgResponse| {
+            resp.headers_mut().entry(hyper::header::ALT_SVC).or_insert_with(|| alt_svc.clone());
+            resp
+        })
+        .layer(service),
+    )
+}
+
+/// Parse the trusted sources of PROXY protocol, a plain ip is taken as a single host network.
+fn create_proxy_protocol(proxy_protocol: &SgProxyProtocol) -> Result<ProxyProtocol, BoxError> {
+    // anyone could forge its address by a PROXY protocol header, so the trusted sources must be explicit
+    if proxy_protocol.trusted_cidrs.is_empty() {
+        return Err("trusted cidrs of proxy protocol should not be empty".into());
     }
+    let trusted_sources = proxy_protocol
+        .trusted_cidrs
+        .iter()
+        .map(|cidr| {
+            let cidr = cidr.trim();
+            cidr.parse::<IpNet>()
+                .or_else(|_| cidr.parse::<std::net::IpAddr>().map(IpNet::from))
+                .map_err(|_| BoxError::from(format!("invalid trusted cidr of proxy protocol: {cidr}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ProxyProtocol::new(trusted_sources))
+}
+
+/// Create the custom tcp services of a listener by the global [`TcpServiceRepository`].
+fn create_tcp_services(tcp_services: &[SgTcpService]) -> Result<Vec<Arc<dyn TcpService>>, BoxError> {
+    tcp_services.iter().map(|service| TcpServiceRepository::global().create(&service.code, service.spec.clone())).collect()
+}
+
+fn apply_connection_limits(mut listen: SgListen, limits: &SgConnectionLimits) -> Result<SgListen, BoxError> {
+    if [limits.max_connections, limits.max_connections_per_ip, limits.max_udp_sessions].contains(&Some(0)) {
+        return Err("the max connections and max udp sessions of listener should be greater than 0".into());
+    }
+    if let Some(max_connections) = limits.max_connections {
+        listen = listen.with_max_connections(max_connections);
+    }
+    if let Some(max_connections_per_ip) = limits.max_connections_per_ip {
+        listen = listen.with_max_connections_per_ip(max_connections_per_ip);
+    }
+    if let Some(drain_timeout_ms) = limits.drain_timeout_ms {
+        listen = listen.with_grace_period(Duration::from_millis(drain_timeout_ms));
+    }
+    Ok(listen)
+}
+
+fn read_pem<'a>(inline: &'a str, path: Option<&str>) -> Result<Cow<'a, [u8]>, BoxError> {
+    match path {
+        Some(path) => Ok(Cow::Owned(std::fs::read(path).map_err(|e| format!("fail to read pem file {path}: {e}"))?)),
+        None => Ok(Cow::Borrowed(inline.as_bytes())),
+    }
+}
+
+/// Create the tls profile of a backend, the backends without tls settings skip the verification if the gateway ignores it.
+///
+/// The client of the profile is created here, so that invalid certificates are reported when the routes are compiled.
+fn create_client_tls_profile(tls: Option<&SgBackendTls>, ignore_tls_verification: bool) -> Result<Option<ClientTlsProfile>, BoxError> {
+    let Some(tls) = tls else {
+        return Ok(ignore_tls_verification.then(ClientTlsProfile::dangerous));
+    };
+    let mut profile = ClientTlsProfile::default().with_verify(tls.verify);
+    if tls.ca.is_some() || tls.ca_path.is_some() {
+        profile = profile.with_ca(read_pem(tls.ca.as_deref().unwrap_or_default(), tls.ca_path.as_deref())?.as_ref());
+    }
+    match (tls.cert.is_some() || tls.cert_path.is_some(), tls.key.is_some() || tls.key_path.is_some()) {
+        (true, true) => {
+            let cert = read_pem(tls.cert.as_deref().unwrap_or_default(), tls.cert_path.as_deref())?;
+            let key = read_pem(tls.key.as_deref().unwrap_or_default(), tls.key_path.as_deref())?;
+            profile = profile.with_client_cert(cert.as_ref(), key.as_ref());
+        }
+        (false, false) => {}
+        _ => return Err("backend tls client certificate and key should be set together".into()),
+    }
+    if let Some(sni) = &tls.sni {
+        profile = profile.with_sni(sni);
+    }
+    ClientRepo::global().read().expect("failed to read global client repo").get_or_create(&profile)?;
+    Ok(Some(profile))
 }
 
 /// Create a gateway service from plugins and http_routes
 pub(crate) fn create_service(item: ConfigItem, reloader: Reloader<HttpRouterService>) -> Result<ArcHyperService, BoxError> {
     let gateway_name: Arc<str> = item.gateway.name.into();
     let http_routes = item.routes;
-    let routes = collect_http_route(gateway_name.clone(), http_routes)?;
+    let routes = collect_http_route(gateway_name.clone(), http_routes, item.gateway.parameters.ignore_tls_verification.unwrap_or(false))?;
     let plugins = item.gateway.plugins.clone();
     let mut builder = spacegate_kernel::service::http_gateway::Gateway::builder(gateway_name.clone());
     if let Some(enable) = item.gateway.parameters.enable_x_request_id {
         builder = builder.x_request_id(enable);
     }
+    builder = builder.body_size_limit(BodySizeLimit::new(
+        item.gateway.parameters.max_request_body_size,
+        item.gateway.parameters.max_response_body_size,
+    ));
+    builder = builder.http_route_precedence(route_precedence(item.gateway.parameters.route_precedence));
     let mut layer = builder.http_routers(routes).http_route_reloader(reloader).build();
     global_batch_mount_plugin(plugins, &mut layer, MountPointIndex::Gateway { gateway: gateway_name });
     let service = layer.as_service();
     Ok(service)
 }
 
+fn route_precedence(precedence: Option<SgRoutePrecedence>) -> RoutePrecedence {
+    match precedence.unwrap_or_default() {
+        SgRoutePrecedence::Priority => RoutePrecedence::Priority,
+        SgRoutePrecedence::GatewayApi => RoutePrecedence::GatewayApi,
+    }
+}
+
 /// create a new sg gateway route, which can be sent to reloader
-pub(crate) fn create_router_service(gateway_name: Arc<str>, http_routes: BTreeMap<String, SgRoute>) -> Result<HttpRouterService, BoxError> {
-    let routes = collect_http_route(gateway_name, http_routes.clone())?;
-    let service = create_http_router(routes.values(), default_gateway_route_fallback());
+pub(crate) fn create_router_service(
+    gateway_name: Arc<str>,
+    http_routes: BTreeMap<String, SgRoute>,
+    ignore_tls_verification: bool,
+    precedence: Option<SgRoutePrecedence>,
+) -> Result<HttpRouterService, BoxError> {
+    let routes = collect_http_route(gateway_name, http_routes.clone(), ignore_tls_verification)?;
+    let service = create_http_router(routes.values(), default_gateway_route_fallback(), route_precedence(precedence));
     Ok(service)
 }
 
@@ -240,6 +564,14 @@ pub struct RunningSgGateway {
     token: CancellationToken,
     handle: tokio::task::JoinHandle<()>,
     pub reloader: Reloader<HttpRouterService>,
+    pub tls_reloader: Reloader<TlsRouter>,
+    pub tcp_reloader: Reloader<StreamRouter>,
+    pub udp_reloader: Reloader<StreamRouter>,
+    /// Certificate reloaders of tls listeners, keyed by the index of listener.
+    cert_reloaders: HashMap<usize, Reloader<SniCertResolver>>,
+    /// Client CAs of tls listeners, keyed by the index of listener.
+    client_cas: HashMap<usize, Vec<u8>>,
+    gateway: SgGateway,
     shutdown_timeout: Duration,
 }
 impl std::fmt::Debug for RunningSgGateway {
@@ -301,20 +633,72 @@ impl RunningSgGateway {
 
     pub fn global_update(gateway_name: impl AsRef<str>, http_routes: BTreeMap<String, SgRoute>) -> Result<(), BoxError> {
         let gateway_name = gateway_name.as_ref();
-        let service = create_router_service(gateway_name.to_string().into(), http_routes)?;
-        let reloader = {
+        let (reloader, tls_reloader, tcp_reloader, udp_reloader, ignore_tls_verification, precedence) = {
             let store = Self::global_store();
             let global_store = store.lock().expect("poisoned lock");
             if let Some(gw) = global_store.get(gateway_name) {
-                gw.reloader.clone()
+                (
+                    gw.reloader.clone(),
+                    gw.tls_reloader.clone(),
+                    gw.tcp_reloader.clone(),
+                    gw.udp_reloader.clone(),
+                    gw.gateway.parameters.ignore_tls_verification.unwrap_or(false),
+                    gw.gateway.parameters.route_precedence,
+                )
             } else {
                 warn!("no such gateway in global repository: {gateway_name}");
                 return Ok(());
             }
         };
+        let tls_router = collect_tls_route(http_routes.clone());
+        let (tcp_router, udp_router) = collect_stream_route(http_routes.clone())?;
+        let service = create_router_service(gateway_name.to_string().into(), http_routes, ignore_tls_verification, precedence)?;
         reloader.reload(service);
+        tls_reloader.reload(tls_router);
+        tcp_reloader.reload(tcp_router);
+        udp_reloader.reload(udp_router);
         Ok(())
     }
+    /// Replace the certificates of a running gateway, if nothing but the tls certificates and keys is changed.
+    ///
+    /// Returns `false` if the gateway should be restarted to apply the new config.
+    pub fn global_reload_certs(gateway: &SgGateway) -> Result<bool, BoxError> {
+        let store = Self::global_store();
+        let mut global_store = store.lock().expect("poisoned lock");
+        let Some(running) = global_store.get_mut(&gateway.name) else {
+            return Ok(false);
+        };
+        if !running.gateway.eq_ignore_tls_material(gateway) {
+            return Ok(false);
+        }
+        // load all certificates first, so that a broken one won't leave the listeners partly updated
+        let mut resolvers = Vec::new();
+        for (index, listener) in gateway.listeners.iter().enumerate() {
+            let SgProtocolConfig::Https { ref tls } = listener.protocol else {
+                continue;
+            };
+            if tls.mode != SgTlsMode::Terminate {
+                continue;
+            }
+            let Some(reloader) = running.cert_reloaders.get(&index) else {
+                // the listener failed to start, restart the gateway to bring it up
+                return Ok(false);
+            };
+            if let Some(client_auth) = &tls.client_auth {
+                // the client verifier can't be reloaded, restart the gateway to pick up the new CA
+                let ca = read_pem(&client_auth.ca, client_auth.ca_path.as_deref())?;
+                if running.client_cas.get(&index).map(Vec::as_slice) != Some(ca.as_ref()) {
+                    return Ok(false);
+                }
+            }
+            resolvers.push((reloader.clone(), create_cert_resolver(tls, listener.hostname.as_deref())?));
+        }
+        for (reloader, resolver) in resolvers {
+            reloader.reload(resolver);
+        }
+        running.gateway = gateway.clone();
+        Ok(true)
+    }
     /// Start a gateway from plugins and http_routes
     #[instrument(fields(gateway=%config_item.gateway.name), skip_all, err)]
     pub fn create(config_item: ConfigItem, cancel_token: CancellationToken) -> Result<Self, BoxError> {
@@ -333,69 +717,116 @@ impl RunningSgGateway {
         }
         tracing::info!("[SG.Server] start gateway");
         let reloader = <Reloader<HttpRouterService>>::default();
+        let tls_reloader = <Reloader<TlsRouter>>::default();
         let gateway = config_item.gateway.clone();
+        let tls_router = collect_tls_route(config_item.routes.clone());
+        let tcp_reloader = <Reloader<StreamRouter>>::default();
+        let udp_reloader = <Reloader<StreamRouter>>::default();
+        let (tcp_router, udp_router) = collect_stream_route(config_item.routes.clone())?;
         let service = create_service(config_item, reloader.clone())?;
         if gateway.listeners.is_empty() {
             error!("[SG.Server] Missing Listeners");
         }
 
         let gateway_name: Arc<str> = Arc::from(gateway.name.to_string());
+        let tls_passthrough = TlsPassthrough::with_gateway_name(tls_router, gateway_name.clone()).with_reloader(&tls_reloader);
+        let tcp_proxy = TcpProxy::with_gateway_name(tcp_router, gateway_name.clone()).with_reloader(&tcp_reloader);
+        let udp_proxy = UdpProxy::with_gateway_name(udp_router, gateway_name.clone()).with_reloader(&udp_reloader);
+        let mut udp_listens: Vec<SgUdpListen> = Vec::new();
         let mut listens: Vec<SgListen> = Vec::new();
-        for listener in &gateway.listeners {
+        #[cfg(feature = "http3")]
+        let mut http3_listens: Vec<SgHttp3Listen> = Vec::new();
+        let mut cert_reloaders = HashMap::new();
+        let mut client_cas = HashMap::new();
+        for (index, listener) in gateway.listeners.iter().enumerate() {
             let ip = listener.ip.unwrap_or(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED));
             let addr = SocketAddr::new(ip, listener.port);
-            let mut listen = SgListen::new(addr, cancel_token.child_token());
+            // custom services go first, since the service of the protocol may accept anything, e.g. the tcp proxy
+            let mut listen = SgListen::new(addr, cancel_token.child_token()).with_services(create_tcp_services(&listener.tcp_services)?);
+            if let Some(proxy_protocol) = &listener.proxy_protocol {
+                listen = listen.with_proxy_protocol(create_proxy_protocol(proxy_protocol)?);
+            }
+            let limits = listener.connection_limits.clone().unwrap_or_default();
+            listen = apply_connection_limits(listen, &limits)?;
+            let header_read_timeout = limits.header_read_timeout_ms.map(Duration::from_millis);
+            let keep_alive_timeout = limits.keep_alive_timeout_ms.map(Duration::from_millis);
             if let SgProtocolConfig::Https { ref tls } = listener.protocol {
                 tracing::debug!("[SG.Server] Tls is init...mode:{:?}", tls.mode);
                 if SgTlsMode::Terminate == tls.mode {
-                    {
-                        let certs = rustls_pemfile::certs(&mut tls.cert.as_bytes()).filter_map(Result::ok).collect::<Vec<_>>();
-                        let mut tls_key = tls.key.as_bytes();
-                        let mut keys = rustls_pemfile::read_all(&mut tls_key).filter_map(Result::ok);
-
-                        let key = keys.find_map(|key| {
-                            debug!("key item: {:?}", key);
-                            match key {
-                                rustls_pemfile::Item::Pkcs1Key(k) => Some(PrivateKeyDer::Pkcs1(k)),
-                                rustls_pemfile::Item::Pkcs8Key(k) => Some(PrivateKeyDer::Pkcs8(k)),
-                                rustls_pemfile::Item::Sec1Key(k) => Some(PrivateKeyDer::Sec1(k)),
-                                rest => {
-                                    warn!("Unsupported key type: {:?}", rest);
-                                    None
-                                }
+                    let tls_server_cfg = create_cert_resolver(tls, listener.hostname.as_deref()).and_then(|resolver| {
+                        let cert_reloader = <Reloader<SniCertResolver>>::default();
+                        let resolver = ReloadableCertResolver::new(resolver).with_reloader(&cert_reloader);
+                        let (tls_server_cfg, client_ca) = create_tls_server_config(tls, Arc::new(resolver))?;
+                        Ok((cert_reloader, tls_server_cfg, client_ca))
+                    });
+                    match tls_server_cfg {
+                        Ok((cert_reloader, tls_server_cfg, client_ca)) => {
+                            cert_reloaders.insert(index, cert_reloader);
+                            if let Some(client_ca) = client_ca {
+                                client_cas.insert(index, client_ca);
                             }
-                        });
-                        if let Some(key) = key {
-                            info!("[SG.Server] using cert key {key:?}");
-                            let _ = rustls::crypto::ring::default_provider().install_default();
-                            let builder = rustls::ServerConfig::builder().with_no_client_auth();
-                            let mut tls_server_cfg = if let Some(ref host_name) = listener.hostname {
-                                info!("Using SNI resolver");
-                                let mut resolver = rustls::server::ResolvesServerCertUsingSni::new();
-                                let provider = rustls::crypto::CryptoProvider::get_default().expect("should installed");
-                                let signed_key = provider.key_provider.load_private_key(key)?;
-                                let ck = rustls::sign::CertifiedKey::new(certs, signed_key);
-                                resolver.add(host_name, ck)?;
-                                builder.with_cert_resolver(Arc::new(resolver))
+                            let https_service = if tls.http3.unwrap_or_default() {
+                                #[cfg(feature = "http3")]
+                                {
+                                    http3_listens.push(
+                                        SgHttp3Listen::new(addr, service.clone(), tls_server_cfg.clone(), cancel_token.child_token()).with_gateway_name(gateway_name.clone()),
+                                    );
+                                    advertise_http3(service.clone(), listener.port)
+                                }
+                                #[cfg(not(feature = "http3"))]
+                                {
+                                    warn!(
+                                        "[SG.Server] http3 of listener {name} is ignored, spacegate is built without http3 feature",
+                                        name = listener.name
+                                    );
+                                    service.clone()
+                                }
                             } else {
-                                info!("Using single cert");
-                                builder.with_single_cert(certs, key)?
+                                service.clone()
                             };
-                            tls_server_cfg.alpn_protocols = vec![b"http/1.1".to_vec(), b"h2".to_vec()];
-                            tls_server_cfg.ignore_client_order = true;
-                            tls_server_cfg.enable_secret_extraction = true;
-                            listen.add_service(service.clone().https_with_gateway_name(tls_server_cfg, gateway_name.clone()))
-                        } else {
-                            error!("[SG.Server] Can not found a valid Tls private key");
+                            let mut https = https_service.https_with_gateway_name(tls_server_cfg, gateway_name.clone());
+                            if let Some(timeout) = header_read_timeout {
+                                https = https.with_header_read_timeout(timeout);
+                            }
+                            if let Some(timeout) = keep_alive_timeout {
+                                https = https.with_keep_alive_timeout(timeout);
+                            }
+                            listen.add_service(https)
                         }
-                    };
+                        Err(e) => {
+                            error!("[SG.Server] fail to load tls config of listener {name}: {e}", name = listener.name);
+                        }
+                    }
+                } else {
+                    listen.add_service(tls_passthrough.clone());
+                }
+            } else if SgProtocolConfig::Tcp == listener.protocol {
+                listen.add_service(tcp_proxy.clone().with_listener(listener.name.as_str()));
+            } else if SgProtocolConfig::Udp == listener.protocol {
+                if !listener.tcp_services.is_empty() {
+                    warn!("[SG.Server] tcp services of udp listener {name} are ignored", name = listener.name);
                 }
+                let mut udp_listen = SgUdpListen::new(addr, udp_proxy.clone(), cancel_token.child_token()).with_listener_id(listener.name.clone());
+                if let Some(max_sessions) = limits.max_udp_sessions {
+                    udp_listen = udp_listen.with_max_sessions(max_sessions);
+                }
+                udp_listens.push(udp_listen);
+                continue;
             } else {
-                listen.add_service(service.clone().http_with_gateway_name(gateway_name.clone()));
+                let mut http = service.clone().http_with_gateway_name(gateway_name.clone());
+                if let Some(timeout) = header_read_timeout {
+                    http = http.with_header_read_timeout(timeout);
+                }
+                if let Some(timeout) = keep_alive_timeout {
+                    http = http.with_keep_alive_timeout(timeout);
+                }
+                listen.add_service(http);
             }
             listens.push(listen)
         }
 
+        // wait for the listeners to drain their connections on shutdown
+        let shutdown_timeout = listens.iter().map(|listen| listen.grace_period + Duration::from_secs(1)).fold(DEFAULT_SHUTDOWN_TIMEOUT, Duration::max);
         // let cancel_guard = cancel_token.clone().drop_guard();
         let cancel_task = cancel_token.clone().cancelled_owned();
         let handle = {
@@ -411,6 +842,25 @@ impl RunningSgGateway {
                         tracing::info!("[Sg.Server] listener[{id}] quit listening")
                     });
                 }
+                for listen in udp_listens {
+                    join_set.spawn(async move {
+                        let id = listen.listener_id.clone();
+                        if let Err(e) = listen.listen().await {
+                            tracing::error!("[Sg.Server] udp listen error: {e}")
+                        }
+                        tracing::info!("[Sg.Server] udp listener[{id}] quit listening")
+                    });
+                }
+                #[cfg(feature = "http3")]
+                for listen in http3_listens {
+                    join_set.spawn(async move {
+                        let addr = listen.socket_addr;
+                        if let Err(e) = listen.listen().await {
+                            tracing::error!("[Sg.Server] http3 listen error: {e}")
+                        }
+                        tracing::info!("[Sg.Server] http3 listener[{addr}] quit listening")
+                    });
+                }
                 tracing::info!(gateway = gateway_name.as_ref(), "[Sg.Server] start all listeners");
                 cancel_task.await;
                 while let Some(result) = join_set.join_next().await {
@@ -424,8 +874,14 @@ impl RunningSgGateway {
             gateway_name: gateway_name.clone(),
             token: cancel_token,
             handle,
-            shutdown_timeout: Duration::from_secs(10),
+            shutdown_timeout,
             reloader,
+            tls_reloader,
+            tcp_reloader,
+            udp_reloader,
+            cert_reloaders,
+            client_cas,
+            gateway,
         })
     }
 
@@ -451,7 +907,7 @@ impl RunningSgGateway {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use spacegate_config::{SgBackendRef, SgMcpLegacySse};
+    use spacegate_config::{SgBackendRef, SgListener, SgMcpLegacySse, SgStreamBackendRef, SgTcpRoute, SgTcpRouteKind, SgUdpRoute, SgUdpRouteKind};
 
     fn backend() -> SgBackendRef {
         SgBackendRef {
@@ -482,6 +938,7 @@ mod tests {
             backends: vec![backend()],
             plugins: Vec::new(),
             timeout_mode: TimeoutMode::Disabled,
+            timeout_ms: None,
             session_affinity: McpSessionAffinity::McpSession,
         });
 
@@ -509,6 +966,7 @@ mod tests {
             backends: vec![backend()],
             plugins: Vec::new(),
             timeout_mode: TimeoutMode::Disabled,
+            timeout_ms: None,
             session_affinity: McpSessionAffinity::McpSession,
         });
 
@@ -517,4 +975,282 @@ mod tests {
         let matches = rule.matches.as_ref().expect("compiled matches");
         assert_eq!(matches.iter().map(match_path_method).collect::<Vec<_>>(), vec![("/events", "GET"), ("/messages", "POST")]);
     }
+
+    #[test]
+    fn mcp_route_idle_timeout_applies_to_rule_and_backends() {
+        let (route, _) = compile_mcp_route_to_http_route(SgMcpRoute {
+            kind: spacegate_config::SgRouteKind::McpRoute,
+            route_name: "mcp-idle".to_string(),
+            hostnames: None,
+            transport: SgMcpTransport::StreamableHttp,
+            path: "/mcp".to_string(),
+            legacy_sse: None,
+            backends: vec![backend()],
+            plugins: Vec::new(),
+            timeout_mode: TimeoutMode::Idle,
+            timeout_ms: Some(60000),
+            session_affinity: McpSessionAffinity::McpSession,
+        });
+
+        let rule = route.rules.first().expect("compiled rule");
+        assert_eq!(rule.timeout_mode, Some(TimeoutMode::Idle));
+        assert_eq!(rule.timeout_ms, Some(60000));
+        assert_eq!(rule.backends[0].timeout_mode, Some(TimeoutMode::Idle));
+    }
+
+    #[test]
+    fn backend_tls_profile() {
+        assert_eq!(create_client_tls_profile(None, false).expect("no tls"), None);
+        assert_eq!(create_client_tls_profile(None, true).expect("ignore verification"), Some(ClientTlsProfile::dangerous()));
+        let tls = SgBackendTls {
+            sni: Some("api.example.com".to_string()),
+            ..Default::default()
+        };
+        // the backend settings take precedence over the gateway
+        let profile = create_client_tls_profile(Some(&tls), true).expect("sni only").expect("profile");
+        assert!(profile.verify);
+        assert_eq!(profile.sni.as_deref(), Some("api.example.com"));
+        let tls = SgBackendTls {
+            cert: Some("cert".to_string()),
+            ..Default::default()
+        };
+        assert!(create_client_tls_profile(Some(&tls), false).is_err());
+    }
+
+    #[test]
+    fn stream_routes_are_collected_by_protocol() {
+        let backends = vec![SgStreamBackendRef {
+            host: BackendHost::Host { host: "10.0.0.1".to_string() },
+            port: 5432,
+            weight: None,
+        }];
+        let tcp = SgRoute::Tcp(SgTcpRoute {
+            kind: SgTcpRouteKind::TcpRoute,
+            route_name: "postgres".to_string(),
+            listener: Some("postgres".to_string()),
+            backends: backends.clone(),
+            idle_timeout_ms: Some(1000),
+        });
+        let udp = SgRoute::Udp(SgUdpRoute {
+            kind: SgUdpRouteKind::UdpRoute,
+            route_name: "dns".to_string(),
+            listener: None,
+            backends,
+            idle_timeout_ms: None,
+        });
+        let (tcp_router, udp_router) = collect_stream_route([("postgres".to_string(), tcp), ("dns".to_string(), udp)]).expect("valid stream routes");
+        let tcp_route = tcp_router.route("postgres").expect("tcp route");
+        assert_eq!(tcp_route.name, "postgres");
+        assert_eq!(tcp_route.idle_timeout, Some(Duration::from_millis(1000)));
+        assert_eq!(tcp_route.backends[0].weight, 1);
+        assert!(tcp_router.route("dns").is_none());
+        assert_eq!(udp_router.route("dns").map(|route| route.name.as_str()), Some("dns"));
+        let idle = SgRoute::Tcp(SgTcpRoute {
+            kind: SgTcpRouteKind::TcpRoute,
+            route_name: "idle".to_string(),
+            listener: None,
+            backends: vec![],
+            idle_timeout_ms: Some(0),
+        });
+        assert!(collect_stream_route([("idle".to_string(), idle)]).is_err());
+    }
+
+    #[test]
+    fn health_check_rejects_zero_interval() {
+        let health_check = convert_health_check(SgHealthCheck::default()).expect("valid health check");
+        assert_eq!(health_check.interval, Duration::from_secs(10));
+        assert!(convert_health_check(SgHealthCheck {
+            interval_ms: 0,
+            ..Default::default()
+        })
+        .is_err());
+        assert!(convert_health_check(SgHealthCheck {
+            timeout_ms: 0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn health_check_rejects_zero_thresholds() {
+        assert!(convert_health_check(SgHealthCheck {
+            healthy_threshold: 0,
+            ..Default::default()
+        })
+        .is_err());
+        assert!(convert_health_check(SgHealthCheck {
+            unhealthy_threshold: 0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn proxy_protocol_trusted_cidrs() {
+        let proxy_protocol = SgProxyProtocol {
+            trusted_cidrs: vec!["10.0.0.0/8".to_string(), " 192.168.1.10 ".to_string()],
+        };
+        let proxy_protocol = create_proxy_protocol(&proxy_protocol).expect("valid cidrs");
+        assert!(proxy_protocol.is_trusted("10.1.2.3".parse().expect("ip")));
+        assert!(proxy_protocol.is_trusted("192.168.1.10".parse().expect("ip")));
+        assert!(!proxy_protocol.is_trusted("192.168.1.11".parse().expect("ip")));
+        let proxy_protocol = SgProxyProtocol {
+            trusted_cidrs: vec!["10.0.0.0/33".to_string()],
+        };
+        assert!(create_proxy_protocol(&proxy_protocol).is_err());
+        assert!(create_proxy_protocol(&SgProxyProtocol::default()).is_err());
+    }
+
+    /// Replies a banner to the connections starting with `0x05`.
+    struct Banner;
+    impl TcpService for Banner {
+        fn protocol_name(&self) -> &str {
+            "banner"
+        }
+        fn sniff_peek_size(&self) -> usize {
+            1
+        }
+        fn sniff(&self, peeked: &[u8]) -> bool {
+            peeked.starts_with(b"\x05")
+        }
+        fn handle(&self, mut stream: tokio::net::TcpStream, _peer: SocketAddr) -> futures_util::future::BoxFuture<'static, spacegate_kernel::BoxResult<()>> {
+            Box::pin(async move {
+                use tokio::io::{AsyncReadExt, AsyncWriteExt};
+                // consume the peeked byte, the unread data would reset the connection on close
+                stream.read_u8().await?;
+                stream.write_all(b"banner").await?;
+                Ok(())
+            })
+        }
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).expect("free port").port()
+    }
+
+    async fn connect(port: u16) -> tokio::net::TcpStream {
+        // the listener is bound by a spawned task
+        for _ in 0..50 {
+            if let Ok(stream) = tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+                return stream;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("gateway is not listening on {port}")
+    }
+
+    #[test]
+    fn tcp_services_from_repository() {
+        TcpServiceRepository::global().register_custom("banner-test", |_spec| Ok(Arc::new(Banner) as Arc<dyn TcpService>));
+        let services = create_tcp_services(&[SgTcpService {
+            code: "banner-test".to_string(),
+            spec: Default::default(),
+        }])
+        .expect("registered service");
+        assert_eq!(services.iter().map(|s| s.protocol_name()).collect::<Vec<_>>(), vec!["banner"]);
+        let unregistered = SgTcpService {
+            code: "unregistered-test".to_string(),
+            spec: Default::default(),
+        };
+        assert!(create_tcp_services(&[unregistered]).is_err());
+    }
+
+    #[tokio::test]
+    async fn tcp_services_are_tried_before_tcp_proxy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        TcpServiceRepository::global().register_custom("banner-before-proxy-test", |_spec| Ok(Arc::new(Banner) as Arc<dyn TcpService>));
+        let port = free_port();
+        let gateway = SgGateway {
+            name: "tcp-services-test".to_string(),
+            listeners: vec![SgListener {
+                name: "tcp".to_string(),
+                ip: Some([127, 0, 0, 1].into()),
+                port,
+                protocol: SgProtocolConfig::Tcp,
+                tcp_services: vec![SgTcpService {
+                    code: "banner-before-proxy-test".to_string(),
+                    spec: Default::default(),
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let running = RunningSgGateway::create(ConfigItem { gateway, routes: BTreeMap::new() }, CancellationToken::new()).expect("create gateway");
+        let mut stream = connect(port).await;
+        stream.write_all(b"\x05").await.expect("write");
+        let mut reply = Vec::new();
+        timeout(Duration::from_secs(5), stream.read_to_end(&mut reply)).await.expect("reply in time").expect("read");
+        assert_eq!(reply, b"banner");
+        running.shutdown().await;
+    }
+
+    #[test]
+    fn connection_limits() {
+        let limits = SgConnectionLimits {
+            max_connections: Some(1024),
+            max_connections_per_ip: Some(16),
+            drain_timeout_ms: Some(30000),
+            ..Default::default()
+        };
+        let listen = apply_connection_limits(SgListen::new(([127, 0, 0, 1], 8080).into(), CancellationToken::new()), &limits).expect("valid limits");
+        assert_eq!(listen.max_connections, Some(1024));
+        assert_eq!(listen.max_connections_per_ip, Some(16));
+        assert_eq!(listen.grace_period, Duration::from_secs(30));
+        for limits in [
+            SgConnectionLimits {
+                max_connections: Some(0),
+                ..Default::default()
+            },
+            SgConnectionLimits {
+                max_connections_per_ip: Some(0),
+                ..Default::default()
+            },
+            SgConnectionLimits {
+                max_udp_sessions: Some(0),
+                ..Default::default()
+            },
+        ] {
+            assert!(apply_connection_limits(SgListen::new(([127, 0, 0, 1], 8080).into(), CancellationToken::new()), &limits).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn connections_beyond_max_connections_are_closed() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let port = free_port();
+        let gateway = SgGateway {
+            name: "max-connections-test".to_string(),
+            listeners: vec![SgListener {
+                name: "http".to_string(),
+                ip: Some([127, 0, 0, 1].into()),
+                port,
+                connection_limits: Some(SgConnectionLimits {
+                    max_connections: Some(1),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let running = RunningSgGateway::create(ConfigItem { gateway, routes: BTreeMap::new() }, CancellationToken::new()).expect("create gateway");
+        let mut first = connect(port).await;
+        // let the listener count the first connection
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut second = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.expect("connect");
+        let mut buf = [0u8; 1];
+        let closed = timeout(Duration::from_secs(5), second.read(&mut buf)).await.expect("closed in time");
+        assert!(matches!(closed, Ok(0) | Err(_)));
+        first.write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await.expect("write");
+        let mut response = Vec::new();
+        timeout(Duration::from_secs(5), first.read_to_end(&mut response)).await.expect("response in time").expect("read");
+        assert!(response.starts_with(b"HTTP/1.1 "));
+        // the slot is released once the connection is closed
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut third = connect(port).await;
+        third.write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await.expect("write");
+        let mut response = Vec::new();
+        timeout(Duration::from_secs(5), third.read_to_end(&mut response)).await.expect("response in time").expect("read");
+        assert!(response.starts_with(b"HTTP/1.1 "));
+        running.shutdown().await;
+    }
 }
@@ -0,0 +1,269 @@
+//! Zero-downtime upgrade by socket handoff
+//!
+//! On `SIGUSR2`, spacegate starts a new process of its binary, which may have been replaced by a newer version, with the same arguments.
+//! The listener sockets are handed off to the new process over a unix socket, whose path is passed by the `SPACEGATE_HANDOFF_SOCKET`
+//! environment variable, in a directory only accessible by the user, and only a process of the same user is handed the sockets. After the new process is serving, this process stops accepting and drains the connections.
+//! If the new process fails to start, this process keeps serving.
+//!
+//! Sockets passed by systemd socket activation are inherited as well, so they are not closed when the service restarts.
+//!
+//! Under systemd, use `Type=notify` and `NotifyAccess=all`, the new process takes over as the main process by `sd_notify`.
+//! Since the new process loads the config from scratch, `ExecReload` could send `SIGUSR2` for both config reloading and binary upgrading.
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    os::unix::{ffi::OsStrExt, fs::DirBuilderExt, net::UnixDatagram},
+    path::PathBuf,
+    process::{Child, Command},
+    sync::{Mutex, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use spacegate_kernel::listener::handoff::{ActiveSocket, Handoff, SocketRegistry, SystemdSockets};
+use tokio::{net::UnixListener, signal::unix::SignalKind};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::BoxError;
+
+/// Environment variable of the unix socket path, set by the running process for the new process.
+pub const HANDOFF_SOCKET_ENV: &str = "SPACEGATE_HANDOFF_SOCKET";
+/// How long to wait for the new process to be serving.
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+/// Inherited sockets not used by any listener are closed after this period since ready.
+const RELEASE_INHERITED_DELAY: Duration = Duration::from_secs(5);
+
+static STARTUP_ENV: OnceLock<StartupEnv> = OnceLock::new();
+static PENDING_HANDOFF: Mutex<Option<Handoff>> = Mutex::new(None);
+static API_SOCKET: Mutex<Option<ActiveSocket>> = Mutex::new(None);
+
+/// Environment variables of socket handoff and systemd, read once at startup.
+#[derive(Debug)]
+struct StartupEnv {
+    systemd: io::Result<Option<SystemdSockets>>,
+    handoff_socket: Option<OsString>,
+    notify_socket: Option<OsString>,
+}
+
+impl StartupEnv {
+    fn from_env() -> Self {
+        Self {
+            systemd: SystemdSockets::from_env(),
+            handoff_socket: std::env::var_os(HANDOFF_SOCKET_ENV),
+            notify_socket: std::env::var_os("NOTIFY_SOCKET"),
+        }
+    }
+}
+
+/// Read the environment variables of socket handoff and systemd.
+///
+/// Since the environment is not safe to access among threads, call it in `main` before the runtime is built.
+/// Otherwise they are read when the sockets are inherited.
+pub fn read_env() {
+    STARTUP_ENV.get_or_init(StartupEnv::from_env);
+}
+
+/// Inherit the sockets from systemd socket activation, and from the previous process if it's started by an upgrade.
+///
+/// # Errors
+/// If fail to receive the sockets from the previous process.
+pub async fn inherit_sockets() -> Result<(), BoxError> {
+    let registry = SocketRegistry::global();
+    let env = STARTUP_ENV.get_or_init(StartupEnv::from_env);
+    let inherited = match &env.systemd {
+        Ok(Some(sockets)) => registry.inherit_systemd(*sockets),
+        Ok(None) => Ok(0),
+        Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+    };
+    match inherited {
+        Ok(0) => {}
+        Ok(count) => info!("[SG.Upgrade] inherit {count} sockets from systemd"),
+        Err(e) => warn!("[SG.Upgrade] fail to inherit sockets from systemd: {e}"),
+    }
+    if let Some(path) = &env.handoff_socket {
+        info!("[SG.Upgrade] receive sockets from the running process by {path:?}");
+        let handoff = registry.receive_handoff(&path).await.map_err(|e| format!("fail to receive sockets from the running process: {e}"))?;
+        *PENDING_HANDOFF.lock().unwrap_or_else(|e| e.into_inner()) = Some(handoff);
+    }
+    Ok(())
+}
+
+/// Tell the previous process and systemd that the gateways are serving.
+pub async fn notify_ready() {
+    let handoff = PENDING_HANDOFF.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(handoff) = handoff {
+        match handoff.ready().await {
+            Ok(()) => info!("[SG.Upgrade] took over from the previous process"),
+            Err(e) => warn!("[SG.Upgrade] fail to notify the previous process: {e}"),
+        }
+    }
+    if SocketRegistry::global().inherited_count() > 0 {
+        tokio::spawn(async {
+            tokio::time::sleep(RELEASE_INHERITED_DELAY).await;
+            let released = SocketRegistry::global().release_inherited();
+            if released > 0 {
+                info!("[SG.Upgrade] close {released} inherited sockets which are not used by any listener");
+            }
+        });
+    }
+    if let Err(e) = sd_notify(&format!("READY=1\nMAINPID={}", std::process::id())) {
+        warn!("[SG.Upgrade] fail to notify systemd: {e}");
+    }
+}
+
+/// Bind the api server of spacegate and its plugins, the socket is handed off like the listeners.
+///
+/// # Errors
+/// If fail to bind the socket.
+pub async fn bind_api_server(addr: std::net::SocketAddr) -> io::Result<tokio::net::TcpListener> {
+    let (listener, active) = SocketRegistry::global().bind_tcp(addr).await?;
+    *API_SOCKET.lock().unwrap_or_else(|e| e.into_inner()) = Some(active);
+    Ok(listener)
+}
+
+/// Upgrade on every `SIGUSR2`, this process is shut down by `shutdown` after the new process is serving.
+///
+/// # Errors
+/// If fail to listen the signal.
+pub fn upgrade_on_signal(shutdown: CancellationToken) -> io::Result<()> {
+    let mut upgrade_signal = tokio::signal::unix::signal(SignalKind::user_defined2())?;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                () = shutdown.cancelled() => return,
+                received = upgrade_signal.recv() => {
+                    if received.is_none() {
+                        return;
+                    }
+                }
+            }
+            info!("[SG.Upgrade] received SIGUSR2, upgrading...");
+            match upgrade().await {
+                Ok(()) => {
+                    info!("[SG.Upgrade] the new process is serving, shutting down...");
+                    shutdown.cancel();
+                    return;
+                }
+                Err(e) => error!("[SG.Upgrade] upgrade failed, keep serving: {e}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Start a new process and hand off the sockets to it.
+///
+/// # Errors
+/// If fail to start the new process, or it's not serving in time.
+pub async fn upgrade() -> Result<(), BoxError> {
+    let dir = handoff_dir()?;
+    let path = dir.join("handoff.sock");
+    let result = async {
+        let listener = UnixListener::bind(&path)?;
+        let exe = current_exe()?;
+        info!("[SG.Upgrade] start new process {exe:?}");
+        // the systemd sockets are handed off, and the variables are not for the new process
+        let mut child = Command::new(&exe)
+            .args(std::env::args_os().skip(1))
+            .env(HANDOFF_SOCKET_ENV, &path)
+            .env_remove("LISTEN_PID")
+            .env_remove("LISTEN_FDS")
+            .env_remove("LISTEN_FDNAMES")
+            .spawn()?;
+        let result = tokio::select! {
+            result = SocketRegistry::global().serve_handoff(&listener, READY_TIMEOUT) => result.map_err(BoxError::from),
+            exited = wait_exited(&mut child) => Err(format!("the new process exited: {exited}").into()),
+        };
+        if result.is_err() {
+            let _ = child.kill();
+            let _ = tokio::task::spawn_blocking(move || child.wait()).await;
+        }
+        result
+    }
+    .await;
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_dir(&dir);
+    result
+}
+
+/// Create a new directory for the handoff socket, which is only accessible by the user.
+fn handoff_dir() -> io::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let dir = std::env::temp_dir().join(format!("spacegate-handoff-{}-{nanos}", std::process::id()));
+    // fails if the path exists, so that it's never a directory prepared by others
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    Ok(dir)
+}
+
+async fn wait_exited(child: &mut Child) -> String {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.to_string(),
+            Ok(None) => tokio::time::sleep(Duration::from_millis(200)).await,
+            Err(e) => return e.to_string(),
+        }
+    }
+}
+
+/// Path of the binary, which is still the path after the binary is replaced.
+fn current_exe() -> io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    // linux appends this to the path of a replaced binary
+    match exe.to_str().and_then(|exe| exe.strip_suffix(" (deleted)")) {
+        Some(exe) => Ok(PathBuf::from(exe)),
+        None => Ok(exe),
+    }
+}
+
+/// Send the state to systemd, if it's started by a service of `Type=notify`.
+fn sd_notify(state: &str) -> io::Result<()> {
+    match &STARTUP_ENV.get_or_init(StartupEnv::from_env).notify_socket {
+        Some(path) => notify_to(path, state),
+        None => Ok(()),
+    }
+}
+
+fn notify_to(path: &OsStr, state: &str) -> io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    match path.as_bytes().strip_prefix(b"@") {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Some(name) => {
+            #[cfg(target_os = "android")]
+            use std::os::android::net::SocketAddrExt;
+            #[cfg(target_os = "linux")]
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_systemd() {
+        let path = std::env::temp_dir().join(format!("spacegate-notify-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).expect("fail to bind notify socket");
+        notify_to(path.as_os_str(), "READY=1").expect("fail to notify");
+        let mut buf = [0u8; 64];
+        let len = systemd.recv(&mut buf).expect("fail to receive");
+        assert_eq!(&buf[..len], b"READY=1");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn handoff_dir_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = handoff_dir().expect("fail to create handoff dir");
+        let mode = std::fs::metadata(&dir).expect("no metadata").permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        let _ = std::fs::remove_dir(&dir);
+    }
+}
@@ -1,13 +1,21 @@
 mod socks5;
 use socks5::Socks5;
-use spacegate_kernel::{listener::SgListen, CancellationToken};
+use spacegate_kernel::{
+    listener::SgListen,
+    service::repository::{ConfigurableTcpService, TcpServiceRepository},
+    CancellationToken,
+};
 #[tokio::main]
 async fn main() -> spacegate_kernel::BoxResult<()> {
     tracing_subscriber::fmt().with_env_filter(tracing_subscriber::EnvFilter::from_default_env().add_directive(tracing_subscriber::filter::LevelFilter::DEBUG.into())).init();
     let ct = CancellationToken::new();
     let bind = "[::]:10908".parse()?;
     let listen_ct = ct.child_token();
-    let handle = SgListen::new(bind, listen_ct).with_service(Socks5::new()).spawn();
+    // register the service, so that listeners could reference it by code in config
+    let repo = TcpServiceRepository::global();
+    repo.register::<Socks5>();
+    let socks5 = repo.create(Socks5::CODE, serde_json::Value::Null)?;
+    let handle = SgListen::new(bind, listen_ct).with_services(vec![socks5]).spawn();
     tokio::signal::ctrl_c().await?;
     ct.cancel();
     handle.await??;
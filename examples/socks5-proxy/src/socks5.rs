@@ -6,7 +6,10 @@ use tokio::{
     net::TcpStream,
 };
 
-use spacegate_kernel::service::TcpService;
+use spacegate_kernel::{
+    service::{repository::ConfigurableTcpService, TcpService},
+    BoxError,
+};
 use tracing::instrument;
 pub struct Socks5 {}
 
@@ -169,6 +172,13 @@ impl Socks5Stream<WantRequest> {
     }
 }
 
+impl ConfigurableTcpService for Socks5 {
+    const CODE: &'static str = "socks5";
+    fn create(_spec: serde_json::Value) -> Result<Self, BoxError> {
+        Ok(Self::new())
+    }
+}
+
 impl TcpService for Socks5 {
     fn protocol_name(&self) -> &str {
         "socks5"
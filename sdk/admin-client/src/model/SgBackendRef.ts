@@ -2,6 +2,8 @@
 import type { BackendHost } from "./BackendHost";
 import type { PluginBinding } from "./PluginBinding";
 import type { SgBackendProtocol } from "./SgBackendProtocol";
+import type { SgBackendTls } from "./SgBackendTls";
+import type { SgHealthCheck } from "./SgHealthCheck";
 import type { TimeoutMode } from "./TimeoutMode";
 
 export type SgBackendRef<P = PluginBinding> = { host: BackendHost,
@@ -12,7 +14,15 @@ port: number | null,
 /**
  * Timeout specifies the timeout for requests forwarded to the referenced backend.
  */
-timeout_ms: number | null, timeout_mode: TimeoutMode | null, protocol: SgBackendProtocol | null,
+timeout_ms: number | null, timeout_mode: TimeoutMode | null,
+/**
+ * Timeout of connecting the referenced backend.
+ */
+connect_timeout_ms: number | null,
+/**
+ * Timeout of waiting for the response head from the referenced backend, regardless of the timeout mode.
+ */
+first_byte_timeout_ms: number | null, protocol: SgBackendProtocol | null,
 /**
  * Downgrade HTTP2 connections, it is useful when the backend does not support HTTP2.
  */
@@ -30,4 +40,12 @@ weight: number | null,
  * # Notice!
  * this field is ordered, the first plugin will be the outermost plugin.
  */
-plugins: Array<P>, };
\ No newline at end of file
+plugins: Array<P>,
+/**
+ * HealthCheck defines how to actively probe the referenced backend, unhealthy backends will be skipped by the balancer.
+ */
+health_check: SgHealthCheck | null,
+/**
+ * Tls defines how to connect the referenced backend over https, the certificate of backend is verified by the system trust roots if it's absent.
+ */
+tls: SgBackendTls | null, };
\ No newline at end of file
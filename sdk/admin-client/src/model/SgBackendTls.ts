@@ -0,0 +1,35 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SgBackendTls = {
+/**
+ * PEM bundle of the CAs which issue the backend certificate, it replaces the system trust roots.
+ */
+ca: string | null,
+/**
+ * Path of the PEM file of `ca`, it takes precedence over `ca`.
+ */
+ca_path: string | null,
+/**
+ * PEM encoded client certificate chain, presented to backends which require mutual tls.
+ */
+cert: string | null,
+/**
+ * Path of the PEM file of `cert`, it takes precedence over `cert`.
+ */
+cert_path: string | null,
+/**
+ * PEM encoded private key of the client certificate.
+ */
+key: string | null,
+/**
+ * Path of the PEM file of `key`, it takes precedence over `key`.
+ */
+key_path: string | null,
+/**
+ * Server name sent by SNI and verified against the backend certificate, the backend host is used if it's absent.
+ */
+sni: string | null,
+/**
+ * Verify the backend certificate, default is true.
+ */
+verify: boolean, };
\ No newline at end of file
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgCanaryPolicy } from "./SgCanaryPolicy";
 
-export type SgBalancePolicy = "random" | "ip_hash" | "mcp_session";
\ No newline at end of file
+export type SgBalancePolicy = "random" | "ip_hash" | "mcp_session" | { "canary": SgCanaryPolicy };
\ No newline at end of file
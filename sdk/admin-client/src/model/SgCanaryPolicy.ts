@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgCanaryKey } from "./SgCanaryKey";
+import type { SgStickyCookie } from "./SgStickyCookie";
+
+export type SgCanaryPolicy = {
+/**
+ * Key defines where the key of a request is read from, requests without the key are assigned randomly by weight.
+ */
+key: SgCanaryKey | null,
+/**
+ * OverrideHeader defines a header to force the backend, its value is the `host:port` or the index of a backend.
+ */
+override_header: string | null,
+/**
+ * StickyCookie defines a cookie to keep the session on the assigned backend, its value is the `host:port` of the backend.
+ */
+sticky_cookie: SgStickyCookie | null, };
\ No newline at end of file
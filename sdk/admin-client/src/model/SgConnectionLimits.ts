@@ -0,0 +1,29 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SgConnectionLimits = {
+/**
+ * Max concurrent connections of the listener, new connections beyond it are closed at once. It can't be 0, default is unlimited.
+ */
+max_connections: number | null,
+/**
+ * Max concurrent connections from one client ip. It can't be 0, default is unlimited.
+ */
+max_connections_per_ip: number | null,
+/**
+ * Close the http/1 connection if the request header is not received in time, including the next request of a keep-alive connection.
+ *
+ * It only applies to http/1, http/2 connections are not covered, their idle connections are closed by `keep_alive_timeout_ms`.
+ */
+header_read_timeout_ms: bigint | null,
+/**
+ * Close the http connection if there is no request in flight for this period.
+ */
+keep_alive_timeout_ms: bigint | null,
+/**
+ * How long to wait for the connections to finish when the gateway is shut down or restarted. Default is 10 seconds.
+ */
+drain_timeout_ms: bigint | null,
+/**
+ * Max concurrent client sessions of a udp listener, the least recently active session is closed for a new client beyond it. It can't be 0, default is 10000.
+ */
+max_udp_sessions: number | null, };
\ No newline at end of file
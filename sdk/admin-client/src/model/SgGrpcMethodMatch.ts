@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SgGrpcMethodMatch = { "kind": "Exact", service: string | null, method: string | null, } | { "kind": "RegExp", service: string | null, method: string | null, };
\ No newline at end of file
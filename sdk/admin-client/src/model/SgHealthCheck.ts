@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgHealthCheckProbe } from "./SgHealthCheckProbe";
+
+export type SgHealthCheck = {
+/**
+ * Probe defines how to check the backend.
+ */
+probe: SgHealthCheckProbe,
+/**
+ * Interval between two probes, in milliseconds.
+ */
+interval_ms: number,
+/**
+ * Timeout of a single probe, in milliseconds.
+ */
+timeout_ms: number,
+/**
+ * Consecutive successes required to mark an unhealthy backend healthy, it can't be 0.
+ */
+healthy_threshold: number,
+/**
+ * Consecutive failures required to mark a healthy backend unhealthy, it can't be 0.
+ */
+unhealthy_threshold: number, };
\ No newline at end of file
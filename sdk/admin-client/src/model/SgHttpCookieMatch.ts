@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SgHttpCookieMatch = { "kind": "exact", name: string, value: string, } | { "kind": "reg_exp", name: string, re: string, };
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type SgHttpPathMatch = { "kind": "Exact", value: string, replace: string | null, } | { "kind": "Prefix", value: string, replace: string | null, } | { "kind": "RegExp", value: string, replace: string | null, };
\ No newline at end of file
+export type SgHttpPathMatch = { "kind": "Exact", value: string, replace: string | null, } | { "kind": "Prefix", value: string, replace: string | null, } | { "kind": "RegExp", value: string, replace: string | null, } | { "kind": "Template", value: string, replace: string | null, };
\ No newline at end of file
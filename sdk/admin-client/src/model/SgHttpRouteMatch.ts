@@ -1,4 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgGrpcMethodMatch } from "./SgGrpcMethodMatch";
+import type { SgHttpCookieMatch } from "./SgHttpCookieMatch";
 import type { SgHttpHeaderMatch } from "./SgHttpHeaderMatch";
 import type { SgHttpMethodMatch } from "./SgHttpMethodMatch";
 import type { SgHttpPathMatch } from "./SgHttpPathMatch";
@@ -24,4 +26,28 @@ query: Array<SgHttpQueryMatch> | null,
  * Method specifies HTTP method matcher.
  * When specified, this route will be matched only if the request has the specified method.
  */
-method: Array<SgHttpMethodMatch> | null, };
\ No newline at end of file
+method: Array<SgHttpMethodMatch> | null,
+/**
+ * Grpc specifies a gRPC service and method matcher.
+ * When specified, this route will be matched only by gRPC requests of the service and method.
+ */
+grpc: SgGrpcMethodMatch | null,
+/**
+ * Cookie specifies HTTP cookie matchers.
+ * Multiple match values are ANDed together, meaning, a request must match all the specified cookies to select the route.
+ */
+cookie: Array<SgHttpCookieMatch> | null,
+/**
+ * SourceIp specifies the CIDRs or IPs of clients, e.g. `10.0.0.0/8`.
+ * When specified, this route will be matched only if the client ip is in any of them.
+ * The client ip is the peer address of the connection, or the source in the PROXY protocol header from a trusted source, forwarded headers are not trusted.
+ */
+source_ip: Array<string> | null,
+/**
+ * Not specifies a match which the request must not match.
+ */
+not: SgHttpRouteMatch | null,
+/**
+ * Any specifies matches which the request must match at least one of.
+ */
+any: Array<SgHttpRouteMatch> | null, };
\ No newline at end of file
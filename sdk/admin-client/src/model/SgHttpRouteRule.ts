@@ -21,4 +21,12 @@ backends: Array<SgBackendRef<P>>,
 /**
  * Timeout define the timeout for requests that match this rule.
  */
-timeout_ms: number | null, timeout_mode: TimeoutMode | null, balance_policy: SgBalancePolicy | null, };
\ No newline at end of file
+timeout_ms: number | null, timeout_mode: TimeoutMode | null,
+/**
+ * Timeout of connecting the backends, it's the default of the backends in this rule.
+ */
+connect_timeout_ms: number | null,
+/**
+ * Timeout of waiting for the response head, regardless of the timeout mode.
+ */
+first_byte_timeout_ms: number | null, balance_policy: SgBalancePolicy | null, };
\ No newline at end of file
@@ -1,5 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgConnectionLimits } from "./SgConnectionLimits";
 import type { SgProtocolConfig } from "./SgProtocolConfig";
+import type { SgProxyProtocol } from "./SgProxyProtocol";
+import type { SgTcpService } from "./SgTcpService";
 
 export type SgListener = {
 /**
@@ -21,4 +24,18 @@ protocol: SgProtocolConfig,
 /**
  * `HostName` is used to define the host on which the listener accepts requests.
  */
-hostname: string | null, };
\ No newline at end of file
+hostname: string | null,
+/**
+ * Accept PROXY protocol headers from layer 4 load balancers, so that the address of the original client is used as the peer address.
+ */
+proxy_protocol: SgProxyProtocol | null,
+/**
+ * Limit the connections of the listener, and how they are closed.
+ */
+connection_limits: SgConnectionLimits | null,
+/**
+ * Custom tcp services on the port, which are registered by libraries and tried in order before the service of the protocol.
+ *
+ * A connection is served by the first service recognizing its first bytes.
+ */
+tcp_services: Array<SgTcpService>, };
\ No newline at end of file
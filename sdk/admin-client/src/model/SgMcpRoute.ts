@@ -7,4 +7,8 @@ import type { SgMcpTransport } from "./SgMcpTransport";
 import type { SgRouteKind } from "./SgRouteKind";
 import type { TimeoutMode } from "./TimeoutMode";
 
-export type SgMcpRoute<P = PluginBinding> = { kind: SgRouteKind, route_name: string, hostnames: Array<string> | null, transport: SgMcpTransport, path: string, legacy_sse: SgMcpLegacySse | null, backends: Array<SgBackendRef<P>>, plugins: Array<P>, timeout_mode: TimeoutMode, session_affinity: McpSessionAffinity, };
\ No newline at end of file
+export type SgMcpRoute<P = PluginBinding> = { kind: SgRouteKind, route_name: string, hostnames: Array<string> | null, transport: SgMcpTransport, path: string, legacy_sse: SgMcpLegacySse | null, backends: Array<SgBackendRef<P>>, plugins: Array<P>, timeout_mode: TimeoutMode,
+/**
+ * Timeout of the requests in milliseconds, it takes effect unless the timeout mode is `disabled`.
+ */
+timeout_ms: number | null, session_affinity: McpSessionAffinity, };
\ No newline at end of file
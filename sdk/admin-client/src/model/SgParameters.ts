@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { ObservabilityConfig } from "./ObservabilityConfig";
+import type { SgRoutePrecedence } from "./SgRoutePrecedence";
 
 export type SgParameters = {
 /**
@@ -21,4 +22,16 @@ ignore_tls_verification: boolean | null,
 /**
  * Add request id for every request
  */
-enable_x_request_id: boolean | null, observability: ObservabilityConfig, };
\ No newline at end of file
+enable_x_request_id: boolean | null,
+/**
+ * Default max request body size in bytes, larger requests are rejected with 413
+ */
+max_request_body_size: bigint | null,
+/**
+ * Default max response body size in bytes
+ */
+max_response_body_size: bigint | null,
+/**
+ * How http routes take precedence over each other, default is by priority
+ */
+route_precedence: SgRoutePrecedence | null, observability: ObservabilityConfig, };
\ No newline at end of file
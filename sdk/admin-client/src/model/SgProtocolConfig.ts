@@ -6,4 +6,4 @@ export type SgProtocolConfig = { "type": "http" } | { "type": "https",
  * TLS is the TLS configuration for the Listener.
  * This field is required if the Protocol field is “HTTPS” or “TLS”. It is invalid to set this field if the Protocol field is “HTTP”, “TCP”, or “UDP”.
  */
-tls: SgTlsConfig, };
\ No newline at end of file
+tls: SgTlsConfig, } | { "type": "tcp" } | { "type": "udp" };
\ No newline at end of file
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * PROXY protocol (v1 and v2) of a listener.
+ */
+export type SgProxyProtocol = {
+/**
+ * CIDRs of the load balancers which send the PROXY protocol header, e.g. `10.0.0.0/8`, it must not be empty.
+ *
+ * Connections from other sources are served as is.
+ */
+trusted_cidrs: Array<string>, };
\ No newline at end of file
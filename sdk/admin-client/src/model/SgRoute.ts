@@ -2,5 +2,8 @@
 import type { PluginBinding } from "./PluginBinding";
 import type { SgHttpRoute } from "./SgHttpRoute";
 import type { SgMcpRoute } from "./SgMcpRoute";
+import type { SgTcpRoute } from "./SgTcpRoute";
+import type { SgTlsRoute } from "./SgTlsRoute";
+import type { SgUdpRoute } from "./SgUdpRoute";
 
-export type SgRoute<P = PluginBinding> = SgMcpRoute<P> | SgHttpRoute<P>;
\ No newline at end of file
+export type SgRoute<P = PluginBinding> = SgMcpRoute<P> | SgTlsRoute | SgTcpRoute | SgUdpRoute | SgHttpRoute<P>;
\ No newline at end of file
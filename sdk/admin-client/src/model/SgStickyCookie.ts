@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SgStickyCookie = { name: string,
+/**
+ * MaxAge of the cookie in seconds, it's a session cookie if it's absent.
+ */
+max_age_s: bigint | null, };
\ No newline at end of file
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BackendHost } from "./BackendHost";
+
+/**
+ * A backend of stream routes, the connections are forwarded as raw bytes.
+ */
+export type SgStreamBackendRef = {
+/**
+ * Name is the kubernetes service name OR url host.
+ */
+host: BackendHost,
+/**
+ * Port specifies the destination port number to use for this resource.
+ */
+port: number,
+/**
+ * Weight specifies the proportion of connections forwarded to the referenced backend.
+ */
+weight: number | null, };
\ No newline at end of file
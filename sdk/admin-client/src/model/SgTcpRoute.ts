@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgStreamBackendRef } from "./SgStreamBackendRef";
+import type { SgTcpRouteKind } from "./SgTcpRouteKind";
+
+/**
+ * TCPRoute forwards TCP connections to backends as raw streams.
+ *
+ * It works on the listeners of `tcp` protocol. It's not supported by the k8s config backend yet, use the file or redis config backend for it.
+ *
+ * Reference: [Kubernetes Gateway](https://gateway-api.sigs.k8s.io/reference/spec/#gateway.networking.k8s.io/v1alpha2.TCPRoute)
+ */
+export type SgTcpRoute = { kind: SgTcpRouteKind,
+/**
+ * Route name
+ */
+route_name: string,
+/**
+ * Name of the listener this route attaches to, like the `sectionName` of a parent reference.
+ *
+ * If it's empty, the route attaches to all `tcp` listeners which no other route attaches to by name.
+ */
+listener: string | null, backends: Array<SgStreamBackendRef>,
+/**
+ * Close the connection if no data is transferred in either direction for this period, in milliseconds.
+ *
+ * If it's empty, the connection is never closed for being idle, and it must not be 0.
+ */
+idle_timeout_ms: bigint | null, };
\ No newline at end of file
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+/**
+ * A custom tcp service of a listener, created by the factory registered with the code.
+ */
+export type SgTcpService = {
+/**
+ * Code of the registered factory, e.g. `socks5`.
+ */
+code: string,
+/**
+ * Spec passed to the factory.
+ */
+spec: JsonValue, };
\ No newline at end of file
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A certificate served for a set of hostnames.
+ */
+export type SgTlsCertificate = {
+/**
+ * Hostnames to be matched against the SNI, wildcard like `*.example.com` is supported.
+ */
+hostnames: Array<string>, key: string, cert: string, key_path: string | null, cert_path: string | null, };
\ No newline at end of file
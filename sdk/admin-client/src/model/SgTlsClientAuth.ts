@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgTlsClientAuthMode } from "./SgTlsClientAuthMode";
+
+/**
+ * Client certificate authentication of a tls listener.
+ */
+export type SgTlsClientAuth = { mode: SgTlsClientAuthMode,
+/**
+ * PEM bundle of the CAs which issue client certificates.
+ */
+ca: string,
+/**
+ * Path of the PEM file of `ca`, it takes precedence over `ca`.
+ *
+ * The file is watched like the certificates, the gateway restarts when its content changes.
+ */
+ca_path: string | null, };
\ No newline at end of file
@@ -1,7 +1,31 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgTlsCertificate } from "./SgTlsCertificate";
+import type { SgTlsClientAuth } from "./SgTlsClientAuth";
 import type { SgTlsMode } from "./SgTlsMode";
 
 /**
  * GatewayTLSConfig describes a TLS configuration.
  */
-export type SgTlsConfig = { mode: SgTlsMode, key: string, cert: string, http2: boolean | null, };
\ No newline at end of file
+export type SgTlsConfig = { mode: SgTlsMode, key: string, cert: string,
+/**
+ * Path of the PEM file of `key`, it takes precedence over `key`, and the file will be watched by the file config backend.
+ */
+key_path: string | null,
+/**
+ * Path of the PEM file of `cert`, it takes precedence over `cert`, and the file will be watched by the file config backend.
+ */
+cert_path: string | null, http2: boolean | null,
+/**
+ * Serve HTTP/3 on the same port over UDP, and advertise it by the `Alt-Svc` header of https responses.
+ *
+ * It only works in `Terminate` mode, and the gateway should be built with the `http3` feature.
+ */
+http3: boolean | null,
+/**
+ * Certificates selected by the SNI of the client, the `key` and `cert` above will be the default one when no certificate matches.
+ */
+certs: Array<SgTlsCertificate>,
+/**
+ * Authenticate clients by their certificates, aka mutual tls.
+ */
+client_auth: SgTlsClientAuth | null, };
\ No newline at end of file
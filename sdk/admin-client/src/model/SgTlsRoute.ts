@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SgStreamBackendRef } from "./SgStreamBackendRef";
+import type { SgTlsRouteKind } from "./SgTlsRouteKind";
+
+/**
+ * TLSRoute routes TLS connections by SNI to backends, the connections are passed through without being terminated.
+ *
+ * It works on the listeners of `https` protocol in `Passthrough` mode.
+ *
+ * Reference: [Kubernetes Gateway](https://gateway-api.sigs.k8s.io/reference/spec/#gateway.networking.k8s.io/v1alpha2.TLSRoute)
+ */
+export type SgTlsRoute = { kind: SgTlsRouteKind,
+/**
+ * Route name
+ */
+route_name: string,
+/**
+ * Hostnames to be matched against the SNI, wildcard like `*.example.com` is supported.
+ *
+ * If it's empty, all connections will be matched, including those without SNI.
+ */
+hostnames: Array<string> | null, backends: Array<SgStreamBackendRef>,
+/**
+ * Rule priority, the route of higher priority will be chosen.
+ */
+priority: number, };
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type TimeoutMode = "request" | "disabled";
\ No newline at end of file
+export type TimeoutMode = "request" | "idle" | "disabled";
\ No newline at end of file
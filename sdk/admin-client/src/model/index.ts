@@ -17,8 +17,16 @@ export * from './PluginInstanceName';
 export * from './PluginMetaData';
 export * from './SgBackendProtocol';
 export * from './SgBackendRef';
+export * from './SgBackendTls';
 export * from './SgBalancePolicy';
+export * from './SgCanaryKey';
+export * from './SgCanaryPolicy';
+export * from './SgConnectionLimits';
 export * from './SgGateway';
+export * from './SgGrpcMethodMatch';
+export * from './SgHealthCheck';
+export * from './SgHealthCheckProbe';
+export * from './SgHttpCookieMatch';
 export * from './SgHttpHeaderMatch';
 export * from './SgHttpMethodMatch';
 export * from './SgHttpPathMatch';
@@ -32,10 +40,24 @@ export * from './SgMcpRoute';
 export * from './SgMcpTransport';
 export * from './SgParameters';
 export * from './SgProtocolConfig';
+export * from './SgProxyProtocol';
 export * from './SgRoute';
 export * from './SgRouteFilter';
 export * from './SgRouteKind';
+export * from './SgRoutePrecedence';
+export * from './SgStickyCookie';
+export * from './SgStreamBackendRef';
+export * from './SgTcpRoute';
+export * from './SgTcpRouteKind';
+export * from './SgTcpService';
+export * from './SgTlsCertificate';
+export * from './SgTlsClientAuth';
+export * from './SgTlsClientAuthMode';
 export * from './SgTlsConfig';
 export * from './SgTlsMode';
+export * from './SgTlsRoute';
+export * from './SgTlsRouteKind';
+export * from './SgUdpRoute';
+export * from './SgUdpRouteKind';
 export * from './TimeoutMode';
 export * from './TraceConfig';